@@ -0,0 +1,177 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rustofluid::log::create_logger;
+use rustofluid::scene::bench::BenchScene;
+use rustofluid::scene::cell::Cell;
+use rustofluid::scene::fixed_grid::FixedGrid;
+use rustofluid::scene::grid::Grid;
+use rustofluid::scene::setup::setup_scene;
+use rustofluid::scene::solver_params::SolverParamsBuilder;
+use rustofluid::scene::timestepper::{ExecutionMode, Integrate, TimeStepper};
+use rustofluid::types::*;
+use std::time::Duration;
+
+/// The resolutions of the step and projection benchmarks.
+const DIMS: [(usize, usize); 3] = [(100, 50), (200, 100), (400, 200)];
+
+fn grid_mut<'a>(timestepper: &'a mut TimeStepper) -> &'a mut Grid {
+    return timestepper.objects[0]
+        .as_any_mut()
+        .downcast_mut::<Grid>()
+        .expect("Not a grid");
+}
+
+fn bench_sample_field(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
+
+    let config = BenchScene::Channel.config(idx!(200, 100));
+    let mut timestepper = setup_scene(&log, &config).unwrap();
+    timestepper.compute_step(config.time.dt).unwrap();
+    let grid = grid_mut(&mut timestepper);
+
+    // Deterministic positions spread over the whole domain.
+    let extent = grid.dim.cast::<Scalar>() * grid.cell_width;
+    let positions: Vec<Vector2> = (0..4096)
+        .map(|i| {
+            let (a, b) = ((i * 7919 % 4096) as Scalar, (i * 104729 % 4096) as Scalar);
+            return vec2!(a / 4096.0 * extent.x, b / 4096.0 * extent.y);
+        })
+        .collect();
+
+    let (min, max) = (idx!(0, 0), grid.dim);
+    let smoke = |cell: &Cell| cell.smoke();
+
+    let mut group = c.benchmark_group("sample_field");
+    group.throughput(Throughput::Elements(positions.len() as u64));
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            return positions
+                .iter()
+                .map(|p| grid.sample_field(min, max, *p, None, smoke))
+                .sum::<Scalar>();
+        })
+    });
+
+    let mut values = vec![0.0; positions.len()];
+    group.bench_function("batch", |b| {
+        b.iter(|| grid.sample_field_batch(min, max, &positions, None, smoke, &mut values))
+    });
+
+    group.finish();
+}
+
+fn bench_projection(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
+
+    let mut group = c.benchmark_group("projection");
+
+    for (x, y) in DIMS {
+        let config = BenchScene::Channel.config(idx!(x, y));
+        let mut timestepper = setup_scene(&log, &config).unwrap();
+        timestepper.compute_step(config.time.dt).unwrap();
+
+        let (dt, density) = (config.time.dt, config.solver.density);
+        let grid = grid_mut(&mut timestepper);
+        group.throughput(Throughput::Elements((x * y) as u64));
+
+        for mode in [
+            ExecutionMode::Single,
+            ExecutionMode::ParallelBands,
+            ExecutionMode::Tiled,
+        ] {
+            let id = BenchmarkId::new(format!("{:?}", mode), format!("{}x{}", x, y));
+            let params = SolverParamsBuilder::default()
+                .iterations(1)
+                .density(density)
+                .execution_mode(mode)
+                .build()
+                .unwrap();
+            group.bench_function(id, |b| {
+                b.iter(|| grid.solve_incompressibility(&log, dt, &params).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_fixed_grid(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
+
+    let config = BenchScene::Channel.config(idx!(100, 50));
+    let mut timestepper = setup_scene(&log, &config).unwrap();
+    timestepper.compute_step(config.time.dt).unwrap();
+
+    let (dt, density) = (config.time.dt, config.solver.density);
+    let grid = grid_mut(&mut timestepper);
+    let mut fixed = FixedGrid::<102, 52>::from_grid(grid.crop(idx!(0, 0), grid.dim)).unwrap();
+
+    let mut group = c.benchmark_group("fixed_grid");
+    group.throughput(Throughput::Elements(100 * 50));
+
+    let params = SolverParamsBuilder::default()
+        .iterations(1)
+        .density(density)
+        .build()
+        .unwrap();
+    group.bench_function("dynamic", |b| {
+        b.iter(|| grid.solve_incompressibility(&log, dt, &params).unwrap())
+    });
+    group.bench_function("fixed", |b| {
+        b.iter(|| fixed.solve_incompressibility(&log, dt, &params).unwrap())
+    });
+
+    group.finish();
+}
+
+fn bench_step(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
+
+    let mut group = c.benchmark_group("step");
+    group.measurement_time(Duration::from_secs(10));
+
+    for (x, y) in DIMS {
+        let config = BenchScene::Channel.config(idx!(x, y));
+        let mut timestepper = setup_scene(&log, &config).unwrap();
+        group.throughput(Throughput::Elements((x * y) as u64));
+
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", x, y)), |b| {
+            b.iter(|| timestepper.compute_step(config.time.dt).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_iterators(c: &mut Criterion) {
+    let grid = Grid::new(idx!(200, 100), 0.01);
+
+    let mut group = c.benchmark_group("iterators");
+    group.throughput(Throughput::Elements((grid.dim.x * grid.dim.y) as u64));
+
+    group.bench_function("iter_index", |b| {
+        b.iter(|| grid.iter_index().map(|i| i.x + i.y).sum::<usize>())
+    });
+    group.bench_function("iter_inside", |b| {
+        b.iter(|| grid.iter_inside().map(|i| i.x + i.y).sum::<usize>())
+    });
+    group.bench_function("iter_faces", |b| {
+        b.iter(|| grid.iter_u_faces().map(|f| f.pos.x).sum::<Scalar>())
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_sample_field,
+    bench_projection,
+    bench_fixed_grid,
+    bench_step,
+    bench_iterators
+);
+criterion_main!(benches);
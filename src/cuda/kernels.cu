@@ -0,0 +1,142 @@
+// CUDA kernels for the pressure solve and the advection (double precision).
+// Mirrors the CPU solver in `scene/grid.rs`: cells are stored `x`-fastest including the
+// border cells and the staggered velocity is interleaved (`u`, `v` per cell).
+
+__device__ int offset(int i, int j, int nx) {
+    return i + j * nx;
+}
+
+__device__ bool is_inside(int i, int j, int nx, int ny) {
+    return i >= 1 && j >= 1 && i + 1 < nx && j + 1 < ny;
+}
+
+// Bilinear sample of `field` with `components` values per cell (component `c`)
+// at `(px, py)` (already shifted to the staggered grid) with indices clamped to `[lo, hi)`.
+__device__ double sample(const double* field, int components, int c,
+                         double px, double py, int lo, int hi_x, int hi_y,
+                         int nx, int ny, double h) {
+    px = fmin(fmax(px, 0.0), nx * h);
+    py = fmin(fmax(py, 0.0), ny * h);
+
+    int i0 = min(max((int)(px / h), lo), hi_x - 1);
+    int j0 = min(max((int)(py / h), lo), hi_y - 1);
+    int i1 = min(i0 + 1, hi_x - 1);
+    int j1 = min(j0 + 1, hi_y - 1);
+
+    double ax = fmin(fmax((px - i0 * h) / h, 0.0), 1.0);
+    double ay = fmin(fmax((py - j0 * h) / h, 0.0), 1.0);
+
+    double v00 = field[components * offset(i0, j0, nx) + c];
+    double v01 = field[components * offset(i0, j1, nx) + c];
+    double v10 = field[components * offset(i1, j0, nx) + c];
+    double v11 = field[components * offset(i1, j1, nx) + c];
+
+    double top = v01 * (1.0 - ax) + v11 * ax;
+    double bottom = v00 * (1.0 - ax) + v10 * ax;
+    return ay * top + (1.0 - ay) * bottom;
+}
+
+// One red-black Gauss-Seidel sweep over the cells with `(i + j) % 2 == color`.
+// Cells of the same color share no face, hence all cells of a sweep are independent.
+extern "C" __global__ void pressure_sweep(double* vel, double* pressure, double* div,
+                                          const double* fluid, int nx, int ny,
+                                          double cp, double r, int color) {
+    int i = blockIdx.x * blockDim.x + threadIdx.x;
+    int j = blockIdx.y * blockDim.y + threadIdx.y;
+    if (!is_inside(i, j, nx, ny) || (i + j) % 2 != color) {
+        return;
+    }
+
+    int k = offset(i, j, nx);
+    if (fluid[k] == 0.0) {
+        return;
+    }
+
+    double s_neg_x = fluid[offset(i - 1, j, nx)];
+    double s_neg_y = fluid[offset(i, j - 1, nx)];
+    double s_pos_x = fluid[offset(i + 1, j, nx)];
+    double s_pos_y = fluid[offset(i, j + 1, nx)];
+    double s = s_neg_x + s_neg_y + s_pos_x + s_pos_y;
+    if (s == 0.0) {
+        return;
+    }
+
+    int kx = offset(i + 1, j, nx);
+    int ky = offset(i, j + 1, nx);
+    double d = vel[2 * kx] - vel[2 * k] + vel[2 * ky + 1] - vel[2 * k + 1];
+    div[k] = d;
+
+    double d_normed = d / s;
+    pressure[k] -= cp * d_normed;
+
+    vel[2 * k] += r * s_neg_x * d_normed;
+    vel[2 * k + 1] += r * s_neg_y * d_normed;
+    vel[2 * kx] -= r * s_pos_x * d_normed;
+    vel[2 * ky + 1] -= r * s_pos_y * d_normed;
+}
+
+extern "C" __global__ void advect_velocity(const double* vel, double* vel_out,
+                                           const double* fluid, int nx, int ny,
+                                           double h, double dt) {
+    int i = blockIdx.x * blockDim.x + threadIdx.x;
+    int j = blockIdx.y * blockDim.y + threadIdx.y;
+    if (i >= nx || j >= ny) {
+        return;
+    }
+
+    int k = offset(i, j, nx);
+    double advected[2] = {vel[2 * k], vel[2 * k + 1]};
+
+    if (is_inside(i, j, nx, ny) && fluid[k] != 0.0) {
+        bool neg_fluid[2] = {fluid[offset(i - 1, j, nx)] != 0.0,
+                             fluid[offset(i, j - 1, nx)] != 0.0};
+        // The staggered offsets of `u` and `v`.
+        double stagger[2][2] = {{0.0, 0.5 * h}, {0.5 * h, 0.0}};
+
+        for (int dir = 0; dir < 2; ++dir) {
+            if (!neg_fluid[dir]) {
+                continue;
+            }
+
+            double px = i * h + stagger[dir][0];
+            double py = j * h + stagger[dir][1];
+            double v[2] = {vel[2 * k], vel[2 * k + 1]};
+
+            int other = (dir + 1) % 2;
+            v[other] = sample(vel, 2, other, px - stagger[other][0], py - stagger[other][1],
+                              1, nx - 1, ny - 1, nx, ny, h);
+
+            px -= dt * v[0];
+            py -= dt * v[1];
+            advected[dir] = sample(vel, 2, dir, px - stagger[dir][0], py - stagger[dir][1],
+                                   1, nx - 1, ny - 1, nx, ny, h);
+        }
+    }
+
+    vel_out[2 * k] = advected[0];
+    vel_out[2 * k + 1] = advected[1];
+}
+
+extern "C" __global__ void advect_smoke(const double* vel, const double* smoke,
+                                        double* smoke_out, const double* fluid,
+                                        int nx, int ny, double h, double dt) {
+    int i = blockIdx.x * blockDim.x + threadIdx.x;
+    int j = blockIdx.y * blockDim.y + threadIdx.y;
+    if (i >= nx || j >= ny) {
+        return;
+    }
+
+    int k = offset(i, j, nx);
+    if (!is_inside(i, j, nx, ny) || fluid[k] == 0.0) {
+        smoke_out[k] = smoke[k];
+        return;
+    }
+
+    // The velocity at the cell center, the smoke is sampled relative to the centers.
+    double vx = 0.5 * (vel[2 * k] + vel[2 * offset(i + 1, j, nx)]);
+    double vy = 0.5 * (vel[2 * k + 1] + vel[2 * offset(i, j + 1, nx) + 1]);
+    double px = i * h - dt * vx;
+    double py = j * h - dt * vy;
+
+    smoke_out[k] = sample(smoke, 1, 0, px, py, 0, nx, ny, nx, ny, h);
+}
@@ -0,0 +1,5 @@
+pub mod solver;
+
+pub use solver::CudaSolver;
+
+mod tests;
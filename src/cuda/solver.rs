@@ -0,0 +1,316 @@
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::memory::{MemoryLocation, MemoryReport};
+use crate::types::*;
+
+use cudarc::driver::{result, sys, CudaDevice, CudaFunction, CudaSlice, DevicePtr};
+use cudarc::driver::{LaunchAsync, LaunchConfig};
+use std::sync::Arc;
+
+const MODULE: &str = "solver";
+
+/// Thread block size of all kernels in both directions.
+const BLOCK: u32 = 16;
+
+/// Page-locked host memory for the transfers,
+/// which the driver copies without an intermediate staging buffer.
+struct PinnedBuffer {
+    ptr: *mut f64,
+    len: usize,
+}
+
+// The buffer is only accessed through `&mut self` of the solver.
+unsafe impl Send for PinnedBuffer {}
+
+impl PinnedBuffer {
+    fn new(len: usize) -> GenericResult<Self> {
+        let mut ptr = std::ptr::null_mut();
+        unsafe { sys::cuMemAllocHost_v2(&mut ptr, len * std::mem::size_of::<f64>()) }.result()?;
+
+        return Ok(PinnedBuffer {
+            ptr: ptr as *mut f64,
+            len,
+        });
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [f64] {
+        return unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) };
+    }
+}
+
+impl Drop for PinnedBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            sys::cuMemFreeHost(self.ptr as *mut std::ffi::c_void);
+        }
+    }
+}
+
+struct Kernels {
+    pressure_sweep: CudaFunction,
+    advect_velocity: CudaFunction,
+    advect_smoke: CudaFunction,
+}
+
+/// Runs the pressure solve (red-black Gauss-Seidel) and the advection of a grid
+/// as CUDA kernels in double precision. The integration stays on the CPU.
+///
+/// All transfers go over page-locked host memory in `upload` and `download`.
+pub struct CudaSolver {
+    device: Arc<CudaDevice>,
+    kernels: Kernels,
+
+    dim: Index2,
+    cell_width: Scalar,
+    over_relaxation: Scalar,
+
+    velocity: CudaSlice<f64>,
+    velocity_tmp: CudaSlice<f64>,
+    smoke: CudaSlice<f64>,
+    smoke_tmp: CudaSlice<f64>,
+    pressure: CudaSlice<f64>,
+    div: CudaSlice<f64>,
+    fluid: CudaSlice<f64>,
+
+    host: PinnedBuffer,
+}
+
+impl CudaSolver {
+    /// Create the solver for grids like `grid` on the CUDA device `ordinal`.
+    /// Fails if there is no such device or the kernels do not compile.
+    pub fn new(grid: &Grid, ordinal: usize) -> GenericResult<Self> {
+        let device = CudaDevice::new(ordinal)?;
+
+        let ptx = cudarc::nvrtc::compile_ptx(include_str!("kernels.cu"))?;
+        device.load_ptx(
+            ptx,
+            MODULE,
+            &["pressure_sweep", "advect_velocity", "advect_smoke"],
+        )?;
+
+        let kernel = |name: &str| {
+            return device
+                .get_func(MODULE, name)
+                .ok_or_else(|| format!("CUDA kernel '{}' not found.", name));
+        };
+        let kernels = Kernels {
+            pressure_sweep: kernel("pressure_sweep")?,
+            advect_velocity: kernel("advect_velocity")?,
+            advect_smoke: kernel("advect_smoke")?,
+        };
+
+        let n = grid.dim.x * grid.dim.y;
+
+        return Ok(CudaSolver {
+            kernels,
+            dim: grid.dim,
+            cell_width: grid.cell_width,
+            over_relaxation: grid.over_relaxation,
+            velocity: device.alloc_zeros(2 * n)?,
+            velocity_tmp: device.alloc_zeros(2 * n)?,
+            smoke: device.alloc_zeros(n)?,
+            smoke_tmp: device.alloc_zeros(n)?,
+            pressure: device.alloc_zeros(n)?,
+            div: device.alloc_zeros(n)?,
+            fluid: device.alloc_zeros(n)?,
+            host: PinnedBuffer::new(2 * n)?,
+            device,
+        });
+    }
+
+    /// Copy the first `count` values of the pinned buffer to `dst`.
+    fn copy_to_device(
+        host: &mut PinnedBuffer,
+        dst: &CudaSlice<f64>,
+        count: usize,
+    ) -> GenericResult<()> {
+        unsafe { result::memcpy_htod_sync(*dst.device_ptr(), &host.as_mut_slice()[0..count]) }?;
+        return Ok(());
+    }
+
+    /// Copy the first `count` values of `src` to the pinned buffer.
+    fn copy_to_host(
+        host: &mut PinnedBuffer,
+        src: &CudaSlice<f64>,
+        count: usize,
+    ) -> GenericResult<()> {
+        unsafe { result::memcpy_dtoh_sync(&mut host.as_mut_slice()[0..count], *src.device_ptr()) }?;
+        return Ok(());
+    }
+
+    /// The bytes of the device fields and of the page-locked host buffer.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        let bytes = |s: &CudaSlice<f64>| s.len() * std::mem::size_of::<f64>();
+        let device = MemoryLocation::Device;
+
+        report.add("velocity", device, bytes(&self.velocity));
+        report.add("velocity-tmp", device, bytes(&self.velocity_tmp));
+        report.add("smoke", device, bytes(&self.smoke));
+        report.add("smoke-tmp", device, bytes(&self.smoke_tmp));
+        report.add("pressure", device, bytes(&self.pressure));
+        report.add("div", device, bytes(&self.div));
+        report.add("fluid", device, bytes(&self.fluid));
+        report.add(
+            "pinned",
+            MemoryLocation::Host,
+            self.host.len * std::mem::size_of::<f64>(),
+        );
+
+        return report;
+    }
+
+    fn check_dim(&self, grid: &Grid) -> GenericResult<()> {
+        if grid.dim != self.dim {
+            bail!(
+                "Grid dimension {} differs from the solver's {}.",
+                grid.dim,
+                self.dim
+            );
+        }
+        return Ok(());
+    }
+
+    /// Copy the velocity, the smoke and the solid cells of `grid` to the device.
+    pub fn upload(&mut self, grid: &Grid) -> GenericResult<()> {
+        self.check_dim(grid)?;
+        self.device.bind_to_thread()?;
+
+        self.cell_width = grid.cell_width;
+        self.over_relaxation = grid.over_relaxation;
+
+        let n = self.dim.x * self.dim.y;
+        let fill = |host: &mut PinnedBuffer, get: &dyn Fn(&Cell) -> Scalar| {
+            for (v, idx) in host.as_mut_slice().iter_mut().zip(grid.iter_index()) {
+                *v = get(grid.cell(idx)) as f64;
+            }
+        };
+
+        let host = self.host.as_mut_slice();
+        for (i, idx) in grid.iter_index().enumerate() {
+            let v = grid.cell(idx).velocity.back;
+            host[2 * i] = v.x as f64;
+            host[2 * i + 1] = v.y as f64;
+        }
+        CudaSolver::copy_to_device(&mut self.host, &self.velocity, 2 * n)?;
+
+        fill(&mut self.host, &|c| c.smoke());
+        CudaSolver::copy_to_device(&mut self.host, &self.smoke, n)?;
+
+        fill(&mut self.host, &|c| {
+            if c.mode == CellTypes::Solid {
+                0.0
+            } else {
+                1.0
+            }
+        });
+        CudaSolver::copy_to_device(&mut self.host, &self.fluid, n)?;
+
+        return Ok(());
+    }
+
+    /// Copy the velocity, the smoke, the pressure and the divergence back to `grid`.
+    pub fn download(&mut self, grid: &mut Grid) -> GenericResult<()> {
+        self.check_dim(grid)?;
+        self.device.bind_to_thread()?;
+
+        let n = self.dim.x * self.dim.y;
+        let indices = grid.iter_index().collect::<Vec<_>>();
+
+        CudaSolver::copy_to_host(&mut self.host, &self.velocity, 2 * n)?;
+        let host = self.host.as_mut_slice();
+        for (i, idx) in indices.iter().enumerate() {
+            grid.cell_mut(*idx).velocity.back = vec2!(host[2 * i] as Scalar, host[2 * i + 1] as Scalar);
+        }
+
+        let fields: [(&CudaSlice<f64>, fn(&mut Cell, Scalar)); 3] = [
+            (&self.smoke, |c, v| c.smoke() = v),
+            (&self.pressure, |c, v| c.pressure = v),
+            (&self.div, |c, v| c.div = v),
+        ];
+
+        for (src, set) in fields {
+            CudaSolver::copy_to_host(&mut self.host, src, n)?;
+            let host = self.host.as_mut_slice();
+            for (i, idx) in indices.iter().enumerate() {
+                set(grid.cell_mut(*idx), host[i] as Scalar);
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Run `iterations` red-black pressure sweeps and advect the velocity and the smoke.
+    pub fn solve_and_advect(
+        &mut self,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+    ) -> GenericResult<()> {
+        self.device.bind_to_thread()?;
+
+        let (nx, ny) = (self.dim.x as i32, self.dim.y as i32);
+        // The kernels compute in double precision.
+        let (h, dt) = (self.cell_width as f64, dt as f64);
+        let cp = density as f64 * h / dt;
+        let r = self.over_relaxation as f64;
+
+        let config = LaunchConfig {
+            grid_dim: (
+                (self.dim.x as u32 + BLOCK - 1) / BLOCK,
+                (self.dim.y as u32 + BLOCK - 1) / BLOCK,
+                1,
+            ),
+            block_dim: (BLOCK, BLOCK, 1),
+            shared_mem_bytes: 0,
+        };
+
+        self.device.memset_zeros(&mut self.pressure)?;
+
+        for _ in 0..iterations {
+            for color in 0..2 {
+                let params = (
+                    &mut self.velocity,
+                    &mut self.pressure,
+                    &mut self.div,
+                    &self.fluid,
+                    nx,
+                    ny,
+                    cp,
+                    r,
+                    color,
+                );
+                unsafe { self.kernels.pressure_sweep.clone().launch(config, params) }?;
+            }
+        }
+
+        let params = (
+            &self.velocity,
+            &mut self.velocity_tmp,
+            &self.fluid,
+            nx,
+            ny,
+            h,
+            dt,
+        );
+        unsafe { self.kernels.advect_velocity.clone().launch(config, params) }?;
+        std::mem::swap(&mut self.velocity, &mut self.velocity_tmp);
+
+        let params = (
+            &self.velocity,
+            &self.smoke,
+            &mut self.smoke_tmp,
+            &self.fluid,
+            nx,
+            ny,
+            h,
+            dt,
+        );
+        unsafe { self.kernels.advect_smoke.clone().launch(config, params) }?;
+        std::mem::swap(&mut self.smoke, &mut self.smoke_tmp);
+
+        self.device.synchronize()?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::cuda::CudaSolver;
+    use crate::log::create_logger;
+    use crate::scene::grid::*;
+    use crate::scene::tests::fixtures::{centroid, create_blob};
+    use crate::scene::timestepper::{ExecutionMode, Integrate};
+    use crate::types::*;
+
+    fn create() -> Grid {
+        let mut grid = Grid::new(dim!(24, 16), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let p = idx.cast::<Scalar>();
+            let c = grid.cell_mut(idx);
+            c.velocity.back = vec2!((0.3 * p.x).sin(), (0.5 * p.y).cos());
+            c.set_smoke((0.1 * (p.x + 2.0 * p.y)).fract());
+        }
+        grid.add_obstacle(vec2!(1.0, 0.8), 0.3, None);
+        return grid;
+    }
+
+    #[test]
+    fn check_cuda_advection_matches_cpu() -> GenericResult<()> {
+        let (log, _) = create_logger();
+        let dt = 0.01;
+
+        let mut cpu = create();
+        let mut gpu = create();
+
+        let mut solver = match CudaSolver::new(&gpu, 0) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no CUDA device available: {}", e);
+                return Ok(());
+            }
+        };
+
+        // Without pressure iterations both solvers compute the same (double precision).
+        cpu.advect(&log, dt, ExecutionMode::Single);
+
+        solver.upload(&gpu)?;
+        solver.solve_and_advect(dt, 0, 1000.0)?;
+        solver.download(&mut gpu)?;
+
+        for idx in cpu.iter_index() {
+            let (a, b) = (cpu.cell(idx), gpu.cell(idx));
+            assert!(
+                (a.velocity.back - b.velocity.back).norm() < 1e-12,
+                "Velocity differs at {}: {} vs. {}.",
+                idx,
+                a.velocity.back,
+                b.velocity.back
+            );
+            assert!((a.smoke() - b.smoke()).abs() < 1e-12, "Smoke differs at {}.", idx);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_cuda_smoke_translation() -> GenericResult<()> {
+        let (log, _) = create_logger();
+        let (velocity, dt, steps) = (vec2!(1.0, 0.5), 0.03, 10);
+
+        let mut cpu = create_blob(velocity);
+        let mut gpu = create_blob(velocity);
+        let mut solver = match CudaSolver::new(&gpu, 0) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no CUDA device available: {}", e);
+                return Ok(());
+            }
+        };
+
+        let expected = centroid(&cpu) + velocity * dt * steps as Scalar;
+        solver.upload(&gpu)?;
+        for _ in 0..steps {
+            cpu.advect(&log, dt, ExecutionMode::Single);
+            solver.solve_and_advect(dt, 0, 1000.0)?;
+        }
+        solver.download(&mut gpu)?;
+
+        // Both translate the smoke like the analytic solution.
+        for (name, grid) in [("CPU", &cpu), ("CUDA", &gpu)] {
+            let error = (centroid(grid) - expected).norm();
+            assert!(error < 0.01 * grid.cell_width, "{} smoke is off by {}.", name, error);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_cuda_pressure_solve() -> GenericResult<()> {
+        let mut solver = match CudaSolver::new(&create(), 0) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no CUDA device available: {}", e);
+                return Ok(());
+            }
+        };
+
+        let mut max_div = |iterations: u64| {
+            let mut grid = create();
+            solver.upload(&grid)?;
+            solver.solve_and_advect(0.01, iterations, 1000.0)?;
+            solver.download(&mut grid)?;
+
+            return GenericResult::Ok(
+                grid.iter_inside()
+                    .map(|idx| grid.cell(idx).div.abs())
+                    .fold(0.0, Scalar::max),
+            );
+        };
+
+        let few = max_div(2)?;
+        let many = max_div(100)?;
+        assert!(many < 0.5 * few, "Divergence {} not reduced from {}.", many, few);
+
+        return Ok(());
+    }
+}
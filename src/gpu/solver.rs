@@ -0,0 +1,709 @@
+use crate::render::{RenderParams, ValueRange};
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::field_layout::{AlignedField, FieldLayout};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::memory::{MemoryLocation, MemoryReport};
+use crate::types::*;
+
+use bytemuck::{Pod, Zeroable};
+use image::RgbaImage;
+use wgpu::util::DeviceExt;
+
+/// Workgroup size of all kernels in both directions (see `solver.wgsl`).
+const WORKGROUP: u32 = 8;
+
+/// The uniform parameters of the kernels (layout of `Params` in `solver.wgsl`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+struct Params {
+    dim: [u32; 2],
+    h: f32,
+    dt: f32,
+    gravity: [f32; 2],
+    cp: f32,
+    r: f32,
+    color: u32,
+    /// The row stride of the fields with one component (see `FieldLayout`).
+    stride: u32,
+    pad: [u32; 2],
+}
+
+/// The uniform parameters of `render_smoke` (layout of `RenderParams` in `solver.wgsl`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+struct RenderUniforms {
+    min: f32,
+    /// The inverse of the value range.
+    scale: f32,
+    gamma_inv: f32,
+    /// The packed RGBA color of the solid cells.
+    solid: u32,
+}
+
+/// Number of entries of the colormap table of `render_smoke`.
+const COLORMAP_SIZE: usize = 256;
+
+/// The `N` values `get` of all cells in rows aligned for the upload.
+fn field<F, const N: usize>(grid: &Grid, get: F) -> AlignedField<f32>
+where
+    F: Fn(&Cell) -> [Scalar; N],
+{
+    let mut field = AlignedField::new(grid.dim, N);
+
+    for row in grid.iter_rows(Index2::zeros(), grid.dim) {
+        let values = field.row_mut(row.y).chunks_exact_mut(N);
+        for (v, c) in values.zip(grid.row_cells(&row)) {
+            for (v, g) in v.iter_mut().zip(get(c)) {
+                *v = g as f32;
+            }
+        }
+    }
+
+    return field;
+}
+
+struct Buffers {
+    params: [wgpu::Buffer; 2],
+    velocity: wgpu::Buffer,
+    velocity_tmp: wgpu::Buffer,
+    smoke: wgpu::Buffer,
+    smoke_tmp: wgpu::Buffer,
+    pressure: wgpu::Buffer,
+    div: wgpu::Buffer,
+    fluid: wgpu::Buffer,
+
+    /// Mappable buffer for the downloads (room for all fields at once).
+    staging: wgpu::Buffer,
+
+    render: wgpu::Buffer,
+    colormap: wgpu::Buffer,
+}
+
+struct Pipelines {
+    integrate: wgpu::ComputePipeline,
+    extrapolate: wgpu::ComputePipeline,
+    pressure_sweep: wgpu::ComputePipeline,
+    advect_velocity: wgpu::ComputePipeline,
+    advect_smoke: wgpu::ComputePipeline,
+    render_smoke: wgpu::ComputePipeline,
+}
+
+/// Runs the integration, the pressure solve (red-black Gauss-Seidel) and the advection
+/// of a grid as wgpu compute shaders in single precision.
+///
+/// The fields live on the GPU between `upload` and `download`, which are the only
+/// transfers: several steps can be computed without touching the CPU grid.
+/// `upload_velocity`, `project` and `download_projection` only run the projection,
+/// such that the advection can be computed on the CPU (see `GpuSchedule`).
+///
+/// `render_smoke` colors the smoke field into `smoke_texture` on the GPU. Renderers
+/// which are created on `device` can sample this texture directly.
+pub struct GpuSolver {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_name: String,
+
+    dim: Index2,
+    cell_width: Scalar,
+    over_relaxation: Scalar,
+
+    buffers: Buffers,
+    bind_groups: [wgpu::BindGroup; 2],
+    pipelines: Pipelines,
+
+    smoke_texture: wgpu::Texture,
+    render_bind_group: wgpu::BindGroup,
+}
+
+impl GpuSolver {
+    /// Create the solver for grids like `grid`.
+    /// Fails if no GPU adapter is available.
+    pub fn new(grid: &Grid) -> GenericResult<Self> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .ok_or("No GPU adapter found.")?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("rustofluid"),
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))?;
+
+        // All fields have rows aligned to `FIELD_ALIGN` bytes.
+        let n = FieldLayout::new::<f32>(grid.dim, 1).size() as u64;
+        let f32_size = std::mem::size_of::<f32>() as u64;
+
+        let storage = |label: &str, size: u64| {
+            return device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        };
+        let uniform = |label: &str| {
+            return device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::bytes_of(&Params::default()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        };
+
+        let buffers = Buffers {
+            params: [uniform("params-red"), uniform("params-black")],
+            velocity: storage("velocity", 2 * n * f32_size),
+            velocity_tmp: storage("velocity-tmp", 2 * n * f32_size),
+            smoke: storage("smoke", n * f32_size),
+            smoke_tmp: storage("smoke-tmp", n * f32_size),
+            pressure: storage("pressure", n * f32_size),
+            div: storage("div", n * f32_size),
+            fluid: storage("fluid", n * f32_size),
+            staging: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("staging"),
+                size: 5 * n * f32_size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            render: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("render"),
+                contents: bytemuck::bytes_of(&RenderUniforms::default()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            }),
+            colormap: storage("colormap", (COLORMAP_SIZE * std::mem::size_of::<u32>()) as u64),
+        };
+
+        let smoke_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("smoke-texture"),
+            size: wgpu::Extent3d {
+                width: grid.dim.x as u32,
+                height: grid.dim.y as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        });
+
+        let entry = |binding: u32, ty: wgpu::BufferBindingType| {
+            return wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
+        };
+        let rw = wgpu::BufferBindingType::Storage { read_only: false };
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("solver"),
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, rw),
+                entry(2, rw),
+                entry(3, rw),
+                entry(4, rw),
+                entry(5, rw),
+                entry(6, rw),
+                entry(7, wgpu::BufferBindingType::Storage { read_only: true }),
+            ],
+        });
+
+        let bind_group = |params: &wgpu::Buffer| {
+            let fields = [
+                params,
+                &buffers.velocity,
+                &buffers.velocity_tmp,
+                &buffers.smoke,
+                &buffers.smoke_tmp,
+                &buffers.pressure,
+                &buffers.div,
+                &buffers.fluid,
+            ];
+
+            let entries = fields
+                .iter()
+                .enumerate()
+                .map(|(i, b)| wgpu::BindGroupEntry {
+                    binding: i as u32,
+                    resource: b.as_entire_binding(),
+                })
+                .collect::<Vec<_>>();
+
+            return device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("solver"),
+                layout: &layout,
+                entries: &entries,
+            });
+        };
+        let bind_groups = [
+            bind_group(&buffers.params[0]),
+            bind_group(&buffers.params[1]),
+        ];
+
+        let render_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("render"),
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Uniform),
+                entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let texture_view = smoke_texture.create_view(&Default::default());
+        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("render"),
+            layout: &render_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.render.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.colormap.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+            ],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("solver"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("solver.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("solver"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render"),
+                bind_group_layouts: &[&layout, &render_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = |entry_point: &str, layout: &wgpu::PipelineLayout| {
+            return device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(layout),
+                module: &module,
+                entry_point,
+            });
+        };
+
+        let pipelines = Pipelines {
+            integrate: pipeline("integrate", &pipeline_layout),
+            extrapolate: pipeline("extrapolate", &pipeline_layout),
+            pressure_sweep: pipeline("pressure_sweep", &pipeline_layout),
+            advect_velocity: pipeline("advect_velocity", &pipeline_layout),
+            advect_smoke: pipeline("advect_smoke", &pipeline_layout),
+            render_smoke: pipeline("render_smoke", &render_pipeline_layout),
+        };
+
+        return Ok(GpuSolver {
+            adapter_name: adapter.get_info().name,
+            device,
+            queue,
+            dim: grid.dim,
+            cell_width: grid.cell_width,
+            over_relaxation: grid.over_relaxation,
+            buffers,
+            bind_groups,
+            pipelines,
+            smoke_texture,
+            render_bind_group,
+        });
+    }
+
+    /// The name of the used GPU.
+    pub fn adapter_name(&self) -> &str {
+        return &self.adapter_name;
+    }
+
+    /// The bytes of the buffers and of the `smoke_texture`. The mappable staging
+    /// buffer is reported on the host, where drivers usually place it.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        let field = FieldLayout::new::<f32>(self.dim, 1).size() * std::mem::size_of::<f32>();
+        let device = MemoryLocation::Device;
+
+        report.add("params", device, 2 * std::mem::size_of::<Params>());
+        report.add("velocity", device, 2 * field);
+        report.add("velocity-tmp", device, 2 * field);
+        report.add("smoke", device, field);
+        report.add("smoke-tmp", device, field);
+        report.add("pressure", device, field);
+        report.add("div", device, field);
+        report.add("fluid", device, field);
+        report.add("staging", MemoryLocation::Host, 5 * field);
+        report.add("render", device, std::mem::size_of::<RenderUniforms>());
+        report.add("colormap", device, COLORMAP_SIZE * std::mem::size_of::<u32>());
+        report.add("smoke-texture", device, 4 * self.dim.x * self.dim.y);
+
+        return report;
+    }
+
+    /// The device on which the fields and the `smoke_texture` live.
+    pub fn device(&self) -> &wgpu::Device {
+        return &self.device;
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        return &self.queue;
+    }
+
+    /// The `Rgba8Unorm` texture of `render_smoke` with one texel per cell
+    /// (the first row is the top row of the grid).
+    pub fn smoke_texture(&self) -> &wgpu::Texture {
+        return &self.smoke_texture;
+    }
+
+    /// Submit the coloring of the smoke on the GPU into `smoke_texture` as
+    /// `render::render_smoke` with `scale = 1` and the colormap sampled at
+    /// `COLORMAP_SIZE` values. Renders the fields of the last `step`. `range` is the
+    /// resolved value range of `params.range` (a `Fixed` range is used as is).
+    pub fn render_smoke(&self, params: &RenderParams, range: (Scalar, Scalar)) {
+        let (min, max) = match params.range {
+            ValueRange::Fixed(min, max) => (min, max),
+            _ => range,
+        };
+
+        let cg = params.colormap.gradient();
+        let colormap: Vec<u32> = (0..COLORMAP_SIZE)
+            .map(|i| {
+                let t = i as f64 / (COLORMAP_SIZE - 1) as f64;
+                return u32::from_le_bytes(cg.at(t).to_rgba8());
+            })
+            .collect();
+
+        let uniforms = RenderUniforms {
+            min: min as f32,
+            scale: if max > min { 1.0 / (max - min) as f32 } else { 1.0 },
+            gamma_inv: (1.0 / params.gamma) as f32,
+            solid: u32::from_le_bytes(params.solid_color.to_rgba8()),
+        };
+
+        self.queue
+            .write_buffer(&self.buffers.colormap, 0, bytemuck::cast_slice(&colormap));
+        self.queue
+            .write_buffer(&self.buffers.render, 0, bytemuck::bytes_of(&uniforms));
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let groups = self.workgroups();
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&self.pipelines.render_smoke);
+            pass.set_bind_group(0, &self.bind_groups[0], &[]);
+            pass.set_bind_group(1, &self.render_bind_group, &[]);
+            pass.dispatch_workgroups(groups[0], groups[1], 1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Copy the velocity, the smoke and the solid cells of `grid` to the GPU.
+    pub fn upload(&mut self, grid: &Grid) -> GenericResult<()> {
+        self.upload_velocity(grid)?;
+
+        self.write(&self.buffers.smoke, &field(grid, |c| [c.smoke()]));
+        self.write(&self.buffers.pressure, &field(grid, |c| [c.pressure]));
+
+        return Ok(());
+    }
+
+    /// Copy the velocity, the divergence and the solid cells of `grid` to the GPU,
+    /// which is all the projection needs.
+    pub fn upload_velocity(&mut self, grid: &Grid) -> GenericResult<()> {
+        self.check_dim(grid)?;
+
+        self.cell_width = grid.cell_width;
+        self.over_relaxation = grid.over_relaxation;
+
+        let velocity = field(grid, |c| [c.velocity.back.x, c.velocity.back.y]);
+        self.write(&self.buffers.velocity, &velocity);
+        self.write(&self.buffers.div, &field(grid, |c| [c.div]));
+        self.write(
+            &self.buffers.fluid,
+            &field(grid, |c| [if c.mode == CellTypes::Solid { 0.0 } else { 1.0 }]),
+        );
+
+        return Ok(());
+    }
+
+    /// Copy the `smoke_texture` back to the CPU (e.g. for a screenshot).
+    /// Blocks until the rendering is finished.
+    pub fn smoke_image(&self) -> GenericResult<RgbaImage> {
+        let (width, height) = (self.dim.x as u32, self.dim.y as u32);
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let row = (4 * width + align - 1) / align * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("smoke-image"),
+            size: (row * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            self.smoke_texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap_or(()));
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let mut img = RgbaImage::new(width, height);
+        {
+            let view = slice.get_mapped_range();
+            let rows = img.chunks_exact_mut(4 * width as usize);
+            for (dst, src) in rows.zip(view.chunks_exact(row as usize)) {
+                dst.copy_from_slice(&src[..dst.len()]);
+            }
+        }
+        buffer.unmap();
+
+        return Ok(img);
+    }
+
+    fn write(&self, buffer: &wgpu::Buffer, values: &AlignedField<f32>) {
+        self.queue
+            .write_buffer(buffer, 0, bytemuck::cast_slice(values.as_slice()));
+    }
+
+    /// Copy the velocity, the smoke, the pressure and the divergence back to `grid`.
+    /// Blocks until all submitted steps are finished.
+    pub fn download(&self, grid: &mut Grid) -> GenericResult<()> {
+        self.check_dim(grid)?;
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        self.encode_download(&mut encoder, true);
+        self.queue.submit(Some(encoder.finish()));
+
+        return self.read_staging(grid, true);
+    }
+
+    /// Copy the velocity, the pressure and the divergence of `project` back to `grid`.
+    /// Blocks until the projection is finished.
+    pub fn download_projection(&self, grid: &mut Grid) -> GenericResult<()> {
+        self.check_dim(grid)?;
+        return self.read_staging(grid, false);
+    }
+
+    fn check_dim(&self, grid: &Grid) -> GenericResult<()> {
+        if grid.dim != self.dim {
+            bail!(
+                "Grid dimension {} differs from the solver's {}.",
+                grid.dim,
+                self.dim
+            );
+        }
+        return Ok(());
+    }
+
+    fn layout(&self, components: usize) -> FieldLayout {
+        return FieldLayout::new::<f32>(self.dim, components);
+    }
+
+    /// The size in bytes of a field with `components` values per cell.
+    fn field_size(&self, components: usize) -> u64 {
+        return (self.layout(components).size() * std::mem::size_of::<f32>()) as u64;
+    }
+
+    /// Record the copies of the velocity, the pressure, the divergence and optionally
+    /// the smoke into the staging buffer (in this order).
+    fn encode_download(&self, encoder: &mut wgpu::CommandEncoder, with_smoke: bool) {
+        let b = &self.buffers;
+        let mut fields = vec![(&b.velocity, 2), (&b.pressure, 1), (&b.div, 1)];
+        if with_smoke {
+            fields.push((&b.smoke, 1));
+        }
+
+        let mut offset = 0;
+        for (buffer, components) in fields {
+            let size = self.field_size(components);
+            encoder.copy_buffer_to_buffer(buffer, 0, &b.staging, offset, size);
+            offset += size;
+        }
+    }
+
+    /// Map the staging buffer of `encode_download` and copy the fields into `grid`.
+    fn read_staging(&self, grid: &mut Grid, with_smoke: bool) -> GenericResult<()> {
+        let size = self.field_size(if with_smoke { 5 } else { 4 });
+
+        let slice = self.buffers.staging.slice(0..size);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap_or(()));
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        {
+            let view = slice.get_mapped_range();
+            let values = bytemuck::cast_slice::<u8, f32>(&view);
+            let (scalars, vectors) = (self.layout(1), self.layout(2));
+            let (velocity, rest) = values.split_at(vectors.size());
+            let (pressure, rest) = rest.split_at(scalars.size());
+            let (div, smoke) = rest.split_at(scalars.size());
+
+            for idx in grid.iter_index().collect::<Vec<_>>() {
+                let (i, j) = (scalars.data_index(idx), vectors.data_index(idx));
+                let cell = grid.cell_mut(idx);
+                cell.velocity.back = vec2!(velocity[j] as Scalar, velocity[j + 1] as Scalar);
+                cell.pressure = pressure[i] as Scalar;
+                cell.div = div[i] as Scalar;
+                if with_smoke {
+                    cell.set_smoke(smoke[i] as Scalar);
+                }
+            }
+        }
+        self.buffers.staging.unmap();
+
+        return Ok(());
+    }
+
+    /// Submit one time step: integration with `gravity`, `iterations` red-black
+    /// pressure sweeps and the advection of the velocity and the smoke.
+    /// Mirrors the CPU solver without vorticity confinement.
+    pub fn step(&mut self, dt: Scalar, gravity: Vector2, iterations: u64, density: Scalar) {
+        self.write_params(dt, gravity, density);
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        self.encode_projection(&mut encoder, iterations);
+
+        self.encode_kernel(&mut encoder, &self.pipelines.advect_velocity);
+        encoder.copy_buffer_to_buffer(
+            &self.buffers.velocity_tmp,
+            0,
+            &self.buffers.velocity,
+            0,
+            self.field_size(2),
+        );
+
+        self.encode_kernel(&mut encoder, &self.pipelines.advect_smoke);
+        encoder.copy_buffer_to_buffer(
+            &self.buffers.smoke_tmp,
+            0,
+            &self.buffers.smoke,
+            0,
+            self.field_size(1),
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Submit the integration with `gravity` and `iterations` red-black pressure sweeps
+    /// without the advection. The copies for `download_projection` are part of the same
+    /// submission, so the transfer starts as soon as the sweeps are done.
+    pub fn project(&mut self, dt: Scalar, gravity: Vector2, iterations: u64, density: Scalar) {
+        self.write_params(dt, gravity, density);
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        self.encode_projection(&mut encoder, iterations);
+        self.encode_download(&mut encoder, false);
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    fn write_params(&self, dt: Scalar, gravity: Vector2, density: Scalar) {
+        let params = |color: u32| Params {
+            dim: [self.dim.x as u32, self.dim.y as u32],
+            h: self.cell_width as f32,
+            dt: dt as f32,
+            gravity: [gravity.x as f32, gravity.y as f32],
+            cp: (density * self.cell_width / dt) as f32,
+            r: self.over_relaxation as f32,
+            color,
+            stride: self.layout(1).stride as u32,
+            pad: [0; 2],
+        };
+        for color in 0..2 {
+            self.queue.write_buffer(
+                &self.buffers.params[color],
+                0,
+                bytemuck::bytes_of(&params(color as u32)),
+            );
+        }
+    }
+
+    fn workgroups(&self) -> [u32; 2] {
+        return [
+            (self.dim.x as u32 + WORKGROUP - 1) / WORKGROUP,
+            (self.dim.y as u32 + WORKGROUP - 1) / WORKGROUP,
+        ];
+    }
+
+    /// Record the integration, the extrapolation and the pressure sweeps.
+    fn encode_projection(&self, encoder: &mut wgpu::CommandEncoder, iterations: u64) {
+        let groups = self.workgroups();
+        encoder.clear_buffer(&self.buffers.pressure, 0, None);
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        let mut dispatch = |pipeline: &wgpu::ComputePipeline, color: usize| {
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &self.bind_groups[color], &[]);
+            pass.dispatch_workgroups(groups[0], groups[1], 1);
+        };
+
+        dispatch(&self.pipelines.integrate, 0);
+        dispatch(&self.pipelines.extrapolate, 0);
+
+        for _ in 0..iterations {
+            dispatch(&self.pipelines.pressure_sweep, 0);
+            dispatch(&self.pipelines.pressure_sweep, 1);
+        }
+    }
+
+    /// Record a pass of `pipeline` over all cells.
+    fn encode_kernel(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline) {
+        let groups = self.workgroups();
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_groups[0], &[]);
+        pass.dispatch_workgroups(groups[0], groups[1], 1);
+    }
+}
@@ -0,0 +1,242 @@
+// Compute kernels of the GPU backend.
+// Mirrors the CPU solver in `scene/grid.rs`: cells are stored `x`-fastest including the
+// border cells and the staggered velocity is interleaved (`u`, `v` per cell).
+// The rows are padded to `stride` values (see `FieldLayout`).
+
+struct Params {
+    dim: vec2<u32>,
+    h: f32,
+    dt: f32,
+    gravity: vec2<f32>,
+    // Pressure factor `density * h / dt`.
+    cp: f32,
+    // Over-relaxation.
+    r: f32,
+    // Red-black color of the pressure sweep.
+    color: u32,
+    // Row stride of the fields with one value per cell.
+    stride: u32,
+    pad1: u32,
+    pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> velocity: array<f32>;
+@group(0) @binding(2) var<storage, read_write> velocity_tmp: array<f32>;
+@group(0) @binding(3) var<storage, read_write> smoke: array<f32>;
+@group(0) @binding(4) var<storage, read_write> smoke_tmp: array<f32>;
+@group(0) @binding(5) var<storage, read_write> pressure: array<f32>;
+@group(0) @binding(6) var<storage, read_write> div: array<f32>;
+// `0`: solid, `1`: fluid.
+@group(0) @binding(7) var<storage, read> fluid: array<f32>;
+
+struct RenderParams {
+    min: f32,
+    // The inverse of the value range.
+    scale: f32,
+    gamma_inv: f32,
+    // Packed RGBA color of the solid cells.
+    solid: u32,
+}
+
+@group(1) @binding(0) var<uniform> render: RenderParams;
+@group(1) @binding(1) var<storage, read> colormap: array<u32>;
+@group(1) @binding(2) var smoke_texture: texture_storage_2d<rgba8unorm, write>;
+
+fn offset(i: u32, j: u32) -> u32 {
+    return i + j * params.stride;
+}
+
+fn vel(i: u32, j: u32, dir: u32) -> f32 {
+    return velocity[2u * offset(i, j) + dir];
+}
+
+fn is_inside(id: vec3<u32>) -> bool {
+    return id.x >= 1u && id.y >= 1u && id.x + 1u < params.dim.x && id.y + 1u < params.dim.y;
+}
+
+fn is_fluid(i: u32, j: u32) -> bool {
+    return fluid[offset(i, j)] != 0.0;
+}
+
+// The staggered offset of the velocity component `dir`.
+fn stagger(dir: i32) -> vec2<f32> {
+    if (dir == 0) {
+        return vec2<f32>(0.0, 0.5 * params.h);
+    }
+    if (dir == 1) {
+        return vec2<f32>(0.5 * params.h, 0.0);
+    }
+    return vec2<f32>(0.0, 0.0);
+}
+
+// Bilinear sample of the velocity component `dir` (or the smoke if `dir < 0`)
+// with indices clamped to `[lo, hi)`, see `Grid::sample_field`.
+fn sample(p: vec2<f32>, dir: i32, lo: vec2<u32>, hi: vec2<u32>) -> f32 {
+    let h = params.h;
+    let extent = vec2<f32>(params.dim) * h;
+    let pos = clamp(p - stagger(dir), vec2<f32>(0.0, 0.0), extent);
+
+    let i0 = min(max(u32(pos.x / h), lo.x), hi.x - 1u);
+    let j0 = min(max(u32(pos.y / h), lo.y), hi.y - 1u);
+    let i1 = min(i0 + 1u, hi.x - 1u);
+    let j1 = min(j0 + 1u, hi.y - 1u);
+
+    let ax = clamp((pos.x - f32(i0) * h) / h, 0.0, 1.0);
+    let ay = clamp((pos.y - f32(j0) * h) / h, 0.0, 1.0);
+
+    var v00: f32;
+    var v01: f32;
+    var v10: f32;
+    var v11: f32;
+    if (dir < 0) {
+        v00 = smoke[offset(i0, j0)];
+        v01 = smoke[offset(i0, j1)];
+        v10 = smoke[offset(i1, j0)];
+        v11 = smoke[offset(i1, j1)];
+    } else {
+        let d = u32(dir);
+        v00 = vel(i0, j0, d);
+        v01 = vel(i0, j1, d);
+        v10 = vel(i1, j0, d);
+        v11 = vel(i1, j1, d);
+    }
+
+    let top = v01 * (1.0 - ax) + v11 * ax;
+    let bottom = v00 * (1.0 - ax) + v10 * ax;
+    return ay * top + (1.0 - ay) * bottom;
+}
+
+@compute @workgroup_size(8, 8)
+fn integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.dim.x || id.y >= params.dim.y || !is_fluid(id.x, id.y)) {
+        return;
+    }
+
+    let k = 2u * offset(id.x, id.y);
+    velocity[k] += params.dt * params.gravity.x;
+    velocity[k + 1u] += params.dt * params.gravity.y;
+}
+
+// Extrapolate the velocity to the fluid border cells by clamped sampling on the inside.
+@compute @workgroup_size(8, 8)
+fn extrapolate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.dim.x || id.y >= params.dim.y || is_inside(id) || !is_fluid(id.x, id.y)) {
+        return;
+    }
+
+    let lo = vec2<u32>(1u, 1u);
+    let hi = params.dim - vec2<u32>(1u, 1u);
+    let pos = vec2<f32>(f32(id.x), f32(id.y)) * params.h;
+
+    let k = 2u * offset(id.x, id.y);
+    velocity[k] = sample(pos + stagger(0), 0, lo, hi);
+    velocity[k + 1u] = sample(pos + stagger(1), 1, lo, hi);
+}
+
+// One red-black Gauss-Seidel sweep over the cells with `(i + j) % 2 == color`.
+// Cells of the same color share no face, hence all cells of a sweep are independent.
+@compute @workgroup_size(8, 8)
+fn pressure_sweep(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let j = id.y;
+    if (!is_inside(id) || (i + j) % 2u != params.color || !is_fluid(i, j)) {
+        return;
+    }
+
+    let s_neg = vec2<f32>(fluid[offset(i - 1u, j)], fluid[offset(i, j - 1u)]);
+    let s_pos = vec2<f32>(fluid[offset(i + 1u, j)], fluid[offset(i, j + 1u)]);
+    let s = s_neg.x + s_neg.y + s_pos.x + s_pos.y;
+    if (s == 0.0) {
+        return;
+    }
+
+    let k = offset(i, j);
+    let d = vel(i + 1u, j, 0u) - vel(i, j, 0u) + vel(i, j + 1u, 1u) - vel(i, j, 1u);
+    div[k] = d;
+
+    let d_normed = d / s;
+    pressure[k] -= params.cp * d_normed;
+
+    let r = params.r;
+    velocity[2u * k] += r * s_neg.x * d_normed;
+    velocity[2u * k + 1u] += r * s_neg.y * d_normed;
+    velocity[2u * offset(i + 1u, j)] -= r * s_pos.x * d_normed;
+    velocity[2u * offset(i, j + 1u) + 1u] -= r * s_pos.y * d_normed;
+}
+
+@compute @workgroup_size(8, 8)
+fn advect_velocity(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let j = id.y;
+    if (i >= params.dim.x || j >= params.dim.y) {
+        return;
+    }
+
+    let k = 2u * offset(i, j);
+    var advected = vec2<f32>(velocity[k], velocity[k + 1u]);
+
+    if (is_inside(id) && is_fluid(i, j)) {
+        let lo = vec2<u32>(1u, 1u);
+        let hi = params.dim - vec2<u32>(1u, 1u);
+        let neg_fluid = vec2<bool>(is_fluid(i - 1u, j), is_fluid(i, j - 1u));
+
+        for (var dir = 0; dir < 2; dir = dir + 1) {
+            if (!neg_fluid[dir]) {
+                continue;
+            }
+
+            var pos = vec2<f32>(f32(i), f32(j)) * params.h + stagger(dir);
+            var v = vec2<f32>(velocity[k], velocity[k + 1u]);
+
+            let other = (dir + 1) % 2;
+            v[other] = sample(pos, other, lo, hi);
+
+            pos -= params.dt * v;
+            advected[dir] = sample(pos, dir, lo, hi);
+        }
+    }
+
+    velocity_tmp[k] = advected.x;
+    velocity_tmp[k + 1u] = advected.y;
+}
+
+@compute @workgroup_size(8, 8)
+fn advect_smoke(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    let j = id.y;
+    if (i >= params.dim.x || j >= params.dim.y) {
+        return;
+    }
+
+    let k = offset(i, j);
+    if (!is_inside(id) || !is_fluid(i, j)) {
+        smoke_tmp[k] = smoke[k];
+        return;
+    }
+
+    // The velocity at the cell center, the smoke is sampled relative to the centers.
+    let v = 0.5 * vec2<f32>(vel(i, j, 0u) + vel(i + 1u, j, 0u), vel(i, j, 1u) + vel(i, j + 1u, 1u));
+    let pos = vec2<f32>(f32(i), f32(j)) * params.h - params.dt * v;
+
+    smoke_tmp[k] = sample(pos, -1, vec2<u32>(0u, 0u), params.dim);
+}
+
+// Color the smoke with the colormap table, see `render_smoke` in `render/raster.rs`.
+@compute @workgroup_size(8, 8)
+fn render_smoke(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.dim.x || id.y >= params.dim.y) {
+        return;
+    }
+
+    var color = render.solid;
+    if (is_fluid(id.x, id.y)) {
+        let t = clamp((smoke[offset(id.x, id.y)] - render.min) * render.scale, 0.0, 1.0);
+        let n = arrayLength(&colormap) - 1u;
+        color = colormap[min(u32(pow(t, render.gamma_inv) * f32(n) + 0.5), n)];
+    }
+
+    // The texture rows go downwards.
+    let texel = vec2<i32>(i32(id.x), i32(params.dim.y - 1u - id.y));
+    textureStore(smoke_texture, texel, unpack4x8unorm(color));
+}
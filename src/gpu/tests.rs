@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::gpu::GpuSolver;
+    use crate::log::create_logger;
+    use crate::scene::grid::*;
+    use crate::scene::tests::fixtures::{centroid, create_blob};
+    use crate::scene::solver_params::SolverParamsBuilder;
+    use crate::scene::timestepper::{ExecutionMode, Integrate};
+    use crate::types::*;
+
+    fn create() -> Grid {
+        let mut grid = Grid::new(dim!(24, 16), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let p = idx.cast::<Scalar>();
+            let c = grid.cell_mut(idx);
+            c.velocity.back = vec2!((0.3 * p.x).sin(), (0.5 * p.y).cos());
+            c.set_smoke((0.1 * (p.x + 2.0 * p.y)).fract());
+        }
+        grid.add_obstacle(vec2!(1.0, 0.8), 0.3, None);
+        return grid;
+    }
+
+    #[test]
+    fn check_gpu_matches_cpu() -> GenericResult<()> {
+        let (log, _) = create_logger();
+        let (dt, gravity) = (0.01, vec2!(0.0, -9.81));
+
+        let mut cpu = create();
+        let mut gpu = create();
+
+        let mut solver = match GpuSolver::new(&gpu) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no GPU available: {}", e);
+                return Ok(());
+            }
+        };
+
+        // Without pressure iterations both solvers compute the same (up to precision).
+        let params = SolverParamsBuilder::default().gravity(gravity).build()?;
+        cpu.integrate(&log, dt, &params);
+        cpu.advect(&log, dt, ExecutionMode::Single);
+
+        solver.upload(&gpu)?;
+        solver.step(dt, gravity, 0, 1000.0);
+        solver.download(&mut gpu)?;
+
+        for idx in cpu.iter_index() {
+            let (a, b) = (cpu.cell(idx), gpu.cell(idx));
+            assert!(
+                (a.velocity.back - b.velocity.back).norm() < 1e-4,
+                "Velocity differs at {}: {} vs. {}.",
+                idx,
+                a.velocity.back,
+                b.velocity.back
+            );
+            assert!((a.smoke() - b.smoke()).abs() < 1e-4, "Smoke differs at {}.", idx);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_gpu_smoke_translation() -> GenericResult<()> {
+        let (log, _) = create_logger();
+        let (velocity, dt, steps) = (vec2!(1.0, 0.5), 0.03, 10);
+
+        let mut cpu = create_blob(velocity);
+        let mut gpu = create_blob(velocity);
+        let mut solver = match GpuSolver::new(&gpu) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no GPU available: {}", e);
+                return Ok(());
+            }
+        };
+
+        let expected = centroid(&cpu) + velocity * dt * steps as Scalar;
+        solver.upload(&gpu)?;
+        for _ in 0..steps {
+            cpu.advect(&log, dt, ExecutionMode::Single);
+            solver.step(dt, Vector2::zeros(), 0, 1000.0);
+        }
+        solver.download(&mut gpu)?;
+
+        // Both translate the smoke like the analytic solution (single precision on the GPU).
+        for (name, grid) in [("CPU", &cpu), ("GPU", &gpu)] {
+            let error = (centroid(grid) - expected).norm();
+            assert!(error < 0.01 * grid.cell_width, "{} smoke is off by {}.", name, error);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_gpu_pressure_solve() -> GenericResult<()> {
+        let mut solver = match GpuSolver::new(&create()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no GPU available: {}", e);
+                return Ok(());
+            }
+        };
+
+        let mut max_div = |iterations: u64| {
+            let mut grid = create();
+            solver.upload(&grid)?;
+            solver.step(0.01, Vector2::zeros(), iterations, 1000.0);
+            solver.download(&mut grid)?;
+
+            return GenericResult::Ok(
+                grid.iter_inside()
+                    .map(|idx| grid.cell(idx).div.abs())
+                    .fold(0.0, Scalar::max),
+            );
+        };
+
+        let few = max_div(2)?;
+        let many = max_div(100)?;
+        assert!(many < 0.5 * few, "Divergence {} not reduced from {}.", many, few);
+
+        // The projection alone gives the same pressure as a full step.
+        let mut full = create();
+        solver.upload(&full)?;
+        solver.step(0.01, vec2!(0.0, -9.81), 20, 1000.0);
+        solver.download(&mut full)?;
+
+        let mut projected = create();
+        solver.upload_velocity(&projected)?;
+        solver.project(0.01, vec2!(0.0, -9.81), 20, 1000.0);
+        solver.download_projection(&mut projected)?;
+
+        let initial = create();
+        for idx in full.iter_inside() {
+            let (a, b) = (full.cell(idx), projected.cell(idx));
+            assert!(a.pressure == b.pressure && a.div == b.div, "Pressure differs at {}.", idx);
+            assert!(b.smoke() == initial.cell(idx).smoke(), "Smoke changed at {}.", idx);
+        }
+
+        // A download into a different grid fails.
+        let mut other = Grid::new(dim!(4, 4), 0.1);
+        assert!(solver.download(&mut other).is_err());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_gpu_render_smoke() -> GenericResult<()> {
+        use crate::render::{render_smoke, RenderParamsBuilder};
+
+        let mut grid = create();
+        let mut solver = match GpuSolver::new(&grid) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Skipped, no GPU available: {}", e);
+                return Ok(());
+            }
+        };
+
+        solver.upload(&grid)?;
+        solver.step(0.01, Vector2::zeros(), 10, 1000.0);
+        solver.download(&mut grid)?;
+
+        let params = RenderParamsBuilder::default().gamma(0.8).build().unwrap();
+        solver.render_smoke(&params, (0.0, 1.0));
+        let gpu = solver.smoke_image()?;
+        let cpu = render_smoke(&grid, &params);
+
+        assert!(gpu.dimensions() == cpu.dimensions());
+        for (a, b) in gpu.pixels().zip(cpu.pixels()) {
+            let close = a.0.iter().zip(b.0).all(|(a, b)| (*a as i32 - b as i32).abs() <= 8);
+            assert!(close, "Colors differ: {:?} vs. {:?}", a, b);
+        }
+
+        return Ok(());
+    }
+}
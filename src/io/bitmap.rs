@@ -0,0 +1,70 @@
+use crate::types::*;
+
+use image::imageops::FilterType;
+
+/// Load an image as luminance in `[0, 1]` with `dim.x * dim.y` values
+/// (row-major, `y`-axis pointing upwards). The image is resampled to `dim`
+/// if `resample` is set, otherwise its size must match `dim`.
+pub fn load_luminance(file: &str, dim: Index2, resample: bool) -> GenericResult<Vec<Scalar>> {
+    let mut img = image::open(file)
+        .map_err(|e| format!("Could not load image '{}': {}", file, e))?
+        .into_luma16();
+
+    if img.width() as usize != dim.x || img.height() as usize != dim.y {
+        if !resample {
+            return Err(format!(
+                "Image '{}' has size {}x{} but needs {}x{}.",
+                file,
+                img.width(),
+                img.height(),
+                dim.x,
+                dim.y
+            )
+            .into());
+        }
+
+        img = image::imageops::resize(&img, dim.x as u32, dim.y as u32, FilterType::Triangle);
+    }
+
+    let mut values = Vec::with_capacity(dim.x * dim.y);
+    for y in 0..dim.y {
+        // Image rows go downwards.
+        let row = (dim.y - 1 - y) as u32;
+        for x in 0..dim.x {
+            values.push(img.get_pixel(x as u32, row).0[0] as Scalar / u16::MAX as Scalar);
+        }
+    }
+
+    return Ok(values);
+}
+
+/// Load a black/white image as mask with `true` for dark pixels
+/// (luminance below `threshold`) or bright ones if `invert` is set.
+pub fn load_mask(
+    file: &str,
+    dim: Index2,
+    threshold: Scalar,
+    invert: bool,
+    resample: bool,
+) -> GenericResult<Vec<bool>> {
+    return Ok(load_luminance(file, dim, resample)?
+        .into_iter()
+        .map(|l| (l < threshold) != invert)
+        .collect());
+}
+
+/// Load an image as field values `offset + scale * luminance`
+/// (or `1 - luminance` if `invert` is set).
+pub fn load_field(
+    file: &str,
+    dim: Index2,
+    scale: Scalar,
+    offset: Scalar,
+    invert: bool,
+    resample: bool,
+) -> GenericResult<Vec<Scalar>> {
+    return Ok(load_luminance(file, dim, resample)?
+        .into_iter()
+        .map(|l| offset + scale * if invert { 1.0 - l } else { l })
+        .collect());
+}
@@ -0,0 +1,506 @@
+use crate::scene::cell::Cell;
+use crate::scene::grid::{CellGetter, Grid, GridIndexIterator};
+use crate::scene::timestepper::{TimeStepper, TimeStepperState};
+use crate::types::*;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Bump this if the layout of the checkpoint changes.
+pub const CHECKPOINT_VERSION: u32 = 2;
+
+/// Magic bytes at the start of each checkpoint file.
+const MAGIC: &[u8; 4] = b"RFCK";
+
+/// Magic bytes at the start of each delta checkpoint file.
+const DELTA_MAGIC: &[u8; 4] = b"RFCD";
+
+/// Cells per side of the tiles which a delta checkpoint stores.
+pub const DELTA_TILE: usize = 16;
+
+/// Compression of the checkpoint data after the header.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Compression {
+    #[default]
+    None,
+
+    /// `zstd` with a level in `1..=22` (feature `zstd`).
+    Zstd(i32),
+}
+
+impl Compression {
+    pub(crate) fn id(&self) -> u8 {
+        return match self {
+            Compression::None => 0,
+            Compression::Zstd(_) => 1,
+        };
+    }
+
+    pub(crate) fn level(&self) -> i32 {
+        return match self {
+            Compression::None => 0,
+            Compression::Zstd(l) => *l,
+        };
+    }
+
+    /// Check the level of `zstd`.
+    pub fn validate(&self) -> GenericResult<()> {
+        if let Compression::Zstd(level) = self {
+            if !(1..=22).contains(level) {
+                return Err(format!("Zstd level must be in [1, 22]: {}", level).into());
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub(crate) fn from_header(id: u8, level: i32) -> GenericResult<Self> {
+        return match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Zstd(level)),
+            _ => Err(format!("Unknown checkpoint compression '{}'.", id).into()),
+        };
+    }
+}
+
+/// The header in front of the (compressed) data of a checkpoint or a state buffer
+/// (see `io::state`): magic, version (`u32`), compression (`u8`) and its level (`i32`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckpointHeader {
+    pub version: u32,
+    pub compression: Compression,
+}
+
+impl CheckpointHeader {
+    pub(crate) fn write<W: Write>(&self, magic: &[u8; 4], w: &mut W) -> GenericResult<()> {
+        w.write_all(magic)?;
+        w.write_all(&self.version.to_le_bytes())?;
+        w.write_all(&[self.compression.id()])?;
+        w.write_all(&self.compression.level().to_le_bytes())?;
+        return Ok(());
+    }
+
+    pub(crate) fn read<R: Read>(magic: &[u8; 4], r: &mut R) -> GenericResult<Self> {
+        let mut bytes = [0u8; 13];
+        r.read_exact(&mut bytes)?;
+
+        if &bytes[0..4] != magic {
+            let magic = String::from_utf8_lossy(magic);
+            return Err(format!("Not a '{}' file (or written by an older version).", magic).into());
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into()?);
+        let level = i32::from_le_bytes(bytes[9..13].try_into()?);
+
+        return Ok(CheckpointHeader {
+            version,
+            compression: Compression::from_header(bytes[8], level)?,
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    state: TimeStepperState,
+    grids: Vec<&'a Grid>,
+}
+
+#[derive(Deserialize)]
+struct CheckpointData {
+    state: TimeStepperState,
+    grids: Vec<Grid>,
+}
+
+/// A deserialized checkpoint with the timestepper state and all grids.
+pub struct Checkpoint {
+    pub header: CheckpointHeader,
+    pub state: TimeStepperState,
+    pub grids: Vec<Grid>,
+
+    /// If the delta checkpoint of the file was applied (see `DeltaCheckpoints`).
+    pub delta: bool,
+}
+
+/// The cells of the window `[min, max)` of a grid (the first dimension is the fastest).
+#[derive(Serialize, Deserialize)]
+struct Tile {
+    min: Index2,
+    max: Index2,
+    cells: Vec<Cell>,
+}
+
+/// The tiles of all grids which changed since the key-frame at time `key_time`.
+#[derive(Serialize, Deserialize)]
+struct CheckpointDelta {
+    key_time: Scalar,
+    state: TimeStepperState,
+    grids: Vec<Vec<Tile>>,
+}
+
+impl CheckpointDelta {
+    fn apply(self, checkpoint: &mut Checkpoint) -> GenericResult<()> {
+        if self.grids.len() != checkpoint.grids.len() {
+            return Err(format!(
+                "Delta checkpoint contains {} grids but the checkpoint has {}.",
+                self.grids.len(),
+                checkpoint.grids.len()
+            )
+            .into());
+        }
+
+        for (grid, tiles) in checkpoint.grids.iter_mut().zip(self.grids.into_iter()) {
+            for tile in tiles {
+                let size = tile.max - tile.min;
+                if !(tile.min <= tile.max && tile.max <= grid.dim)
+                    || tile.cells.len() != size.x * size.y
+                {
+                    bail!("Delta checkpoint does not match the grid.");
+                }
+
+                let indices = GridIndexIterator::new_range(tile.min, tile.max);
+                for (idx, cell) in indices.zip(tile.cells.into_iter()) {
+                    *grid.cell_mut(idx) = cell;
+                }
+            }
+        }
+
+        checkpoint.state = self.state;
+        checkpoint.delta = true;
+
+        return Ok(());
+    }
+}
+
+pub(crate) fn grids<'a>(timestepper: &'a TimeStepper) -> Vec<&'a Grid> {
+    return timestepper
+        .objects
+        .iter()
+        .filter_map(|o| o.as_any().downcast_ref::<Grid>())
+        .collect();
+}
+
+/// The path of the delta checkpoint which belongs to the checkpoint `file`.
+pub fn delta_path(file: &str) -> String {
+    return format!("{}.delta", file);
+}
+
+enum SnapshotData {
+    Full {
+        state: TimeStepperState,
+        grids: Vec<Grid>,
+    },
+    Delta(CheckpointDelta),
+}
+
+/// An owned copy of the checkpoint data of a timestepper,
+/// e.g. to write it on another thread.
+pub struct CheckpointSnapshot {
+    data: SnapshotData,
+}
+
+impl CheckpointSnapshot {
+    pub fn new(timestepper: &TimeStepper) -> Self {
+        return CheckpointSnapshot {
+            data: SnapshotData::Full {
+                state: timestepper.state(),
+                grids: grids(timestepper).into_iter().map(Grid::snapshot).collect(),
+            },
+        };
+    }
+
+    /// Write a full checkpoint to `file` or a delta to `delta_path(file)`.
+    pub fn write(self, file: &str, compression: Compression) -> GenericResult<()> {
+        return match self.data {
+            SnapshotData::Full { state, grids } => {
+                let checkpoint = CheckpointRef {
+                    state,
+                    grids: grids.iter().collect(),
+                };
+                write_checkpoint(&checkpoint, file, compression)
+            }
+            SnapshotData::Delta(delta) => {
+                write_data(&delta, DELTA_MAGIC, &delta_path(file), compression)
+            }
+        };
+    }
+}
+
+/// Writes a full checkpoint (a key-frame) every `key_interval` checkpoints and in
+/// between only deltas: the tiles of `DELTA_TILE x DELTA_TILE` cells in which the
+/// velocity, the pressure or the smoke of a cell changed by more than `tolerance` (or a
+/// cell changed its mode) since the key-frame.
+///
+/// A delta replaces the last delta next to the key-frame (see `delta_path`), and
+/// `read_checkpoint` applies it. The unchanged tiles are restored from the key-frame,
+/// hence they are off by at most `tolerance` (exact with `0.0`). The deltas grow with
+/// the moving parts of the flow, which are small in quiescent or sparse scenes.
+/// A copy of the grids at the key-frame is kept in memory.
+pub struct DeltaCheckpoints {
+    pub key_interval: u64,
+    pub tolerance: Scalar,
+
+    count: u64,
+
+    /// The time and the grids of the last key-frame.
+    key: Option<(Scalar, Vec<Grid>)>,
+}
+
+impl DeltaCheckpoints {
+    pub fn new(key_interval: u64, tolerance: Scalar) -> Self {
+        return DeltaCheckpoints {
+            key_interval: key_interval.max(1),
+            tolerance,
+            count: 0,
+            key: None,
+        };
+    }
+
+    /// The next checkpoint of `timestepper`: a key-frame or a delta.
+    /// Fails if the dimension of a grid changed since the key-frame.
+    pub fn snapshot(&mut self, timestepper: &TimeStepper) -> FluidResult<CheckpointSnapshot> {
+        let grids = grids(timestepper);
+        let is_key = self.count % self.key_interval == 0
+            || self.key.as_ref().map_or(true, |k| k.1.len() != grids.len());
+
+        if is_key {
+            let key = grids.iter().map(|g| g.snapshot()).collect();
+            self.key = Some((timestepper.time(), key));
+            self.count = 1;
+            return Ok(CheckpointSnapshot::new(timestepper));
+        }
+
+        let (key_time, key) = self.key.as_ref().unwrap();
+        let tiles = grids
+            .iter()
+            .zip(key.iter())
+            .map(|(grid, key)| changed_tiles(grid, key, self.tolerance))
+            .collect::<FluidResult<_>>()?;
+        self.count += 1;
+
+        return Ok(CheckpointSnapshot {
+            data: SnapshotData::Delta(CheckpointDelta {
+                key_time: *key_time,
+                state: timestepper.state(),
+                grids: tiles,
+            }),
+        });
+    }
+}
+
+/// The tiles of `grid` with a cell which changed by more than `tolerance` since `key`.
+/// Fails if the dimension of `grid` is not the one of `key`.
+fn changed_tiles(grid: &Grid, key: &Grid, tolerance: Scalar) -> FluidResult<Vec<Tile>> {
+    if grid.dim != key.dim {
+        return Err(Error::DimensionMismatch {
+            dim: grid.dim,
+            expected: key.dim,
+        });
+    }
+
+    let changed = |a: &Cell, b: &Cell| {
+        return a.mode != b.mode
+            || (a.velocity.back - b.velocity.back).amax() > tolerance
+            || (a.pressure - b.pressure).abs() > tolerance
+            || (a.smoke() - b.smoke()).abs() > tolerance;
+    };
+
+    let mut tiles = vec![];
+    for y in (0..grid.dim.y).step_by(DELTA_TILE) {
+        for x in (0..grid.dim.x).step_by(DELTA_TILE) {
+            let min = idx!(x, y);
+            let max = (min + idx!(DELTA_TILE, DELTA_TILE)).inf(&grid.dim);
+
+            let mut indices = GridIndexIterator::new_range(min, max);
+            if indices.any(|i| changed(grid.cell(i), key.cell(i))) {
+                let cells = GridIndexIterator::new_range(min, max);
+                tiles.push(Tile {
+                    min,
+                    max,
+                    cells: cells.map(|i| grid.cell(i).clone()).collect(),
+                });
+            }
+        }
+    }
+
+    return Ok(tiles);
+}
+
+pub fn save_checkpoint(
+    timestepper: &TimeStepper,
+    file: &str,
+    compression: Compression,
+) -> GenericResult<()> {
+    let checkpoint = CheckpointRef {
+        state: timestepper.state(),
+        grids: grids(timestepper),
+    };
+    return write_checkpoint(&checkpoint, file, compression);
+}
+
+/// Write a full checkpoint and remove the delta of the previous key-frame.
+fn write_checkpoint(
+    checkpoint: &CheckpointRef,
+    file: &str,
+    compression: Compression,
+) -> GenericResult<()> {
+    write_data(checkpoint, MAGIC, file, compression)?;
+
+    let delta = delta_path(file);
+    if Path::new(&delta).exists() {
+        std::fs::remove_file(delta)?;
+    }
+
+    return Ok(());
+}
+
+fn write_data<T: Serialize>(
+    data: &T,
+    magic: &[u8; 4],
+    file: &str,
+    compression: Compression,
+) -> GenericResult<()> {
+    compression.validate()?;
+    let header = CheckpointHeader {
+        version: CHECKPOINT_VERSION,
+        compression,
+    };
+
+    let mut w = BufWriter::new(File::create(file)?);
+    header.write(magic, &mut w)?;
+
+    match compression {
+        Compression::None => bincode::serialize_into(&mut w, data)?,
+
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => {
+            let mut z = zstd::Encoder::new(&mut w, level)?;
+            bincode::serialize_into(&mut z, data)?;
+            z.finish()?;
+        }
+
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd(_) => bail!("Compressed checkpoints need the feature 'zstd'."),
+    }
+
+    w.flush()?;
+
+    return Ok(());
+}
+
+/// Read the checkpoint `file` and apply its delta checkpoint if there is one
+/// for this key-frame (see `DeltaCheckpoints`).
+pub fn read_checkpoint(file: &str) -> GenericResult<Checkpoint> {
+    let (header, data): (_, CheckpointData) = read_data(MAGIC, file)?;
+
+    let mut checkpoint = Checkpoint {
+        header,
+        state: data.state,
+        grids: data.grids,
+        delta: false,
+    };
+
+    let delta = delta_path(file);
+    if Path::new(&delta).exists() {
+        let (_, delta): (_, CheckpointDelta) = read_data(DELTA_MAGIC, &delta)?;
+
+        // A delta of an older key-frame is stale (e.g. after an interrupted write).
+        if delta.key_time == checkpoint.state.t {
+            delta.apply(&mut checkpoint)?;
+        }
+    }
+
+    return Ok(checkpoint);
+}
+
+fn read_data<T: DeserializeOwned>(
+    magic: &[u8; 4],
+    file: &str,
+) -> GenericResult<(CheckpointHeader, T)> {
+    let mut r = BufReader::new(File::open(file)?);
+    let header = CheckpointHeader::read(magic, &mut r)?;
+
+    if header.version != CHECKPOINT_VERSION {
+        return Err(format!(
+            "Checkpoint version '{}' is not supported (expected '{}').",
+            header.version, CHECKPOINT_VERSION
+        )
+        .into());
+    }
+
+    let data: T = match header.compression {
+        Compression::None => bincode::deserialize_from(r)?,
+
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(_) => bincode::deserialize_from(zstd::Decoder::with_buffer(r)?)?,
+
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd(_) => bail!("Compressed checkpoints need the feature 'zstd'."),
+    };
+
+    return Ok((header, data));
+}
+
+pub fn load_checkpoint(timestepper: &mut TimeStepper, file: &str) -> GenericResult<()> {
+    let checkpoint = read_checkpoint(file)?;
+    return restore(timestepper, checkpoint.state, checkpoint.grids);
+}
+
+/// Fail if the cells of the read `grid` do not fill its dimension.
+pub(crate) fn check_cells(grid: &Grid) -> GenericResult<()> {
+    if grid.cells().len() != grid.dim.x * grid.dim.y {
+        return Err(format!(
+            "Grid has {} cells but the dimension {}.",
+            grid.cells().len(),
+            grid.dim
+        )
+        .into());
+    }
+    return Ok(());
+}
+
+/// Set the state and the cells of the grids of `timestepper` (of the same scene).
+/// The settings of the grids (e.g. the boundary conditions, the over-relaxation or the
+/// activity threshold) are the ones of the scene, not the ones of the read grids.
+pub(crate) fn restore(
+    timestepper: &mut TimeStepper,
+    state: TimeStepperState,
+    grids: Vec<Grid>,
+) -> GenericResult<()> {
+    let mut targets: Vec<&mut Grid> = timestepper
+        .objects
+        .iter_mut()
+        .filter_map(|o| o.as_any_mut().downcast_mut::<Grid>())
+        .collect();
+
+    if targets.len() != grids.len() {
+        return Err(format!(
+            "Checkpoint contains {} grids but the scene has {}.",
+            grids.len(),
+            targets.len()
+        )
+        .into());
+    }
+
+    for (target, grid) in targets.iter().zip(grids.iter()) {
+        if target.dim != grid.dim {
+            return Err(format!(
+                "Checkpoint grid dimension {} is not {} of the scene.",
+                grid.dim, target.dim
+            )
+            .into());
+        }
+        check_cells(grid)?;
+    }
+
+    // Only the cells and the statistics, the settings of the scene are kept.
+    for (target, grid) in targets.iter_mut().zip(grids.iter()) {
+        target.restore(grid)?;
+    }
+
+    timestepper.set_state(state);
+
+    return Ok(());
+}
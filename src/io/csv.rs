@@ -0,0 +1,98 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+
+/// Write all cells of the grid as CSV with one row per cell.
+/// The velocity is interpolated to the cell centers.
+/// The indices are local to the grid, the positions are in world coordinates.
+pub fn write_field_csv(grid: &Grid, file: &str) -> GenericResult<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+
+    writeln!(w, "i,j,x,y,solid,pressure,smoke,div,vel_x,vel_y")?;
+
+    for idx in grid.iter_index() {
+        let cell = grid.cell(idx);
+        let pos = grid.origin_position() + grid.cell_center(idx);
+        let vel = grid.cell_center_velocity(idx);
+
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{},{},{},{}",
+            idx.x,
+            idx.y,
+            pos.x,
+            pos.y,
+            (cell.mode == CellTypes::Solid) as u8,
+            cell.pressure,
+            cell.smoke(),
+            cell.div,
+            vel.x,
+            vel.y
+        )?;
+    }
+
+    w.flush()?;
+    return Ok(());
+}
+
+/// Writer for per-step scalar diagnostics with one row per step.
+pub struct TimeSeriesCsv {
+    writer: BufWriter<File>,
+}
+
+impl TimeSeriesCsv {
+    pub fn new(file: &str) -> GenericResult<Self> {
+        return TimeSeriesCsv::open(file, false);
+    }
+
+    /// Open the file. With `append` the rows are appended to an existing file.
+    pub fn open(file: &str, append: bool) -> GenericResult<Self> {
+        let exists = std::path::Path::new(file).metadata().map_or(false, |m| m.len() > 0);
+
+        let f = if append {
+            OpenOptions::new().create(true).append(true).open(file)?
+        } else {
+            File::create(file)?
+        };
+        let mut writer = BufWriter::new(f);
+
+        if !(append && exists) {
+            writeln!(
+                writer,
+                "step,time,pressure_min,pressure_max,div_min,div_max,vel_norm_min,vel_norm_max,smoke_total"
+            )?;
+        }
+
+        return Ok(TimeSeriesCsv { writer });
+    }
+
+    /// Append the diagnostics of the grid at step `step` and time `t`.
+    pub fn append(&mut self, step: u64, t: Scalar, grid: &Grid) -> GenericResult<()> {
+        let smoke_total: Scalar = grid
+            .iter_index()
+            .map(|idx| grid.cell(idx).smoke())
+            .sum();
+
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{}",
+            step,
+            t,
+            grid.stats[0].pressure,
+            grid.stats[1].pressure,
+            grid.stats[0].div,
+            grid.stats[1].div,
+            grid.stats[0].velocity_norm,
+            grid.stats[1].velocity_norm,
+            smoke_total
+        )?;
+
+        // Flush such that long runs can be inspected while running.
+        self.writer.flush()?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,321 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Bump this if the layout of the dump changes.
+pub const DUMP_VERSION: u32 = 1;
+
+/// Magic bytes at the start of each dump.
+const MAGIC: &[u8; 4] = b"RFDP";
+
+/// Storage type of the values of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpType {
+    U8,
+    F32,
+    F64,
+}
+
+impl DumpType {
+    fn id(&self) -> u8 {
+        return match self {
+            DumpType::U8 => 0,
+            DumpType::F32 => 1,
+            DumpType::F64 => 2,
+        };
+    }
+
+    fn from_id(id: u8) -> GenericResult<Self> {
+        return match id {
+            0 => Ok(DumpType::U8),
+            1 => Ok(DumpType::F32),
+            2 => Ok(DumpType::F64),
+            _ => Err(format!("Unknown dump field type '{}'.", id).into()),
+        };
+    }
+
+    pub fn size(&self) -> usize {
+        return match self {
+            DumpType::U8 => 1,
+            DumpType::F32 => 4,
+            DumpType::F64 => 8,
+        };
+    }
+}
+
+/// A field of the dump with `components` values per cell
+/// in `x`-fastest order (components interleaved).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DumpField {
+    pub name: String,
+    pub value_type: DumpType,
+    pub components: usize,
+    pub values: Vec<Scalar>,
+}
+
+/// The state of a grid at one step. Much smaller and faster to write
+/// than a checkpoint, hence suited for dumping every step.
+///
+/// Layout (little endian): magic `RFDP`, version (`u32`), step (`u64`), time (`f64`),
+/// dimension (`2 x u32`), cell width (`f64`), number of fields (`u16`) and the field
+/// table with name length (`u8`), name, type (`u8`) and components (`u8`) per field,
+/// followed by the raw values of all fields in table order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dump {
+    pub version: u32,
+    pub step: u64,
+    pub time: Scalar,
+
+    /// The number of cells including the border cells.
+    pub dim: Index2,
+    pub cell_width: Scalar,
+    pub fields: Vec<DumpField>,
+}
+
+/// Little endian reader over the dump bytes.
+struct DumpReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> DumpReader<'a> {
+    fn bytes(&mut self, n: usize) -> GenericResult<&'a [u8]> {
+        if self.pos + n > self.data.len() {
+            return Err(format!("Dump is truncated at byte {}.", self.pos).into());
+        }
+
+        let b = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        return Ok(b);
+    }
+
+    fn u8(&mut self) -> GenericResult<u8> {
+        return Ok(self.bytes(1)?[0]);
+    }
+
+    fn u16(&mut self) -> GenericResult<u16> {
+        return Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?));
+    }
+
+    fn u32(&mut self) -> GenericResult<u32> {
+        return Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?));
+    }
+
+    fn u64(&mut self) -> GenericResult<u64> {
+        return Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?));
+    }
+
+    fn f64(&mut self) -> GenericResult<f64> {
+        return Ok(f64::from_le_bytes(self.bytes(8)?.try_into()?));
+    }
+}
+
+impl Dump {
+    /// Dump the fields of the grid: `smoke`, `pressure`, `div`, the staggered `velocity`
+    /// (2 components, unmodified face values) and `solid`.
+    /// The scalar fields are stored as `f32` if `single` is set.
+    pub fn new(grid: &Grid, step: u64, time: Scalar, single: bool) -> Self {
+        let value_type = if single { DumpType::F32 } else { DumpType::F64 };
+
+        let field = |name: &str, value_type: DumpType, components: usize, values: Vec<Scalar>| {
+            return DumpField {
+                name: name.to_string(),
+                value_type,
+                components,
+                values,
+            };
+        };
+        let scalars = |get: &dyn Fn(Index2) -> Scalar| -> Vec<Scalar> {
+            return grid.iter_index().map(get).collect();
+        };
+
+        let velocity = grid
+            .iter_index()
+            .flat_map(|idx| {
+                let v = grid.cell(idx).velocity.back;
+                return [v.x, v.y];
+            })
+            .collect();
+
+        return Dump {
+            version: DUMP_VERSION,
+            step,
+            time,
+            dim: grid.dim,
+            cell_width: grid.cell_width,
+            fields: vec![
+                field("smoke", value_type, 1, scalars(&|idx| grid.cell(idx).smoke())),
+                field("pressure", value_type, 1, scalars(&|idx| grid.cell(idx).pressure)),
+                field("div", value_type, 1, scalars(&|idx| grid.cell(idx).div)),
+                field("velocity", value_type, 2, velocity),
+                field(
+                    "solid",
+                    DumpType::U8,
+                    1,
+                    scalars(&|idx| (grid.cell(idx).mode == CellTypes::Solid) as u8 as Scalar),
+                ),
+            ],
+        };
+    }
+
+    pub fn field(&self, name: &str) -> Option<&DumpField> {
+        return self.fields.iter().find(|f| f.name == name);
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let n = self.dim.x * self.dim.y;
+        let size = self
+            .fields
+            .iter()
+            .map(|f| 3 + f.name.len() + n * f.components * f.value_type.size())
+            .sum::<usize>();
+
+        let mut data = Vec::with_capacity(42 + size);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(&self.step.to_le_bytes());
+        data.extend_from_slice(&(self.time as f64).to_le_bytes());
+        data.extend_from_slice(&(self.dim.x as u32).to_le_bytes());
+        data.extend_from_slice(&(self.dim.y as u32).to_le_bytes());
+        data.extend_from_slice(&(self.cell_width as f64).to_le_bytes());
+        data.extend_from_slice(&(self.fields.len() as u16).to_le_bytes());
+
+        for f in self.fields.iter() {
+            data.push(f.name.len() as u8);
+            data.extend_from_slice(f.name.as_bytes());
+            data.push(f.value_type.id());
+            data.push(f.components as u8);
+        }
+
+        for f in self.fields.iter() {
+            for v in f.values.iter() {
+                match f.value_type {
+                    DumpType::U8 => data.push(*v as u8),
+                    DumpType::F32 => data.extend_from_slice(&(*v as f32).to_le_bytes()),
+                    DumpType::F64 => data.extend_from_slice(&(*v as f64).to_le_bytes()),
+                }
+            }
+        }
+
+        return data;
+    }
+
+    pub fn decode(data: &[u8]) -> GenericResult<Self> {
+        let mut r = DumpReader { data, pos: 0 };
+
+        if r.bytes(4)? != MAGIC {
+            bail!("Not a dump file.");
+        }
+
+        let version = r.u32()?;
+        if version != DUMP_VERSION {
+            return Err(format!(
+                "Dump version '{}' is not supported (expected '{}').",
+                version,
+                DUMP_VERSION
+            )
+            .into());
+        }
+
+        let step = r.u64()?;
+        let time = r.f64()? as Scalar;
+        let dim = idx!(r.u32()? as usize, r.u32()? as usize);
+        let cell_width = r.f64()? as Scalar;
+
+        let mut table = vec![];
+        for _ in 0..r.u16()? {
+            let len = r.u8()? as usize;
+            let name = String::from_utf8(r.bytes(len)?.to_vec())?;
+            let value_type = DumpType::from_id(r.u8()?)?;
+            let components = r.u8()? as usize;
+            table.push((name, value_type, components));
+        }
+
+        let mut fields = vec![];
+        for (name, value_type, components) in table {
+            let count = dim.x * dim.y * components;
+            let bytes = r.bytes(count * value_type.size())?;
+
+            let values = match value_type {
+                DumpType::U8 => bytes.iter().map(|b| *b as Scalar).collect(),
+                DumpType::F32 => bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as Scalar)
+                    .collect(),
+                DumpType::F64 => bytes
+                    .chunks_exact(8)
+                    .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as Scalar)
+                    .collect(),
+            };
+
+            fields.push(DumpField {
+                name,
+                value_type,
+                components,
+                values,
+            });
+        }
+
+        return Ok(Dump {
+            version,
+            step,
+            time,
+            dim,
+            cell_width,
+            fields,
+        });
+    }
+
+    /// Restore the dumped fields into `grid` which must have the same dimension.
+    /// Unknown fields are ignored.
+    pub fn apply(&self, grid: &mut Grid) -> GenericResult<()> {
+        if grid.dim != self.dim {
+            return Err(format!(
+                "Dump dimension {} differs from the grid's {}.",
+                self.dim, grid.dim
+            )
+            .into());
+        }
+
+        for f in self.fields.iter() {
+            for idx in grid.iter_index().collect::<Vec<_>>() {
+                let i = (idx.x + idx.y * self.dim.x) * f.components;
+                let cell = grid.cell_mut(idx);
+
+                match f.name.as_str() {
+                    "smoke" => cell.set_smoke(f.values[i]),
+                    "pressure" => cell.pressure = f.values[i],
+                    "div" => cell.div = f.values[i],
+                    "velocity" => cell.velocity.back = vec2!(f.values[i], f.values[i + 1]),
+                    "solid" => {
+                        cell.mode = if f.values[i] != 0.0 {
+                            CellTypes::Solid
+                        } else {
+                            CellTypes::Fluid
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Write the grid as a dump file (see `Dump`).
+pub fn write_dump(grid: &Grid, step: u64, time: Scalar, single: bool, file: &str) -> GenericResult<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+    w.write_all(&Dump::new(grid, step, time, single).encode())?;
+    w.flush()?;
+    return Ok(());
+}
+
+pub fn read_dump(file: &str) -> GenericResult<Dump> {
+    let mut data = Vec::new();
+    BufReader::new(File::open(file)?).read_to_end(&mut data)?;
+    return Dump::decode(&data);
+}
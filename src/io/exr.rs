@@ -0,0 +1,121 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Minimal OpenEXR writer for single-part scanline images without compression.
+// Reference: "The OpenEXR File Layout" (openexr.com, `TechnicalIntroduction`).
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+
+/// Pixel type `FLOAT` (32-bit) of a channel.
+const PIXEL_FLOAT: i32 = 2;
+
+struct ExrBuffer {
+    data: Vec<u8>,
+}
+
+impl ExrBuffer {
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+    fn i32(&mut self, v: i32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f32(&mut self, v: f32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn cstr(&mut self, s: &str) {
+        self.data.extend_from_slice(s.as_bytes());
+        self.u8(0);
+    }
+    fn attribute(&mut self, name: &str, type_name: &str, value: &[u8]) {
+        self.cstr(name);
+        self.cstr(type_name);
+        self.i32(value.len() as i32);
+        self.data.extend_from_slice(value);
+    }
+}
+
+fn box2i(max: Index2) -> Vec<u8> {
+    return [0, 0, max.x as i32 - 1, max.y as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+}
+
+/// Write the smoke, pressure and velocity fields as 32-bit float channels
+/// of an OpenEXR image. Row `0` of the image is the top row of the grid.
+/// The velocity is interpolated to the cell centers.
+/// The run metadata is stored as `string` header attributes.
+pub fn write_exr(grid: &Grid, file: &str, meta: Option<&RunMetadata>) -> GenericResult<()> {
+    // Channels must be sorted by name.
+    let channels: [(&str, Box<dyn Fn(Index2) -> Scalar>); 4] = [
+        ("pressure", Box::new(|idx| grid.cell(idx).pressure)),
+        ("smoke", Box::new(|idx| grid.cell(idx).smoke())),
+        ("velocity.x", Box::new(|idx| grid.cell_center_velocity(idx).x)),
+        ("velocity.y", Box::new(|idx| grid.cell_center_velocity(idx).y)),
+    ];
+
+    let mut buf = ExrBuffer { data: Vec::new() };
+    buf.u32(MAGIC);
+    buf.u32(VERSION);
+
+    let mut chlist = ExrBuffer { data: Vec::new() };
+    for (name, _) in channels.iter() {
+        chlist.cstr(name);
+        chlist.i32(PIXEL_FLOAT);
+        chlist.data.extend_from_slice(&[0, 0, 0, 0]); // Linear flag and reserved.
+        chlist.i32(1); // x-sampling.
+        chlist.i32(1); // y-sampling.
+    }
+    chlist.u8(0);
+
+    buf.attribute("channels", "chlist", &chlist.data);
+    buf.attribute("compression", "compression", &[0]);
+    buf.attribute("dataWindow", "box2i", &box2i(grid.dim));
+    buf.attribute("displayWindow", "box2i", &box2i(grid.dim));
+    buf.attribute("lineOrder", "lineOrder", &[0]);
+    buf.attribute("pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    buf.attribute("screenWindowCenter", "v2f", &[0; 8]);
+    buf.attribute("screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    for (key, value) in meta.map(|m| m.entries()).unwrap_or_default() {
+        buf.attribute(key, "string", value.as_bytes());
+    }
+    buf.u8(0);
+
+    // Offset table: one uncompressed scanline per block.
+    let height = grid.dim.y;
+    let block_size = 8 + channels.len() * grid.dim.x * 4;
+    let first = buf.data.len() + height * 8;
+    for y in 0..height {
+        buf.u64((first + y * block_size) as u64);
+    }
+
+    for y in 0..height {
+        buf.i32(y as i32);
+        buf.i32((block_size - 8) as i32);
+
+        let j = height - 1 - y;
+        for (_, get) in channels.iter() {
+            for i in 0..grid.dim.x {
+                buf.f32(get(idx!(i, j)) as f32);
+            }
+        }
+    }
+
+    let mut w = BufWriter::new(File::create(file)?);
+    w.write_all(&buf.data)?;
+    w.flush()?;
+
+    return Ok(());
+}
@@ -0,0 +1,125 @@
+use crate::io::metadata::RunMetadata;
+use crate::render::contour_segments;
+use crate::scene::grid::Grid;
+use crate::types::*;
+
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Minimal binary glTF 2.0 (`.glb`) writer for line meshes.
+// Reference: https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html#binary-gltf-layout
+
+const MAGIC: u32 = 0x46546C67;
+const VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F534A;
+const CHUNK_BIN: u32 = 0x004E4942;
+
+const COMPONENT_FLOAT: u32 = 5126;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const MODE_LINES: u32 = 1;
+
+fn pad(data: &mut Vec<u8>, fill: u8) {
+    while data.len() % 4 != 0 {
+        data.push(fill);
+    }
+}
+
+/// Write the smoke iso-contour at `level` as a binary glTF line mesh.
+/// Each vertex carries the interpolated velocity as the custom attribute `_VELOCITY`.
+/// Coordinates are in world units with `z = 0` (relative to the node's translation).
+/// The run metadata is stored in the `extras` of the asset.
+pub fn write_gltf(
+    grid: &Grid,
+    level: Scalar,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    let segments = contour_segments(grid, level, |c| c.smoke());
+
+    let vertices: Vec<Vector2> = segments.iter().flat_map(|(a, b)| [*a, *b]).collect();
+    let count = vertices.len();
+
+    let mut bin: Vec<u8> = Vec::with_capacity(count * 2 * 12);
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+
+    for p in vertices.iter() {
+        let v = [p.x as f32, p.y as f32, 0.0];
+        for k in 0..3 {
+            min[k] = min[k].min(v[k]);
+            max[k] = max[k].max(v[k]);
+        }
+        v.iter().for_each(|c| bin.extend_from_slice(&c.to_le_bytes()));
+    }
+
+    let velocity_offset = bin.len();
+    for p in vertices.iter() {
+        let vel = grid.sample_velocity(*p);
+        [vel.x as f32, vel.y as f32, 0.0]
+            .iter()
+            .for_each(|c| bin.extend_from_slice(&c.to_le_bytes()));
+    }
+
+    let mut node = json!({ "name": format!("smoke-{}", level) });
+
+    // Cropped grids are placed in the domain by the node.
+    let origin = grid.origin_position();
+    if origin != Vector2::zeros() {
+        node["translation"] = json!([origin.x, origin.y, 0.0]);
+    }
+    let mut doc = json!({
+        "asset": { "version": "2.0", "generator": "rustofluid" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+    });
+    if let Some(m) = meta {
+        doc["asset"]["extras"] = serde_json::to_value(m)?;
+    }
+
+    // Accessors must not be empty, hence an empty contour has no mesh.
+    if count > 0 {
+        node["mesh"] = json!(0);
+        doc["meshes"] = json!([{
+            "primitives": [{
+                "attributes": { "POSITION": 0, "_VELOCITY": 1 },
+                "mode": MODE_LINES,
+            }]
+        }]);
+        doc["buffers"] = json!([{ "byteLength": bin.len() }]);
+        doc["bufferViews"] = json!([
+            { "buffer": 0, "byteOffset": 0, "byteLength": velocity_offset, "target": TARGET_ARRAY_BUFFER },
+            { "buffer": 0, "byteOffset": velocity_offset, "byteLength": bin.len() - velocity_offset, "target": TARGET_ARRAY_BUFFER },
+        ]);
+        doc["accessors"] = json!([
+            { "bufferView": 0, "componentType": COMPONENT_FLOAT, "count": count, "type": "VEC3", "min": min, "max": max },
+            { "bufferView": 1, "componentType": COMPONENT_FLOAT, "count": count, "type": "VEC3" },
+        ]);
+    }
+    doc["nodes"] = json!([node]);
+
+    let mut json = serde_json::to_vec(&doc)?;
+    pad(&mut json, b' ');
+    pad(&mut bin, 0);
+
+    let has_bin = count > 0;
+    let length = 12 + 8 + json.len() + if has_bin { 8 + bin.len() } else { 0 };
+
+    let mut w = BufWriter::new(File::create(file)?);
+    w.write_all(&MAGIC.to_le_bytes())?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    w.write_all(&(length as u32).to_le_bytes())?;
+
+    w.write_all(&(json.len() as u32).to_le_bytes())?;
+    w.write_all(&CHUNK_JSON.to_le_bytes())?;
+    w.write_all(&json)?;
+
+    if has_bin {
+        w.write_all(&(bin.len() as u32).to_le_bytes())?;
+        w.write_all(&CHUNK_BIN.to_le_bytes())?;
+        w.write_all(&bin)?;
+    }
+    w.flush()?;
+
+    return Ok(());
+}
@@ -0,0 +1,132 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use ::hdf5::types::VarLenUnicode;
+use ::hdf5::{Dataset, File};
+use ndarray::{s, Array1, Array2, Array3};
+
+const FIELDS: [(&str, fn(&Cell) -> Scalar); 3] = [
+    ("pressure", |c| c.pressure),
+    ("smoke", |c| c.smoke()),
+    ("div", |c| c.div),
+];
+
+/// Writer which appends each frame's fields into one chunked and
+/// compressed HDF5 file with a time axis (first dimension).
+///
+/// Layout:
+/// - `time`: `(T,)`
+/// - `pressure`, `smoke`, `div`: `(T, dim.y, dim.x)`
+/// - `velocity`: `(T, dim.y, dim.x, 2)` (interpolated to cell centers)
+/// - `solid`: `(dim.y, dim.x)` (written once)
+///
+/// The run metadata (without step and time) is stored as string attributes of the file.
+pub struct Hdf5Writer {
+    file: File,
+    dim: Index2,
+    frames: usize,
+
+    time: Dataset,
+    fields: Vec<Dataset>,
+    velocity: Dataset,
+}
+
+impl Hdf5Writer {
+    pub fn new(
+        file: &str,
+        grid: &Grid,
+        compression: u8,
+        meta: Option<&RunMetadata>,
+    ) -> GenericResult<Self> {
+        let file = File::create(file)?;
+        let (nx, ny) = (grid.dim.x, grid.dim.y);
+
+        for (key, value) in meta.map(|m| m.entries()).unwrap_or_default() {
+            if key == "step" || key == "time" {
+                continue;
+            }
+
+            let value: VarLenUnicode = value.parse()?;
+            file.new_attr::<VarLenUnicode>().create(key)?.write_scalar(&value)?;
+        }
+
+        // Placement of cropped grids in the domain.
+        let origin = grid.origin();
+        file.new_attr::<u64>()
+            .shape(2)
+            .create("origin")?
+            .write(&Array1::from_vec(vec![origin.x as u64, origin.y as u64]))?;
+
+        file.new_dataset::<u8>()
+            .shape((ny, nx))
+            .create("solid")?
+            .write(&Array2::from_shape_fn((ny, nx), |(j, i)| {
+                (grid.cell(idx!(i, j)).mode == CellTypes::Solid) as u8
+            }))?;
+
+        let time = file
+            .new_dataset::<Scalar>()
+            .chunk(1024)
+            .shape(0..)
+            .create("time")?;
+
+        let fields = FIELDS
+            .iter()
+            .map(|(name, _)| {
+                file.new_dataset::<Scalar>()
+                    .chunk((1, ny, nx))
+                    .deflate(compression)
+                    .shape((0.., ny, nx))
+                    .create(*name)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let velocity = file
+            .new_dataset::<Scalar>()
+            .chunk((1, ny, nx, 2))
+            .deflate(compression)
+            .shape((0.., ny, nx, 2))
+            .create("velocity")?;
+
+        return Ok(Hdf5Writer {
+            file,
+            dim: grid.dim,
+            frames: 0,
+            time,
+            fields,
+            velocity,
+        });
+    }
+
+    /// Append the fields of the grid at time `t` as a new frame.
+    pub fn append(&mut self, t: Scalar, grid: &Grid) -> GenericResult<()> {
+        if grid.dim != self.dim {
+            return Err(format!("Grid dimension {} differs from {}.", grid.dim, self.dim).into());
+        }
+
+        let (nx, ny) = (self.dim.x, self.dim.y);
+        let n = self.frames;
+
+        self.time.resize(n + 1)?;
+        self.time.write_slice(&Array1::from_elem(1, t), s![n..n + 1])?;
+
+        for (ds, (_, get)) in self.fields.iter().zip(FIELDS.iter()) {
+            let a = Array2::from_shape_fn((ny, nx), |(j, i)| get(grid.cell(idx!(i, j))));
+            ds.resize((n + 1, ny, nx))?;
+            ds.write_slice(&a, s![n, .., ..])?;
+        }
+
+        let v = Array3::from_shape_fn((ny, nx, 2), |(j, i, d)| {
+            grid.cell_center_velocity(idx!(i, j))[d]
+        });
+        self.velocity.resize((n + 1, ny, nx, 2))?;
+        self.velocity.write_slice(&v, s![n, .., .., ..])?;
+
+        self.frames += 1;
+        self.file.flush()?;
+
+        return Ok(());
+    }
+}
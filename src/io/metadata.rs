@@ -0,0 +1,72 @@
+use crate::scene::config::{SceneConfig, SolverConfig};
+use crate::types::*;
+
+use serde::Serialize;
+
+/// Describes how an output was produced. Attached to all exported files
+/// (as far as the format allows) to trace a stray file back to its run.
+#[derive(Clone, Debug, Serialize)]
+pub struct RunMetadata {
+    pub version: String,
+
+    /// Hash of the full scene configuration.
+    pub scene_hash: String,
+    pub dt: Scalar,
+    pub step: u64,
+    pub time: Scalar,
+    pub solver: SolverConfig,
+}
+
+/// FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+/// A stable hash of the scene configuration.
+pub fn scene_hash(config: &SceneConfig) -> String {
+    let json = serde_json::to_vec(config).expect("Scene config is serializable");
+    return format!("{:016x}", fnv1a(&json));
+}
+
+impl RunMetadata {
+    pub fn new(config: &SceneConfig, dt: Scalar) -> Self {
+        return RunMetadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            scene_hash: scene_hash(config),
+            dt,
+            step: 0,
+            time: 0.0,
+            solver: config.solver.clone(),
+        };
+    }
+
+    /// The metadata for the output after `step` steps at simulation time `time`.
+    pub fn at(&self, step: u64, time: Scalar) -> Self {
+        return RunMetadata {
+            step,
+            time,
+            ..self.clone()
+        };
+    }
+
+    /// All entries as key/value strings for formats with textual metadata.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        return vec![
+            ("rustofluid-version", self.version.clone()),
+            ("scene-hash", self.scene_hash.clone()),
+            ("dt", self.dt.to_string()),
+            ("step", self.step.to_string()),
+            ("time", self.time.to_string()),
+            ("solver", serde_json::to_string(&self.solver).unwrap_or_default()),
+        ];
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).unwrap_or_default();
+    }
+}
@@ -0,0 +1,21 @@
+pub mod bitmap;
+pub mod checkpoint;
+pub mod csv;
+pub mod dump;
+pub mod exr;
+pub mod gltf;
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
+pub mod metadata;
+pub mod npy;
+pub mod output;
+pub mod state;
+#[cfg(feature = "stream")]
+pub mod stream;
+pub mod tecplot;
+pub mod vdb;
+pub mod velocity;
+pub mod vtk;
+pub mod writer;
+
+mod tests;
@@ -0,0 +1,197 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use itertools::Itertools;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// The NumPy type descriptor of `Scalar`.
+const SCALAR_DESCR: &str = if std::mem::size_of::<Scalar>() == 4 { "<f4" } else { "<f8" };
+
+/// A C-ordered array in NumPy `.npy` format.
+pub struct NpyArray {
+    /// The NumPy type descriptor, e.g. `<f8`.
+    pub descr: &'static str,
+    pub shape: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+impl NpyArray {
+    /// Serialize to `.npy` (format version 1.0).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!("({})", self.shape.iter().join(", ")),
+        };
+
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            self.descr, shape
+        );
+
+        // Magic (6) + version (2) + header length (2) + header
+        // must be aligned to 64 bytes and end with a newline.
+        let total = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - total % 64) % 64));
+        header.push('\n');
+
+        let mut bytes = Vec::with_capacity(10 + header.len() + self.data.len());
+        bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&self.data);
+
+        return bytes;
+    }
+}
+
+/// Collect the values of all cells into a `(dim.y, dim.x, components)` array such that
+/// `a[j, i]` is the value of cell `(i, j)`.
+fn field_array<F, const N: usize>(grid: &Grid, f: F) -> NpyArray
+where
+    F: Fn(Index2) -> [Scalar; N],
+{
+    let mut data = Vec::with_capacity(grid.dim.x * grid.dim.y * N * std::mem::size_of::<Scalar>());
+    for idx in grid.iter_index() {
+        for v in f(idx) {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    let mut shape = vec![grid.dim.y, grid.dim.x];
+    if N > 1 {
+        shape.push(N);
+    }
+
+    return NpyArray {
+        descr: SCALAR_DESCR,
+        shape,
+        data,
+    };
+}
+
+/// All fields of the grid as named arrays.
+/// The velocity is interpolated to the cell centers.
+pub fn grid_arrays(grid: &Grid) -> Vec<(&'static str, NpyArray)> {
+    let solid = NpyArray {
+        descr: "|u1",
+        shape: vec![grid.dim.y, grid.dim.x],
+        data: grid
+            .iter_index()
+            .map(|idx| (grid.cell(idx).mode == CellTypes::Solid) as u8)
+            .collect(),
+    };
+
+    return vec![
+        ("pressure", field_array(grid, |idx| [grid.cell(idx).pressure])),
+        ("smoke", field_array(grid, |idx| [grid.cell(idx).smoke()])),
+        ("div", field_array(grid, |idx| [grid.cell(idx).div])),
+        (
+            "velocity",
+            field_array(grid, |idx| {
+                let v = grid.cell_center_velocity(idx);
+                [v.x, v.y]
+            }),
+        ),
+        ("solid", solid),
+    ];
+}
+
+/// Read a C-ordered `.npy` file of floats (`<f8` or `<f4`).
+/// Returns the shape and the values as `f64`.
+pub fn read_npy(file: &str) -> GenericResult<(Vec<usize>, Vec<Scalar>)> {
+    let bytes = std::fs::read(file)?;
+    if bytes.len() < 10 || &bytes[0..6] != b"\x93NUMPY" {
+        return Err(format!("'{}' is not a '.npy' file.", file).into());
+    }
+
+    let (header_len, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 => (u32::from_le_bytes(bytes[8..12].try_into()?) as usize, 12),
+        v => return Err(format!("Unsupported '.npy' version {} in '{}'.", v, file).into()),
+    };
+
+    let header = std::str::from_utf8(&bytes[start..start + header_len])?;
+    let value = |key: &str| {
+        let key = format!("'{}':", key);
+        return header
+            .find(&key)
+            .map(|s| header[s + key.len()..].trim_start())
+            .ok_or(format!("Header of '{}' has no {}.", file, key));
+    };
+
+    let descr = value("descr")?.trim_start_matches('\'');
+    let descr = &descr[..descr.find('\'').unwrap_or(0)];
+
+    if value("fortran_order")?.starts_with("True") {
+        return Err(format!("Fortran-ordered arrays are not supported ('{}').", file).into());
+    }
+
+    let shape = value("shape")?;
+    let shape = shape[1..shape.find(')').unwrap_or(1)]
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let data = &bytes[start + header_len..];
+    let values: Vec<Scalar> = match descr {
+        "<f8" => data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()) as Scalar)
+            .collect(),
+        "<f4" => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as Scalar)
+            .collect(),
+        _ => {
+            return Err(format!(
+                "Unsupported type '{}' in '{}' (use '<f8' or '<f4').",
+                descr, file
+            )
+            .into())
+        }
+    };
+
+    if values.len() != shape.iter().product::<usize>() {
+        return Err(format!("Data of '{}' does not match its shape {:?}.", file, shape).into());
+    }
+
+    return Ok((shape, values));
+}
+
+/// Write each field of the grid into its own file `<prefix>-<field>.npy`.
+pub fn write_npy(grid: &Grid, prefix: &str) -> GenericResult<()> {
+    for (name, a) in grid_arrays(grid) {
+        let mut w = BufWriter::new(File::create(format!("{}-{}.npy", prefix, name))?);
+        w.write_all(&a.to_bytes())?;
+        w.flush()?;
+    }
+
+    return Ok(());
+}
+
+/// Write all fields of the grid into one compressed `.npz` file.
+/// The run metadata is added as `metadata.json`.
+pub fn write_npz(grid: &Grid, file: &str, meta: Option<&RunMetadata>) -> GenericResult<()> {
+    let mut zip = ZipWriter::new(File::create(file)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (name, a) in grid_arrays(grid) {
+        zip.start_file(format!("{}.npy", name), options)?;
+        zip.write_all(&a.to_bytes())?;
+    }
+
+    if let Some(m) = meta {
+        zip.start_file("metadata.json", options)?;
+        zip.write_all(m.to_json().as_bytes())?;
+    }
+
+    zip.finish()?;
+    return Ok(());
+}
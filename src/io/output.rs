@@ -0,0 +1,319 @@
+use crate::scene::grid::Grid;
+use crate::scene::grid_arena::{GridArena, PooledGrid};
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How often frames are written.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputInterval {
+    /// Every `n` steps.
+    Steps(u64),
+    /// Every `t` units of simulation time.
+    Time(Scalar),
+}
+
+impl Default for OutputInterval {
+    fn default() -> Self {
+        return OutputInterval::Steps(1);
+    }
+}
+
+impl FromStr for OutputInterval {
+    type Err = String;
+
+    /// Parse `n` (steps) or `t s` (simulation time), e.g. `10` or `0.5s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let err = |e: &dyn std::fmt::Display| format!("Output interval '{}' is invalid: {}", s, e);
+
+        let interval = match s.strip_suffix('s') {
+            Some(t) => OutputInterval::Time(t.trim().parse::<Scalar>().map_err(|e| err(&e))?),
+            None => OutputInterval::Steps(s.parse::<u64>().map_err(|e| err(&e))?),
+        };
+
+        return match interval {
+            OutputInterval::Steps(0) => Err(err(&"must be positive")),
+            OutputInterval::Time(t) if t <= 0.0 => Err(err(&"must be positive")),
+            i => Ok(i),
+        };
+    }
+}
+
+/// What to do if an output file already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    #[default]
+    Overwrite,
+    /// Keep the existing file and do not write it.
+    Skip,
+    /// Fail the run.
+    Error,
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s.trim() {
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "skip" => Ok(OverwritePolicy::Skip),
+            "error" => Ok(OverwritePolicy::Error),
+            _ => Err(format!(
+                "Overwrite policy '{}' is not 'overwrite', 'skip' or 'error'.",
+                s
+            )),
+        };
+    }
+}
+
+/// The part of the domain written by the frame exporters.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportRegion {
+    /// Cell window `[min, max)` in grid indices (including the border cells).
+    Cells { min: [usize; 2], max: [usize; 2] },
+    /// World-space rectangle. All cells overlapping it are written.
+    World { min: [Scalar; 2], max: [Scalar; 2] },
+}
+
+impl ExportRegion {
+    fn parse_values<T: FromStr>(s: &str) -> Result<[T; 4], String> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<T>())
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(|_| format!("Region '{}' contains an invalid value.", s))?;
+
+        return values
+            .try_into()
+            .map_err(|_| format!("Region '{}' needs 4 comma-separated values.", s));
+    }
+
+    /// Parse a cell window `i0,j0,i1,j1`.
+    pub fn parse_cells(s: &str) -> Result<Self, String> {
+        let [i0, j0, i1, j1] = ExportRegion::parse_values::<usize>(s)?;
+        if i1 <= i0 || j1 <= j0 {
+            return Err(format!("Region '{}' is empty.", s));
+        }
+
+        return Ok(ExportRegion::Cells {
+            min: [i0, j0],
+            max: [i1, j1],
+        });
+    }
+
+    /// Parse a world-space rectangle `x0,y0,x1,y1`.
+    pub fn parse_world(s: &str) -> Result<Self, String> {
+        let [x0, y0, x1, y1] = ExportRegion::parse_values::<Scalar>(s)?;
+        if x1 <= x0 || y1 <= y0 {
+            return Err(format!("Region '{}' is empty.", s));
+        }
+
+        return Ok(ExportRegion::World {
+            min: [x0, y0],
+            max: [x1, y1],
+        });
+    }
+
+    /// The cell window `[min, max)` of the region in `grid` (clamped to the grid).
+    pub fn window(&self, grid: &Grid) -> (Index2, Index2) {
+        let (min, max) = match *self {
+            ExportRegion::Cells { min, max } => (idx!(min[0], min[1]), idx!(max[0], max[1])),
+            ExportRegion::World { min, max } => {
+                let h = grid.cell_width;
+                let cell = |v: Scalar, round: fn(Scalar) -> Scalar| round(v / h).max(0.0) as usize;
+                (
+                    idx!(cell(min[0], Scalar::floor), cell(min[1], Scalar::floor)),
+                    idx!(cell(max[0], Scalar::ceil), cell(max[1], Scalar::ceil)),
+                )
+            }
+        };
+
+        let max = idx!(max.x.min(grid.dim.x), max.y.min(grid.dim.y));
+        return (idx!(min.x.min(max.x), min.y.min(max.y)), max);
+    }
+}
+
+/// Replace the frame placeholder in `pattern`: either `{}` (six digits)
+/// or a printf-like `%0Nd` (e.g. `frame_%06d.png`).
+pub fn format_frame(pattern: &str, frame: u64) -> String {
+    if pattern.contains("{}") {
+        return pattern.replace("{}", &format!("{:06}", frame));
+    }
+
+    if let Some(start) = pattern.find('%') {
+        let rest = &pattern[start + 1..];
+        if let Some(end) = rest.find('d') {
+            let spec = &rest[..end];
+            let width = spec.trim_start_matches('0').parse::<usize>();
+
+            if spec.is_empty() || width.is_ok() {
+                let number = format!("{:0w$}", frame, w = width.unwrap_or(0));
+                return format!("{}{}{}", &pattern[..start], number, &rest[end + 1..]);
+            }
+        }
+    }
+
+    return pattern.to_string();
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(pattern = "mutable")]
+pub struct OutputParams {
+    /// Directory prepended to all relative output paths.
+    #[builder(default)]
+    pub dir: Option<String>,
+
+    #[builder(default)]
+    pub interval: OutputInterval,
+
+    #[builder(default)]
+    pub overwrite: OverwritePolicy,
+
+    /// Append to existing time series files instead of overwriting them.
+    #[builder(default)]
+    pub append: bool,
+
+    /// Restrict the frame exporters to this part of the domain.
+    #[builder(default)]
+    pub region: Option<ExportRegion>,
+}
+
+/// Decides when frames are written and where all outputs go.
+/// Shared by all exporters.
+pub struct OutputManager {
+    pub params: OutputParams,
+
+    dt: Scalar,
+    next_frame: u64,
+}
+
+impl OutputManager {
+    /// The manager for a run with timestep `dt` starting at time `t0`.
+    /// The frame numbering continues from `t0` when resuming.
+    pub fn new(params: OutputParams, t0: Scalar, dt: Scalar) -> Self {
+        let next_frame = match params.interval {
+            OutputInterval::Steps(n) => (t0 / dt).round() as u64 / n,
+            OutputInterval::Time(t) => (t0 / t + 1e-9).floor() as u64,
+        };
+
+        return OutputManager {
+            params,
+            dt,
+            next_frame,
+        };
+    }
+
+    /// Use the interval `interval` after the step ending at time `t`, e.g. on a reload
+    /// of the scene. The frame numbering continues.
+    pub fn set_interval(&mut self, interval: OutputInterval, t: Scalar) {
+        let next = OutputManager::new(
+            OutputParams {
+                interval,
+                ..self.params.clone()
+            },
+            t,
+            self.dt,
+        );
+
+        self.params.interval = interval;
+        self.next_frame = self.next_frame.max(next.next_frame);
+    }
+
+    /// The frame number if frames are due after the step ending at time `t`.
+    pub fn frame(&mut self, t: Scalar) -> Option<u64> {
+        let due = match self.params.interval {
+            OutputInterval::Steps(n) => {
+                let steps = (t / self.dt).round() as u64;
+                steps > 0 && steps % n == 0 && steps / n > self.next_frame
+            }
+            OutputInterval::Time(i) => t >= self.frame_time(self.next_frame, i),
+        };
+
+        if !due {
+            return None;
+        }
+
+        let frame = self.next_frame;
+        self.next_frame += 1;
+
+        // Skip frames if the interval is smaller than the timestep.
+        if let OutputInterval::Time(i) = self.params.interval {
+            while t >= self.frame_time(self.next_frame, i) {
+                self.next_frame += 1;
+            }
+        }
+
+        return Some(frame);
+    }
+
+    /// The time of `frame` for the interval `i` (with a tolerance for the accumulated time).
+    fn frame_time(&self, frame: u64, i: Scalar) -> Scalar {
+        return (frame + 1) as Scalar * i - 1e-6 * self.dt;
+    }
+
+    /// The part of `grid` to export if a region is set.
+    pub fn crop(&self, grid: &Grid) -> Option<Grid> {
+        return self.params.region.map(|r| {
+            let (min, max) = r.window(grid);
+            return grid.crop(min, max);
+        });
+    }
+
+    /// A copy of the part of `grid` to export (all of it if no region is set)
+    /// in storage from `arena`.
+    pub fn frame_grid(&self, grid: &Grid, arena: &GridArena) -> PooledGrid {
+        return match self.params.region {
+            Some(r) => {
+                let (min, max) = r.window(grid);
+                arena.crop(grid, min, max)
+            }
+            None => arena.snapshot(grid),
+        };
+    }
+
+    /// Prepend the output directory to the relative `path`.
+    pub fn path(&self, path: &str) -> String {
+        return match self.params.dir {
+            Some(ref d) if Path::new(path).is_relative() => {
+                Path::new(d).join(path).to_string_lossy().into_owned()
+            }
+            _ => path.to_string(),
+        };
+    }
+
+    /// Resolve the file for `frame` from `pattern` and create its directory.
+    /// Returns `None` if the file exists and should be skipped.
+    pub fn frame_path(&self, pattern: &str, frame: u64) -> GenericResult<Option<String>> {
+        return self.file(&format_frame(pattern, frame), false);
+    }
+
+    /// Resolve a single output file (e.g. a time series or video) and create its directory.
+    /// Existing files are accepted if `existing_ok` is set (e.g. to append to them).
+    /// Returns `None` if the file exists and should be skipped.
+    pub fn file(&self, file: &str, existing_ok: bool) -> GenericResult<Option<String>> {
+        let file = PathBuf::from(self.path(file));
+
+        if let Some(p) = file.parent() {
+            create_dir_all(p)?;
+        }
+
+        if file.exists() && !existing_ok {
+            match self.params.overwrite {
+                OverwritePolicy::Overwrite => {}
+                OverwritePolicy::Skip => return Ok(None),
+                OverwritePolicy::Error => {
+                    return Err(format!("Output '{}' already exists.", file.display()).into());
+                }
+            }
+        }
+
+        return Ok(Some(file.to_string_lossy().into_owned()));
+    }
+}
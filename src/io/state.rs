@@ -0,0 +1,228 @@
+use crate::io::checkpoint::{check_cells, grids, restore, CheckpointHeader, Compression};
+use crate::scene::cell::Cell;
+use crate::scene::grid::Grid;
+use crate::scene::rng::RngStream;
+use crate::scene::timestepper::{TimeStepper, TimeStepperState};
+use crate::types::*;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+/// Bump this if the layout of the state buffers changes.
+pub const STATE_VERSION: u32 = 1;
+
+/// Magic bytes at the start of each state buffer.
+const MAGIC: &[u8; 4] = b"RFST";
+
+/// The sizes of `Scalar` and `RenderScalar` in bytes, the first two bytes of the
+/// (compressed) data after the header.
+const FLOAT_SIZES: [u8; 2] = [
+    std::mem::size_of::<Scalar>() as u8,
+    std::mem::size_of::<RenderScalar>() as u8,
+];
+
+/// The upper bound of the encoded size of a state without its cells and streams, and of
+/// its `StateSizes`.
+const OVERHEAD_SIZE: u64 = 4096;
+
+/// The sizes of a state, encoded before it. Decoding reads at most the bytes which a
+/// state of these sizes needs, such that a corrupted buffer can not exhaust the memory.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct StateSizes {
+    dims: Vec<Index2>,
+    streams: u64,
+
+    /// The encoded size of the streams (their names have any length).
+    streams_size: u64,
+}
+
+impl StateSizes {
+    fn of(grids: &[&Grid], streams: &BTreeMap<String, RngStream>) -> GenericResult<Self> {
+        return Ok(StateSizes {
+            dims: grids.iter().map(|g| g.dim).collect(),
+            streams: streams.len() as u64,
+            streams_size: options().serialized_size(streams)?,
+        });
+    }
+
+    /// The upper bound of the encoded size of the state.
+    fn limit(&self) -> u64 {
+        // Integers have a variable length (also the bits of a half precision smoke), the
+        // largest values bound the size of all cells.
+        let mut cell = Cell::new(idx!(usize::MAX, usize::MAX));
+        cell.smoke.front = RenderScalar::store(Scalar::MAX);
+        cell.smoke.back = cell.smoke.front;
+        let cell = options().serialized_size(&cell).unwrap_or(u64::MAX);
+
+        let cells = self.dims.iter().fold(0u64, |n, d| {
+            return n.saturating_add((d.x as u64).saturating_mul(d.y as u64));
+        });
+        let grids = (self.dims.len() as u64).saturating_add(1);
+
+        return cells
+            .saturating_mul(cell)
+            .saturating_add(grids.saturating_mul(OVERHEAD_SIZE))
+            .saturating_add(self.streams_size);
+    }
+}
+
+#[derive(Serialize)]
+struct SimulationStateRef<'a> {
+    state: TimeStepperState,
+    steps: u64,
+    dt: Scalar,
+    grids: Vec<&'a Grid>,
+    streams: &'a BTreeMap<String, RngStream>,
+}
+
+/// The full state of a simulation decoded from a byte buffer (see `encode_state`).
+#[derive(Deserialize)]
+pub struct SimulationState {
+    pub state: TimeStepperState,
+    pub steps: u64,
+    pub dt: Scalar,
+    pub grids: Vec<Grid>,
+
+    /// The used random number streams of the simulation (see `Simulation::rng_stream`).
+    pub streams: BTreeMap<String, RngStream>,
+}
+
+impl SimulationState {
+    /// Continue `timestepper` (of the same scene) from this state.
+    pub fn restore(self, timestepper: &mut TimeStepper) -> GenericResult<()> {
+        return restore(timestepper, self.state, self.grids);
+    }
+
+    /// Check that the decoded state has the `sizes` it was encoded with.
+    fn check(&self, sizes: &StateSizes) -> GenericResult<()> {
+        let dims: Vec<Index2> = self.grids.iter().map(|g| g.dim).collect();
+        if dims != sizes.dims {
+            let message = format!("State has the grids {:?} instead of {:?}.", dims, sizes.dims);
+            return Err(message.into());
+        }
+
+        for grid in self.grids.iter() {
+            check_cells(grid)?;
+        }
+
+        if self.streams.len() as u64 != sizes.streams {
+            return Err(format!(
+                "State has {} random number streams instead of {}.",
+                self.streams.len(),
+                sizes.streams
+            )
+            .into());
+        }
+
+        return Ok(());
+    }
+}
+
+/// The encoding of the data after the header: little-endian with variable-length
+/// integers, independent of the platform.
+fn options() -> impl Options {
+    return bincode::DefaultOptions::new()
+        .with_little_endian()
+        .with_varint_encoding();
+}
+
+fn write_payload<W: Write>(mut w: W, data: &SimulationStateRef) -> GenericResult<()> {
+    options().serialize_into(&mut w, &FLOAT_SIZES)?;
+    options().serialize_into(&mut w, &StateSizes::of(&data.grids, data.streams)?)?;
+    options().serialize_into(&mut w, data)?;
+    return Ok(());
+}
+
+fn read_payload<R: Read>(mut r: R) -> GenericResult<SimulationState> {
+    let floats: [u8; 2] = options().deserialize_from(&mut r)?;
+    if floats != FLOAT_SIZES {
+        return Err(format!(
+            "State has {}/{} byte floats but this build {}/{}.",
+            floats[0], floats[1], FLOAT_SIZES[0], FLOAT_SIZES[1]
+        )
+        .into());
+    }
+
+    let sizes: StateSizes = options().with_limit(OVERHEAD_SIZE).deserialize_from(&mut r)?;
+    let state: SimulationState = options().with_limit(sizes.limit()).deserialize_from(r)?;
+    state.check(&sizes)?;
+
+    return Ok(state);
+}
+
+/// The state of `timestepper` with all grids and the random number `streams` after
+/// `steps` steps of size `dt` as a compact byte buffer, e.g. to migrate a simulation to
+/// another process or to send it to a remote viewer. Unlike a checkpoint it needs no
+/// file system. The manipulators, the hooks and the force fields are not part of the
+/// state, the receiving side keeps its own (the ones of the same scene).
+pub fn encode_state(
+    timestepper: &TimeStepper,
+    streams: &BTreeMap<String, RngStream>,
+    steps: u64,
+    dt: Scalar,
+    compression: Compression,
+) -> GenericResult<Vec<u8>> {
+    compression.validate()?;
+
+    let data = SimulationStateRef {
+        state: timestepper.state(),
+        steps,
+        dt,
+        grids: grids(timestepper),
+        streams,
+    };
+
+    let header = CheckpointHeader {
+        version: STATE_VERSION,
+        compression,
+    };
+
+    let mut buffer = vec![];
+    header.write(MAGIC, &mut buffer)?;
+
+    match compression {
+        Compression::None => write_payload(&mut buffer, &data)?,
+
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => {
+            let mut z = zstd::Encoder::new(&mut buffer, level)?;
+            write_payload(&mut z, &data)?;
+            z.finish()?;
+        }
+
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd(_) => bail!("Compressed states need the feature 'zstd'."),
+    }
+
+    return Ok(buffer);
+}
+
+/// Decode a buffer of `encode_state`. Buffers of another version or of a build with
+/// other floating point types (features `f32` and `f16-smoke`) are rejected, as are
+/// truncated or corrupted ones.
+pub fn decode_state(bytes: &[u8]) -> GenericResult<(CheckpointHeader, SimulationState)> {
+    let mut data = bytes;
+    let header = CheckpointHeader::read(MAGIC, &mut data)?;
+
+    if header.version != STATE_VERSION {
+        return Err(format!(
+            "State version '{}' is not supported (expected '{}').",
+            header.version, STATE_VERSION
+        )
+        .into());
+    }
+
+    let state = match header.compression {
+        Compression::None => read_payload(data)?,
+
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(_) => read_payload(zstd::Decoder::new(data)?)?,
+
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd(_) => bail!("Compressed states need the feature 'zstd'."),
+    };
+
+    return Ok((header, state));
+}
@@ -0,0 +1,136 @@
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use slog::{warn, Logger};
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// Fields sent in every frame, in this order.
+pub const STREAM_FIELDS: [&str; 4] = ["smoke", "pressure", "vel_x", "vel_y"];
+
+/// The header of a frame with the per-step diagnostics.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameHeader {
+    pub step: u64,
+    pub time: Scalar,
+    pub dim: [usize; 2],
+    pub cell_width: Scalar,
+    pub fields: Vec<String>,
+
+    pub pressure: [Scalar; 2],
+    pub div: [Scalar; 2],
+    pub velocity_norm: [Scalar; 2],
+}
+
+/// Encode a frame as binary message:
+/// `u32` (little endian) header length, the JSON header and then the
+/// zlib-compressed fields as `f32` (little endian) in `x`-fastest order.
+pub fn encode_frame(step: u64, t: Scalar, grid: &Grid) -> GenericResult<Vec<u8>> {
+    let header = FrameHeader {
+        step,
+        time: t,
+        dim: [grid.dim.x, grid.dim.y],
+        cell_width: grid.cell_width,
+        fields: STREAM_FIELDS.iter().map(|f| f.to_string()).collect(),
+        pressure: [grid.stats[0].pressure, grid.stats[1].pressure],
+        div: [grid.stats[0].div, grid.stats[1].div],
+        velocity_norm: [grid.stats[0].velocity_norm, grid.stats[1].velocity_norm],
+    };
+    let header = serde_json::to_vec(&header)?;
+
+    let mut z = ZlibEncoder::new(Vec::new(), Compression::fast());
+    for field in 0..STREAM_FIELDS.len() {
+        for idx in grid.iter_index() {
+            let v = match field {
+                0 => grid.cell(idx).smoke(),
+                1 => grid.cell(idx).pressure,
+                f => grid.cell_center_velocity(idx)[f - 2],
+            };
+            z.write_all(&(v as f32).to_le_bytes())?;
+        }
+    }
+
+    let mut data = Vec::with_capacity(4 + header.len());
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(&header);
+    data.extend_from_slice(&z.finish()?);
+
+    return Ok(data);
+}
+
+/// WebSocket server broadcasting frames to all connected clients.
+/// Clients connect at any time and are dropped when sending fails.
+pub struct FrameServer<'a> {
+    log: &'a Logger,
+    listener: TcpListener,
+    clients: Vec<WebSocket<TcpStream>>,
+}
+
+impl<'a> FrameServer<'a> {
+    /// Listen on `addr`, e.g. `127.0.0.1:9001`.
+    pub fn bind(log: &'a Logger, addr: &str) -> GenericResult<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        return Ok(FrameServer {
+            log,
+            listener,
+            clients: Vec::new(),
+        });
+    }
+
+    pub fn local_addr(&self) -> GenericResult<String> {
+        return Ok(self.listener.local_addr()?.to_string());
+    }
+
+    pub fn num_clients(&self) -> usize {
+        return self.clients.len();
+    }
+
+    /// Accept all pending connections.
+    /// With `block` set, wait for at least one client if there is none.
+    pub fn accept(&mut self, block: bool) -> GenericResult<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+                    match tungstenite::accept(stream) {
+                        Ok(ws) => self.clients.push(ws),
+                        Err(e) => warn!(self.log, "WebSocket handshake failed: {}", e),
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if block && self.clients.is_empty() {
+                        std::thread::sleep(Duration::from_millis(50));
+                        continue;
+                    }
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Send the frame of the grid to all clients.
+    /// Returns the number of clients which received it.
+    pub fn broadcast(&mut self, step: u64, t: Scalar, grid: &Grid) -> GenericResult<usize> {
+        self.accept(false)?;
+
+        if self.clients.is_empty() {
+            return Ok(0);
+        }
+
+        let frame = encode_frame(step, t, grid)?;
+        self.clients
+            .retain_mut(|ws| ws.write_message(Message::Binary(frame.clone())).is_ok());
+
+        return Ok(self.clients.len());
+    }
+}
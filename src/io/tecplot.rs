@@ -0,0 +1,135 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Where the field values of a Tecplot zone are located.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TecplotLocation {
+    /// The zone spans the cell corners and the fields are cell-centered
+    /// (`VARLOCATION=CELLCENTERED`), which stores the values unmodified.
+    Cell,
+
+    /// The zone spans the cell centers and the fields are nodal.
+    /// Preferred by some Tecplot tools (e.g. stream traces, older macros).
+    Node,
+}
+
+/// Number of values per line in block data (Tecplot limits the line length).
+const VALUES_PER_LINE: usize = 8;
+
+type Field<'a> = (&'static str, Box<dyn Fn(Index2) -> Scalar + 'a>);
+
+/// Auxiliary data names may only contain letters, digits, `_` and `.`.
+fn aux_name(key: &str) -> String {
+    return key.replace('-', "_");
+}
+
+fn write_block<W: Write, I: Iterator<Item = Scalar>>(w: &mut W, values: I) -> GenericResult<()> {
+    let mut n = 0;
+    for v in values {
+        write!(w, "{}{:e}", if n % VALUES_PER_LINE == 0 { "" } else { " " }, v)?;
+        n += 1;
+        if n % VALUES_PER_LINE == 0 {
+            writeln!(w)?;
+        }
+    }
+
+    if n % VALUES_PER_LINE != 0 {
+        writeln!(w)?;
+    }
+
+    return Ok(());
+}
+
+/// Write the grid as a Tecplot ASCII data file (`.dat`) with one ordered zone
+/// and the variables `X`, `Y`, `pressure`, `smoke`, `divergence`, `vel_x`, `vel_y`
+/// and `solid`. The velocity is interpolated to the cell centers.
+/// Coordinates are in world units.
+/// The run metadata is stored as dataset auxiliary data and the zone's solution time.
+pub fn write_tecplot(
+    grid: &Grid,
+    location: TecplotLocation,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    let fields: [Field; 6] = [
+        ("pressure", Box::new(|idx| grid.cell(idx).pressure)),
+        ("smoke", Box::new(|idx| grid.cell(idx).smoke())),
+        ("divergence", Box::new(|idx| grid.cell(idx).div)),
+        ("vel_x", Box::new(|idx| grid.cell_center_velocity(idx).x)),
+        ("vel_y", Box::new(|idx| grid.cell_center_velocity(idx).y)),
+        (
+            "solid",
+            Box::new(|idx| (grid.cell(idx).mode == CellTypes::Solid) as u8 as Scalar),
+        ),
+    ];
+
+    let mut w = BufWriter::new(File::create(file)?);
+    let h = grid.cell_width;
+    let origin = grid.origin_position();
+
+    writeln!(w, r#"TITLE = "RustoFluid""#)?;
+    write!(w, r#"VARIABLES = "X" "Y""#)?;
+    for (name, _) in fields.iter() {
+        write!(w, r#" "{}""#, name)?;
+    }
+    writeln!(w)?;
+
+    for (key, value) in meta.map(|m| m.entries()).unwrap_or_default() {
+        writeln!(
+            w,
+            r#"DATASETAUXDATA {}="{}""#,
+            aux_name(key),
+            value.replace('"', "\\\"")
+        )?;
+    }
+
+    let time = meta.map_or(String::new(), |m| format!(", SOLUTIONTIME={}", m.time));
+
+    match location {
+        TecplotLocation::Cell => {
+            let (ni, nj) = (grid.dim.x + 1, grid.dim.y + 1);
+            writeln!(
+                w,
+                r#"ZONE T="grid", I={}, J={}, DATAPACKING=BLOCK, VARLOCATION=([3-{}]=CELLCENTERED){}"#,
+                ni,
+                nj,
+                2 + fields.len(),
+                time
+            )?;
+
+            // Node coordinates with `I` varying fastest.
+            let x = |i: usize| origin.x + i as Scalar * h;
+            let y = |j: usize| origin.y + j as Scalar * h;
+            write_block(&mut w, (0..nj).flat_map(|_| (0..ni).map(x)))?;
+            write_block(&mut w, (0..nj).flat_map(|j| (0..ni).map(move |_| y(j))))?;
+
+            for (_, get) in fields.iter() {
+                write_block(&mut w, grid.iter_index().map(get))?;
+            }
+        }
+        TecplotLocation::Node => {
+            writeln!(
+                w,
+                r#"ZONE T="grid", I={}, J={}, DATAPACKING=POINT{}"#,
+                grid.dim.x, grid.dim.y, time
+            )?;
+
+            for idx in grid.iter_index() {
+                let pos = origin + grid.cell_center(idx);
+                write!(w, "{:e} {:e}", pos.x, pos.y)?;
+                for (_, get) in fields.iter() {
+                    write!(w, " {:e}", get(idx))?;
+                }
+                writeln!(w)?;
+            }
+        }
+    }
+
+    w.flush()?;
+    return Ok(());
+}
@@ -0,0 +1,899 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::io::checkpoint::*;
+    use crate::io::csv::*;
+    use crate::io::dump::*;
+    use crate::io::exr::write_exr;
+    use crate::io::gltf::write_gltf;
+    use crate::io::npy::*;
+    use crate::io::state::*;
+    use crate::io::tecplot::{write_tecplot, TecplotLocation};
+    use crate::io::vdb::write_vdb;
+    use crate::io::vtk::{pvd_path, write_vti, PvdCollection};
+    use crate::log::create_logger;
+    use crate::scene::cell::CellTypes;
+    use crate::scene::grid::*;
+    use crate::scene::solver_params::SolverParamsBuilder;
+    use crate::scene::timestepper::*;
+    use crate::types::*;
+
+    #[test]
+    fn test_write_vti() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(4, 2), 0.5);
+        grid.cell_mut(idx!(1, 1)).set_smoke(1.0);
+
+        let file = std::env::temp_dir().join("rustofluid-test.vti");
+        write_vti(&grid, file.to_str().unwrap(), None)?;
+
+        let data = std::fs::read(&file)?;
+        let header = String::from_utf8_lossy(&data[0..data.len().min(1024)]);
+        assert!(header.contains(r#"WholeExtent="0 6 0 4 0 0""#), "{}", header);
+        assert!(header.contains(r#"Name="velocity" NumberOfComponents="3""#));
+
+        // The arrays are stored in the precision of the solver.
+        let type_name = if cfg!(feature = "f32") { "Float32" } else { "Float64" };
+        assert!(header.contains(&format!(r#"type="{}" Name="smoke""#, type_name)), "{}", header);
+
+        // 4 scalar arrays (3 x `Scalar`, 1 x u8) and one vector array, each with a `u64` header.
+        let (n, s) = (6 * 4, std::mem::size_of::<Scalar>());
+        let appended = 5 * 8 + 3 * n * s + n * 3 * s + n;
+        assert!(data.len() > appended, "Size: {}", data.len());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_csv() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(2, 2), 1.0);
+        grid.cell_mut(idx!(1, 2)).set_smoke(0.5);
+
+        let file = std::env::temp_dir().join("rustofluid-test-field.csv");
+        write_field_csv(&grid, file.to_str().unwrap())?;
+
+        let data = std::fs::read_to_string(&file)?;
+        let lines: Vec<&str> = data.lines().collect();
+        assert!(lines.len() == 1 + 4 * 4, "Lines: {}", lines.len());
+        assert!(lines[0].starts_with("i,j,x,y"));
+        assert!(lines[1 + 1 + 2 * 4] == "1,2,1.5,2.5,0,0,0.5,0,0,0", "{}", lines[10]);
+
+        let file = std::env::temp_dir().join("rustofluid-test-series.csv");
+        let mut series = TimeSeriesCsv::new(file.to_str().unwrap())?;
+        series.append(0, 0.0, &grid)?;
+        series.append(1, 0.1, &grid)?;
+
+        let data = std::fs::read_to_string(&file)?;
+        assert!(data.lines().count() == 3);
+        assert!(data.lines().last().unwrap().ends_with(",0.5"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_npy_header() {
+        let a = NpyArray {
+            descr: "<f8",
+            shape: vec![2, 3],
+            data: vec![0; 6 * 8],
+        };
+
+        let bytes = a.to_bytes();
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+
+        assert!(bytes.starts_with(b"\x93NUMPY\x01\x00"));
+        assert!((10 + header_len) % 64 == 0);
+        assert!(bytes[10 + header_len - 1] == b'\n');
+        assert!(bytes.len() == 10 + header_len + 6 * 8);
+
+        let header = String::from_utf8_lossy(&bytes[10..10 + header_len]);
+        assert!(header.contains("'shape': (2, 3)"), "{}", header);
+    }
+
+    #[test]
+    fn test_run_metadata() -> GenericResult<()> {
+        use crate::io::metadata::{scene_hash, RunMetadata};
+        use crate::render::{save_smoke_png, RenderParamsBuilder};
+        use crate::scene::config::SceneConfig;
+
+        let config = SceneConfig::channel(dim!(20, 10));
+        let meta = RunMetadata::new(&config, 0.01).at(5, 0.05);
+        assert!(meta.scene_hash == scene_hash(&config));
+        assert!(meta.version == env!("CARGO_PKG_VERSION"));
+
+        let mut other = config.clone();
+        other.solver.iterations += 1;
+        assert!(scene_hash(&other) != meta.scene_hash);
+
+        let grid = Grid::new(dim!(4, 2), 0.5);
+
+        let file = std::env::temp_dir().join("rustofluid-test-meta.vti");
+        write_vti(&grid, file.to_str().unwrap(), Some(&meta))?;
+        let data = std::fs::read(&file)?;
+        let header = String::from_utf8_lossy(&data[0..data.len().min(4096)]);
+        assert!(header.contains("<FieldData>"));
+        assert!(header.contains(r#"Name="step" NumberOfTuples="1" format="ascii">5<"#), "{}", header);
+
+        let file = std::env::temp_dir().join("rustofluid-test-meta.png");
+        let params = RenderParamsBuilder::default().build().unwrap();
+        save_smoke_png(&grid, &params, file.to_str().unwrap(), Some(&meta))?;
+
+        let decoder = png::Decoder::new(std::fs::File::open(&file)?);
+        let reader = decoder.read_info()?;
+        let text = &reader.info().uncompressed_latin1_text;
+        assert!(text.iter().any(|t| t.keyword == "scene-hash" && t.text == meta.scene_hash));
+        assert!(text.iter().any(|t| t.keyword == "time" && t.text == "0.05"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_load_velocity() -> GenericResult<()> {
+        use crate::io::velocity::{load_velocity, resample_bilinear};
+
+        let mut grid = Grid::new(dim!(4, 3), 1.0);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).velocity.back = vec2!(idx.x as Scalar, 1.0);
+        }
+
+        // The exported fields include the border cells and are cropped.
+        let prefix = std::env::temp_dir().join("rustofluid-test-vel");
+        let prefix = prefix.to_str().unwrap();
+        write_npy(&grid, prefix)?;
+
+        let file = format!("{}-velocity.npy", prefix);
+        let (shape, _) = read_npy(&file)?;
+        assert!(shape == [5, 6, 2], "{:?}", shape);
+
+        let v = load_velocity(&file, dim!(4, 3), false)?;
+        assert!(v.len() == 12);
+        // Cell `(1, 1)` is centered between the faces `1` and `2`.
+        assert!(v[0] == vec2!(1.5, 1.0), "{}", v[0]);
+
+        let file = std::env::temp_dir().join("rustofluid-test-vel.csv");
+        let file = file.to_str().unwrap();
+        write_field_csv(&grid, file)?;
+        assert!(load_velocity(file, dim!(4, 3), false)? == v);
+
+        assert!(load_velocity(file, dim!(8, 6), false).is_err());
+        assert!(load_velocity(file, dim!(8, 6), true)?.len() == 48);
+
+        // Bilinear resampling of a linear field is exact inside.
+        let src: Vec<Vector2> = (0..4).map(|i| vec2!(i as Scalar, 0.0)).collect();
+        let r = resample_bilinear(dim!(4, 1), &src, dim!(2, 1));
+        assert!(r == [vec2!(0.5, 0.0), vec2!(2.5, 0.0)], "{:?}", r);
+
+        // Staggered faces get the average of the adjacent cells.
+        let mut target = Grid::new(dim!(4, 3), 1.0);
+        target.set_inside_velocity(&v).unwrap();
+        let u = target.cell(idx!(2, 1)).velocity.back.x;
+        assert!(u == 0.5 * (v[0].x + v[1].x), "{}", u);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_exr() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(3, 2), 0.1);
+        grid.cell_mut(idx!(1, 3)).set_smoke(0.25);
+
+        let file = std::env::temp_dir().join("rustofluid-test.exr");
+        write_exr(&grid, file.to_str().unwrap(), None)?;
+
+        let data = std::fs::read(&file)?;
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as usize;
+
+        assert!(u32_at(0) == 20000630);
+
+        // Grid with borders is 5x4, 4 float channels per scanline.
+        let (width, height) = (5, 4);
+        let block_size = 8 + 4 * width * 4;
+        let header_end = data.len() - height * 8 - height * block_size;
+
+        assert!(data[header_end - 1] == 0, "Header not terminated.");
+        assert!(u64_at(header_end) == header_end + height * 8);
+
+        // Top cell row is written first, smoke is the second channel.
+        let block = u64_at(header_end);
+        let at = block + 8 + width * 4 + 4;
+        assert!(f32::from_le_bytes(data[at..at + 4].try_into().unwrap()) == 0.25);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_tecplot() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(3, 2), 0.1);
+        grid.cell_mut(idx!(1, 3)).set_smoke(0.25);
+
+        let file = std::env::temp_dir().join("rustofluid-test.dat");
+        let file = file.to_str().unwrap();
+
+        let values = |content: &str| -> Vec<Scalar> {
+            let data = content.split_once("ZONE").unwrap().1;
+            return data
+                .lines()
+                .skip(1)
+                .flat_map(|l| l.split_whitespace().map(|v| v.parse::<Scalar>().unwrap()))
+                .collect();
+        };
+
+        // Grid with borders is 5x4 cells, i.e. 6x5 nodes.
+        write_tecplot(&grid, TecplotLocation::Cell, file, None)?;
+        let content = std::fs::read_to_string(file)?;
+        assert!(content.contains("I=6, J=5, DATAPACKING=BLOCK, VARLOCATION=([3-8]=CELLCENTERED)"));
+
+        let v = values(&content);
+        assert!(v.len() == 2 * 30 + 6 * 20, "Wrong number of values.");
+        assert!(v[29] == 0.5 && v[59] == 0.4, "Wrong node coordinates.");
+        assert!(v[60 + 20 + 3 * 5 + 1] == 0.25, "Smoke is not at cell (1, 3).");
+
+        write_tecplot(&grid, TecplotLocation::Node, file, None)?;
+        let content = std::fs::read_to_string(file)?;
+        assert!(content.contains("I=5, J=4, DATAPACKING=POINT"));
+
+        let v = values(&content);
+        let row = &v[8 * (3 * 5 + 1)..8 * (3 * 5 + 2)];
+        assert!(v.len() == 8 * 20, "Wrong number of values.");
+        assert!(row[0] == grid.cell_center(idx!(1, 3)).x && row[3] == 0.25);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_gltf() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(4, 4), 0.5);
+        grid.cell_mut(idx!(2, 2)).set_smoke(1.0);
+
+        let file = std::env::temp_dir().join("rustofluid-test.glb");
+        write_gltf(&grid, 0.5, file.to_str().unwrap(), None)?;
+
+        let data = std::fs::read(&file)?;
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+
+        assert!(&data[0..4] == b"glTF");
+        assert!(u32_at(8) == data.len());
+
+        let json_len = u32_at(12);
+        let doc: serde_json::Value = serde_json::from_slice(&data[20..20 + json_len])?;
+
+        // A closed contour of 4 segments around the blob.
+        assert!(doc["accessors"][0]["count"] == 8, "{}", doc);
+        assert!(doc["meshes"][0]["primitives"][0]["attributes"]["_VELOCITY"] == 1);
+
+        let bin_len = u32_at(20 + json_len);
+        assert!(bin_len == 2 * 8 * 12);
+        assert!(20 + json_len + 8 + bin_len == data.len());
+
+        // Without smoke the file contains only the node.
+        let grid = Grid::new(dim!(4, 4), 0.5);
+        write_gltf(&grid, 0.5, file.to_str().unwrap(), None)?;
+        let data = std::fs::read(&file)?;
+        let json_len = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+        assert!(data.len() == 20 + json_len);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_pvd_collection() -> GenericResult<()> {
+        assert!(pvd_path("frames/frame-{}.vti") == "frames/frame.pvd");
+        assert!(pvd_path("out/frame_%06d.vti") == "out/frame.pvd");
+        assert!(pvd_path("{}.vti") == "frames.pvd");
+
+        let dir = std::env::temp_dir().join("rustofluid-test-pvd");
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("frame.pvd");
+        let file = file.to_str().unwrap();
+        let frame = |n: u64| dir.join(format!("frame-{}.vti", n)).to_string_lossy().into_owned();
+
+        let mut c = PvdCollection::open(file, false)?;
+        for n in 0..3 {
+            c.add(0.1 * (n + 1) as Scalar, &frame(n))?;
+        }
+
+        let content = std::fs::read_to_string(file)?;
+        assert!(content.contains(r#"file="frame-2.vti""#), "{}", content);
+
+        // Resuming at `t = 0.2` replaces the last frame.
+        let mut c = PvdCollection::open(file, true)?;
+        assert!(c.entries().len() == 3);
+        c.add(0.25, &frame(3))?;
+
+        let times: Vec<Scalar> = c.entries().iter().map(|e| e.0).collect();
+        assert!(times == [0.1, 0.2, 0.25], "{:?}", times);
+
+        assert!(PvdCollection::open(file, false)?.entries().is_empty());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_write_vdb() -> GenericResult<()> {
+        let grid = Grid::new(dim!(10, 4), 0.1);
+
+        let file = std::env::temp_dir().join("rustofluid-test.vdb");
+        write_vdb(&grid, file.to_str().unwrap(), None)?;
+
+        let data = std::fs::read(&file)?;
+        let i64_at = |at: usize| i64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+
+        assert!(i64_at(0) == 0x56444220);
+
+        // Header (8 + 3 * 4 + 1 + 36), file metadata count (4), grid count (4).
+        let mut at = 8 + 12 + 1 + 36 + 4;
+        assert!(u32_at(at) == 3);
+        at += 4;
+
+        // Walk all grid descriptors over their end positions.
+        for name in ["density", "pressure", "divergence"] {
+            assert!(u32_at(at) as usize == name.len());
+            assert!(&data[at + 4..at + 4 + name.len()] == name.as_bytes());
+
+            at += 4 + name.len();
+            at += 4 + "Tree_float_5_4_3".len() + 4;
+            at = i64_at(at + 16) as usize;
+        }
+
+        assert!(at == data.len());
+
+        return Ok(());
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn test_encode_frame() -> GenericResult<()> {
+        use crate::io::stream::*;
+        use std::io::Read;
+
+        let mut grid = Grid::new(dim!(3, 2), 0.5);
+        grid.cell_mut(idx!(1, 1)).set_smoke(0.25);
+
+        let data = encode_frame(7, 1.5, &grid)?;
+        let len = u32::from_le_bytes(data[0..4].try_into()?) as usize;
+
+        let header: FrameHeader = serde_json::from_slice(&data[4..4 + len])?;
+        assert!(header.step == 7 && header.time == 1.5, "Wrong header.");
+        assert!(header.dim == [5, 4] && header.fields.len() == STREAM_FIELDS.len());
+
+        let mut fields = Vec::new();
+        flate2::read::ZlibDecoder::new(&data[4 + len..]).read_to_end(&mut fields)?;
+        assert!(fields.len() == STREAM_FIELDS.len() * 5 * 4 * 4, "Wrong field size.");
+
+        // Smoke of cell (1, 1) is the first field at offset `1 + 1 * 5`.
+        let v = f32::from_le_bytes(fields[6 * 4..7 * 4].try_into()?);
+        assert!(v == 0.25, "Wrong smoke value {}.", v);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_output_manager() -> GenericResult<()> {
+        use crate::io::output::*;
+
+        assert!(format_frame("frame-{}.png", 12) == "frame-000012.png");
+        assert!(format_frame("frame_%04d.vti", 12) == "frame_0012.vti");
+        assert!(format_frame("frame_%d.vti", 12) == "frame_12.vti");
+        assert!(format_frame("frame.vti", 12) == "frame.vti");
+
+        assert!("10".parse::<OutputInterval>() == Ok(OutputInterval::Steps(10)));
+        assert!("0.5s".parse::<OutputInterval>() == Ok(OutputInterval::Time(0.5)));
+        assert!("0".parse::<OutputInterval>().is_err());
+
+        // Every 3 steps.
+        let mut builder = OutputParamsBuilder::default();
+        let mut outputs = OutputManager::new(builder.interval(OutputInterval::Steps(3)).build()?, 0.0, 0.1);
+        let frames: Vec<_> = (1..=7).map(|s| outputs.frame(s as Scalar * 0.1)).collect();
+        assert!(frames == [None, None, Some(0), None, None, Some(1), None], "{:?}", frames);
+
+        // Every 0.25 time units with `dt = 0.1` and resumed at `t = 0.5`.
+        let mut outputs = OutputManager::new(builder.interval(OutputInterval::Time(0.25)).build()?, 0.5, 0.1);
+        let frames: Vec<_> = (6..=10).map(|s| outputs.frame(s as Scalar * 0.1)).collect();
+        assert!(frames == [None, None, Some(2), None, Some(3)], "{:?}", frames);
+
+        // Overwrite policies.
+        let dir = std::env::temp_dir().join("rustofluid-test-output");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("frame-000000.txt"), "")?;
+
+        builder.dir(Some(dir.to_string_lossy().into_owned()));
+
+        let outputs = OutputManager::new(builder.overwrite(OverwritePolicy::Skip).build()?, 0.0, 0.1);
+        assert!(outputs.frame_path("frame-{}.txt", 0)?.is_none());
+        assert!(outputs.frame_path("frame-{}.txt", 1)?.is_some());
+
+        let outputs = OutputManager::new(builder.overwrite(OverwritePolicy::Error).build()?, 0.0, 0.1);
+        assert!(outputs.frame_path("frame-{}.txt", 0).is_err());
+        assert!(outputs.file("frame-000000.txt", true)?.is_some());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_export_region() -> GenericResult<()> {
+        use crate::io::output::*;
+
+        let mut grid = Grid::new(dim!(10, 6), 0.5);
+        grid.cell_mut(idx!(3, 1)).set_smoke(1.0);
+
+        assert!(ExportRegion::parse_cells("1,2,3").is_err());
+        assert!(ExportRegion::parse_cells("3,0,1,5").is_err(), "Empty region accepted.");
+
+        // All cells overlapping the rectangle.
+        let rect = ExportRegion::parse_world("1.2, 0.4, 2.6, 1.5")?;
+        assert!(rect.window(&grid) == (idx!(2, 0), idx!(6, 3)));
+
+        let window = ExportRegion::parse_cells("10,6,100,100")?;
+        assert!(window.window(&grid) == (idx!(10, 6), idx!(12, 8)), "Window is not clamped.");
+
+        let mut builder = OutputParamsBuilder::default();
+        let outputs = OutputManager::new(builder.region(Some(rect)).build()?, 0.0, 0.1);
+        let cropped = outputs.crop(&grid).unwrap();
+
+        assert!(cropped.dim == idx!(4, 3) && cropped.origin() == idx!(2, 0));
+        assert!(cropped.cell(idx!(1, 1)).smoke() == 1.0);
+        assert!(cropped.cell(idx!(1, 1)).index() == idx!(1, 1));
+
+        let file = std::env::temp_dir().join("rustofluid-test-region.vti");
+        write_vti(&cropped, file.to_str().unwrap(), None)?;
+        let data = std::fs::read(&file)?;
+        let header = String::from_utf8_lossy(&data[0..data.len().min(1024)]);
+        assert!(header.contains(r#"WholeExtent="0 4 0 3 0 0" Origin="1 0 0""#), "{}", header);
+
+        let outputs = OutputManager::new(builder.region(None).build()?, 0.0, 0.1);
+        assert!(outputs.crop(&grid).is_none());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_dump() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(3, 2), 0.1);
+        grid.cell_mut(idx!(1, 2)).set_smoke(0.1);
+        grid.cell_mut(idx!(2, 1)).velocity.back = vec2!(0.5, -1.0 / 3.0);
+        grid.cell_mut(idx!(0, 0)).mode = CellTypes::Solid;
+
+        let file = std::env::temp_dir().join("rustofluid-test.rfd");
+        let file = file.to_str().unwrap();
+        write_dump(&grid, 7, 0.7, false, file)?;
+
+        let dump = read_dump(file)?;
+        assert!(dump.step == 7 && dump.time == 0.7 && dump.dim == idx!(5, 4));
+        assert!(dump.field("velocity").unwrap().components == 2);
+
+        // Header, table and 5x4 cells with 4 x f64, 1 x (2 x f64) and 1 x u8.
+        let names = ["smoke", "pressure", "div", "velocity", "solid"];
+        let table: usize = names.iter().map(|n| 3 + n.len()).sum();
+        assert!(std::fs::metadata(file)?.len() as usize == 42 + table + 20 * (5 * 8 + 1));
+
+        let mut restored = Grid::new(dim!(3, 2), 0.1);
+        dump.apply(&mut restored)?;
+        for idx in grid.iter_index() {
+            let (a, b) = (grid.cell(idx), restored.cell(idx));
+            assert!(a.velocity.back == b.velocity.back && a.smoke() == b.smoke());
+            assert!(a.mode == b.mode);
+        }
+
+        // Single precision.
+        let dump = Dump::decode(&Dump::new(&grid, 7, 0.7, true).encode())?;
+        let smoke = dump.field("smoke").unwrap();
+        assert!(smoke.value_type == DumpType::F32);
+        assert!(smoke.values[2 * 5 + 1] == 0.1f32 as Scalar);
+
+        assert!(Dump::decode(&std::fs::read(file)?[..50]).is_err(), "Truncated dump accepted.");
+        assert!(Dump::apply(&dump, &mut Grid::new(dim!(2, 2), 0.1)).is_err());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_checkpoint() -> GenericResult<()> {
+        let (log, _) = create_logger();
+
+        let mut grid = Grid::new(dim!(4, 4), 1.0);
+        grid.cell_mut(idx!(2, 3)).set_smoke(0.25);
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let params = SolverParamsBuilder::default()
+            .gravity(vec2!(0.0, -9.81))
+            .iterations(10)
+            .build()?;
+        let mut timestepper = TimeStepper::new(&log, params, objs, vec![]);
+        timestepper.compute_step(0.1)?;
+
+        let file = std::env::temp_dir().join("rustofluid-test.ckpt");
+        let file = file.to_str().unwrap();
+        timestepper.save_checkpoint(file, Compression::None)?;
+
+        let get_cell = |t: &TimeStepper| {
+            let grid = t.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+            return grid.cell(idx!(2, 3)).clone();
+        };
+        let expected = get_cell(&timestepper);
+
+        timestepper.compute_step(0.1)?;
+        timestepper.load_checkpoint(file)?;
+
+        let cell = get_cell(&timestepper);
+        assert!(timestepper.time() == 0.1, "Time: {}", timestepper.time());
+        assert!(cell.smoke() == expected.smoke());
+        assert!(cell.velocity.back == expected.velocity.back);
+
+        let header = read_checkpoint(file)?.header;
+        assert!(header.version == CHECKPOINT_VERSION && header.compression == Compression::None);
+
+        #[cfg(feature = "zstd")]
+        {
+            let compressed = std::env::temp_dir().join("rustofluid-test.ckpt.zst");
+            let compressed = compressed.to_str().unwrap();
+            timestepper.save_checkpoint(compressed, Compression::Zstd(3))?;
+
+            let checkpoint = read_checkpoint(compressed)?;
+            assert!(checkpoint.header.compression == Compression::Zstd(3));
+            assert!(std::fs::metadata(compressed)?.len() < std::fs::metadata(file)?.len());
+
+            let grid = &checkpoint.grids[0];
+            assert!(grid.cell(idx!(2, 3)).smoke() == expected.smoke());
+
+            for level in [0, 23] {
+                let file = std::env::temp_dir().join("rustofluid-test-zstd-level.ckpt");
+                let _ = std::fs::remove_file(&file);
+
+                let compression = Compression::Zstd(level);
+                let result = timestepper.save_checkpoint(file.to_str().unwrap(), compression);
+                assert!(result.is_err(), "Level {} is accepted.", level);
+                assert!(!file.exists(), "Level {} created a file.", level);
+            }
+        }
+
+        std::fs::write(file, b"garbage")?;
+        assert!(read_checkpoint(file).is_err());
+
+        return Ok(());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_checkpoint_zstd() -> GenericResult<()> {
+        let (log, _) = create_logger();
+
+        let mut grid = Grid::new(dim!(20, 10), 1.0);
+        for idx in grid.iter_index() {
+            let c = grid.cell_mut(idx);
+            c.set_smoke((idx.x * idx.y) as Scalar / 200.0);
+            c.velocity.back = vec2!(idx.x as Scalar, -(idx.y as Scalar));
+            c.pressure = idx.x as Scalar - 0.5;
+        }
+        let expected = grid.snapshot();
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let params = SolverParamsBuilder::default().build()?;
+        let mut timestepper = TimeStepper::new(&log, params, objs, vec![]);
+
+        let file = std::env::temp_dir().join("rustofluid-test-zstd.ckpt");
+        let file = file.to_str().unwrap();
+        timestepper.save_checkpoint(file, Compression::Zstd(19))?;
+
+        let checkpoint = read_checkpoint(file)?;
+        assert!(checkpoint.header.compression == Compression::Zstd(19));
+
+        let grid = &checkpoint.grids[0];
+        for idx in grid.iter_index() {
+            let (a, b) = (grid.cell(idx), expected.cell(idx));
+            assert!(
+                a.smoke() == b.smoke() && a.velocity.back == b.velocity.back,
+                "Cell {:?} differs.",
+                idx
+            );
+            assert!(a.pressure == b.pressure, "Pressure of {:?} differs.", idx);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn test_checkpoint_zstd_missing() -> GenericResult<()> {
+        // A header with the compression `zstd` (id 1) at level 3.
+        let mut bytes = b"RFCK".to_vec();
+        bytes.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        bytes.push(1);
+        bytes.extend_from_slice(&3i32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+
+        let file = std::env::temp_dir().join("rustofluid-test-zstd-missing.ckpt");
+        let file = file.to_str().unwrap();
+        std::fs::write(file, &bytes)?;
+
+        let error = read_checkpoint(file).err().expect("Compressed checkpoint is read.");
+        assert!(error.to_string().contains("zstd"), "Error: {}", error);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_frame_writer() -> GenericResult<()> {
+        use crate::io::writer::{FrameWriter, WriteJob};
+        use std::sync::{Arc, Mutex};
+
+        let grid = Arc::new(Grid::new(dim!(4, 2), 0.5));
+        let dir = std::env::temp_dir().join("rustofluid-test-writer");
+        std::fs::create_dir_all(&dir)?;
+
+        // Slow outputs with a queue of one frame stall the submits.
+        let mut writer = FrameWriter::new(1);
+        let files = (0..4).map(|i| dir.join(format!("frame-{}.vti", i))).collect::<Vec<_>>();
+        for file in files.iter() {
+            let (grid, file) = (grid.clone(), file.clone());
+            writer.submit(vec![Box::new(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                return write_vti(&grid, file.to_str().unwrap(), None);
+            })])?;
+        }
+        assert!(writer.stalls() > 0, "Submits did not wait.");
+
+        // Dropping the writer flushes all frames.
+        drop(writer);
+        assert!(files.iter().all(|f| f.exists()), "Frames not written.");
+
+        // Outputs after a failed one are skipped and the error is reported.
+        let written = Arc::new(Mutex::new(0));
+        let mut writer = FrameWriter::new(4);
+        let count = written.clone();
+        let jobs: Vec<WriteJob> = vec![
+            Box::new(|| write_vti(&Grid::new(dim!(1, 1), 1.0), "/nonexistent/dir/a.vti", None)),
+            Box::new(move || {
+                *count.lock().unwrap() += 1;
+                return Ok(());
+            }),
+        ];
+        writer.submit(jobs)?;
+        assert!(writer.finish().is_err(), "Error not reported.");
+        assert!(*written.lock().unwrap() == 0, "Output after an error written.");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_checkpoint_snapshot() -> GenericResult<()> {
+        let (log, _) = create_logger();
+
+        let mut grid = Grid::new(dim!(4, 6), 1.0);
+        grid.cell_mut(idx!(2, 3)).set_smoke(0.5);
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let params = SolverParamsBuilder::default()
+            .gravity(vec2!(0.0, -9.81))
+            .iterations(10)
+            .build()?;
+        let mut timestepper = TimeStepper::new(&log, params, objs, vec![]);
+        timestepper.compute_step(0.1)?;
+
+        let snapshot = CheckpointSnapshot::new(&timestepper);
+        let expected = {
+            let grid = timestepper.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+            grid.cell(idx!(2, 3)).clone()
+        };
+
+        // Later steps do not change the snapshot.
+        timestepper.compute_step(0.1)?;
+
+        let file = std::env::temp_dir().join("rustofluid-test-snapshot.ckpt");
+        let file = file.to_str().unwrap().to_string();
+        let path = file.clone();
+        let write = move || snapshot.write(&path, Compression::None).map_err(|e| e.to_string());
+        std::thread::spawn(write).join().unwrap()?;
+
+        let checkpoint = read_checkpoint(&file)?;
+        let cell = checkpoint.grids[0].cell(idx!(2, 3));
+        assert!(checkpoint.state.t == 0.1, "Time: {}", checkpoint.state.t);
+        assert!(cell.smoke() == expected.smoke());
+        assert!(cell.velocity.back == expected.velocity.back);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_delta_checkpoint() -> GenericResult<()> {
+        let (log, _) = create_logger();
+
+        // 4 x 2 tiles of which only the one of the changed cell is stored.
+        let mut grid = Grid::new(dim!(64, 32), 1.0);
+        grid.cell_mut(idx!(2, 3)).set_smoke(0.5);
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let params = SolverParamsBuilder::default().iterations(10).build()?;
+        let mut timestepper = TimeStepper::new(&log, params, objs, vec![]);
+
+        let file = std::env::temp_dir().join("rustofluid-test-delta.ckpt");
+        let file = file.to_str().unwrap();
+        let delta = delta_path(file);
+
+        let mut deltas = DeltaCheckpoints::new(3, 0.0);
+        deltas.snapshot(&timestepper)?.write(file, Compression::None)?;
+        let full_size = std::fs::metadata(file)?.len();
+        assert!(!std::path::Path::new(&delta).exists(), "Key-frame wrote a delta.");
+
+        // Only the tile of the changed cell is stored.
+        timestepper.compute_step(0.1)?;
+        {
+            let grid = timestepper.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+            grid.cell_mut(idx!(30, 15)).set_smoke(0.25);
+        }
+        deltas.snapshot(&timestepper)?.write(file, Compression::None)?;
+        assert!(std::fs::metadata(&delta)?.len() < full_size / 2, "Delta is too large.");
+
+        let checkpoint = read_checkpoint(file)?;
+        let grid = &checkpoint.grids[0];
+        let expected = timestepper.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+        assert!(checkpoint.delta && checkpoint.state.t == 0.1);
+        for i in [idx!(30, 15), idx!(2, 3)] {
+            assert!(grid.cell(i).smoke() == expected.cell(i).smoke(), "Cell {}", i);
+        }
+
+        // The fourth checkpoint is a key-frame which removes the delta.
+        deltas.snapshot(&timestepper)?.write(file, Compression::None)?;
+        deltas.snapshot(&timestepper)?.write(file, Compression::None)?;
+        assert!(!std::path::Path::new(&delta).exists(), "Stale delta not removed.");
+        assert!(!read_checkpoint(file)?.delta);
+
+        // A delta needs the dimension of the key-frame.
+        {
+            let grid = timestepper.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+            *grid = Grid::new(dim!(8, 8), 1.0);
+        }
+        assert!(deltas.snapshot(&timestepper).is_err(), "Delta of another dimension.");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_state_buffer() -> GenericResult<()> {
+        use crate::scene::rng::Rng;
+        use std::collections::BTreeMap;
+
+        let (log, _) = create_logger();
+
+        let mut grid = Grid::new(dim!(4, 4), 1.0);
+        grid.cell_mut(idx!(2, 3)).set_smoke(0.25);
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let params = SolverParamsBuilder::default()
+            .gravity(vec2!(0.0, -9.81))
+            .iterations(10)
+            .build()?;
+        let mut timestepper = TimeStepper::new(&log, params.clone(), objs, vec![]);
+        timestepper.compute_step(0.1)?;
+
+        let streams = BTreeMap::from([("a".to_string(), Rng::new(3).stream("a"))]);
+        let bytes = encode_state(&timestepper, &streams, 1, 0.1, Compression::None)?;
+        assert!(&bytes[0..4] == b"RFST", "Magic: {:?}", &bytes[0..4]);
+        assert!(bytes[4..8] == STATE_VERSION.to_le_bytes(), "Version is not little-endian.");
+
+        let get_cell = |t: &TimeStepper| {
+            let grid = t.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+            return grid.cell(idx!(2, 3)).clone();
+        };
+        let expected = get_cell(&timestepper);
+
+        timestepper.compute_step(0.1)?;
+        {
+            let grid = timestepper.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+            grid.set_activity_threshold(1e-3);
+        }
+        let (header, state) = decode_state(&bytes)?;
+        assert!(header.version == STATE_VERSION && header.compression == Compression::None);
+        assert!(state.steps == 1 && state.dt == 0.1 && state.streams == streams);
+        state.restore(&mut timestepper)?;
+
+        let cell = get_cell(&timestepper);
+        assert!(timestepper.time() == 0.1, "Time: {}", timestepper.time());
+        assert!(cell.smoke() == expected.smoke());
+        assert!(cell.velocity.back == expected.velocity.back);
+
+        // The settings of the grid are kept.
+        let grid = timestepper.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+        assert!(grid.active_region().is_some(), "Activity threshold is reset.");
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(Grid::new(dim!(5, 4), 1.0))];
+        let mut other = TimeStepper::new(&log, params, objs, vec![]);
+        let (_, state) = decode_state(&bytes)?;
+        assert!(state.restore(&mut other).is_err(), "Another dimension is restored.");
+
+        #[cfg(feature = "zstd")]
+        {
+            let compressed = encode_state(&timestepper, &streams, 1, 0.1, Compression::Zstd(3))?;
+            assert!(compressed.len() < bytes.len());
+
+            let (header, state) = decode_state(&compressed)?;
+            assert!(header.compression == Compression::Zstd(3));
+            assert!(state.grids[0].cell(idx!(2, 3)).smoke() == expected.smoke());
+        }
+
+        let mut other = bytes.clone();
+        other[4] += 1;
+        assert!(decode_state(&other).is_err(), "Another version is decoded.");
+
+        // The float sizes follow the 13 bytes of the header.
+        let mut other = bytes.clone();
+        other[13] = 2;
+        assert!(decode_state(&other).is_err(), "Other float sizes are decoded.");
+        assert!(encode_state(&timestepper, &streams, 1, 0.1, Compression::Zstd(0)).is_err());
+
+        // The sizes follow: one grid of 6 x 6 cells (with the border) and one stream.
+        assert!(bytes[15..19] == [1, 6, 6, 1], "Sizes: {:?}", &bytes[15..19]);
+        for (i, value) in [(17, 7), (17, 250), (18, 2)] {
+            let mut other = bytes.clone();
+            other[i] = value;
+            assert!(decode_state(&other).is_err(), "Corrupted byte {} is decoded.", i);
+        }
+
+        for n in 0..bytes.len() {
+            assert!(decode_state(&bytes[..n]).is_err(), "{} bytes are decoded.", n);
+        }
+        assert!(decode_state(b"garbage").is_err());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_checkpoint_resume() -> GenericResult<()> {
+        let (log, _) = create_logger();
+
+        // The settings of the grid are not the defaults.
+        let create = |vorticity_confinement: Scalar| {
+            let mut grid = Grid::new(dim!(12, 10), 0.1);
+            grid.cell_mut(idx!(4, 5)).set_smoke(0.5);
+            grid.cell_mut(idx!(4, 5)).velocity.back = vec2!(1.0, 0.5);
+            grid.vorticity_confinement = vorticity_confinement;
+            grid.over_relaxation = 1.5;
+
+            let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+            let params = SolverParamsBuilder::default()
+                .gravity(vec2!(0.0, -9.81))
+                .iterations(10)
+                .build()
+                .unwrap();
+            return TimeStepper::new(&log, params, objs, vec![]);
+        };
+        let grid = |t: &TimeStepper| {
+            return t.objects[0].as_any().downcast_ref::<Grid>().unwrap().snapshot();
+        };
+
+        let file = std::env::temp_dir().join("rustofluid-test-resume.ckpt");
+        let file = file.to_str().unwrap();
+
+        let mut run = create(0.2);
+        run.compute_step(0.05)?;
+        run.save_checkpoint(file, Compression::None)?;
+        for _ in 0..3 {
+            run.compute_step(0.05)?;
+        }
+
+        // The resumed run continues as if it was not interrupted.
+        let mut resumed = create(0.2);
+        resumed.load_checkpoint(file)?;
+        for _ in 0..3 {
+            resumed.compute_step(0.05)?;
+        }
+
+        let (a, b) = (grid(&run), grid(&resumed));
+        assert!(run.time() == resumed.time(), "Time: {}", resumed.time());
+        for idx in a.iter_index() {
+            let (ca, cb) = (a.cell(idx), b.cell(idx));
+            assert!(ca.velocity.back == cb.velocity.back, "Velocity differs at {}.", idx);
+            assert!(ca.smoke() == cb.smoke() && ca.pressure == cb.pressure, "Cell {}", idx);
+        }
+
+        // The settings of the scene are kept.
+        let mut other = create(0.0);
+        other.load_checkpoint(file)?;
+        let (a, b) = (read_checkpoint(file)?.grids.remove(0), grid(&other));
+        assert!(b.vorticity_confinement == 0.0 && b.over_relaxation == 1.5);
+        assert!(a.vorticity_confinement == 0.2, "Checkpoint has the settings of the run.");
+        assert!(b.cell(idx!(4, 5)).smoke() == a.cell(idx!(4, 5)).smoke());
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,251 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::Cell;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Minimal OpenVDB writer for `Tree_float_5_4_3` grids without compression.
+// The 2D grid is stored as a single voxel slice at `z = 0`.
+// Reference: `openvdb/io/Archive.cc` and the node `writeTopology/writeBuffers`.
+
+const MAGIC: i64 = 0x56444220;
+const FILE_VERSION: u32 = 224;
+const LIBRARY_VERSION: [u32; 2] = [9, 0];
+
+/// Metadata flag for `writeCompressedValues`: all values follow, no mask.
+const NO_MASK_AND_ALL_VALS: u8 = 6;
+
+const LEAF_LOG2: u32 = 3;
+const LOWER_LOG2: u32 = 4;
+const UPPER_LOG2: u32 = 5;
+
+type Coord = (i32, i32, i32);
+
+struct Leaf {
+    mask: Vec<u64>,
+    values: Vec<f32>,
+}
+
+type Lower = BTreeMap<Coord, Leaf>;
+type Upper = BTreeMap<Coord, Lower>;
+type Root = BTreeMap<Coord, Upper>;
+
+fn origin(c: Coord, log2_total: u32) -> Coord {
+    let m = !((1i32 << log2_total) - 1);
+    return (c.0 & m, c.1 & m, c.2 & m);
+}
+
+/// Linear offset of `c` inside a node with `2^log2_dim` children per axis,
+/// each covering `2^log2_child` voxels.
+fn offset(c: Coord, log2_dim: u32, log2_child: u32) -> usize {
+    let dim = (1i32 << (log2_dim + log2_child)) - 1;
+    let x = ((c.0 & dim) >> log2_child) as usize;
+    let y = ((c.1 & dim) >> log2_child) as usize;
+    let z = ((c.2 & dim) >> log2_child) as usize;
+    return (x << (2 * log2_dim)) | (y << log2_dim) | z;
+}
+
+fn set_bit(mask: &mut [u64], n: usize) {
+    mask[n >> 6] |= 1 << (n & 63);
+}
+
+struct VdbBuffer {
+    data: Vec<u8>,
+}
+
+impl VdbBuffer {
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i32(&mut self, v: i32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn i64(&mut self, v: i64) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f32(&mut self, v: f32) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f64(&mut self, v: f64) {
+        self.data.extend_from_slice(&v.to_le_bytes());
+    }
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.data.extend_from_slice(s.as_bytes());
+    }
+    fn coord(&mut self, c: Coord) {
+        self.i32(c.0);
+        self.i32(c.1);
+        self.i32(c.2);
+    }
+    fn mask(&mut self, mask: &[u64]) {
+        for w in mask {
+            self.data.extend_from_slice(&w.to_le_bytes());
+        }
+    }
+    fn values(&mut self, values: &[f32]) {
+        self.u8(NO_MASK_AND_ALL_VALS);
+        for v in values {
+            self.f32(*v);
+        }
+    }
+    fn pos(&self) -> i64 {
+        return self.data.len() as i64;
+    }
+    fn patch_i64(&mut self, at: i64, v: i64) {
+        let at = at as usize;
+        self.data[at..at + 8].copy_from_slice(&v.to_le_bytes());
+    }
+}
+
+fn build_tree<F: Fn(&Cell) -> Scalar>(grid: &Grid, get: &F) -> Root {
+    let mut tree = Root::new();
+
+    for idx in grid.iter_index() {
+        let pos = grid.origin() + idx;
+        let c: Coord = (pos.x as i32, pos.y as i32, 0);
+
+        let leaf = tree
+            .entry(origin(c, LEAF_LOG2 + LOWER_LOG2 + UPPER_LOG2))
+            .or_default()
+            .entry(origin(c, LEAF_LOG2 + LOWER_LOG2))
+            .or_default()
+            .entry(origin(c, LEAF_LOG2))
+            .or_insert_with(|| Leaf {
+                mask: vec![0; (1 << (3 * LEAF_LOG2)) / 64],
+                values: vec![0.0; 1 << (3 * LEAF_LOG2)],
+            });
+
+        let n = offset(c, LEAF_LOG2, 0);
+        set_bit(&mut leaf.mask, n);
+        leaf.values[n] = get(grid.cell(idx)) as f32;
+    }
+
+    return tree;
+}
+
+/// Write an internal node's topology: child mask, value mask and (zero) tile values.
+fn write_internal_topology<'a, I>(buf: &mut VdbBuffer, children: I, log2_dim: u32, log2_child: u32)
+where
+    I: Iterator<Item = &'a Coord>,
+{
+    let n = 1usize << (3 * log2_dim);
+    let mut child_mask = vec![0u64; n / 64];
+    for c in children {
+        set_bit(&mut child_mask, offset(*c, log2_dim, log2_child));
+    }
+
+    buf.mask(&child_mask);
+    buf.mask(&vec![0u64; n / 64]);
+    buf.values(&vec![0.0; n]);
+}
+
+fn write_grid<F: Fn(&Cell) -> Scalar>(buf: &mut VdbBuffer, grid: &Grid, name: &str, get: F) {
+    let tree = build_tree(grid, &get);
+
+    // Grid descriptor.
+    buf.string(name);
+    buf.string("Tree_float_5_4_3");
+    buf.string(""); // Instance parent.
+
+    let offsets_pos = buf.pos();
+    buf.i64(0); // Grid position.
+    buf.i64(0); // Block position.
+    buf.i64(0); // End position.
+    let grid_pos = buf.pos();
+
+    buf.u32(0); // Compression: none.
+
+    // Grid metadata.
+    buf.u32(1);
+    buf.string("class");
+    buf.string("string");
+    buf.string("fog volume");
+
+    // Transform.
+    let h = grid.cell_width;
+    buf.string("UniformScaleMap");
+    for v in [h, h, 1.0 / h, 1.0 / (h * h), 0.5 / h] {
+        // Scale, voxel size, inverse scale, inverse scale squared, inverse twice scale.
+        for _ in 0..3 {
+            buf.f64(v as f64);
+        }
+    }
+
+    // Topology.
+    buf.i32(1); // Buffer count.
+    buf.f32(0.0); // Background.
+    buf.u32(0); // Number of tiles.
+    buf.u32(tree.len() as u32);
+
+    for (upper_origin, upper) in tree.iter() {
+        buf.coord(*upper_origin);
+        write_internal_topology(buf, upper.keys(), UPPER_LOG2, LOWER_LOG2 + LEAF_LOG2);
+
+        for lower in upper.values() {
+            write_internal_topology(buf, lower.keys(), LOWER_LOG2, LEAF_LOG2);
+
+            for leaf in lower.values() {
+                buf.mask(&leaf.mask);
+            }
+        }
+    }
+
+    // Leaf buffers.
+    let block_pos = buf.pos();
+    for leaf in tree.values().flat_map(|u| u.values()).flat_map(|l| l.values()) {
+        buf.mask(&leaf.mask);
+        buf.values(&leaf.values);
+    }
+    let end_pos = buf.pos();
+
+    buf.patch_i64(offsets_pos, grid_pos);
+    buf.patch_i64(offsets_pos + 8, block_pos);
+    buf.patch_i64(offsets_pos + 16, end_pos);
+}
+
+/// Write the smoke, pressure and divergence fields as OpenVDB float grids.
+/// The run metadata is stored as `string` file metadata.
+pub fn write_vdb(grid: &Grid, file: &str, meta: Option<&RunMetadata>) -> GenericResult<()> {
+    let mut buf = VdbBuffer { data: Vec::new() };
+
+    // Header.
+    buf.i64(MAGIC);
+    buf.u32(FILE_VERSION);
+    buf.u32(LIBRARY_VERSION[0]);
+    buf.u32(LIBRARY_VERSION[1]);
+    buf.u8(1); // Has grid offsets.
+    buf.data
+        .extend_from_slice(b"00000000-0000-0000-0000-000000000000"); // UUID.
+
+    let entries = meta.map(|m| m.entries()).unwrap_or_default();
+    buf.u32(entries.len() as u32); // File metadata count.
+    for (key, value) in entries {
+        buf.string(key);
+        buf.string("string");
+        buf.string(&value);
+    }
+
+    let fields: [(&str, fn(&Cell) -> Scalar); 3] = [
+        ("density", |c| c.smoke()),
+        ("pressure", |c| c.pressure),
+        ("divergence", |c| c.div),
+    ];
+
+    buf.i32(fields.len() as i32);
+    for (name, get) in fields {
+        write_grid(&mut buf, grid, name, get);
+    }
+
+    let mut w = BufWriter::new(File::create(file)?);
+    w.write_all(&buf.data)?;
+    w.flush()?;
+
+    return Ok(());
+}
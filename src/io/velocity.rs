@@ -0,0 +1,146 @@
+use crate::io::npy::read_npy;
+use crate::types::*;
+
+use std::path::Path;
+
+/// Load a cell-centered velocity field with one value per inside cell of a grid
+/// with `dim` cells (row-major, `y`-axis pointing upwards).
+///
+/// - `.npy`: shape `(ny, nx, 2)` with `a[j, i]` the velocity of cell `(i, j)`
+///   (as written by `--output-npy`).
+/// - `.csv`: columns `i`, `j`, `vel_x` and `vel_y` (as written by `--output-fields-csv`).
+///
+/// Fields which include the border cells (`dim + 2`) are cropped. Other sizes are
+/// resampled bilinearly if `resample` is set.
+pub fn load_velocity(file: &str, dim: Index2, resample: bool) -> GenericResult<Vec<Vector2>> {
+    let ext = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    let (src_dim, values) = match ext {
+        "npy" => read_velocity_npy(file)?,
+        "csv" => read_velocity_csv(file)?,
+        _ => {
+            return Err(format!(
+                "Unknown velocity file format '{}' (use '.npy' or '.csv').",
+                file
+            )
+            .into())
+        }
+    };
+
+    if src_dim == dim {
+        return Ok(values);
+    }
+
+    if src_dim == dim + idx!(2, 2) {
+        return Ok(crop_border(src_dim, &values));
+    }
+
+    if !resample {
+        return Err(format!(
+            "Velocity field '{}' has size {}x{} but needs {}x{}.",
+            file,
+            src_dim.x,
+            src_dim.y,
+            dim.x,
+            dim.y
+        )
+        .into());
+    }
+
+    return Ok(resample_bilinear(src_dim, &values, dim));
+}
+
+fn read_velocity_npy(file: &str) -> GenericResult<(Index2, Vec<Vector2>)> {
+    let (shape, data) = read_npy(file)?;
+
+    if shape.len() != 3 || shape[2] != 2 {
+        return Err(format!(
+            "Velocity array '{}' has shape {:?} but needs (ny, nx, 2).",
+            file, shape
+        )
+        .into());
+    }
+
+    let values = data.chunks_exact(2).map(|v| vec2!(v[0], v[1])).collect();
+    return Ok((idx!(shape[1], shape[0]), values));
+}
+
+fn read_velocity_csv(file: &str) -> GenericResult<(Index2, Vec<Vector2>)> {
+    let content = std::fs::read_to_string(file)?;
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+
+    let header: Vec<&str> = lines.next().ok_or("Empty CSV file.")?.split(',').map(|h| h.trim()).collect();
+    let column = |name: &str| {
+        return header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or(format!("CSV file '{}' has no column '{}'.", file, name));
+    };
+    let cols = [column("i")?, column("j")?, column("vel_x")?, column("vel_y")?];
+
+    let mut rows = vec![];
+    for line in lines {
+        let v: Vec<&str> = line.split(',').map(|v| v.trim()).collect();
+        let get = |c: usize| v.get(c).ok_or(format!("Row '{}' has too few columns.", line));
+
+        let index = idx!(get(cols[0])?.parse::<usize>()?, get(cols[1])?.parse::<usize>()?);
+        let vel = vec2!(get(cols[2])?.parse::<Scalar>()?, get(cols[3])?.parse::<Scalar>()?);
+        rows.push((index, vel));
+    }
+
+    let dim = rows
+        .iter()
+        .fold(idx!(0, 0), |d, (i, _)| idx!(d.x.max(i.x + 1), d.y.max(i.y + 1)));
+
+    let mut values = vec![Vector2::zeros(); dim.x * dim.y];
+    let mut set = vec![false; values.len()];
+    for (i, vel) in rows {
+        values[i.y * dim.x + i.x] = vel;
+        set[i.y * dim.x + i.x] = true;
+    }
+
+    if set.iter().any(|s| !s) {
+        return Err(format!(
+            "CSV file '{}' does not contain all {}x{} cells.",
+            file, dim.x, dim.y
+        )
+        .into());
+    }
+
+    return Ok((dim, values));
+}
+
+/// Remove the border cells of a field with `dim` cells.
+fn crop_border(dim: Index2, values: &[Vector2]) -> Vec<Vector2> {
+    let mut cropped = Vec::with_capacity((dim.x - 2) * (dim.y - 2));
+    for j in 1..dim.y - 1 {
+        cropped.extend_from_slice(&values[j * dim.x + 1..(j + 1) * dim.x - 1]);
+    }
+    return cropped;
+}
+
+/// Resample the cell-centered field with `src_dim` cells to `dim` cells.
+pub fn resample_bilinear(src_dim: Index2, values: &[Vector2], dim: Index2) -> Vec<Vector2> {
+    // Source coordinate and interpolation weight of target cell `i` along one axis.
+    let coord = |i: usize, n: usize, src_n: usize| {
+        let u = ((i as Scalar + 0.5) * src_n as Scalar / n as Scalar - 0.5).clamp(0.0, (src_n - 1) as Scalar);
+        let i0 = u.floor() as usize;
+        return (i0, (i0 + 1).min(src_n - 1), u - i0 as Scalar);
+    };
+
+    let at = |i: usize, j: usize| values[j * src_dim.x + i];
+
+    let mut out = Vec::with_capacity(dim.x * dim.y);
+    for j in 0..dim.y {
+        let (j0, j1, tj) = coord(j, dim.y, src_dim.y);
+        for i in 0..dim.x {
+            let (i0, i1, ti) = coord(i, dim.x, src_dim.x);
+
+            let bottom = at(i0, j0) * (1.0 - ti) + at(i1, j0) * ti;
+            let top = at(i0, j1) * (1.0 - ti) + at(i1, j1) * ti;
+            out.push(bottom * (1.0 - tj) + top * tj);
+        }
+    }
+
+    return out;
+}
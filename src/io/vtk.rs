@@ -0,0 +1,256 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const SCALAR_SIZE: usize = std::mem::size_of::<Scalar>();
+
+/// The VTK type of `Scalar`.
+const SCALAR_TYPE: &str = if SCALAR_SIZE == 4 { "Float32" } else { "Float64" };
+
+/// A cell data array which gets appended in raw binary format.
+struct DataArray {
+    name: &'static str,
+    type_name: &'static str,
+    components: usize,
+    bytes: Vec<u8>,
+}
+
+impl DataArray {
+    fn scalars<F: Fn(Index2) -> Scalar>(grid: &Grid, name: &'static str, f: F) -> DataArray {
+        let mut bytes = Vec::with_capacity(grid.dim.x * grid.dim.y * SCALAR_SIZE);
+        for idx in grid.iter_index() {
+            bytes.extend_from_slice(&f(idx).to_le_bytes());
+        }
+
+        return DataArray {
+            name,
+            type_name: SCALAR_TYPE,
+            components: 1,
+            bytes,
+        };
+    }
+
+    fn vectors<F: Fn(Index2) -> Vector2>(grid: &Grid, name: &'static str, f: F) -> DataArray {
+        let mut bytes = Vec::with_capacity(grid.dim.x * grid.dim.y * 3 * SCALAR_SIZE);
+        for idx in grid.iter_index() {
+            let v = f(idx);
+            // VTK vectors are always 3-dimensional.
+            for c in [v.x, v.y, 0.0] {
+                bytes.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+
+        return DataArray {
+            name,
+            type_name: SCALAR_TYPE,
+            components: 3,
+            bytes,
+        };
+    }
+
+    fn cell_types(grid: &Grid) -> DataArray {
+        return DataArray {
+            name: "cell_type",
+            type_name: "UInt8",
+            components: 1,
+            bytes: grid
+                .iter_index()
+                .map(|idx| match grid.cell(idx).mode {
+                    CellTypes::Fluid => 0u8,
+                    CellTypes::Solid => 1u8,
+                })
+                .collect(),
+        };
+    }
+}
+
+/// Write the run metadata as VTK field data. Numbers are `Float64` arrays,
+/// text is a `String` array (character codes terminated by `0`).
+fn write_field_data<W: Write>(w: &mut W, meta: &RunMetadata) -> GenericResult<()> {
+    writeln!(w, r#"    <FieldData>"#)?;
+
+    for (key, value) in meta.entries() {
+        let (type_name, data) = match key {
+            "dt" | "step" | "time" => ("Float64", value),
+            _ => (
+                "String",
+                value
+                    .bytes()
+                    .chain([0])
+                    .map(|b| b.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        };
+
+        writeln!(
+            w,
+            r#"      <DataArray type="{}" Name="{}" NumberOfTuples="1" format="ascii">{}</DataArray>"#,
+            type_name, key, data
+        )?;
+    }
+
+    writeln!(w, r#"    </FieldData>"#)?;
+    return Ok(());
+}
+
+/// Write the grid's cell fields as VTK ImageData (`.vti`) with
+/// binary appended data, readable by ParaView.
+/// The velocity is interpolated to the cell centers.
+pub fn write_vti(grid: &Grid, file: &str, meta: Option<&RunMetadata>) -> GenericResult<()> {
+    let arrays = [
+        DataArray::scalars(grid, "pressure", |idx| grid.cell(idx).pressure),
+        DataArray::scalars(grid, "smoke", |idx| grid.cell(idx).smoke()),
+        DataArray::scalars(grid, "divergence", |idx| grid.cell(idx).div),
+        DataArray::vectors(grid, "velocity", |idx| grid.cell_center_velocity(idx)),
+        DataArray::cell_types(grid),
+    ];
+
+    let mut w = BufWriter::new(File::create(file)?);
+    let h = grid.cell_width;
+    let extent = format!("0 {} 0 {} 0 0", grid.dim.x, grid.dim.y);
+    let origin = grid.origin_position();
+
+    writeln!(w, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        w,
+        r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )?;
+    writeln!(
+        w,
+        r#"  <ImageData WholeExtent="{}" Origin="{} {} 0" Spacing="{} {} {}">"#,
+        extent, origin.x, origin.y, h, h, h
+    )?;
+    if let Some(m) = meta {
+        write_field_data(&mut w, m)?;
+    }
+    writeln!(w, r#"    <Piece Extent="{}">"#, extent)?;
+    writeln!(w, r#"      <CellData Scalars="smoke" Vectors="velocity">"#)?;
+
+    // Each appended block is prefixed by its byte count (`UInt64`).
+    let mut offset = 0;
+    for a in arrays.iter() {
+        writeln!(
+            w,
+            r#"        <DataArray type="{}" Name="{}" NumberOfComponents="{}" format="appended" offset="{}"/>"#,
+            a.type_name, a.name, a.components, offset
+        )?;
+        offset += std::mem::size_of::<u64>() + a.bytes.len();
+    }
+
+    writeln!(w, r#"      </CellData>"#)?;
+    writeln!(w, r#"    </Piece>"#)?;
+    writeln!(w, r#"  </ImageData>"#)?;
+    write!(w, r#"  <AppendedData encoding="raw">"#)?;
+    write!(w, "\n   _")?;
+
+    for a in arrays.iter() {
+        w.write_all(&(a.bytes.len() as u64).to_le_bytes())?;
+        w.write_all(&a.bytes)?;
+    }
+
+    writeln!(w, "\n  </AppendedData>")?;
+    writeln!(w, "</VTKFile>")?;
+    w.flush()?;
+
+    return Ok(());
+}
+
+/// The `.pvd` collection file for the frame `pattern`,
+/// e.g. `frames/frame-{}.vti` gives `frames/frame.pvd`.
+pub fn pvd_path(pattern: &str) -> String {
+    let path = Path::new(pattern);
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+
+    let stem = match stem.find("{}").or(stem.find('%')) {
+        Some(i) => &stem[..i],
+        None => &stem[..],
+    };
+    let stem = match stem.trim_end_matches(['-', '_', '.']) {
+        "" => "frames",
+        s => s,
+    };
+
+    return path
+        .with_file_name(format!("{}.pvd", stem))
+        .to_string_lossy()
+        .into_owned();
+}
+
+/// Value of the XML attribute `name` in `line`.
+fn xml_attribute<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!(r#" {}=""#, name);
+    let start = line.find(&key)? + key.len();
+    let end = line[start..].find('"')?;
+    return Some(&line[start..start + end]);
+}
+
+/// A ParaView collection (`.pvd`) which maps the simulation time to the frame files,
+/// such that ParaView loads a run as a time series.
+/// The file is rewritten after each added frame.
+pub struct PvdCollection {
+    file: String,
+    entries: Vec<(Scalar, String)>,
+}
+
+impl PvdCollection {
+    /// Open the collection `file`. With `append` the entries of an existing file are kept.
+    pub fn open(file: &str, append: bool) -> GenericResult<Self> {
+        let mut entries = vec![];
+
+        if append && Path::new(file).exists() {
+            for line in std::fs::read_to_string(file)?.lines() {
+                if let (Some(t), Some(f)) = (xml_attribute(line, "timestep"), xml_attribute(line, "file")) {
+                    entries.push((t.parse::<Scalar>()?, f.to_string()));
+                }
+            }
+        }
+
+        return Ok(PvdCollection {
+            file: file.to_string(),
+            entries,
+        });
+    }
+
+    pub fn entries(&self) -> &[(Scalar, String)] {
+        return &self.entries;
+    }
+
+    /// Add the frame `frame_file` at simulation time `time` and rewrite the collection.
+    /// Entries at or after `time` (e.g. from before a resume) are replaced.
+    pub fn add(&mut self, time: Scalar, frame_file: &str) -> GenericResult<()> {
+        // Frame files are referenced relative to the collection.
+        let dir = Path::new(&self.file).parent().unwrap_or(Path::new(""));
+        let rel = Path::new(frame_file)
+            .strip_prefix(dir)
+            .unwrap_or(Path::new(frame_file))
+            .to_string_lossy()
+            .into_owned();
+
+        self.entries.retain(|(t, _)| *t < time);
+        self.entries.push((time, rel));
+
+        return self.write();
+    }
+
+    fn write(&self) -> GenericResult<()> {
+        let mut w = BufWriter::new(File::create(&self.file)?);
+
+        writeln!(w, r#"<?xml version="1.0"?>"#)?;
+        writeln!(w, r#"<VTKFile type="Collection" version="1.0" byte_order="LittleEndian">"#)?;
+        writeln!(w, r#"  <Collection>"#)?;
+        for (t, f) in self.entries.iter() {
+            writeln!(w, r#"    <DataSet timestep="{:e}" group="" part="0" file="{}"/>"#, t, f)?;
+        }
+        writeln!(w, r#"  </Collection>"#)?;
+        writeln!(w, "</VTKFile>")?;
+        w.flush()?;
+
+        return Ok(());
+    }
+}
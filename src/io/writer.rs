@@ -0,0 +1,111 @@
+use crate::types::*;
+
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A file output of a frame. It owns (a snapshot of) the data it writes.
+pub type WriteJob = Box<dyn FnOnce() -> GenericResult<()> + Send>;
+
+/// Writes the outputs of frames on a background thread, so the solver does not
+/// wait for the disk.
+///
+/// At most `capacity` frames are pending; `submit` blocks if the writer falls behind
+/// (back-pressure), which bounds the memory of the queued snapshots.
+/// Dropping the writer waits until all pending frames are written.
+pub struct FrameWriter {
+    sender: Option<SyncSender<Vec<WriteJob>>>,
+    worker: Option<JoinHandle<()>>,
+
+    /// The first error of the background thread.
+    error: Arc<Mutex<Option<String>>>,
+
+    stalls: usize,
+}
+
+impl FrameWriter {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, receiver) = sync_channel::<Vec<WriteJob>>(capacity);
+        let error = Arc::new(Mutex::new(None));
+
+        let worker_error = error.clone();
+        let worker = std::thread::spawn(move || {
+            for jobs in receiver {
+                for job in jobs {
+                    // Skip all remaining outputs after an error.
+                    if worker_error.lock().unwrap().is_some() {
+                        continue;
+                    }
+
+                    if let Err(e) = job() {
+                        *worker_error.lock().unwrap() = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        return FrameWriter {
+            sender: Some(sender),
+            worker: Some(worker),
+            error,
+            stalls: 0,
+        };
+    }
+
+    /// Queue the outputs `jobs` of a frame.
+    /// Blocks while the queue is full and fails if a previous output failed.
+    pub fn submit(&mut self, jobs: Vec<WriteJob>) -> GenericResult<()> {
+        self.check()?;
+
+        if jobs.is_empty() {
+            return Ok(());
+        }
+
+        let sender = self.sender.as_ref().expect("Writer is finished.");
+        match sender.try_send(jobs) {
+            Ok(()) => {}
+            Err(TrySendError::Full(jobs)) => {
+                self.stalls += 1;
+                sender.send(jobs).map_err(|_| "Frame writer stopped.")?;
+            }
+            Err(TrySendError::Disconnected(_)) => bail!("Frame writer stopped."),
+        }
+
+        return Ok(());
+    }
+
+    /// Number of `submit` calls which had to wait for the writer.
+    pub fn stalls(&self) -> usize {
+        return self.stalls;
+    }
+
+    /// Write all pending frames and report the first error.
+    pub fn finish(mut self) -> GenericResult<()> {
+        self.flush();
+        return self.check();
+    }
+
+    fn check(&self) -> GenericResult<()> {
+        if let Some(ref e) = *self.error.lock().unwrap() {
+            return Err(format!("Writing the outputs failed: {}", e).into());
+        }
+        return Ok(());
+    }
+
+    /// Close the queue and wait for the background thread.
+    fn flush(&mut self) {
+        drop(self.sender.take());
+
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                *self.error.lock().unwrap() = Some("Frame writer panicked.".to_string());
+            }
+        }
+    }
+}
+
+impl Drop for FrameWriter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -7,8 +7,21 @@
 #[macro_use]
 extern crate derive_builder;
 
+#[cfg(feature = "cuda")]
+pub mod cuda;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod io;
 pub mod log;
+#[cfg(feature = "plotting")]
 pub mod plotting;
+pub mod render;
 pub mod scene;
 pub mod types;
 pub mod math;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "python")]
+pub mod python;
@@ -1,18 +1,29 @@
-use slog::{o, Drain};
+use serde_json::{Map, Value};
+use slog::{o, Drain, Level, KV};
 use slog_async;
+use std::collections::HashMap;
 use std::io;
 use std::result;
+use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use std::sync::{atomic, Arc};
+use std::sync::{atomic, Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The environment variable with the `LevelFilter` of `create_logger`.
+pub const LOG_ENV: &str = "RUSTOFLUID_LOG";
 
 #[allow(dead_code)]
 fn no_out(_io: &mut dyn io::Write) -> io::Result<()> {
     return Ok(());
 }
 
+/// The runtime control of a logger: switch it on and off, change the levels of the
+/// modules and set the step of the records (see `LogContext`).
 pub struct LevelSwitch {
-  switch: Arc<AtomicBool>
+  switch: Arc<AtomicBool>,
+  levels: Arc<RwLock<LevelFilter>>,
+  context: LogContext,
 }
 
 impl LevelSwitch {
@@ -22,6 +33,38 @@ impl LevelSwitch {
   pub fn disable(&self) {
     self.switch.store(false, Ordering::Relaxed);
   }
+
+  /// The levels of the modules which apply from now on.
+  pub fn levels(&self) -> LevelFilter {
+    return self.levels.read().unwrap().clone();
+  }
+
+  /// Replace the levels of the modules for all following records.
+  pub fn set_levels(&self, levels: LevelFilter) {
+    *self.levels.write().unwrap() = levels;
+  }
+
+  /// Stamp the following records with the step index and the simulation time.
+  pub fn set_step(&self, step: u64, time: f64) {
+    self.context.set(step, time);
+  }
+}
+
+/// The step index and the simulation time of a run, which the JSON records carry.
+/// Both are `null` until the first step is set.
+#[derive(Clone, Debug, Default)]
+pub struct LogContext {
+    step: Arc<Mutex<Option<(u64, f64)>>>,
+}
+
+impl LogContext {
+    pub fn set(&self, step: u64, time: f64) {
+        *self.step.lock().unwrap() = Some((step, time));
+    }
+
+    pub fn get(&self) -> Option<(u64, f64)> {
+        return *self.step.lock().unwrap();
+    }
 }
 
 /// Custom Drain logic
@@ -56,27 +99,381 @@ where
     }
 }
 
+/// The minimal level of the records per module, parsed from e.g.
+/// `info,rustofluid::scene::grid=warn`: a default level and levels for the modules
+/// with the given path prefix (the longest matching prefix wins).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelFilter {
+    pub default: Level,
+    pub modules: Vec<(String, Level)>,
+}
+
+impl Default for LevelFilter {
+    fn default() -> Self {
+        return LevelFilter {
+            default: Level::Trace,
+            modules: vec![],
+        };
+    }
+}
+
+impl LevelFilter {
+    /// The minimal level of the records of `module`.
+    pub fn level(&self, module: &str) -> Level {
+        return self
+            .modules
+            .iter()
+            .filter(|(m, _)| module.starts_with(m.as_str()))
+            .max_by_key(|(m, _)| m.len())
+            .map_or(self.default, |(_, l)| *l);
+    }
+}
+
+impl FromStr for LevelFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = |l: &str| {
+            return Level::from_str(l.trim())
+                .map_err(|_| format!("Log level '{}' is invalid.", l.trim()));
+        };
+
+        let mut filter = LevelFilter::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((module, l)) => filter.modules.push((module.trim().to_string(), level(l)?)),
+                None => filter.default = level(part)?,
+            }
+        }
+
+        return Ok(filter);
+    }
+}
+
+/// Let at most `max` records of the same call site through per `interval`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub max: u32,
+    pub interval: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        return RateLimit {
+            max: 10,
+            interval: Duration::from_secs(1),
+        };
+    }
+}
+
+/// Drops the records below the level of their module.
+/// The levels are shared with `LevelSwitch` and can change at any time.
+struct ModuleLevelFilter<D> {
+    drain: D,
+    levels: Arc<RwLock<LevelFilter>>,
+}
+
+impl<D> Drain for ModuleLevelFilter<D>
+where
+    D: Drain,
+{
+    type Ok = Option<D::Ok>;
+    type Err = Option<D::Err>;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let level = self.levels.read().unwrap().level(record.module());
+        if record.level().is_at_least(level) {
+            self.drain.log(record, values).map(Some).map_err(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+struct CallSite {
+    start: Instant,
+    count: u32,
+}
+
+/// Drops the records of a call site beyond the `RateLimit`. The number of dropped
+/// records is reported with the first record of the call site after the interval.
+struct RateLimitFilter<D> {
+    drain: D,
+    limit: RateLimit,
+    sites: Mutex<HashMap<(&'static str, u32), CallSite>>,
+}
+
+impl<D> Drain for RateLimitFilter<D>
+where
+    D: Drain,
+{
+    type Ok = Option<D::Ok>;
+    type Err = Option<D::Err>;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let (pass, dropped) = {
+            let mut sites = self.sites.lock().unwrap();
+            let now = Instant::now();
+            let site = sites.entry((record.file(), record.line())).or_insert(CallSite {
+                start: now,
+                count: 0,
+            });
+
+            let mut dropped = 0;
+            if now.duration_since(site.start) >= self.limit.interval {
+                dropped = site.count.saturating_sub(self.limit.max);
+                *site = CallSite {
+                    start: now,
+                    count: 0,
+                };
+            }
+
+            site.count += 1;
+            (site.count <= self.limit.max, dropped)
+        };
+
+        if dropped > 0 {
+            static RS: slog::RecordStatic<'static> = slog::record_static!(Level::Warning, "");
+            let (file, line) = (record.file(), record.line());
+            // The arguments of `format_args!` only live until the end of the statement.
+            self.drain
+                .log(
+                    &slog::Record::new(
+                        &RS,
+                        &format_args!(
+                            "Dropped {} repeated records of {}:{}.",
+                            dropped, file, line
+                        ),
+                        slog::b!(),
+                    ),
+                    values,
+                )
+                .map_err(Some)?;
+        }
+
+        if !pass {
+            return Ok(None);
+        }
+        self.drain.log(record, values).map(Some).map_err(Some)
+    }
+}
+
+/// Collects the key/values of a record as JSON values.
+struct JsonFields(Map<String, Value>);
+
+macro_rules! emit_json(
+    ($($emit:ident: $t:ty),*) => {
+        $(
+            fn $emit(&mut self, key: slog::Key, val: $t) -> slog::Result {
+                return self.insert(key, Value::from(val));
+            }
+        )*
+    };
+);
+
+impl JsonFields {
+    /// The innermost value of a key wins, i.e. the record's over its logger's.
+    fn insert(&mut self, key: slog::Key, value: Value) -> slog::Result {
+        self.0.entry(key.to_string()).or_insert(value);
+        return Ok(());
+    }
+}
+
+impl slog::Serializer for JsonFields {
+    fn emit_arguments(&mut self, key: slog::Key, val: &std::fmt::Arguments) -> slog::Result {
+        return self.insert(key, Value::String(val.to_string()));
+    }
+
+    fn emit_none(&mut self, key: slog::Key) -> slog::Result {
+        return self.insert(key, Value::Null);
+    }
+
+    emit_json!(
+        emit_bool: bool,
+        emit_u32: u32,
+        emit_i32: i32,
+        emit_u64: u64,
+        emit_i64: i64,
+        emit_usize: usize,
+        emit_isize: isize,
+        emit_f32: f32,
+        emit_f64: f64,
+        emit_str: &str
+    );
+}
+
+/// Writes one JSON object per record and line (JSON lines), e.g.
+/// ```text
+/// {"ts":1700000000.12,"level":"INFO","module":"rustofluid::scene::setup",
+///  "step":42,"time":0.42,"msg":"Setup done.","kv":{"cells":20000}}
+/// ```
+/// with the wall time `ts` in seconds since the Unix epoch, the `step` and the
+/// simulation `time` of the `LogContext` and the key/values of the record and its
+/// logger in `kv`. Each line is flushed.
+struct JsonDrain<W> {
+    out: Mutex<W>,
+    context: LogContext,
+}
+
+impl<W: io::Write> JsonDrain<W> {
+    fn format(&self, record: &slog::Record, values: &slog::OwnedKVList) -> io::Result<Value> {
+        let mut kv = JsonFields(Map::new());
+        record.kv().serialize(record, &mut kv)?;
+        values.serialize(record, &mut kv)?;
+
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64());
+        let (step, time) = match self.context.get() {
+            Some((step, time)) => (Value::from(step), Value::from(time)),
+            None => (Value::Null, Value::Null),
+        };
+
+        let mut line = Map::new();
+        line.insert("ts".to_string(), Value::from(ts));
+        line.insert("level".to_string(), Value::from(record.level().as_str()));
+        line.insert("module".to_string(), Value::from(record.module()));
+        line.insert("step".to_string(), step);
+        line.insert("time".to_string(), time);
+        line.insert("msg".to_string(), Value::String(record.msg().to_string()));
+        line.insert("kv".to_string(), Value::Object(kv.0));
+
+        return Ok(Value::Object(line));
+    }
+}
+
+impl<W: io::Write> Drain for JsonDrain<W> {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> io::Result<()> {
+        let line = self.format(record, values)?;
+
+        let mut out = self.out.lock().unwrap();
+        serde_json::to_writer(&mut *out, &line)?;
+        out.write_all(b"\n")?;
+        return out.flush();
+    }
+}
+
+/// The levels of `LOG_ENV` (default: all levels).
+fn env_levels() -> LevelFilter {
+    return match std::env::var(LOG_ENV) {
+        Ok(spec) => spec.parse().unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", LOG_ENV, e);
+            LevelFilter::default()
+        }),
+        Err(_) => LevelFilter::default(),
+    };
+}
+
+/// A logger with the levels of `LOG_ENV` (default: all levels), see `create_logger_with`.
 pub fn create_logger() -> (slog::Logger, LevelSwitch) {
+    return create_logger_with(env_levels(), RateLimit::default());
+}
 
-    let switch = Arc::new(atomic::AtomicBool::new(true));
+/// A logger with the levels of `LOG_ENV` which writes JSON lines to `out`,
+/// see `create_json_logger_with`.
+pub fn create_json_logger<W>(out: W) -> (slog::Logger, LevelSwitch)
+where
+    W: io::Write + Send + 'static,
+{
+    return create_json_logger_with(out, env_levels(), RateLimit::default());
+}
 
+/// A logger which writes each record as one line of JSON to `out` (e.g. a file of a
+/// headless run), stamped with the step set by `LevelSwitch::set_step`.
+///
+/// Unlike the terminal, the lines are written on the logging thread to stamp them with
+/// the current step. `out` should therefore be a file and not a slow pipe.
+pub fn create_json_logger_with<W>(
+    out: W,
+    levels: LevelFilter,
+    limit: RateLimit,
+) -> (slog::Logger, LevelSwitch)
+where
+    W: io::Write + Send + 'static,
+{
+    let context = LogContext::default();
+    let drain = JsonDrain {
+        out: Mutex::new(out),
+        context: context.clone(),
+    }
+    .fuse();
+
+    return create_root(drain, levels, limit, context);
+}
+
+/// A logger which writes to the terminal on a background thread.
+///
+/// The records are filtered by `levels` and `limit` on the logging thread before they
+/// are sent, and records are dropped (and counted) if the channel is full, such that
+/// a slow terminal never stalls the solver.
+pub fn create_logger_with(levels: LevelFilter, limit: RateLimit) -> (slog::Logger, LevelSwitch) {
     let decorator = slog_term::TermDecorator::new().build();
     let drain = slog_term::FullFormat::new(decorator)
         //.use_custom_timestamp(no_out)
         .build()
         .fuse();
+
+    let drain = slog_async::Async::new(drain)
+        .chan_size(100_000)
+        .overflow_strategy(slog_async::OverflowStrategy::DropAndReport)
+        .build()
+        .fuse();
+
+    return create_root(drain, levels, limit, LogContext::default());
+}
+
+/// The root logger of `drain` behind the filters of the levels and the rate limit.
+fn create_root<D>(
+    drain: D,
+    levels: LevelFilter,
+    limit: RateLimit,
+    context: LogContext,
+) -> (slog::Logger, LevelSwitch)
+where
+    D: Drain<Ok = (), Err = slog::Never>
+        + Send
+        + Sync
+        + std::panic::RefUnwindSafe
+        + std::panic::UnwindSafe
+        + 'static,
+{
+    let switch = Arc::new(atomic::AtomicBool::new(true));
+    let levels = Arc::new(RwLock::new(levels));
+
+    let drain = RateLimitFilter {
+        drain,
+        limit,
+        sites: Mutex::new(HashMap::new()),
+    }
+    .fuse();
+    let drain = ModuleLevelFilter {
+        drain,
+        levels: levels.clone(),
+    }
+    .fuse();
     let drain = RuntimeLevelFilter {
         drain,
         on: switch.clone(),
     }
     .fuse();
 
-    let drain = slog_async::Async::new(drain)
-        .chan_size(5_000_000)
-        .build()
-        .fuse();
-
-    return (slog::Logger::root(drain, o!()), LevelSwitch{switch});
+    let switch = LevelSwitch {
+        switch,
+        levels,
+        context,
+    };
+    return (slog::Logger::root(drain, o!()), switch);
 }
 
 pub type Logger = slog::Logger;
@@ -160,3 +557,5 @@ macro_rules! log_panic(
 );
 
 pub use log_panic;
+
+mod tests;
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::log::*;
+    use slog::{o, Drain, Level};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Collects the messages of all records.
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl Drain for Collect {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> Result<(), slog::Never> {
+            self.0.lock().unwrap().push(record.msg().to_string());
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn check_level_filter() {
+        let filter: LevelFilter = "info, rustofluid::scene=debug,rustofluid::scene::grid=warn"
+            .parse()
+            .unwrap();
+
+        assert!(filter.level("rustofluid::io") == Level::Info);
+        assert!(filter.level("rustofluid::scene::setup") == Level::Debug);
+        assert!(filter.level("rustofluid::scene::grid") == Level::Warning);
+
+        assert!("".parse::<LevelFilter>().unwrap() == LevelFilter::default());
+        assert!("loud".parse::<LevelFilter>().is_err());
+        assert!("rustofluid=loud".parse::<LevelFilter>().is_err());
+    }
+
+    #[test]
+    fn check_rate_limit() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let limit = RateLimit {
+            max: 3,
+            interval: Duration::from_millis(200),
+        };
+        let drain = RateLimitFilter {
+            drain: Collect(messages.clone()),
+            limit,
+            sites: Mutex::new(HashMap::new()),
+        };
+        let log = slog::Logger::root(drain.fuse(), o!());
+
+        let emit = |i: usize| warn!(log, "Record {}", i);
+        for i in 0..10 {
+            emit(i);
+        }
+        info!(log, "Other site");
+        assert!(messages.lock().unwrap().len() == 4, "{:?}", messages.lock().unwrap());
+
+        std::thread::sleep(limit.interval);
+        emit(10);
+
+        let messages = messages.lock().unwrap();
+        assert!(messages.len() == 6, "{:?}", messages);
+        assert!(messages[4].starts_with("Dropped 7 repeated records"), "{:?}", messages);
+        assert!(messages[5] == "Record 10");
+    }
+
+    /// A writer into a shared buffer.
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            return self.0.lock().unwrap().write(buf);
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn check_json_logger() {
+        use serde_json::Value;
+
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let levels = "info,rustofluid::io=warn".parse().unwrap();
+        let (log, switch) =
+            create_json_logger_with(Buffer(buffer.clone()), levels, RateLimit::default());
+        let log = log.new(o!("run" => "test"));
+
+        debug!(log, "Dropped");
+        info!(log, "Before"; "cells" => 200u64);
+        switch.set_step(3, 0.5);
+        warn!(log, "After"; "field" => "smoke", "run" => "inner");
+
+        let mut levels = switch.levels();
+        levels.default = Level::Debug;
+        switch.set_levels(levels);
+        debug!(log, "Debug");
+
+        let text = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert!(lines.len() == 3, "{}", text);
+
+        assert!(lines[0]["msg"] == "Before" && lines[0]["level"] == "INFO");
+        assert!(lines[0]["module"] == "rustofluid::log::tests::tests");
+        assert!(lines[0]["step"].is_null() && lines[0]["kv"]["cells"] == 200);
+        assert!(lines[0]["kv"]["run"] == "test", "Logger values are missing.");
+
+        assert!(lines[1]["step"] == 3 && lines[1]["time"] == 0.5);
+        assert!(lines[1]["kv"]["field"] == "smoke");
+        assert!(lines[1]["kv"]["run"] == "inner", "Record values do not win.");
+
+        assert!(lines[2]["msg"] == "Debug", "Levels are not changed at runtime.");
+        assert!(switch.levels().level("rustofluid::io::vtk") == Level::Warning);
+    }
+}
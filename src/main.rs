@@ -1,9 +1,51 @@
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use std::fmt::Write;
-use std::fs::create_dir_all;
+use std::fs::{create_dir_all, File};
+use std::sync::Arc;
+use std::time::Instant;
 
+use rustofluid::io::checkpoint::{
+    read_checkpoint, CheckpointSnapshot, Compression, DeltaCheckpoints,
+};
+use rustofluid::io::csv::{write_field_csv, TimeSeriesCsv};
+use rustofluid::io::dump::write_dump;
+#[cfg(feature = "hdf5")]
+use rustofluid::io::hdf5::Hdf5Writer;
+#[cfg(feature = "stream")]
+use rustofluid::io::stream::FrameServer;
+use rustofluid::io::npy::{write_npy, write_npz};
+use rustofluid::io::output::{OutputManager, OutputParams, OutputParamsBuilder};
+use rustofluid::io::exr::write_exr;
+use rustofluid::io::gltf::write_gltf;
+use rustofluid::io::metadata::RunMetadata;
+use rustofluid::io::tecplot::{write_tecplot, TecplotLocation};
+use rustofluid::io::vdb::write_vdb;
+use rustofluid::io::vtk::{pvd_path, write_vti, PvdCollection};
+use rustofluid::io::writer::{FrameWriter, WriteJob};
 use rustofluid::log::*;
-use rustofluid::scene::setup::{parse_args, setup_scene, CLIArgs};
+use rustofluid::render::{
+    create_encoder, print_terminal, render_smoke, save_heatmap_png, save_quiver_png, save_smoke_png,
+    save_streamlines_png, write_svg, Colormap, HeatmapField, HeatmapParams, HeatmapParamsBuilder,
+    QuiverBackground, QuiverParams, QuiverParamsBuilder, RenderParams, RenderParamsBuilder,
+    SvgParams, SvgParamsBuilder, TerminalParams, TerminalParamsBuilder, ValueRange,
+};
+use rustofluid::scene::bench::{format_bench_table, run_bench};
+use rustofluid::scene::config::SceneConfig;
+use rustofluid::scene::flux::FluxMeter;
+use rustofluid::scene::grid::Grid;
+use rustofluid::scene::grid_arena::{GridArena, PooledGrid};
+use rustofluid::scene::memory::{format_bytes, MemoryLocation};
+use rustofluid::scene::probes::Probes;
+use rustofluid::scene::profiler::{Profiler, Stage};
+use rustofluid::scene::reload::SceneWatcher;
+use rustofluid::scene::replay::{Recorder, Recording};
+use rustofluid::scene::setup::{
+    apply_scene_config, parse_args, scene_config, setup_scene, BenchArgs, CLIArgs, Commands,
+    RenderArgs, RenderOptions, SweepArgs,
+};
+use rustofluid::scene::streamlines::{StreamlineParams, StreamlineParamsBuilder};
+use rustofluid::scene::timestepper::TimeStepper;
+use rustofluid::scene::sweep::{format_sweep_header, format_sweep_rows, run_sweep};
 use rustofluid::scene::visualization::{save_plots, PlotParams, PlotParamsBuilder};
 use rustofluid::types::*;
 
@@ -14,8 +56,205 @@ fn assert_output_path(output: &str) {
 }
 
 fn main() -> GenericResult<()> {
-    let cli = parse_args();
-    return run(&cli);
+    return match parse_args().command {
+        Commands::Run(args) => run_scene(args),
+        Commands::Resume {
+            checkpoint,
+            mut args,
+        } => {
+            args.resume = Some(checkpoint);
+            run_scene(args)
+        }
+        Commands::Replay { recording, args } => replay(&recording, args),
+        Commands::Render(args) => render(&args),
+        Commands::Info(args) => info(&args),
+        Commands::Bench(args) => bench(&args),
+        Commands::Sweep(args) => sweep(&args),
+        #[cfg(feature = "viewer")]
+        Commands::View(args) => view(args),
+    };
+}
+
+#[cfg(feature = "viewer")]
+fn view(args: CLIArgs) -> GenericResult<()> {
+    use rustofluid::viewer::{run_viewer, ViewerParamsBuilder};
+
+    let config = scene_config(&args)?;
+
+    // The event loop never returns, hence the logger must live forever.
+    // Only warnings, the records of each step would flood the terminal.
+    let (log, switch) = create_logger();
+    switch.set_levels(LevelFilter {
+        default: slog::Level::Warning,
+        modules: vec![],
+    });
+    let log: &'static Logger = Box::leak(Box::new(log));
+
+    let timestepper = setup_scene(log, &config)?;
+    let params = ViewerParamsBuilder::default()
+        .dt(config.time.dt)
+        .build()
+        .unwrap();
+
+    let recorder = args
+        .record
+        .clone()
+        .map(|file| (Recorder::new(&config, config.time.dt), file));
+
+    return run_viewer(log, timestepper, params, create_render_params(&args.render)?, recorder);
+}
+
+fn run_scene(mut args: CLIArgs) -> GenericResult<()> {
+    let config = scene_config(&args)?;
+    apply_scene_config(&mut args, &config);
+
+    return run(&args, &config, None);
+}
+
+fn replay(file: &str, mut args: CLIArgs) -> GenericResult<()> {
+    let recording = Recording::load(file)?;
+    apply_scene_config(&mut args, &recording.config);
+    args.dt = recording.dt;
+
+    return run(&args, &recording.config, Some(&recording));
+}
+
+fn info(args: &CLIArgs) -> GenericResult<()> {
+    let config = scene_config(args)?;
+
+    let dim = config.dim();
+    let h = config.cell_width();
+    let steps = (config.time.end / config.time.dt) as u64;
+
+    println!("# Domain: {:.3} x {:.3}", dim.x as Scalar * h, dim.y as Scalar * h);
+    println!("# Cells: {} x {} (+ border), cell width: {}", dim.x, dim.y, h);
+    println!("# Steps: {} (dt: {}, end: {})", steps, config.time.dt, config.time.end);
+    println!(
+        "# Memory of the cells: {}",
+        format_bytes(Grid::estimate_memory(dim).total(MemoryLocation::Host))
+    );
+    print!("{}", serde_yaml::to_string(&config)?);
+
+    return Ok(());
+}
+
+fn bench(args: &BenchArgs) -> GenericResult<()> {
+    let (log, switch) = create_logger();
+    switch.disable();
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
+
+    let mut results = vec![];
+    for scene in args.scenes.iter() {
+        for dim in args.dims.iter() {
+            eprintln!("Running '{}' with {}x{} cells ...", scene.name(), dim.x, dim.y);
+            results.push(run_bench(&log, *scene, *dim, args.steps, args.execution_mode())?);
+        }
+    }
+
+    print!("{}", format_bench_table(&results));
+
+    return Ok(());
+}
+
+fn sweep(args: &SweepArgs) -> GenericResult<()> {
+    let base = match args.scene {
+        Some(ref file) => SceneConfig::from_file(file)?,
+        None => SceneConfig::default(),
+    };
+
+    if args.shard.is_some() || args.jobs <= 1 {
+        let results = run_sweep(&base, &args.params, args.steps, args.shard);
+        if args.shard.is_none() {
+            print!("{}", format_sweep_header(&args.params));
+        }
+        print!("{}", format_sweep_rows(&args.params, &results));
+        return Ok(());
+    }
+
+    // Each process runs every `jobs`-th combination and prints its rows.
+    let exe = std::env::current_exe()?;
+    let processes = (0..args.jobs)
+        .map(|k| {
+            return std::process::Command::new(&exe)
+                .args(std::env::args_os().skip(1))
+                .arg(format!("--shard={}/{}", k, args.jobs))
+                .stdout(std::process::Stdio::piped())
+                .spawn();
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut rows = vec![];
+    for p in processes {
+        let output = p.wait_with_output()?;
+        if !output.status.success() {
+            return Err(format!("Sweep process failed: {}", output.status).into());
+        }
+        rows.extend(String::from_utf8(output.stdout)?.lines().map(str::to_string));
+    }
+
+    let index = |row: &String| row.split_whitespace().next().and_then(|i| i.parse::<usize>().ok());
+    rows.sort_by_key(index);
+
+    print!("{}", format_sweep_header(&args.params));
+    for row in rows {
+        println!("{}", row);
+    }
+
+    return Ok(());
+}
+
+fn render(args: &RenderArgs) -> GenericResult<()> {
+    let checkpoint = read_checkpoint(&args.checkpoint)?;
+    let grid = checkpoint.grids.first().ok_or("Checkpoint contains no grid.")?;
+    let opts = &args.render;
+
+    let render_params = create_render_params(opts)?;
+
+    assert_output_path(&args.output);
+    save_smoke_png(grid, &render_params, &args.output, None)?;
+
+    if let Some(ref output) = args.output_quiver {
+        assert_output_path(output);
+        save_quiver_png(grid, &render_params, &create_quiver_params(opts), output, None)?;
+    }
+
+    if let Some(ref output) = args.output_svg {
+        assert_output_path(output);
+        let streamlines = create_streamline_params(opts);
+        write_svg(grid, &create_svg_params(opts), Some(&streamlines), output, None)?;
+    }
+
+    if let Some(ref output) = args.output_streamlines {
+        assert_output_path(output);
+        let streamlines = create_streamline_params(opts);
+        let background = quiver_background(opts);
+        save_streamlines_png(grid, &render_params, &streamlines, background, output, None)?;
+    }
+
+    for (output, field) in [
+        (&args.output_pressure_map, HeatmapField::Pressure),
+        (&args.output_divergence_map, HeatmapField::Divergence),
+    ] {
+        if let Some(ref output) = output {
+            assert_output_path(output);
+            let heatmap = create_heatmap_params(opts, field);
+            save_heatmap_png(grid, &render_params, &heatmap, output, None)?;
+        }
+    }
+
+    if let Some(ref output) = args.output_vtk {
+        assert_output_path(output);
+        write_vti(grid, output, None)?;
+    }
+
+    if args.terminal {
+        print_terminal(grid, &create_terminal_params(opts), false);
+    }
+
+    return Ok(());
 }
 
 fn create_progressbar(steps: u64) -> ProgressBar {
@@ -46,33 +285,487 @@ fn create_plot_params(cli: &CLIArgs) -> PlotParams {
         .unwrap();
 }
 
-fn run(cli: &CLIArgs) -> GenericResult<()> {
-    let (log, switch) = create_logger();
+fn smoke_colormap(cli: &RenderOptions) -> Colormap {
+    return match cli.colormap {
+        Some(ref c) => c.clone(),
+        None if cli.render_grayscale => Colormap::Grayscale,
+        None => Colormap::Turbo,
+    };
+}
+
+fn create_render_params(cli: &RenderOptions) -> GenericResult<RenderParams> {
+    return Ok(RenderParamsBuilder::default()
+        .scale(cli.render_scale)
+        .gamma(cli.render_gamma)
+        .colormap(smoke_colormap(cli))
+        .range(cli.smoke_range)
+        .build()?);
+}
+
+fn create_quiver_params(cli: &RenderOptions) -> QuiverParams {
+    return QuiverParamsBuilder::default()
+        .stride(cli.quiver_stride)
+        .background(quiver_background(cli))
+        .build()
+        .unwrap();
+}
+
+fn create_output_params(cli: &CLIArgs) -> OutputParams {
+    return OutputParamsBuilder::default()
+        .dir(cli.output_dir.clone())
+        .interval(cli.output_interval.unwrap_or_default())
+        .overwrite(cli.overwrite.unwrap_or_default())
+        .append(cli.append || cli.resume.is_some())
+        .region(cli.export_window.or(cli.export_rect))
+        .build()
+        .unwrap();
+}
+
+fn quiver_background(cli: &RenderOptions) -> QuiverBackground {
+    return if cli.quiver_pressure {
+        QuiverBackground::Pressure
+    } else {
+        QuiverBackground::Smoke
+    };
+}
+
+fn create_streamline_params(cli: &RenderOptions) -> StreamlineParams {
+    return StreamlineParamsBuilder::default()
+        .seed_stride(cli.streamline_stride)
+        .max_steps(cli.streamline_length)
+        .build()
+        .unwrap();
+}
+
+fn create_svg_params(cli: &RenderOptions) -> SvgParams {
+    return SvgParamsBuilder::default()
+        .scale(cli.render_scale as Scalar)
+        .levels(cli.contour_levels.clone())
+        .build()
+        .unwrap();
+}
+
+fn create_terminal_params(cli: &RenderOptions) -> TerminalParams {
+    return TerminalParamsBuilder::default()
+        .columns(cli.terminal_columns)
+        .color(!cli.terminal_no_color)
+        .quiver_stride(cli.terminal_quiver)
+        .colormap(smoke_colormap(cli))
+        .build()
+        .unwrap();
+}
+
+fn create_heatmap_params(cli: &RenderOptions, field: HeatmapField) -> HeatmapParams {
+    // The divergence is centered around zero.
+    let (range, colormap) = match field {
+        HeatmapField::Divergence if cli.heatmap_range == ValueRange::Auto => {
+            (ValueRange::Symmetric, Colormap::RdBu)
+        }
+        HeatmapField::Divergence => (cli.heatmap_range, Colormap::RdBu),
+        HeatmapField::Pressure => (cli.heatmap_range, Colormap::Turbo),
+    };
+
+    return HeatmapParamsBuilder::default()
+        .field(field)
+        .range(range)
+        .colormap(cli.heatmap_colormap.clone().unwrap_or(colormap))
+        .build()
+        .unwrap();
+}
+
+/// The snapshot the outputs of a frame are written from.
+struct FrameData {
+    grid: PooledGrid,
+    meta: RunMetadata,
+}
+
+/// Collects the per-frame outputs of `frame` for the frame writer.
+struct FrameJobs<'a> {
+    outputs: &'a OutputManager,
+    data: Arc<FrameData>,
+    frame: u64,
+    jobs: Vec<WriteJob>,
+}
+
+impl<'a> FrameJobs<'a> {
+    /// Queue the per-frame output `pattern` unless it is skipped.
+    /// Returns the file which will be written.
+    fn add<F>(&mut self, pattern: &Option<String>, write: F) -> GenericResult<Option<String>>
+    where
+        F: FnOnce(&Grid, &str, Option<&RunMetadata>) -> GenericResult<()> + Send + 'static,
+    {
+        if let Some(ref pattern) = pattern {
+            if let Some(file) = self.outputs.frame_path(pattern, self.frame)? {
+                let (data, f) = (self.data.clone(), file.clone());
+                self.jobs.push(Box::new(move || write(&data.grid, &f, Some(&data.meta))));
+                return Ok(Some(file));
+            }
+        }
+
+        return Ok(None);
+    }
+}
+
+/// Apply the changes of the watched scene file from the next step on.
+fn reload_scene(
+    log: &Logger,
+    watcher: &mut SceneWatcher,
+    timestepper: &mut TimeStepper,
+    outputs: &mut OutputManager,
+) {
+    let reload = match watcher.poll() {
+        None => return,
+        Some(Err(e)) => {
+            warn!(log, "Reload of '{}' failed, keeping the scene: {}", watcher.file(), e);
+            return;
+        }
+        Some(Ok(reload)) => reload,
+    };
+
+    if !reload.applied.is_empty() {
+        if let Err(e) = reload.apply(timestepper) {
+            warn!(log, "Reload of '{}' failed: {}", watcher.file(), e);
+            return;
+        }
+        if let Some(interval) = reload.config.output.interval {
+            outputs.set_interval(interval, timestepper.time());
+        }
+        info!(log, "Reloaded from '{}': {}", watcher.file(), reload.applied.join(", "));
+    }
+
+    if !reload.restart.is_empty() {
+        let fields = reload.restart.join(", ");
+        warn!(log, "Changes of '{}' need a restart: {}", watcher.file(), fields);
+    }
+}
+
+fn run(cli: &CLIArgs, config: &SceneConfig, replay: Option<&Recording>) -> GenericResult<()> {
+    let (log, switch) = match cli.log_json {
+        Some(ref file) => create_json_logger(std::io::BufWriter::new(File::create(file)?)),
+        None => create_logger(),
+    };
 
-    assert_output_path(&cli.output);
+    let mut timestepper = setup_scene(&log, config)?;
+    if let Some(ref file) = cli.resume {
+        if cli.record.is_some() {
+            bail!("Recording a resumed run is not supported.");
+        }
+
+        info!(log, "Resume from checkpoint '{}'.", file);
+        timestepper.load_checkpoint(file)?;
+    }
+    debug!(log, "Memory:\n{}", timestepper.memory_report().format_table());
 
     let dt = cli.dt;
-    let n_steps = (cli.time_end / dt) as u64;
+    let n_steps = match replay {
+        Some(r) => r.steps,
+        None => ((cli.time_end - timestepper.time()).max(0.0) / dt) as u64,
+    };
+
+    let mut recorder = cli.record.as_ref().map(|_| Recorder::new(config, dt));
+    let run_meta = RunMetadata::new(config, dt);
+
+    let mut outputs = OutputManager::new(create_output_params(cli), timestepper.time(), dt);
 
     let mut progress = None;
 
     if cli.show_progress {
-        switch.disable();
+        // The JSON log does not interfere with the progress bar.
+        if cli.log_json.is_none() {
+            switch.disable();
+        }
         progress = Some(create_progressbar(n_steps));
     }
 
-    let mut timestepper = setup_scene(&log, &cli)?;
-    let plot_params = create_plot_params(&cli);
+    let mut plot_params = create_plot_params(&cli);
+    plot_params.output = outputs.file(&cli.output, true)?.unwrap();
+
+    // Shared with the frame writer.
+    let render_params = Arc::new(create_render_params(&cli.render)?);
+    let quiver_params = Arc::new(create_quiver_params(&cli.render));
+    let pressure_params = Arc::new(create_heatmap_params(&cli.render, HeatmapField::Pressure));
+    let divergence_params = Arc::new(create_heatmap_params(&cli.render, HeatmapField::Divergence));
+    let streamline_params = Arc::new(create_streamline_params(&cli.render));
+    let svg_params = Arc::new(create_svg_params(&cli.render));
+    let terminal_params = create_terminal_params(&cli.render);
+
+    let mut writer = FrameWriter::new(cli.output_queue);
+    // The pending frames, the one being written and the next one.
+    let arena = GridArena::new(cli.output_queue + 2);
+
+    let single_file = |file: &Option<String>, existing_ok: bool| match file {
+        Some(ref f) => outputs.file(f, existing_ok),
+        None => Ok(None),
+    };
+
+    let mut encoder = match single_file(&cli.output_video, false)? {
+        Some(ref output) => Some(create_encoder(output, cli.video_fps)?),
+        None => None,
+    };
+
+    #[cfg(feature = "hdf5")]
+    let mut hdf5 = match single_file(&cli.output_hdf5, false)? {
+        Some(ref output) => {
+            let grid = timestepper.objects[0]
+                .as_any()
+                .downcast_ref::<Grid>()
+                .expect("Not a grid");
+            let cropped = outputs.crop(grid);
+            Some(Hdf5Writer::new(output, cropped.as_ref().unwrap_or(grid), 4, Some(&run_meta))?)
+        }
+        None => None,
+    };
+
+    let append = outputs.params.append;
+    let mut series = match single_file(&cli.output_series_csv, append)? {
+        Some(ref output) => Some(TimeSeriesCsv::open(output, append)?),
+        None => None,
+    };
+
+    let mut pvd = match cli.output_vtk {
+        Some(ref pattern) => match outputs.file(&pvd_path(pattern), true)? {
+            Some(ref file) => Some(PvdCollection::open(file, append)?),
+            None => None,
+        },
+        None => None,
+    };
+
+    let tecplot_location = if cli.tecplot_node_centered {
+        TecplotLocation::Node
+    } else {
+        TecplotLocation::Cell
+    };
+
+    let mut probes = Probes::from_config(&config.probes);
+    let probes_file = single_file(&cli.output_probes, false)?;
+    let mut fluxes = FluxMeter::from_config(&config.sections);
+    let flux_file = single_file(&cli.output_flux, false)?;
+
+    // Checkpoints are rewritten during the run.
+    let compression = cli
+        .checkpoint_compression
+        .map_or(Compression::None, Compression::Zstd);
+    let checkpoint = single_file(&cli.output_checkpoint, true)?;
+    let mut deltas = cli
+        .checkpoint_key_interval
+        .map(|n| DeltaCheckpoints::new(n, cli.checkpoint_tolerance));
+
+    #[cfg(feature = "stream")]
+    let mut server = match cli.stream {
+        Some(ref addr) => {
+            let mut server = FrameServer::bind(log, addr)?;
+            info!(log, "Streaming frames on 'ws://{}'.", server.local_addr()?);
+            if cli.stream_wait {
+                info!(log, "Waiting for a client ...");
+                server.accept(true)?;
+            }
+            Some(server)
+        }
+        None => None,
+    };
+
+    let mut profiler = if cli.profile || cli.profile_trace.is_some() {
+        Some(Profiler::new(cli.profile_trace.is_some()))
+    } else {
+        None
+    };
+
+    // A replay reproduces the recorded scene.
+    let mut watcher = match cli.scene {
+        Some(ref file) if cli.watch && replay.is_none() => {
+            info!(log, "Watching the scene file '{}'.", file);
+            Some(SceneWatcher::new(file, &cli.overrides, config.clone()))
+        }
+        _ => None,
+    };
 
     for step in 0..n_steps {
-        timestepper.compute_step(dt);
+        switch.set_step(step, timestepper.time() as f64);
+
+        if let Some(r) = replay {
+            r.apply(step, &mut timestepper);
+        }
 
-        save_plots(&log, &timestepper, step, &plot_params)?;
+        if let Some(ref mut w) = watcher {
+            reload_scene(&log, w, &mut timestepper, &mut outputs);
+        }
+
+        let start = Instant::now();
+        match profiler {
+            Some(ref mut p) => p.record_step(start, &timestepper.compute_step_timed(dt)?),
+            None => timestepper.compute_step(dt)?,
+        }
+        let output_start = Instant::now();
+
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        if let Some(frame) = outputs.frame(timestepper.time()) {
+            let t = timestepper.time();
+            let frame_meta = run_meta.at((t / dt).round() as u64, t);
+
+            save_plots(&log, &timestepper, frame, &plot_params)?;
+
+            let data = Arc::new(FrameData {
+                grid: outputs.frame_grid(grid, &arena),
+                meta: frame_meta,
+            });
+            let mut jobs = FrameJobs {
+                outputs: &outputs,
+                data: data.clone(),
+                frame,
+                jobs: vec![],
+            };
+            let grid = &data.grid;
+
+            let vti = jobs.add(&cli.output_vtk, write_vti)?;
+            if let (Some(c), Some(f)) = (pvd.as_mut(), vti) {
+                c.add(t, &f)?;
+            }
+
+            let p = render_params.clone();
+            jobs.add(&cli.output_render, move |g, f, m| save_smoke_png(g, &p, f, m))?;
+            let (p, q) = (render_params.clone(), quiver_params.clone());
+            jobs.add(&cli.output_quiver, move |g, f, m| save_quiver_png(g, &p, &q, f, m))?;
+            let (p, s) = (render_params.clone(), streamline_params.clone());
+            let background = quiver_background(&cli.render);
+            jobs.add(&cli.output_streamlines, move |g, f, m| {
+                save_streamlines_png(g, &p, &s, background, f, m)
+            })?;
+            let (p, s) = (svg_params.clone(), streamline_params.clone());
+            jobs.add(&cli.output_svg, move |g, f, m| write_svg(g, &p, Some(&s), f, m))?;
+            let (p, h) = (render_params.clone(), pressure_params.clone());
+            jobs.add(&cli.output_pressure_map, move |g, f, m| {
+                save_heatmap_png(g, &p, &h, f, m)
+            })?;
+            let (p, h) = (render_params.clone(), divergence_params.clone());
+            jobs.add(&cli.output_divergence_map, move |g, f, m| {
+                save_heatmap_png(g, &p, &h, f, m)
+            })?;
+            jobs.add(&cli.output_fields_csv, |g, f, _| write_field_csv(g, f))?;
+            jobs.add(&cli.output_vdb, write_vdb)?;
+            jobs.add(&cli.output_exr, write_exr)?;
+            let level = cli.surface_level;
+            jobs.add(&cli.output_gltf, move |g, f, m| write_gltf(g, level, f, m))?;
+            jobs.add(&cli.output_tecplot, move |g, f, m| {
+                write_tecplot(g, tecplot_location, f, m)
+            })?;
+            let (step, single) = (data.meta.step, cli.dump_f32);
+            jobs.add(&cli.output_dump, move |g, f, _| write_dump(g, step, t, single, f))?;
+            jobs.add(&cli.output_npy, |g, f, _| write_npy(g, f))?;
+            jobs.add(&cli.output_npz, write_npz)?;
+
+            writer.submit(jobs.jobs)?;
+
+            #[cfg(feature = "hdf5")]
+            if let Some(ref mut h) = hdf5 {
+                h.append(timestepper.time(), grid)?;
+            }
+
+            if let Some(ref mut e) = encoder {
+                e.add_frame(&render_smoke(grid, &render_params))?;
+            }
+        }
+
+        #[cfg(feature = "stream")]
+        if let Some(ref mut s) = server {
+            if (step + 1) % cli.stream_interval.max(1) == 0 {
+                s.broadcast(step, timestepper.time(), grid)?;
+            }
+        }
+
+        if let Some(ref mut s) = series {
+            s.append(step, timestepper.time(), grid)?;
+        }
+
+        if !probes.is_empty() {
+            probes.record(step, timestepper.time(), grid);
+        }
+
+        if !fluxes.is_empty() {
+            fluxes.record(step, timestepper.time(), dt, grid);
+        }
+
+        if let Some(ref output) = checkpoint {
+            let is_last = step + 1 == n_steps;
+            let is_interval = cli.checkpoint_interval > 0 && (step + 1) % cli.checkpoint_interval == 0;
+
+            if is_last || is_interval {
+                let snapshot = match deltas {
+                    Some(ref mut d) => d.snapshot(&timestepper)?,
+                    None => CheckpointSnapshot::new(&timestepper),
+                };
+                let file = output.clone();
+                writer.submit(vec![Box::new(move || snapshot.write(&file, compression))])?;
+            }
+        }
+
+        if cli.terminal_interval > 0 && (step + 1) % cli.terminal_interval == 0 {
+            print_terminal(grid, &terminal_params, true);
+        }
+
+        if let Some(ref mut p) = profiler {
+            p.record(Stage::Output, output_start, output_start.elapsed());
+            let stages = p.end_step();
+            debug!(log, "Step {}: {}", step, Profiler::format_step(&stages));
+        }
 
         if let Some(ref p) = progress {
             p.inc(1);
         }
     }
 
+    if let Some(e) = encoder {
+        e.finish()?;
+    }
+
+    if writer.stalls() > 0 {
+        info!(log, "Waited {} times for the frame writer.", writer.stalls());
+    }
+    writer.finish()?;
+
+    if let Some(ref p) = profiler {
+        println!("Stages of {} steps:\n{}", p.steps(), p.report());
+
+        if let Some(ref file) = cli.profile_trace {
+            p.write_trace(file)?;
+            info!(log, "Trace written to '{}'.", file);
+        }
+    }
+
+    let grid = timestepper.objects[0]
+        .as_any()
+        .downcast_ref::<Grid>()
+        .expect("Not a grid");
+
+    if let Some(ref file) = probes_file {
+        probes.write_csv(file)?;
+        info!(log, "Probes written to '{}'.", file);
+    }
+
+    for (i, section) in fluxes.sections().iter().enumerate() {
+        if let Some(s) = fluxes.latest(i) {
+            info!(log, "Section '{}': volume {:.4}, smoke {:.4}.", section.name, s.volume, s.smoke);
+        }
+    }
+
+    if let Some(ref file) = flux_file {
+        fluxes.write_csv(file)?;
+        info!(log, "Fluxes written to '{}'.", file);
+    }
+
+    if let (Some(r), Some(file)) = (recorder.as_mut(), cli.record.as_ref()) {
+        assert_output_path(file);
+        r.finish(n_steps, grid, file)?;
+        info!(log, "Recording written to '{}'.", file);
+    }
+
+    if let Some(r) = replay {
+        r.verify(grid)?;
+        info!(log, "Replay matches the recording bit-for-bit.");
+    }
+
     return Ok(());
 }
@@ -10,3 +10,58 @@ where
         nalgebra::clamp(index.y, min.y, max.y),
     );
 }
+
+/// `a * b + c`, fused into one instruction with feature `fast-math`. Without hardware
+/// FMA (e.g. `-C target-cpu=native`) the fused version is a slow library call.
+#[inline(always)]
+pub fn mul_add(a: Scalar, b: Scalar, c: Scalar) -> Scalar {
+    #[cfg(feature = "fast-math")]
+    return a.mul_add(b, c);
+
+    #[cfg(not(feature = "fast-math"))]
+    return a * b + c;
+}
+
+/// `a / b`, with feature `fast-math` the multiplication with the cached `b_inv = 1 / b`.
+#[inline(always)]
+pub fn div_by(a: Scalar, b: Scalar, b_inv: Scalar) -> Scalar {
+    #[cfg(feature = "fast-math")]
+    {
+        let _ = b;
+        return a * b_inv;
+    }
+
+    #[cfg(not(feature = "fast-math"))]
+    {
+        let _ = b_inv;
+        return a / b;
+    }
+}
+
+/// The reciprocals `1 / s` of the fluid neighbor counts `s` of the pressure solve.
+pub const NEIGHBOR_COUNT_INV: [Scalar; 5] = [0.0, 1.0, 0.5, 1.0 / 3.0, 0.25];
+
+/// Flush subnormal results and inputs to zero on the current thread with feature
+/// `fast-math` on x86-64 (the `FTZ` and `DAZ` bits). Subnormals appear where the
+/// smoke and the velocity decay and slow down the arithmetic by orders of magnitude.
+pub fn set_flush_to_zero() {
+    #[cfg(all(feature = "fast-math", target_arch = "x86_64"))]
+    #[allow(deprecated)]
+    unsafe {
+        use std::arch::x86_64::{_mm_getcsr, _mm_setcsr};
+        _mm_setcsr(_mm_getcsr() | 0x8040);
+    }
+}
+
+/// `set_flush_to_zero` on the current thread and once on all threads of rayon's global
+/// pool, which run the parallel loops of a step without an own pool.
+pub fn set_flush_to_zero_global() {
+    #[cfg(all(feature = "fast-math", target_arch = "x86_64"))]
+    {
+        static GLOBAL_POOL: std::sync::Once = std::sync::Once::new();
+        GLOBAL_POOL.call_once(|| {
+            rayon::broadcast(|_| set_flush_to_zero());
+        });
+    }
+    set_flush_to_zero();
+}
@@ -23,4 +23,29 @@ mod tests {
 
         assert!(c == Index2::new(4, 7), "Clamp not working {}", c);
     }
+
+    #[test]
+    fn check_fast_math() {
+        let (a, b, c) = (0.3, 0.7, 0.1);
+        let r = mul_add(a, b, c);
+        assert!((r - (a * b + c)).abs() < 1e-6, "Multiply-add not working {}", r);
+
+        for (s, s_inv) in NEIGHBOR_COUNT_INV.iter().enumerate().skip(1) {
+            let r = div_by(1.5, s as Scalar, *s_inv);
+            let e = 1.5 / s as Scalar;
+            assert!((r - e).abs() < 1e-6, "Division not working {} != {}", r, e);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "fast-math", target_arch = "x86_64"))]
+    fn check_flush_to_zero() {
+        use rayon::prelude::*;
+
+        set_flush_to_zero_global();
+        let tiny = std::hint::black_box(Scalar::MIN_POSITIVE);
+        let halved: Vec<Scalar> = (0..64).into_par_iter().map(|_| tiny * 0.5).collect();
+        assert!(halved.iter().all(|h| *h == 0.0), "Subnormals are not flushed to zero.");
+        assert!(std::hint::black_box(tiny) * 0.5 == 0.0, "Subnormals are not flushed to zero.");
+    }
 }
@@ -14,7 +14,7 @@ mod tests {
                 return cg.at(0.0);
             }
 
-            return cg.at(((index.x as Scalar) / 15.0).sin() * ((index.y as Scalar) / 10.0).cos());
+            return cg.at(((index.x as f64) / 15.0).sin() * ((index.y as f64) / 10.0).cos());
         };
 
         let file = "test.png";
@@ -0,0 +1,218 @@
+use crate::io::checkpoint::Compression;
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::config::SceneConfig;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::render::RenderParamsBuilder;
+use crate::scene::setup::setup_scene;
+use crate::scene::snapshot::{screenshot, Snapshot};
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyReadonlyArray2};
+use pyo3::types::PyDict;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use slog::o;
+
+fn to_py(e: Box<dyn std::error::Error>) -> PyErr {
+    return PyRuntimeError::new_err(e.to_string());
+}
+
+/// A simulation scripted from Python.
+/// The fields are returned as NumPy arrays of shape `(dim_y, dim_x)`
+/// (row `j` is the cell row `y = j`) including the border cells.
+#[pyclass(name = "Simulation", unsendable)]
+pub struct PySimulation {
+    timestepper: Box<TimeStepper<'static>>,
+
+    #[pyo3(get, set)]
+    dt: Scalar,
+}
+
+impl PySimulation {
+    fn create(config: &SceneConfig) -> PyResult<Self> {
+        // The timestepper borrows the logger for its whole lifetime.
+        let log: &'static slog::Logger = Box::leak(Box::new(slog::Logger::root(slog::Discard, o!())));
+        let timestepper = setup_scene(log, config).map_err(to_py)?;
+
+        return Ok(PySimulation {
+            timestepper,
+            dt: config.time.dt,
+        });
+    }
+
+    fn grid(&self) -> &Grid {
+        return self.timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+    }
+
+    fn grid_mut(&mut self) -> &mut Grid {
+        return self.timestepper.objects[0]
+            .as_any_mut()
+            .downcast_mut::<Grid>()
+            .expect("Not a grid");
+    }
+
+    fn field<'py, F: Fn(&Cell) -> Scalar>(&self, py: Python<'py>, get: F) -> &'py PyArray2<Scalar> {
+        let grid = self.grid();
+        let dim = grid.dim;
+        return Array2::from_shape_fn((dim.y, dim.x), |(j, i)| get(grid.cell(idx!(i, j)))).into_pyarray(py);
+    }
+}
+
+#[pymethods]
+impl PySimulation {
+    /// The channel scene with `dim = (dim_x, dim_y)` cells.
+    #[new]
+    #[pyo3(signature = (dim = (200, 100)))]
+    fn new(dim: (usize, usize)) -> PyResult<Self> {
+        return PySimulation::create(&SceneConfig::channel(idx!(dim.0, dim.1)));
+    }
+
+    /// Load a scene file with optional overrides `key.path=value`.
+    #[staticmethod]
+    #[pyo3(signature = (file, overrides = vec![]))]
+    fn from_file(file: &str, overrides: Vec<String>) -> PyResult<Self> {
+        return PySimulation::create(&SceneConfig::load(file, &overrides).map_err(to_py)?);
+    }
+
+    /// Parse a scene from a string in the format `format` (`toml`, `yaml` or `ron`).
+    #[staticmethod]
+    fn from_str(content: &str, format: &str) -> PyResult<Self> {
+        return PySimulation::create(&SceneConfig::parse(content, format).map_err(to_py)?);
+    }
+
+    /// Compute `steps` timesteps with the timestep `dt`.
+    #[pyo3(signature = (steps = 1))]
+    fn step(&mut self, py: Python, steps: u64) -> PyResult<()> {
+        for _ in 0..steps {
+            self.timestepper.compute_step(self.dt).map_err(|e| to_py(e.into()))?;
+            py.check_signals()?;
+        }
+        return Ok(());
+    }
+
+    #[getter]
+    fn time(&self) -> Scalar {
+        return self.timestepper.time();
+    }
+
+    /// The number of cells `(dim_x, dim_y)` including the border cells.
+    #[getter]
+    fn dim(&self) -> (usize, usize) {
+        let dim = self.grid().dim;
+        return (dim.x, dim.y);
+    }
+
+    #[getter]
+    fn cell_width(&self) -> Scalar {
+        return self.grid().cell_width;
+    }
+
+    fn smoke<'py>(&self, py: Python<'py>) -> &'py PyArray2<Scalar> {
+        return self.field(py, |c| c.smoke());
+    }
+
+    fn pressure<'py>(&self, py: Python<'py>) -> &'py PyArray2<Scalar> {
+        return self.field(py, |c| c.pressure);
+    }
+
+    fn divergence<'py>(&self, py: Python<'py>) -> &'py PyArray2<Scalar> {
+        return self.field(py, |c| c.div);
+    }
+
+    fn solid<'py>(&self, py: Python<'py>) -> &'py PyArray2<Scalar> {
+        return self.field(py, |c| (c.mode == CellTypes::Solid) as u8 as Scalar);
+    }
+
+    /// The velocity interpolated to the cell centers with shape `(dim_y, dim_x, 2)`.
+    fn velocity<'py>(&self, py: Python<'py>) -> &'py PyArray3<Scalar> {
+        let grid = self.grid();
+        let dim = grid.dim;
+        return Array3::from_shape_fn((dim.y, dim.x, 2), |(j, i, d)| {
+            grid.cell_center_velocity(idx!(i, j))[d]
+        })
+        .into_pyarray(py);
+    }
+
+    /// Copy all fields at the current time into a dictionary with the keys
+    /// `time`, `pressure`, `smoke`, `divergence`, `solid` and `velocity` (cell-centered).
+    fn snapshot<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let s = Snapshot::of(&self.timestepper);
+        let (nx, ny) = (s.dim.x, s.dim.y);
+        let field = |values: &[Scalar]| {
+            Array2::from_shape_fn((ny, nx), |(j, i)| values[s.offset(idx!(i, j))]).into_pyarray(py)
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item("time", s.time)?;
+        dict.set_item("pressure", field(&s.pressure))?;
+        dict.set_item("smoke", field(&s.smoke))?;
+        dict.set_item("divergence", field(&s.divergence))?;
+        dict.set_item(
+            "solid",
+            Array2::from_shape_fn((ny, nx), |(j, i)| s.solid[s.offset(idx!(i, j))]).into_pyarray(py),
+        )?;
+        dict.set_item(
+            "velocity",
+            Array3::from_shape_fn((ny, nx, 2), |(j, i, d)| s.velocity[s.offset(idx!(i, j))][d])
+                .into_pyarray(py),
+        )?;
+
+        return Ok(dict);
+    }
+
+    /// Render the smoke into an RGBA image of shape `(height, width, 4)`
+    /// with `scale x scale` pixels per cell (top row first).
+    #[pyo3(signature = (scale = 1))]
+    fn screenshot<'py>(&self, py: Python<'py>, scale: usize) -> PyResult<&'py PyArray3<u8>> {
+        let params = RenderParamsBuilder::default().scale(scale.max(1)).build().unwrap();
+        let img = screenshot(&self.timestepper, &params);
+        let shape = (img.height() as usize, img.width() as usize, 4);
+
+        return Array3::from_shape_vec(shape, img.into_raw())
+            .map(|a| a.into_pyarray(py))
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()));
+    }
+
+    /// Set the smoke field from an array of shape `(dim_y, dim_x)`.
+    fn set_smoke(&mut self, smoke: PyReadonlyArray2<Scalar>) -> PyResult<()> {
+        let smoke = smoke.as_array();
+        let dim = self.grid().dim;
+
+        if smoke.shape() != [dim.y, dim.x] {
+            return Err(PyValueError::new_err(format!(
+                "Smoke needs shape {:?} but got {:?}.",
+                (dim.y, dim.x),
+                smoke.shape()
+            )));
+        }
+
+        let grid = self.grid_mut();
+        for idx in grid.iter_index() {
+            grid.cell_mut(idx).set_smoke(smoke[[idx.y, idx.x]]);
+        }
+
+        return Ok(());
+    }
+
+    /// Save a checkpoint, compressed with `zstd` if a `level` is given.
+    #[pyo3(signature = (file, level = None))]
+    fn save_checkpoint(&mut self, file: &str, level: Option<i32>) -> PyResult<()> {
+        let compression = level.map_or(Compression::None, Compression::Zstd);
+        return self.timestepper.save_checkpoint(file, compression).map_err(to_py);
+    }
+
+    fn load_checkpoint(&mut self, file: &str) -> PyResult<()> {
+        return self.timestepper.load_checkpoint(file).map_err(to_py);
+    }
+}
+
+#[pymodule]
+fn rustofluid(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PySimulation>()?;
+    return Ok(());
+}
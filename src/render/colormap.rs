@@ -0,0 +1,177 @@
+use crate::types::*;
+use colorgrad;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Colormap {
+    Grayscale,
+    Turbo,
+    Viridis,
+    Magma,
+    /// Diverging blue-white-red (Moreland).
+    Coolwarm,
+    /// Diverging, reversed `RdBu` such that negative values are blue.
+    RdBu,
+    /// Custom stops `(position, html color)` with positions in `[0, 1]`.
+    Custom(Vec<(Scalar, String)>),
+}
+
+impl Colormap {
+    pub fn gradient(&self) -> colorgrad::Gradient {
+        return match self {
+            Colormap::Grayscale => colorgrad::CustomGradient::new()
+                .html_colors(&["#000000", "#ffffff"])
+                .build()
+                .unwrap(),
+            Colormap::Turbo => colorgrad::turbo(),
+            Colormap::Viridis => colorgrad::viridis(),
+            Colormap::Magma => colorgrad::magma(),
+            Colormap::Coolwarm => colorgrad::CustomGradient::new()
+                .html_colors(&["#3b4cc0", "#dddddd", "#b40426"])
+                .build()
+                .unwrap(),
+            Colormap::RdBu => colorgrad::CustomGradient::new()
+                .colors(&colorgrad::rd_bu().colors(11).into_iter().rev().collect::<Vec<_>>())
+                .build()
+                .unwrap(),
+            Colormap::Custom(stops) => Colormap::custom_gradient(stops).unwrap(),
+        };
+    }
+
+    fn custom_gradient(stops: &[(Scalar, String)]) -> Result<colorgrad::Gradient, String> {
+        let colors: Vec<&str> = stops.iter().map(|(_, c)| c.as_str()).collect();
+        let domain: Vec<Scalar> = stops.iter().map(|(p, _)| *p).collect();
+
+        return colorgrad::CustomGradient::new()
+            .html_colors(&colors)
+            .domain(&domain)
+            .build()
+            .map_err(|e| format!("Invalid colormap stops {:?}: {}", stops, e));
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    /// Parse a name (`grayscale`, `turbo`, `viridis`, `magma`, `coolwarm`, `rdbu`)
+    /// or custom stops `color,...` or `position:color,...`, e.g. `0:#000000,0.7:red,1:#ffffff`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let map = match s.trim().to_lowercase().as_str() {
+            "grayscale" | "gray" => Colormap::Grayscale,
+            "turbo" => Colormap::Turbo,
+            "viridis" => Colormap::Viridis,
+            "magma" => Colormap::Magma,
+            "coolwarm" => Colormap::Coolwarm,
+            "rdbu" => Colormap::RdBu,
+            _ => {
+                let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
+                if parts.len() < 2 {
+                    return Err(format!("Unknown colormap '{}'.", s));
+                }
+
+                let n = parts.len() - 1;
+                let stops = parts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| match p.split_once(':') {
+                        Some((pos, c)) => pos
+                            .trim()
+                            .parse::<Scalar>()
+                            .map(|pos| (pos, c.trim().to_string()))
+                            .map_err(|e| format!("Invalid stop '{}': {}", p, e)),
+                        None => Ok((i as Scalar / n as Scalar, p.to_string())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Colormap::custom_gradient(&stops)?;
+                Colormap::Custom(stops)
+            }
+        };
+
+        return Ok(map);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueRange {
+    /// Min. and max. over all fluid cells of the current frame.
+    Auto,
+    /// Symmetric range `[-m, m]` around zero with `m` the max. absolute value.
+    Symmetric,
+    /// Min. and max. over all frames so far (never shrinks).
+    Running,
+    /// Fixed range `[min, max]`.
+    Fixed(Scalar, Scalar),
+}
+
+impl FromStr for ValueRange {
+    type Err = String;
+
+    /// Parse `auto`, `symmetric`, `running` or `min,max`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "auto" => return Ok(ValueRange::Auto),
+            "symmetric" => return Ok(ValueRange::Symmetric),
+            "running" => return Ok(ValueRange::Running),
+            _ => {}
+        }
+
+        let vals = s
+            .split(',')
+            .map(|v| v.trim().parse::<Scalar>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Value range '{}' is invalid: {}", s, e))?;
+
+        if vals.len() != 2 || vals[0] >= vals[1] {
+            return Err(format!("Value range '{}' needs 'min,max' with min < max.", s));
+        }
+
+        return Ok(ValueRange::Fixed(vals[0], vals[1]));
+    }
+}
+
+/// The range `[min, max]` seen over all frames so far (for `ValueRange::Running`).
+/// The frames are rendered on the background writer, hence the lock.
+#[derive(Debug, Default)]
+pub struct RunningRange(Mutex<Option<(Scalar, Scalar)>>);
+
+impl ValueRange {
+    /// Compute the range `[min, max]` of the `values` of this frame.
+    pub fn resolve<I: Iterator<Item = Scalar>>(&self, running: &RunningRange, values: I) -> (Scalar, Scalar) {
+        if let ValueRange::Fixed(min, max) = *self {
+            return (min, max);
+        }
+
+        let (mut min, mut max) = values.fold((Scalar::MAX, Scalar::MIN), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+
+        if min > max {
+            (min, max) = (0.0, 0.0);
+        }
+
+        return match self {
+            ValueRange::Symmetric => {
+                let m = min.abs().max(max.abs());
+                (-m, m)
+            }
+            ValueRange::Running => {
+                let mut seen = running.0.lock().unwrap();
+                let (min, max) = match *seen {
+                    Some((a, b)) => (a.min(min), b.max(max)),
+                    None => (min, max),
+                };
+                *seen = Some((min, max));
+                (min, max)
+            }
+            _ => (min, max),
+        };
+    }
+
+    /// Normalize `v` to `[0, 1]` in the range `[min, max]`.
+    pub fn normalize(v: Scalar, (min, max): (Scalar, Scalar)) -> Scalar {
+        let range = if max > min { max - min } else { 1.0 };
+        return ((v - min) / range).clamp(0.0, 1.0);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::render::colormap::{Colormap, RunningRange, ValueRange};
+use crate::io::metadata::RunMetadata;
+use crate::render::raster::{render_cells, save_png, RenderParams};
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use image::RgbaImage;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeatmapField {
+    Pressure,
+    Divergence,
+}
+
+impl HeatmapField {
+    pub fn value(&self, cell: &Cell) -> Scalar {
+        return match self {
+            HeatmapField::Pressure => cell.pressure,
+            HeatmapField::Divergence => cell.div,
+        };
+    }
+}
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct HeatmapParams {
+    #[builder(default = "HeatmapField::Pressure")]
+    pub field: HeatmapField,
+
+    #[builder(default = "ValueRange::Auto")]
+    pub range: ValueRange,
+
+    #[builder(default = "Colormap::Turbo")]
+    pub colormap: Colormap,
+
+    #[builder(setter(skip), default)]
+    pub running: RunningRange,
+}
+
+impl HeatmapParams {
+    /// Compute the value range `[min, max]` used for the colormap.
+    pub fn value_range(&self, grid: &Grid) -> (Scalar, Scalar) {
+        return self.range.resolve(
+            &self.running,
+            grid.iter_index()
+                .map(|idx| grid.cell(idx))
+                .filter(|c| c.mode == CellTypes::Fluid)
+                .map(|c| self.field.value(c)),
+        );
+    }
+}
+
+/// Render the pressure or divergence field as a colormapped heatmap.
+pub fn render_heatmap(grid: &Grid, params: &RenderParams, heatmap: &HeatmapParams) -> RgbaImage {
+    let cg = heatmap.colormap.gradient();
+    let range = heatmap.value_range(grid);
+
+    return render_cells(grid.dim, params.scale, |idx: Index2| {
+        let cell = grid.cell(idx);
+        if cell.mode == CellTypes::Solid {
+            return params.solid_color.clone();
+        }
+
+        return cg.at(ValueRange::normalize(heatmap.field.value(cell), range) as f64);
+    });
+}
+
+/// Render the heatmap and save it as PNG.
+pub fn save_heatmap_png(
+    grid: &Grid,
+    params: &RenderParams,
+    heatmap: &HeatmapParams,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    return save_png(&render_heatmap(grid, params, heatmap), file, meta);
+}
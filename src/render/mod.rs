@@ -0,0 +1,25 @@
+mod colormap;
+pub use colormap::*;
+
+mod raster;
+pub use raster::*;
+
+mod heatmap;
+pub use heatmap::*;
+
+mod quiver;
+pub use quiver::*;
+
+mod svg;
+pub use svg::*;
+
+mod streamlines;
+pub use streamlines::*;
+
+mod video;
+pub use video::*;
+
+mod terminal;
+pub use terminal::*;
+
+mod tests;
@@ -0,0 +1,118 @@
+use crate::render::heatmap::{render_heatmap, HeatmapParamsBuilder};
+use crate::io::metadata::RunMetadata;
+use crate::render::raster::{render_smoke, save_png, RenderParams};
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use colorgrad;
+use image::{Rgba, RgbaImage};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuiverBackground {
+    Smoke,
+    Pressure,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct QuiverParams {
+    /// Draw an arrow every `stride` cells in each direction.
+    #[builder(default = "8")]
+    pub stride: usize,
+
+    /// Length of the largest arrow in units of `stride` cells.
+    #[builder(default = "1.0")]
+    pub arrow_scale: Scalar,
+
+    #[builder(default = "QuiverBackground::Smoke")]
+    pub background: QuiverBackground,
+}
+
+/// Draw a line segment from `a` to `b` (pixel coordinates) into the image.
+/// Pixels outside the image are skipped.
+pub fn draw_line(img: &mut RgbaImage, a: Vector2, b: Vector2, color: Rgba<u8>) {
+    let d = b - a;
+    let steps = d.x.abs().max(d.y.abs()).ceil().max(1.0) as usize;
+
+    for s in 0..=steps {
+        let p = a + d * (s as Scalar / steps as Scalar);
+        let (x, y) = (p.x.round(), p.y.round());
+
+        if x >= 0.0 && y >= 0.0 && (x as u32) < img.width() && (y as u32) < img.height() {
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// Draw an arrow from `a` to `b` (pixel coordinates) into the image.
+pub fn draw_arrow(img: &mut RgbaImage, a: Vector2, b: Vector2, color: Rgba<u8>) {
+    draw_line(img, a, b, color);
+
+    let d = b - a;
+    let len = d.norm();
+    if len < 1.0 {
+        return;
+    }
+
+    // Arrow head with two wings at +/- 25 degrees.
+    let back = -d / len * (0.3 * len).max(2.0);
+    let (s, c) = (25.0 as Scalar).to_radians().sin_cos();
+    for sign in [-1.0, 1.0] {
+        let wing = vec2!(c * back.x - sign * s * back.y, sign * s * back.x + c * back.y);
+        draw_line(img, b, b + wing, color);
+    }
+}
+
+/// Render the velocity field as arrows (colored by magnitude)
+/// over the smoke or pressure field.
+pub fn render_quiver(grid: &Grid, params: &RenderParams, quiver: &QuiverParams) -> RgbaImage {
+    assert!(quiver.stride >= 1, "Stride must be at least 1.");
+
+    let mut img = match quiver.background {
+        QuiverBackground::Smoke => render_smoke(grid, params),
+        QuiverBackground::Pressure => {
+            render_heatmap(grid, params, &HeatmapParamsBuilder::default().build().unwrap())
+        }
+    };
+
+    let samples: Vec<(Index2, Vector2)> = grid
+        .iter_inside()
+        .filter(|idx| idx.x % quiver.stride == 0 && idx.y % quiver.stride == 0)
+        .filter(|idx| grid.cell(*idx).mode == CellTypes::Fluid)
+        .map(|idx| (idx, grid.cell_center_velocity(idx)))
+        .collect();
+
+    let max_norm = samples.iter().map(|(_, v)| v.norm()).fold(0.0, Scalar::max);
+    if max_norm <= 0.0 {
+        return img;
+    }
+
+    let cg = colorgrad::turbo();
+    let scale = params.scale as Scalar;
+    let px_per_vel = quiver.arrow_scale * (quiver.stride as Scalar) * scale / max_norm;
+    let height = img.height() as Scalar;
+
+    for (idx, v) in samples {
+        let c = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * scale;
+
+        // Image `y`-axis points downwards.
+        let a = vec2!(c.x, height - c.y);
+        let b = a + vec2!(v.x, -v.y) * px_per_vel;
+
+        let color = Rgba(cg.at((v.norm() / max_norm) as f64).to_rgba8());
+        draw_arrow(&mut img, a, b, color);
+    }
+
+    return img;
+}
+
+/// Render the velocity arrows and save them as PNG.
+pub fn save_quiver_png(
+    grid: &Grid,
+    params: &RenderParams,
+    quiver: &QuiverParams,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    return save_png(&render_quiver(grid, params, quiver), file, meta);
+}
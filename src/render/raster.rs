@@ -0,0 +1,116 @@
+use crate::io::metadata::RunMetadata;
+use crate::render::colormap::{Colormap, RunningRange, ValueRange};
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use colorgrad;
+use image::{Rgba, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+
+#[derive(Builder)]
+#[builder(
+    pattern = "mutable",
+    build_fn(validate = "Self::validate", error = "Error")
+)]
+pub struct RenderParams {
+    /// Number of pixels per cell in each direction.
+    #[builder(default = "1")]
+    pub scale: usize,
+
+    /// Gamma correction applied to the normalized field value.
+    #[builder(default = "1.0")]
+    pub gamma: Scalar,
+
+    #[builder(default = "Colormap::Turbo")]
+    pub colormap: Colormap,
+
+    /// Value range of the smoke mapped to the colormap.
+    #[builder(default = "ValueRange::Fixed(0.0, 1.0)")]
+    pub range: ValueRange,
+
+    #[builder(setter(skip), default)]
+    pub running: RunningRange,
+
+    #[builder(default = "colorgrad::Color::new(0.2, 0.2, 0.2, 1.0)")]
+    pub solid_color: colorgrad::Color,
+}
+
+impl RenderParamsBuilder {
+    fn validate(&self) -> FluidResult<()> {
+        if self.scale == Some(0) {
+            return Err(Error::invalid_parameter("scale", "must be at least 1".to_string()));
+        }
+
+        return Ok(());
+    }
+}
+
+/// Render a cell field into an image with `scale x scale` pixels per cell.
+/// The image is flipped such that the `y`-axis points upwards.
+pub fn render_cells<F>(dim: Index2, scale: usize, get_color: F) -> RgbaImage
+where
+    F: Fn(Index2) -> colorgrad::Color,
+{
+    assert!(scale >= 1, "Scale must be at least 1.");
+
+    let size = dim * scale;
+
+    return RgbaImage::from_fn(size.x as u32, size.y as u32, |px, py| {
+        let i = px as usize / scale;
+        let j = dim.y - 1 - py as usize / scale;
+
+        return Rgba(get_color(idx!(i, j)).to_rgba8());
+    });
+}
+
+/// Render the smoke field of the grid.
+pub fn render_smoke(grid: &Grid, params: &RenderParams) -> RgbaImage {
+    let cg = params.colormap.gradient();
+    let gamma_inv = 1.0 / params.gamma;
+
+    let range = params.range.resolve(
+        &params.running,
+        grid.iter_index()
+            .map(|idx| grid.cell(idx))
+            .filter(|c| c.mode == CellTypes::Fluid)
+            .map(|c| c.smoke()),
+    );
+
+    return render_cells(grid.dim, params.scale, |idx: Index2| {
+        let cell = grid.cell(idx);
+        if cell.mode == CellTypes::Solid {
+            return params.solid_color.clone();
+        }
+
+        let t = ValueRange::normalize(cell.smoke(), range).powf(gamma_inv);
+        return cg.at(t as f64);
+    });
+}
+
+/// Save the image as PNG with the run metadata as text chunks.
+pub fn save_png(img: &RgbaImage, file: &str, meta: Option<&RunMetadata>) -> GenericResult<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(file)?), img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    for (key, value) in meta.map(|m| m.entries()).unwrap_or_default() {
+        encoder.add_text_chunk(key.to_string(), value)?;
+    }
+
+    let mut w = encoder.write_header()?;
+    w.write_image_data(img.as_raw())?;
+    w.finish()?;
+
+    return Ok(());
+}
+
+/// Render the smoke field of the grid and save it as PNG.
+pub fn save_smoke_png(
+    grid: &Grid,
+    params: &RenderParams,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    return save_png(&render_smoke(grid, params), file, meta);
+}
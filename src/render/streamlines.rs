@@ -0,0 +1,101 @@
+use crate::render::heatmap::{render_heatmap, HeatmapParamsBuilder};
+use crate::render::quiver::QuiverBackground;
+use crate::io::metadata::RunMetadata;
+use crate::render::raster::{render_smoke, save_png, RenderParams};
+use crate::scene::grid::Grid;
+use crate::scene::streamlines::{compute_streamlines, StreamlineParams};
+use crate::types::*;
+use image::{Rgba, RgbaImage};
+
+/// Blend `color` with the coverage `alpha` into the pixel `(x, y)`.
+fn blend(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, alpha: Scalar) {
+    if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+        return;
+    }
+
+    let a = alpha.clamp(0.0, 1.0) * color.0[3] as Scalar / 255.0;
+    let p = img.get_pixel_mut(x as u32, y as u32);
+    for c in 0..3 {
+        p.0[c] = (p.0[c] as Scalar * (1.0 - a) + color.0[c] as Scalar * a).round() as u8;
+    }
+}
+
+/// Draw an anti-aliased line segment from `a` to `b` (pixel coordinates)
+/// with Xiaolin Wu's algorithm.
+pub fn draw_line_aa(img: &mut RgbaImage, a: Vector2, b: Vector2, color: Rgba<u8>) {
+    let (mut a, mut b) = (a, b);
+    let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+
+    if steep {
+        a = vec2!(a.y, a.x);
+        b = vec2!(b.y, b.x);
+    }
+    if a.x > b.x {
+        std::mem::swap(&mut a, &mut b);
+    }
+
+    let d = b - a;
+    let gradient = if d.x.abs() < 1e-12 { 1.0 } else { d.y / d.x };
+
+    let mut plot = |x: i64, y: i64, c: Scalar| {
+        if steep {
+            blend(img, y, x, color, c);
+        } else {
+            blend(img, x, y, color, c);
+        }
+    };
+
+    let x_start = a.x.round() as i64;
+    let x_end = b.x.round() as i64;
+
+    for x in x_start..=x_end {
+        let y = a.y + gradient * (x as Scalar - a.x);
+        let (y0, f) = (y.floor(), y - y.floor());
+        plot(x, y0 as i64, 1.0 - f);
+        plot(x, y0 as i64 + 1, f);
+    }
+}
+
+/// Render streamlines over the smoke or pressure field.
+pub fn render_streamlines(
+    grid: &Grid,
+    params: &RenderParams,
+    streamlines: &StreamlineParams,
+    background: QuiverBackground,
+    color: Rgba<u8>,
+) -> RgbaImage {
+    let mut img = match background {
+        QuiverBackground::Smoke => render_smoke(grid, params),
+        QuiverBackground::Pressure => {
+            render_heatmap(grid, params, &HeatmapParamsBuilder::default().build().unwrap())
+        }
+    };
+
+    let px = params.scale as Scalar / grid.cell_width;
+    let height = img.height() as Scalar;
+
+    // Image `y`-axis points downwards.
+    let to_pixel = |p: &Vector2| vec2!(p.x * px, height - p.y * px);
+
+    for line in compute_streamlines(grid, streamlines) {
+        for seg in line.windows(2) {
+            draw_line_aa(&mut img, to_pixel(&seg[0]), to_pixel(&seg[1]), color);
+        }
+    }
+
+    return img;
+}
+
+/// Render the streamlines and save them as PNG.
+pub fn save_streamlines_png(
+    grid: &Grid,
+    params: &RenderParams,
+    streamlines: &StreamlineParams,
+    background: QuiverBackground,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    let white = Rgba([255, 255, 255, 200]);
+    let img = render_streamlines(grid, params, streamlines, background, white);
+    return save_png(&img, file, meta);
+}
@@ -0,0 +1,149 @@
+use crate::io::metadata::RunMetadata;
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::streamlines::{compute_streamlines, StreamlineParams};
+use crate::types::*;
+
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct SvgParams {
+    /// Number of SVG units per cell.
+    #[builder(default = "4.0")]
+    pub scale: Scalar,
+
+    /// Smoke values of the iso-contours.
+    #[builder(default = "vec![0.25, 0.5, 0.75]")]
+    pub levels: Vec<Scalar>,
+
+    #[builder(default = "1.0")]
+    pub stroke_width: Scalar,
+}
+
+pub type Segment = (Vector2, Vector2);
+
+/// Compute the iso-contour of the cell-centered field `get` at `level`
+/// with marching squares. Returns line segments in world coordinates.
+pub fn contour_segments<F: Fn(&Cell) -> Scalar>(grid: &Grid, level: Scalar, get: F) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for j in 0..grid.dim.y - 1 {
+        for i in 0..grid.dim.x - 1 {
+            // Corners counter-clockwise: (0,0), (1,0), (1,1), (0,1).
+            let idx = [idx!(i, j), idx!(i + 1, j), idx!(i + 1, j + 1), idx!(i, j + 1)];
+            let v = idx.map(|x| get(grid.cell(x)) - level);
+            let p = idx.map(|x| grid.cell_center(x));
+
+            // Crossings on the edges bottom, right, top, left.
+            let mut cross: [Option<Vector2>; 4] = [None; 4];
+            for e in 0..4 {
+                let (a, b) = (e, (e + 1) % 4);
+                if (v[a] >= 0.0) != (v[b] >= 0.0) {
+                    let t = v[a] / (v[a] - v[b]);
+                    cross[e] = Some(p[a] + (p[b] - p[a]) * t);
+                }
+            }
+
+            let pts: Vec<(usize, Vector2)> = cross
+                .iter()
+                .enumerate()
+                .filter_map(|(e, c)| c.map(|c| (e, c)))
+                .collect();
+
+            match pts.len() {
+                2 => segments.push((pts[0].1, pts[1].1)),
+                4 => {
+                    // Saddle: decide with the center value which corners are separated.
+                    let center = v.iter().sum::<Scalar>() / 4.0;
+                    let c = |e: usize| cross[e].unwrap();
+
+                    if (center >= 0.0) == (v[0] >= 0.0) {
+                        segments.push((c(0), c(1)));
+                        segments.push((c(2), c(3)));
+                    } else {
+                        segments.push((c(3), c(0)));
+                        segments.push((c(1), c(2)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    return segments;
+}
+
+/// Write the smoke iso-contours, the obstacle outlines and optionally
+/// the streamlines of the grid as SVG paths.
+pub fn write_svg(
+    grid: &Grid,
+    params: &SvgParams,
+    streamlines: Option<&StreamlineParams>,
+    file: &str,
+    meta: Option<&RunMetadata>,
+) -> GenericResult<()> {
+    let px = params.scale / grid.cell_width;
+    let size = grid.dim.cast::<Scalar>() * params.scale;
+
+    // SVG `y`-axis points downwards.
+    let pt = |p: &Vector2| format!("{:.2} {:.2}", p.x * px, size.y - p.y * px);
+
+    let path = |segments: &[Segment]| {
+        let mut d = String::new();
+        for (a, b) in segments {
+            let _ = write!(d, "M{}L{}", pt(a), pt(b));
+        }
+        return d;
+    };
+
+    let mut w = BufWriter::new(File::create(file)?);
+
+    writeln!(
+        w,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        size.x, size.y
+    )?;
+    if let Some(m) = meta {
+        writeln!(w, "<metadata>{}</metadata>", m.to_json())?;
+    }
+    writeln!(
+        w,
+        r#"<g fill="none" stroke-width="{}" stroke-linecap="round">"#,
+        params.stroke_width
+    )?;
+
+    let outline = contour_segments(grid, 0.5, |c| (c.mode == CellTypes::Solid) as u8 as Scalar);
+    writeln!(w, r##"<path id="obstacles" stroke="#404040" d="{}"/>"##, path(&outline))?;
+
+    let cg = colorgrad::turbo();
+    for level in params.levels.iter() {
+        let contour = contour_segments(grid, *level, |c| c.smoke());
+        writeln!(
+            w,
+            r#"<path class="smoke" data-level="{}" stroke="{}" d="{}"/>"#,
+            level,
+            cg.at(*level as f64).to_hex_string(),
+            path(&contour)
+        )?;
+    }
+
+    if let Some(s) = streamlines {
+        let mut d = String::new();
+        for line in compute_streamlines(grid, s) {
+            let _ = write!(d, "M{}", pt(&line[0]));
+            for p in line[1..].iter() {
+                let _ = write!(d, "L{}", pt(p));
+            }
+        }
+        writeln!(w, r##"<path id="streamlines" stroke="#000000" stroke-opacity="0.6" d="{}"/>"##, d)?;
+    }
+
+    writeln!(w, "</g>")?;
+    writeln!(w, "</svg>")?;
+    w.flush()?;
+
+    return Ok(());
+}
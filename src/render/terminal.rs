@@ -0,0 +1,155 @@
+use crate::render::colormap::{Colormap, ValueRange};
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use std::fmt::Write;
+
+/// Shades for the smoke density without colors (empty to full).
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Arrows for the 8 directions, counter-clockwise starting at `+x`.
+const ARROWS: [char; 8] = ['→', '↗', '↑', '↖', '←', '↙', '↓', '↘'];
+
+const SOLID: char = '#';
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct TerminalParams {
+    /// Number of character columns.
+    #[builder(default = "80")]
+    pub columns: usize,
+
+    /// Use 24-bit ANSI colors with half blocks (two samples per character).
+    #[builder(default = "true")]
+    pub color: bool,
+
+    #[builder(default = "Colormap::Turbo")]
+    pub colormap: Colormap,
+
+    /// Overlay velocity arrows every `quiver_stride` characters (`0` disables them).
+    #[builder(default = "0")]
+    pub quiver_stride: usize,
+}
+
+/// The cell index for the sample `(i, j)` on a raster with `samples` samples
+/// (with `j` pointing downwards).
+fn sample_index(grid: &Grid, samples: Index2, i: usize, j: usize) -> Index2 {
+    let x = (i * grid.dim.x) / samples.x;
+    let y = (j * grid.dim.y) / samples.y;
+    return idx!(x.min(grid.dim.x - 1), grid.dim.y - 1 - y.min(grid.dim.y - 1));
+}
+
+/// The arrow for the velocity at `idx` or `None` if it is too small.
+fn arrow(grid: &Grid, idx: Index2, v_max: Scalar) -> Option<char> {
+    let v = grid.cell_center_velocity(idx);
+    if v_max <= 0.0 || v.norm() < 0.1 * v_max {
+        return None;
+    }
+
+    let angle = v.y.atan2(v.x).to_degrees().rem_euclid(360.0);
+    return Some(ARROWS[((angle + 22.5) / 45.0) as usize % 8]);
+}
+
+/// Render the smoke field as Unicode text for the terminal.
+/// Solid cells are drawn as `#` (or gray with colors).
+pub fn render_terminal(grid: &Grid, params: &TerminalParams) -> String {
+    assert!(params.columns >= 1, "Need at least one column.");
+
+    // Characters are about twice as high as wide.
+    let cols = params.columns;
+    let rows = ((cols * grid.dim.y) as Scalar / (2 * grid.dim.x) as Scalar).round().max(1.0) as usize;
+
+    let v_max = if params.quiver_stride > 0 {
+        grid.iter_inside()
+            .filter(|idx| grid.cell(*idx).mode == CellTypes::Fluid)
+            .map(|idx| grid.cell_center_velocity(idx).norm())
+            .fold(0.0, Scalar::max)
+    } else {
+        0.0
+    };
+
+    let is_quiver = |i: usize, j: usize| {
+        return params.quiver_stride > 0 && i % params.quiver_stride == 0 && j % params.quiver_stride == 0;
+    };
+
+    let cg = params.colormap.gradient();
+    let color = |idx: Index2| -> [u8; 4] {
+        let cell = grid.cell(idx);
+        if cell.mode == CellTypes::Solid {
+            return [80, 80, 80, 255];
+        }
+        return cg.at(ValueRange::normalize(cell.smoke(), (0.0, 1.0)) as f64).to_rgba8();
+    };
+
+    let mut out = String::new();
+
+    if params.color {
+        let samples = idx!(cols, 2 * rows);
+
+        for j in 0..rows {
+            for i in 0..cols {
+                let upper = sample_index(grid, samples, i, 2 * j);
+                let lower = sample_index(grid, samples, i, 2 * j + 1);
+                let (fg, bg) = (color(upper), color(lower));
+
+                let arrow = match is_quiver(i, j) && grid.cell(upper).mode == CellTypes::Fluid {
+                    true => arrow(grid, upper, v_max),
+                    false => None,
+                };
+
+                let _ = match arrow {
+                    Some(a) => write!(
+                        out,
+                        "\x1b[38;2;255;255;255;48;2;{};{};{}m{}",
+                        bg[0], bg[1], bg[2], a
+                    ),
+                    None => write!(
+                        out,
+                        "\x1b[38;2;{};{};{};48;2;{};{};{}m▀",
+                        fg[0], fg[1], fg[2], bg[0], bg[1], bg[2]
+                    ),
+                };
+            }
+            out.push_str("\x1b[0m\n");
+        }
+    } else {
+        let samples = idx!(cols, rows);
+
+        for j in 0..rows {
+            for i in 0..cols {
+                let idx = sample_index(grid, samples, i, j);
+                let cell = grid.cell(idx);
+
+                let arrow = match is_quiver(i, j) {
+                    true => arrow(grid, idx, v_max),
+                    false => None,
+                };
+
+                let c = if cell.mode == CellTypes::Solid {
+                    SOLID
+                } else if let Some(a) = arrow {
+                    a
+                } else {
+                    let t = cell.smoke().clamp(0.0, 1.0);
+                    SHADES[(t * (SHADES.len() - 1) as Scalar).round() as usize]
+                };
+
+                out.push(c);
+            }
+            out.push('\n');
+        }
+    }
+
+    return out;
+}
+
+/// Print the smoke field to the terminal. If `redraw` is set, the cursor
+/// is moved to the top left first to draw over the previous frame.
+pub fn print_terminal(grid: &Grid, params: &TerminalParams, redraw: bool) {
+    let frame = render_terminal(grid, params);
+    if redraw {
+        print!("\x1b[H\x1b[2J{}", frame);
+    } else {
+        print!("{}", frame);
+    }
+}
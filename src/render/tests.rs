@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::render::*;
+    use crate::scene::cell::CellTypes;
+    use crate::scene::grid::*;
+    use crate::types::*;
+
+    #[test]
+    fn test_render_smoke() {
+        let mut grid = Grid::new(dim!(2, 1), 1.0);
+        grid.cell_mut(idx!(1, 1)).set_smoke(1.0);
+        grid.cell_mut(idx!(0, 0)).mode = CellTypes::Solid;
+
+        let params = RenderParamsBuilder::default()
+            .scale(3)
+            .colormap(Colormap::Grayscale)
+            .build()
+            .unwrap();
+
+        let img = render_smoke(&grid, &params);
+        assert!(img.width() == 4 * 3 && img.height() == 3 * 3);
+
+        // Cell (1, 1) is in the middle row of the flipped image.
+        assert!(img.get_pixel(4, 4).0 == [255, 255, 255, 255]);
+        assert!(img.get_pixel(7, 4).0 == [0, 0, 0, 255]);
+
+        // Cell (0, 0) is solid and in the bottom row.
+        assert!(img.get_pixel(0, 8).0 == params.solid_color.to_rgba8());
+
+        let zero = RenderParamsBuilder::default().scale(0).build();
+        assert!(matches!(zero, Err(Error::InvalidParameter { name: "scale", .. })));
+    }
+
+    #[test]
+    fn test_render_quiver() {
+        let mut grid = Grid::new(dim!(8, 8), 1.0);
+        for idx in grid.iter_index() {
+            grid.cell_mut(idx).velocity.back = vec2!(1.0, 0.0);
+        }
+
+        let params = RenderParamsBuilder::default()
+            .scale(4)
+            .colormap(Colormap::Grayscale)
+            .build()
+            .unwrap();
+        let quiver = QuiverParamsBuilder::default().stride(4).build().unwrap();
+
+        let img = render_quiver(&grid, &params, &quiver);
+        assert!(img.width() == 10 * 4 && img.height() == 10 * 4);
+
+        // Arrow at cell (4, 4) starts at the cell center and points in `x`.
+        let black = [0, 0, 0, 255];
+        let (x, y) = (4 * 4 + 2, 40 - (4 * 4 + 2));
+        assert!(img.get_pixel(x, y).0 != black);
+        assert!(img.get_pixel(x + 8, y).0 != black);
+        assert!(img.get_pixel(x, y + 3).0 == black);
+    }
+
+    #[test]
+    fn test_render_streamlines() {
+        use crate::scene::streamlines::StreamlineParamsBuilder;
+
+        let mut grid = Grid::new(dim!(8, 8), 1.0);
+        for idx in grid.iter_index() {
+            grid.cell_mut(idx).velocity.back = vec2!(1.0, 0.0);
+        }
+
+        let params = RenderParamsBuilder::default()
+            .scale(4)
+            .colormap(Colormap::Grayscale)
+            .build()
+            .unwrap();
+        let streamlines = StreamlineParamsBuilder::default().seed_stride(4).build().unwrap();
+
+        let white = image::Rgba([255, 255, 255, 255]);
+        let img = render_streamlines(&grid, &params, &streamlines, QuiverBackground::Smoke, white);
+
+        // The streamline through the center of cell (4, 4) is horizontal.
+        let y = 40 - (4 * 4 + 2);
+        assert!(img.get_pixel(4 * 4 + 2, y).0[0] > 0);
+        assert!(img.get_pixel(4 * 4 + 10, y).0[0] > 0);
+        assert!(img.get_pixel(4 * 4 + 2, y + 3).0[0] == 0);
+    }
+
+    #[test]
+    fn test_contour_segments() {
+        // A smoke blob in the center cell.
+        let mut grid = Grid::new(dim!(1, 1), 1.0);
+        grid.cell_mut(idx!(1, 1)).set_smoke(1.0);
+
+        let segments = contour_segments(&grid, 0.5, |c| c.smoke());
+        assert!(segments.len() == 4, "Wrong number of segments {}.", segments.len());
+
+        // All crossings lie halfway between the cell centers.
+        for (a, b) in segments {
+            for p in [a, b] {
+                let d = p - vec2!(1.5, 1.5);
+                assert!((d.x.abs() - 0.5).abs() < 1e-9 || (d.y.abs() - 0.5).abs() < 1e-9);
+            }
+        }
+
+        let file = std::env::temp_dir().join("rustofluid-test.svg");
+        let params = SvgParamsBuilder::default().build().unwrap();
+        write_svg(&grid, &params, None, file.to_str().unwrap(), None).unwrap();
+
+        let svg = std::fs::read_to_string(&file).unwrap();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r#"data-level="0.5""#));
+    }
+
+    #[test]
+    fn test_heatmap_range() {
+        let mut grid = Grid::new(dim!(2, 1), 1.0);
+        grid.cell_mut(idx!(1, 1)).div = -2.0;
+        grid.cell_mut(idx!(2, 1)).div = 1.0;
+
+        let mut builder = HeatmapParamsBuilder::default();
+        builder.field(HeatmapField::Divergence);
+
+        let heatmap = builder.range(ValueRange::Auto).build().unwrap();
+        assert!(heatmap.value_range(&grid) == (-2.0, 1.0));
+
+        let heatmap = builder.range(ValueRange::Symmetric).build().unwrap();
+        assert!(heatmap.value_range(&grid) == (-2.0, 2.0));
+
+        let heatmap = builder.range(ValueRange::Fixed(-1.0, 3.0)).build().unwrap();
+        assert!(heatmap.value_range(&grid) == (-1.0, 3.0));
+
+        // The running range only grows.
+        let heatmap = builder.range(ValueRange::Running).build().unwrap();
+        assert!(heatmap.value_range(&grid) == (-2.0, 1.0));
+        grid.cell_mut(idx!(1, 1)).div = 0.0;
+        grid.cell_mut(idx!(2, 1)).div = 4.0;
+        assert!(heatmap.value_range(&grid) == (-2.0, 4.0));
+    }
+
+    #[test]
+    fn test_colormap() {
+        assert!("viridis".parse::<Colormap>() == Ok(Colormap::Viridis));
+        assert!("unknown".parse::<Colormap>().is_err());
+
+        let c = "#000000,#ff0000,#ffffff".parse::<Colormap>().unwrap();
+        let g = c.gradient();
+        assert!(g.at(0.5).to_rgba8() == [255, 0, 0, 255]);
+
+        let c = "0:#000000,0.8:#ff0000,1:#ffffff".parse::<Colormap>().unwrap();
+        assert!(c.gradient().at(0.8).to_rgba8() == [255, 0, 0, 255]);
+        assert!("0:#000000,x:#ffffff".parse::<Colormap>().is_err());
+
+        let g = Colormap::Grayscale.gradient();
+        assert!(g.at(0.0).to_rgba8() == [0, 0, 0, 255] && g.at(1.0).to_rgba8() == [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_terminal() {
+        let mut grid = Grid::new(dim!(6, 2), 1.0);
+        grid.cell_mut(idx!(1, 3)).set_smoke(1.0);
+        grid.cell_mut(idx!(2, 1)).mode = CellTypes::Solid;
+
+        let mut builder = TerminalParamsBuilder::default();
+        builder.columns(8).color(false);
+
+        // Two rows: the top row samples `y = 3` and the bottom row `y = 1`.
+        let text = render_terminal(&grid, &builder.build().unwrap());
+        let lines: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+        assert!(lines.len() == 2 && lines[0].len() == 8, "Wrong size:\n{}", text);
+        assert!(lines[0][1] == '█', "Smoke not drawn:\n{}", text);
+        assert!(lines[1][2] == '#', "Solid not drawn:\n{}", text);
+        assert!(lines[1][1] == ' ', "Empty cell not blank:\n{}", text);
+
+        for idx in grid.iter_index() {
+            grid.cell_mut(idx).velocity.back = vec2!(0.0, 1.0);
+        }
+        let text = render_terminal(&grid, &builder.quiver_stride(4).build().unwrap());
+        assert!(text.starts_with('↑'), "Arrow not drawn:\n{}", text);
+    }
+
+    #[test]
+    fn test_gif_encoder() -> GenericResult<()> {
+        let grid = Grid::new(dim!(4, 4), 1.0);
+        let params = RenderParamsBuilder::default().build().unwrap();
+
+        let file = std::env::temp_dir().join("rustofluid-test.gif");
+        let mut encoder = create_encoder(file.to_str().unwrap(), 30)?;
+        for _ in 0..2 {
+            encoder.add_frame(&render_smoke(&grid, &params))?;
+        }
+        encoder.finish()?;
+
+        let data = std::fs::read(&file)?;
+        assert!(data.starts_with(b"GIF89a"));
+
+        return Ok(());
+    }
+}
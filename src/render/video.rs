@@ -0,0 +1,134 @@
+use crate::types::*;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// An encoder which consumes a sequence of rendered frames.
+pub trait FrameEncoder {
+    fn add_frame(&mut self, img: &RgbaImage) -> GenericResult<()>;
+
+    /// Finish the video and flush all data.
+    fn finish(self: Box<Self>) -> GenericResult<()>;
+}
+
+/// Animated GIF encoder (pure Rust).
+pub struct GifFrameEncoder {
+    encoder: GifEncoder<BufWriter<File>>,
+    delay: Delay,
+}
+
+impl GifFrameEncoder {
+    pub fn new(file: &str, fps: u32) -> GenericResult<Self> {
+        let mut encoder = GifEncoder::new(BufWriter::new(File::create(file)?));
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        return Ok(GifFrameEncoder {
+            encoder,
+            delay: Delay::from_numer_denom_ms(1000, fps.max(1)),
+        });
+    }
+}
+
+impl FrameEncoder for GifFrameEncoder {
+    fn add_frame(&mut self, img: &RgbaImage) -> GenericResult<()> {
+        self.encoder
+            .encode_frame(Frame::from_parts(img.clone(), 0, 0, self.delay))?;
+        return Ok(());
+    }
+
+    fn finish(self: Box<Self>) -> GenericResult<()> {
+        // The GIF trailer is written on drop.
+        drop(self.encoder);
+        return Ok(());
+    }
+}
+
+/// Video encoder which pipes raw frames into an `ffmpeg` subprocess.
+/// The subprocess is started on the first frame when the size is known.
+pub struct FfmpegEncoder {
+    file: String,
+    fps: u32,
+    size: Option<(u32, u32)>,
+    process: Option<(Child, ChildStdin)>,
+}
+
+impl FfmpegEncoder {
+    pub fn new(file: &str, fps: u32) -> Self {
+        return FfmpegEncoder {
+            file: file.to_string(),
+            fps: fps.max(1),
+            size: None,
+            process: None,
+        };
+    }
+
+    fn spawn(&self, width: u32, height: u32) -> GenericResult<(Child, ChildStdin)> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &self.fps.to_string()])
+            .args(["-i", "-"])
+            // `yuv420p` needs even dimensions.
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(&self.file)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start 'ffmpeg': {}", e))?;
+
+        let stdin = child.stdin.take().ok_or("No stdin for 'ffmpeg'.")?;
+        return Ok((child, stdin));
+    }
+}
+
+impl FrameEncoder for FfmpegEncoder {
+    fn add_frame(&mut self, img: &RgbaImage) -> GenericResult<()> {
+        let size = img.dimensions();
+
+        if self.process.is_none() {
+            self.process = Some(self.spawn(size.0, size.1)?);
+            self.size = Some(size);
+        }
+
+        if self.size != Some(size) {
+            return Err(format!("Frame size {:?} differs from {:?}.", size, self.size).into());
+        }
+
+        let (_, stdin) = self.process.as_mut().unwrap();
+        stdin.write_all(img.as_raw())?;
+
+        return Ok(());
+    }
+
+    fn finish(self: Box<Self>) -> GenericResult<()> {
+        if let Some((mut child, stdin)) = self.process {
+            drop(stdin); // Close the pipe to end the stream.
+
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(format!("'ffmpeg' failed with {}.", status).into());
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Create a frame encoder by the file extension (`.gif` or `.mp4`, `.mkv`, `.webm`, ...).
+pub fn create_encoder(file: &str, fps: u32) -> GenericResult<Box<dyn FrameEncoder>> {
+    let ext = Path::new(file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    return match ext.as_deref() {
+        Some("gif") => Ok(Box::new(GifFrameEncoder::new(file, fps)?)),
+        Some(_) => Ok(Box::new(FfmpegEncoder::new(file, fps))),
+        None => Err(format!("Video file '{}' has no extension.", file).into()),
+    };
+}
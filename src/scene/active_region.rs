@@ -0,0 +1,108 @@
+use crate::scene::cell::Cell;
+use crate::types::*;
+
+use rayon::prelude::*;
+
+/// Cells per side of the blocks of an `ActiveRegion` (a power of two).
+pub const ACTIVE_BLOCK: usize = 16;
+const ACTIVE_BLOCK_SHIFT: u32 = ACTIVE_BLOCK.trailing_zeros();
+
+/// Tracks the blocks of a grid in which the fluid moves, so that the projection
+/// and the advection can skip the quiescent parts of the domain.
+///
+/// A cell is active if its velocity exceeds `threshold` or its velocity or smoke
+/// changed by more than `threshold` since the last step. A block is active if one of
+/// its cells is active or if it neighbors such a block (the halo), since the fluid
+/// moves less than a block per step.
+pub struct ActiveRegion {
+    pub threshold: Scalar,
+
+    dim: Index2,
+    blocks: Index2,
+    active: Vec<bool>,
+
+    /// The velocity and smoke at the end of the last step.
+    last: Vec<(Vector2, Scalar)>,
+}
+
+impl ActiveRegion {
+    /// All blocks are active until the first step is stored.
+    pub fn new(dim: Index2, threshold: Scalar) -> Self {
+        let blocks = dim.map(|d| (d + ACTIVE_BLOCK - 1) / ACTIVE_BLOCK);
+
+        return ActiveRegion {
+            threshold,
+            dim,
+            blocks,
+            active: vec![true; blocks.x * blocks.y],
+            last: vec![],
+        };
+    }
+
+    /// Is the block of cell `idx` active.
+    #[inline(always)]
+    pub fn is_active(&self, idx: Index2) -> bool {
+        let (bx, by) = (idx.x >> ACTIVE_BLOCK_SHIFT, idx.y >> ACTIVE_BLOCK_SHIFT);
+        return self.active[bx + by * self.blocks.x];
+    }
+
+    /// The share of active blocks in `[0, 1]`.
+    pub fn active_fraction(&self) -> Scalar {
+        let n = self.active.iter().filter(|a| **a).count();
+        return n as Scalar / self.active.len() as Scalar;
+    }
+
+    pub fn bytes(&self) -> usize {
+        return self.active.capacity() * std::mem::size_of::<bool>()
+            + self.last.capacity() * std::mem::size_of::<(Vector2, Scalar)>();
+    }
+
+    /// Mark the blocks with changes since the last `store` and their neighbors active.
+    pub fn update(&mut self, cells: &[Cell]) {
+        assert!(
+            cells.len() == self.dim.x * self.dim.y,
+            "Cells do not match the region."
+        );
+        if self.last.is_empty() {
+            return;
+        }
+
+        let thr = self.threshold;
+        let is_active = |(c, (v, s)): (&Cell, &(Vector2, Scalar))| {
+            let vel = c.velocity.back;
+            return vel.amax() > thr || (vel - v).amax() > thr || (c.smoke() - s).abs() > thr;
+        };
+
+        let (dim, blocks) = (self.dim, self.blocks);
+        let block_rows: Vec<Vec<bool>> = cells
+            .par_chunks(ACTIVE_BLOCK * dim.x)
+            .zip(self.last.par_chunks(ACTIVE_BLOCK * dim.x))
+            .map(|(cells, last)| {
+                let mut row = vec![false; blocks.x];
+                for (i, cl) in cells.iter().zip(last).enumerate() {
+                    let b = (i % dim.x) >> ACTIVE_BLOCK_SHIFT;
+                    row[b] = row[b] || is_active(cl);
+                }
+                return row;
+            })
+            .collect();
+
+        // Dilate by one block.
+        for by in 0..blocks.y {
+            for bx in 0..blocks.x {
+                let rows = by.saturating_sub(1)..(by + 2).min(blocks.y);
+                let cols = bx.saturating_sub(1)..(bx + 2).min(blocks.x);
+                self.active[bx + by * blocks.x] = rows
+                    .into_iter()
+                    .any(|y| block_rows[y][cols.clone()].iter().any(|a| *a));
+            }
+        }
+    }
+
+    /// Store the velocity and smoke at the end of a step.
+    pub fn store(&mut self, cells: &[Cell]) {
+        self.last.clear();
+        self.last
+            .extend(cells.iter().map(|c| (c.velocity.back, c.smoke())));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::log::Logger;
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::ExecutionMode;
+use crate::types::*;
+
+/// The fields of a grid which are advected in each step, in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdvectedField {
+    Velocity = 0,
+
+    /// Transported with the velocity which was advected before.
+    Smoke = 1,
+}
+
+impl AdvectedField {
+    pub const ALL: [AdvectedField; 2] = [AdvectedField::Velocity, AdvectedField::Smoke];
+}
+
+/// Transports a field of a grid with the velocity of the grid over a step and
+/// stores the result in the back buffer of the field. Each field of a grid has
+/// its own scheme (see `Grid::set_advection_scheme`).
+pub trait AdvectionScheme: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn advect(
+        &mut self,
+        log: &Logger,
+        grid: &mut Grid,
+        field: AdvectedField,
+        dt: Scalar,
+        execution_mode: ExecutionMode,
+    );
+}
+
+/// Semi-Lagrangian advection: each value is interpolated bilinearly at the point which
+/// reaches it in `dt` (the default scheme of all fields).
+/// The smoke is only advected in the window of the smoke region if it is tracked.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SemiLagrangian;
+
+impl AdvectionScheme for SemiLagrangian {
+    fn name(&self) -> &str {
+        return "semi-lagrangian";
+    }
+
+    fn advect(
+        &mut self,
+        log: &Logger,
+        grid: &mut Grid,
+        field: AdvectedField,
+        dt: Scalar,
+        execution_mode: ExecutionMode,
+    ) {
+        match field {
+            AdvectedField::Velocity => grid.advect_velocity(log, dt, execution_mode),
+            AdvectedField::Smoke => grid.advect_smoke(log, dt, None, execution_mode),
+        }
+    }
+}
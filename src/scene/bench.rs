@@ -0,0 +1,150 @@
+use crate::log::*;
+use crate::scene::boundary::BoundaryCondition;
+use crate::scene::config::{EmitterConfig, SceneConfig};
+use crate::scene::setup::setup_scene;
+use crate::scene::timestepper::{ExecutionMode, StageTimings};
+use crate::types::*;
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The standard benchmark scenes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BenchScene {
+    /// Closed box with gravity and nothing else.
+    EmptyBox,
+    /// The channel flow around a cylinder.
+    Channel,
+    /// The channel without obstacle but a column of many small emitters.
+    DenseEmitters,
+}
+
+impl BenchScene {
+    pub const ALL: [BenchScene; 3] = [
+        BenchScene::EmptyBox,
+        BenchScene::Channel,
+        BenchScene::DenseEmitters,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            BenchScene::EmptyBox => "empty-box",
+            BenchScene::Channel => "channel",
+            BenchScene::DenseEmitters => "dense-emitters",
+        };
+    }
+
+    /// The scene configuration with `dim` cells.
+    pub fn config(&self, dim: Index2) -> SceneConfig {
+        let mut config = SceneConfig::channel(dim);
+
+        match self {
+            BenchScene::EmptyBox => {
+                config.boundary.right = BoundaryCondition::Solid;
+                config.boundary.inflow_velocity = None;
+                config.solver.gravity = [0.0, -9.81];
+                config.obstacles.clear();
+                config.emitters.clear();
+            }
+            BenchScene::Channel => {}
+            BenchScene::DenseEmitters => {
+                config.obstacles.clear();
+                config.emitters = (1..dim.y + 1)
+                    .step_by(4)
+                    .map(|y| EmitterConfig {
+                        min: [1, y],
+                        max: [3, (y + 2).min(dim.y + 1)],
+                        region: None,
+                        smoke: 1.0,
+                    })
+                    .collect();
+            }
+        }
+
+        return config;
+    }
+}
+
+impl FromStr for BenchScene {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return BenchScene::ALL
+            .into_iter()
+            .find(|b| b.name() == s.trim())
+            .ok_or_else(|| format!("Unknown benchmark scene '{}'.", s));
+    }
+}
+
+pub struct BenchResult {
+    pub scene: BenchScene,
+    pub dim: Index2,
+    pub steps: u64,
+    pub timings: StageTimings,
+}
+
+impl BenchResult {
+    /// Throughput in cells times steps per second.
+    pub fn throughput(&self) -> Scalar {
+        let cells = (self.dim.x * self.dim.y) as Scalar;
+        return cells * self.steps as Scalar / (self.timings.total().as_secs_f64() as Scalar).max(1e-12);
+    }
+}
+
+/// Run `scene` with `dim` cells for `steps` steps and measure the stages.
+pub fn run_bench(
+    log: &Logger,
+    scene: BenchScene,
+    dim: Index2,
+    steps: u64,
+    execution_mode: ExecutionMode,
+) -> GenericResult<BenchResult> {
+    let mut config = scene.config(dim);
+    config.solver.execution_mode = execution_mode;
+
+    let mut timestepper = setup_scene(log, &config)?;
+
+    let mut timings = StageTimings::default();
+    for _ in 0..steps {
+        timings += timestepper.compute_step_timed(config.time.dt)?;
+    }
+
+    return Ok(BenchResult {
+        scene,
+        dim,
+        steps,
+        timings,
+    });
+}
+
+/// Format the results as table with the average time per step
+/// of each stage in milliseconds.
+pub fn format_bench_table(results: &[BenchResult]) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{:<16} {:>11} {:>6} {:>11} {:>11} {:>11} {:>11} {:>11} {:>14}",
+        "scene", "dim", "steps", "manip [ms]", "integ [ms]", "solve [ms]", "advect [ms]", "total [ms]", "cells*steps/s"
+    );
+
+    for r in results {
+        let per_step = |d: std::time::Duration| 1e3 * d.as_secs_f64() as Scalar / r.steps.max(1) as Scalar;
+
+        let _ = writeln!(
+            out,
+            "{:<16} {:>11} {:>6} {:>11.3} {:>11.3} {:>11.3} {:>11.3} {:>11.3} {:>14.3e}",
+            r.scene.name(),
+            format!("{}x{}", r.dim.x, r.dim.y),
+            r.steps,
+            per_step(r.timings.manipulate),
+            per_step(r.timings.integrate),
+            per_step(r.timings.solve),
+            per_step(r.timings.advect),
+            per_step(r.timings.total()),
+            r.throughput()
+        );
+    }
+
+    return out;
+}
@@ -0,0 +1,44 @@
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+
+/// The condition on a side of a grid (see `Grid::set_boundary`). It decides whether the
+/// border cells of the side are solid and is applied to them after the body forces of
+/// each step (see `Grid::apply_boundary`), before the projection.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundaryCondition {
+    /// A wall: the border cells are solid.
+    Solid,
+
+    /// A wall through which the fluid enters: the border cells are solid and the faces
+    /// of the side keep the normal component of `velocity`.
+    Inflow { velocity: Vector2 },
+
+    /// The fluid leaves freely: the border cells are fluid with zero pressure and take
+    /// the velocity of the inside cells (zero gradient).
+    #[default]
+    Outflow,
+
+    /// The fluid leaves on this side and enters on the opposite one, which must be
+    /// periodic too. The border cells are ghost cells of the inside cells on the opposite
+    /// side and the faces of both sides take their mean. As for the couplings between
+    /// domains, the projection sees the other side with a lag of one step.
+    Periodic,
+
+    /// Ambient fluid at rest: the border cells are fluid with zero pressure, no velocity
+    /// and no smoke. The fluid leaves or enters through the faces of the side.
+    Open,
+}
+
+impl BoundaryCondition {
+    /// The border cells of a side with this condition are solid.
+    pub fn is_solid(&self) -> bool {
+        return matches!(self, BoundaryCondition::Solid | BoundaryCondition::Inflow { .. });
+    }
+
+    /// The condition needs the CPU backend (the GPU extrapolates all fluid border cells).
+    pub fn needs_cpu(&self) -> bool {
+        return matches!(self, BoundaryCondition::Periodic | BoundaryCondition::Open);
+    }
+}
@@ -1,15 +1,17 @@
 use crate::types::*;
 use crate::log::Logger;
+use crate::scene::solver_params::SolverParams;
 use crate::scene::timestepper::Integrate;
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CellTypes {
     Solid,
     Fluid,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Cell {
     /// The index of the cell.
     index: Index2,
@@ -25,8 +27,9 @@ pub struct Cell {
     /// The pressure value.
     pub pressure: Scalar,
 
-    /// The advected smoke value in `[0,1]`.
-    pub smoke: FrontBackBuffer<Scalar>,
+    /// The advected smoke value in `[0,1]` (in half precision with feature `f16-smoke`).
+    /// Use `smoke()` and `set_smoke()` to access it in full precision.
+    pub smoke: FrontBackBuffer<RenderScalar>,
 
     /// The divergence in the cell.
     /// Corresponds to the net-outflow.
@@ -49,7 +52,7 @@ impl Cell {
     pub fn new(index: Index2) -> Self {
         let default_vel = Vector2::from_element(0.0);
         let default_pressure = 0.0;
-        let default_smoke = 0.0;
+        let default_smoke = RenderScalar::store(0.0);
 
         return Cell {
             index,
@@ -72,13 +75,53 @@ impl Cell {
     pub fn index(&self) -> Index2 {
         return self.index;
     }
+
+    /// The current smoke value.
+    #[inline(always)]
+    pub fn smoke(&self) -> Scalar {
+        return self.smoke.back.load();
+    }
+
+    #[inline(always)]
+    pub fn set_smoke(&mut self, smoke: Scalar) {
+        self.smoke.back = RenderScalar::store(smoke);
+    }
+
+    /// The names and sizes in bytes of the fields of a cell.
+    /// The last entry is the padding of the struct.
+    pub fn field_sizes() -> Vec<(&'static str, usize)> {
+        use std::mem::size_of;
+
+        let mut fields = vec![
+            ("index", size_of::<Index2>()),
+            ("mode", size_of::<CellTypes>()),
+            ("velocity", size_of::<FrontBackBuffer<Vector2>>()),
+            ("pressure", size_of::<Scalar>()),
+            ("smoke", size_of::<FrontBackBuffer<RenderScalar>>()),
+            ("div", size_of::<Scalar>()),
+            ("s_tot_inv", size_of::<Scalar>()),
+            ("s_nbs", size_of::<[Vector2; 2]>()),
+        ];
+        let used: usize = fields.iter().map(|f| f.1).sum();
+        fields.push(("padding", size_of::<Cell>() - used));
+
+        return fields;
+    }
+
+    /// A copy of the cell with the new index `index`.
+    pub fn moved_to(&self, index: Index2) -> Cell {
+        return Cell {
+            index,
+            ..self.clone()
+        };
+    }
 }
 
 impl Integrate for Cell {
-    fn integrate(&mut self, _log: &Logger, dt: Scalar, gravity: Vector2) {
+    fn integrate(&mut self, _log: &Logger, dt: Scalar, params: &SolverParams) {
         self.velocity.back = match self.mode {
             CellTypes::Solid => self.velocity.back,
-            CellTypes::Fluid => self.velocity.back + dt * gravity,
+            CellTypes::Fluid => self.velocity.back + dt * params.gravity,
         };
     }
 
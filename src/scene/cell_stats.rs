@@ -1,7 +1,8 @@
 use crate::scene::cell::*;
 use crate::types::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Stats {
     pub velocity: Vector2,
     pub velocity_norm: Scalar,
@@ -12,7 +13,7 @@ pub struct Stats {
 
 impl Stats {
     pub fn identity<const I: usize>() -> Stats {
-        let init = if I == 0 { std::f64::MAX } else { std::f64::MIN };
+        let init = if I == 0 { Scalar::MAX } else { Scalar::MIN };
         let init_vec2 = Vector2::from_element(init);
 
         return Stats {
@@ -29,13 +30,13 @@ impl Stats {
             velocity: cell.velocity.back,
             velocity_norm: cell.velocity.back.norm(),
             pressure: cell.pressure,
-            smoke: cell.smoke.back,
+            smoke: cell.smoke(),
             div: cell.div,
         };
     }
 
     pub fn accumulate<const I: usize>(&self, stats: &Stats) -> Stats {
-        const MIN_MAX: [fn(f64, f64) -> f64; 2] = [Scalar::min, Scalar::max];
+        const MIN_MAX: [fn(Scalar, Scalar) -> Scalar; 2] = [Scalar::min, Scalar::max];
         const MIN_MAX_V2: [fn(&Vector2, &Vector2) -> Vector2; 2] = [Vector2::inf, Vector2::sup];
 
         return Stats {
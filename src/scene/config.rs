@@ -0,0 +1,1091 @@
+use crate::io::output::{ExportRegion, OutputInterval, OverwritePolicy};
+use crate::scene::boundary::BoundaryCondition;
+use crate::scene::integrator::Integrator;
+use crate::scene::profile::VelocityProfile;
+use crate::scene::timestepper::{Backend, ExecutionMode, GpuSchedule};
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Key listing files (relative to the including file) which are merged
+/// before the content of the including file.
+pub const INCLUDE_KEY: &str = "include";
+
+/// A scene description which can be loaded from TOML or YAML.
+/// All sections are optional and fall back to the default channel scene,
+/// except obstacles and emitters which default to none.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneConfig {
+    pub grid: GridConfig,
+    pub solver: SolverConfig,
+    pub time: TimeConfig,
+    pub boundary: BoundaryConfig,
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleConfig>,
+    #[serde(default)]
+    pub emitters: Vec<EmitterConfig>,
+    #[serde(default)]
+    pub masks: Vec<MaskConfig>,
+
+    /// The initial smoke and velocity in regions.
+    #[serde(default)]
+    pub initial: Vec<InitialConfig>,
+    #[serde(default)]
+    pub probes: Vec<ProbeConfig>,
+
+    /// The cross-sections whose flux is measured each step.
+    #[serde(default)]
+    pub sections: Vec<SectionConfig>,
+    #[serde(default)]
+    pub scripts: Vec<ScriptConfig>,
+
+    /// More grids next to `grid` (domain `0`), the domains `1, 2, ...`.
+    #[serde(default)]
+    pub domains: Vec<DomainConfig>,
+
+    /// The openings between the domains.
+    #[serde(default)]
+    pub couplings: Vec<CouplingConfig>,
+    pub fields: FieldsConfig,
+    pub output: OutputConfig,
+
+    /// The seed of all stochastic features (see `scene::rng::Rng`).
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GridConfig {
+    /// Number of cells in `x` and `y` (without the border cells).
+    pub dim: [usize; 2],
+
+    /// The cell width. Defaults to `1 / dim.y` (domain height `1.0`).
+    pub cell_width: Option<Scalar>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SolverConfig {
+    pub density: Scalar,
+    pub gravity: [Scalar; 2],
+    pub iterations: u64,
+    pub execution_mode: ExecutionMode,
+    pub over_relaxation: Scalar,
+    pub vorticity_confinement: Scalar,
+
+    /// Number of worker threads for the parallel execution modes (`0`: all cores).
+    pub threads: usize,
+
+    /// Pin the worker threads to these cores (round-robin, empty: no pinning).
+    /// With `threads = 0` one worker per listed core is started.
+    pub cores: Vec<usize>,
+
+    /// Compute on the `Cpu`, the `Gpu` or with `Cuda` (falls back to the CPU if not available).
+    pub backend: Backend,
+
+    /// The split of the steps between the CPU and the GPU with backend `Gpu`.
+    pub gpu_schedule: GpuSchedule,
+
+    /// Skip the blocks of cells which change less than this per step (`0`: disabled),
+    /// see `Grid::set_activity_threshold`.
+    pub activity_threshold: Scalar,
+
+    /// Advect the smoke only around the cells with more smoke than this (`0`: disabled),
+    /// see `Grid::set_smoke_threshold`.
+    pub smoke_threshold: Scalar,
+
+    /// Advect before the projection and add the body force in the same pass
+    /// (see `TimeStepper::set_fused_integration`).
+    pub fused_integration: bool,
+
+    /// Precompute the stencils of the `Single` pressure solve once per step
+    /// (see `Grid::set_stencil_table`).
+    pub stencil_table: bool,
+
+    /// The integrator of the body forces (see `Grid::set_integrator`).
+    pub integrator: Integrator,
+
+    /// The integrator of the departure points of the advection (see `Grid::set_backtrace`).
+    pub backtrace: Integrator,
+
+    /// Bit-identical results for any number of threads (see `Grid::set_deterministic`).
+    pub deterministic: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeConfig {
+    pub dt: Scalar,
+    pub end: Scalar,
+}
+
+/// The boundary conditions of the domain sides, e.g. `"solid"`, `"outflow"`,
+/// `"periodic"`, `"open"` or `{ inflow = { velocity = [2.0, 0.0] } }`. For older scenes
+/// `true` is `"solid"` and `false` is `"outflow"`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoundaryConfig {
+    #[serde(deserialize_with = "deserialize_condition")]
+    pub left: BoundaryCondition,
+    #[serde(deserialize_with = "deserialize_condition")]
+    pub right: BoundaryCondition,
+    #[serde(deserialize_with = "deserialize_condition")]
+    pub bottom: BoundaryCondition,
+    #[serde(deserialize_with = "deserialize_condition")]
+    pub top: BoundaryCondition,
+
+    /// An inflow on the left side if it is solid (the shorthand of older scenes).
+    pub inflow_velocity: Option<VelocityConfig>,
+
+    /// The inflows whose velocity varies over time. They replace the conditions of
+    /// their sides.
+    pub inflows: Vec<InflowConfig>,
+}
+
+/// An inflow on `side` with a velocity which may vary over time, e.g.
+/// `{ side = "left", velocity = { sine = { amplitude = [1.0, 0.0], frequency = 0.5 } } }`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InflowConfig {
+    pub side: Side,
+    pub velocity: VelocityConfig,
+}
+
+impl BoundaryConfig {
+    /// The condition of `side` as given, without the inflows.
+    fn given(&self, side: Side) -> BoundaryCondition {
+        return match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Bottom => self.bottom,
+            Side::Top => self.top,
+        };
+    }
+
+    /// The velocity of the inflow on `side`, from `inflows` or `inflow_velocity`.
+    pub fn inflow(&self, side: Side) -> Option<&VelocityConfig> {
+        let inflow = self.inflows.iter().rev().find(|i| i.side == side);
+        return match (inflow, side, self.given(side)) {
+            (Some(i), _, _) => Some(&i.velocity),
+            (None, Side::Left, BoundaryCondition::Solid) => self.inflow_velocity.as_ref(),
+            _ => None,
+        };
+    }
+
+    /// The condition of `side` at the start with the inflows applied.
+    pub fn condition(&self, side: Side) -> BoundaryCondition {
+        return match self.inflow(side) {
+            Some(v) => BoundaryCondition::Inflow {
+                velocity: VelocityProfile::from_config(v).at(0.0),
+            },
+            None => self.given(side),
+        };
+    }
+}
+
+/// A velocity which may vary over time (see `scene::profile::VelocityProfile`):
+/// `[x, y]`, `{ sine = { amplitude = [x, y], frequency = f, phase = p, offset = [x, y] } }`
+/// or `{ table = [[t, x, y], ...] }` with increasing `t`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VelocityConfig {
+    Constant([Scalar; 2]),
+    Profile(VelocityProfileConfig),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum VelocityProfileConfig {
+    Sine {
+        amplitude: [Scalar; 2],
+        frequency: Scalar,
+        #[serde(default)]
+        phase: Scalar,
+        #[serde(default)]
+        offset: [Scalar; 2],
+    },
+    /// The rows `[t, x, y]`.
+    Table(Vec<[Scalar; 3]>),
+}
+
+impl VelocityConfig {
+    pub fn validate(&self) -> SimpleResult<()> {
+        match self {
+            VelocityConfig::Constant(v) => {
+                if !v.iter().all(|x| x.is_finite()) {
+                    bail!("Velocity is not finite: {:?}", v);
+                }
+            }
+            VelocityConfig::Profile(VelocityProfileConfig::Sine {
+                amplitude,
+                frequency,
+                phase,
+                offset,
+            }) => {
+                let values = amplitude.iter().chain(offset.iter());
+                if !values.chain([frequency, phase]).all(|x| x.is_finite()) {
+                    bail!("Velocity sine is not finite: {:?}", self);
+                }
+            }
+            VelocityConfig::Profile(VelocityProfileConfig::Table(rows)) => {
+                if rows.is_empty() {
+                    bail!("Velocity table is empty.");
+                }
+
+                if !rows.iter().flatten().all(|x| x.is_finite()) {
+                    bail!("Velocity table is not finite: {:?}", rows);
+                }
+
+                if rows.windows(2).any(|w| w[0][0] >= w[1][0]) {
+                    bail!("Velocity table times must increase: {:?}", rows);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// A boundary condition or a bool (`true`: solid, `false`: outflow).
+fn deserialize_condition<'de, D>(deserializer: D) -> Result<BoundaryCondition, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Wall(bool),
+        Condition(BoundaryCondition),
+    }
+
+    return Ok(match Value::deserialize(deserializer)? {
+        Value::Wall(true) => BoundaryCondition::Solid,
+        Value::Wall(false) => BoundaryCondition::Outflow,
+        Value::Condition(c) => c,
+    });
+}
+
+/// A circular obstacle in world coordinates, or any shape with `region`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObstacleConfig {
+    #[serde(default)]
+    pub center: [Scalar; 2],
+    #[serde(default)]
+    pub radius: Scalar,
+
+    /// The shape instead of the circle.
+    #[serde(default)]
+    pub region: Option<RegionConfig>,
+
+    /// The velocity of the solid cells, constant or varying over time.
+    #[serde(default)]
+    pub velocity: Option<VelocityConfig>,
+}
+
+impl ObstacleConfig {
+    /// The shape of the obstacle.
+    pub fn shape(&self) -> RegionConfig {
+        return self.region.clone().unwrap_or(RegionConfig::Circle {
+            center: self.center,
+            radius: self.radius,
+        });
+    }
+}
+
+/// A shape in world coordinates (see `scene::region::Region`), e.g.
+/// `{ circle = { center = [0.5, 0.5], radius = 0.1 } }`. A `mask` is an image stretched
+/// over the inside cells of the grid with the solid pixels (see `MaskConfig`) inside.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum RegionConfig {
+    Box {
+        min: [Scalar; 2],
+        max: [Scalar; 2],
+    },
+    Circle {
+        center: [Scalar; 2],
+        radius: Scalar,
+    },
+    Polygon {
+        points: Vec<[Scalar; 2]>,
+    },
+    Mask {
+        file: String,
+        #[serde(default = "default_threshold")]
+        threshold: Scalar,
+        #[serde(default)]
+        invert: bool,
+    },
+    Union {
+        regions: Vec<RegionConfig>,
+    },
+    Intersection {
+        regions: Vec<RegionConfig>,
+    },
+    /// The points of `region` which are not in `minus`.
+    Difference {
+        region: Box<RegionConfig>,
+        minus: Box<RegionConfig>,
+    },
+}
+
+impl RegionConfig {
+    pub fn validate(&self) -> SimpleResult<()> {
+        match self {
+            RegionConfig::Box { min, max } => {
+                if min[0] >= max[0] || min[1] >= max[1] {
+                    bail!("Region box [{:?}, {:?}] is empty.", min, max);
+                }
+            }
+            RegionConfig::Circle { radius, .. } => {
+                if *radius <= 0.0 {
+                    bail!("Region circle radius must be positive: {}", radius);
+                }
+            }
+            RegionConfig::Polygon { points } => {
+                if points.len() < 3 {
+                    bail!("Region polygon needs 3 points: {:?}", points);
+                }
+            }
+            RegionConfig::Mask { threshold, .. } => {
+                if *threshold < 0.0 || *threshold > 1.0 {
+                    bail!("Region mask threshold must be in [0, 1]: {}", threshold);
+                }
+            }
+            RegionConfig::Union { regions } | RegionConfig::Intersection { regions } => {
+                for r in regions.iter() {
+                    r.validate()?;
+                }
+            }
+            RegionConfig::Difference { region, minus } => {
+                region.validate()?;
+                minus.validate()?;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// A grid of its own with solid walls on all sides, e.g. a corridor next to the room of
+/// the main grid, with the cell width of the main grid. The obstacles are in the world
+/// coordinates of the domain (its lower-left corner is `(0, 0)`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DomainConfig {
+    /// Number of cells in `x` and `y` (without the border cells).
+    pub dim: [usize; 2],
+
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleConfig>,
+}
+
+/// A side of a domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+impl Side {
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Bottom, Side::Top];
+
+    /// The axis of the normal of the side (`0`: `x`).
+    pub fn axis(&self) -> usize {
+        return match self {
+            Side::Left | Side::Right => 0,
+            Side::Bottom | Side::Top => 1,
+        };
+    }
+
+    /// The side is at the lower end of its axis.
+    pub fn is_lower(&self) -> bool {
+        return matches!(self, Side::Left | Side::Bottom);
+    }
+
+    pub fn opposite(&self) -> Side {
+        return match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Bottom => Side::Top,
+            Side::Top => Side::Bottom,
+        };
+    }
+}
+
+/// An opening of `length` cells between the side `side` of the domain `from` and the
+/// opposite side of the domain `to`, through which the fluid and the smoke flow (see
+/// `scene::coupling::DomainCoupling`). The opening starts at the inside cell `offset`
+/// along the side of `from` and at `to_offset` along the side of `to`, e.g. the cells
+/// `y = offset..offset + length` for the side `right`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CouplingConfig {
+    pub from: usize,
+    pub to: usize,
+    pub side: Side,
+    pub offset: usize,
+    pub to_offset: usize,
+    pub length: usize,
+}
+
+/// A smoke emitter which sets the smoke in the cell index window `[min, max)` every step,
+/// or in the cells with their center in `region`. An emitter with smoke `0` is a sink.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterConfig {
+    #[serde(default)]
+    pub min: [usize; 2],
+    #[serde(default)]
+    pub max: [usize; 2],
+
+    /// The shape instead of the index window.
+    #[serde(default)]
+    pub region: Option<RegionConfig>,
+
+    #[serde(default = "default_smoke")]
+    pub smoke: Scalar,
+}
+
+fn default_smoke() -> Scalar {
+    return 1.0;
+}
+
+impl EmitterConfig {
+    /// The shape of the emitter on a grid with the cell width `cell_width`.
+    pub fn shape(&self, cell_width: Scalar) -> RegionConfig {
+        let corner = |i: [usize; 2]| [i[0] as Scalar * cell_width, i[1] as Scalar * cell_width];
+
+        return self.region.clone().unwrap_or(RegionConfig::Box {
+            min: corner(self.min),
+            max: corner(self.max),
+        });
+    }
+}
+
+/// Initial values of the fluid cells with their center in `region`, set after the
+/// initial fields.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InitialConfig {
+    pub region: RegionConfig,
+
+    #[serde(default)]
+    pub smoke: Option<Scalar>,
+
+    #[serde(default)]
+    pub velocity: Option<[Scalar; 2]>,
+}
+
+/// A Rhai script which runs before each step (feature `scripting`),
+/// from a file or inline (see `SceneScript`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptConfig {
+    #[serde(default)]
+    pub file: Option<String>,
+
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Solid cells from a black/white image stretched over the domain (without border cells).
+/// Dark pixels become solid.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaskConfig {
+    pub file: String,
+
+    /// Luminance in `[0, 1]` below which a pixel is solid.
+    #[serde(default = "default_threshold")]
+    pub threshold: Scalar,
+
+    /// Make bright pixels solid instead.
+    #[serde(default)]
+    pub invert: bool,
+
+    /// Resample the image to the grid resolution. Otherwise the sizes must match.
+    #[serde(default = "default_resample")]
+    pub resample: bool,
+}
+
+/// A named probe at `position` (world coordinates) or, with `size`,
+/// averaged over the region `[position, position + size]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeConfig {
+    pub name: String,
+    pub position: [Scalar; 2],
+
+    #[serde(default)]
+    pub size: Option<[Scalar; 2]>,
+}
+
+impl std::str::FromStr for ProbeConfig {
+    type Err = String;
+
+    /// Parse `name=x,y` or `name=x,y,width,height`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, values) = s
+            .split_once('=')
+            .ok_or(format!("Probe '{}' is not 'name=x,y[,width,height]'.", s))?;
+
+        let v = values
+            .split(',')
+            .map(|v| v.trim().parse::<Scalar>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Probe '{}': {}", s, e))?;
+
+        return match v.len() {
+            2 | 4 => Ok(ProbeConfig {
+                name: name.trim().to_string(),
+                position: [v[0], v[1]],
+                size: (v.len() == 4).then(|| [v[2], v[3]]),
+            }),
+            _ => Err(format!("Probe '{}' needs 2 or 4 values.", s)),
+        };
+    }
+}
+
+/// A named line segment from `start` to `end` (world coordinates) through which the
+/// flux of the fluid and of the smoke is measured (see `scene::flux::CrossSection`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionConfig {
+    pub name: String,
+    pub start: [Scalar; 2],
+    pub end: [Scalar; 2],
+}
+
+impl std::str::FromStr for SectionConfig {
+    type Err = String;
+
+    /// Parse `name=x0,y0,x1,y1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, values) = s
+            .split_once('=')
+            .ok_or(format!("Section '{}' is not 'name=x0,y0,x1,y1'.", s))?;
+
+        let v = values
+            .split(',')
+            .map(|v| v.trim().parse::<Scalar>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Section '{}': {}", s, e))?;
+
+        return match v.len() {
+            4 => Ok(SectionConfig {
+                name: name.trim().to_string(),
+                start: [v[0], v[1]],
+                end: [v[2], v[3]],
+            }),
+            _ => Err(format!("Section '{}' needs 4 values.", s)),
+        };
+    }
+}
+
+/// Initial fields loaded from grayscale images or data files stretched over
+/// the domain (without border cells).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldsConfig {
+    pub smoke: Option<FieldImageConfig>,
+    pub velocity: Option<VelocityFieldConfig>,
+}
+
+/// An initial cell-centered velocity field from a `.npy` or `.csv` file
+/// (see `io::velocity::load_velocity`), multiplied by `scale`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VelocityFieldConfig {
+    pub file: String,
+
+    #[serde(default = "default_field_scale")]
+    pub scale: Scalar,
+
+    /// Resample the field to the grid resolution. Otherwise the sizes must match.
+    #[serde(default = "default_resample")]
+    pub resample: bool,
+}
+
+/// A field initialized with `offset + scale * luminance` of an image.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldImageConfig {
+    pub file: String,
+
+    #[serde(default = "default_field_scale")]
+    pub scale: Scalar,
+
+    #[serde(default)]
+    pub offset: Scalar,
+
+    /// Use `1 - luminance` (dark pixels are high values).
+    #[serde(default)]
+    pub invert: bool,
+
+    /// Resample the image to the grid resolution. Otherwise the sizes must match.
+    #[serde(default = "default_resample")]
+    pub resample: bool,
+}
+
+fn default_field_scale() -> Scalar {
+    return 1.0;
+}
+
+fn default_threshold() -> Scalar {
+    return 0.5;
+}
+
+fn default_resample() -> bool {
+    return true;
+}
+
+/// Output settings. Values given on the command line take precedence.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub frames: Option<String>,
+    pub vtk: Option<String>,
+    pub render: Option<String>,
+    pub video: Option<String>,
+    pub checkpoint: Option<String>,
+
+    /// E.g. `{ steps = 10 }` or `{ time = 0.1 }`.
+    pub interval: Option<OutputInterval>,
+    pub overwrite: Option<OverwritePolicy>,
+
+    /// E.g. `{ cells = { min = [10, 10], max = [60, 40] } }`
+    /// or `{ world = { min = [0.5, 0.2], max = [1.5, 0.8] } }`.
+    pub region: Option<ExportRegion>,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        return GridConfig {
+            dim: [200, 100],
+            cell_width: None,
+        };
+    }
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        return SolverConfig {
+            density: 1000.0,
+            gravity: [0.0, 0.0],
+            iterations: 100,
+            execution_mode: ExecutionMode::Single,
+            over_relaxation: 1.9,
+            vorticity_confinement: 0.0,
+            threads: 0,
+            cores: vec![],
+            backend: Backend::Cpu,
+            gpu_schedule: GpuSchedule::All,
+            activity_threshold: 0.0,
+            smoke_threshold: 0.0,
+            fused_integration: false,
+            stencil_table: false,
+            integrator: Integrator::Euler,
+            backtrace: Integrator::Euler,
+            deterministic: false,
+        };
+    }
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        return TimeConfig { dt: 0.016, end: 5.0 };
+    }
+}
+
+impl Default for BoundaryConfig {
+    fn default() -> Self {
+        return BoundaryConfig {
+            left: BoundaryCondition::Solid,
+            right: BoundaryCondition::Outflow,
+            bottom: BoundaryCondition::Solid,
+            top: BoundaryCondition::Solid,
+            inflow_velocity: Some(VelocityConfig::Constant([2.0, 0.0])),
+            inflows: vec![],
+        };
+    }
+}
+
+impl Default for SceneConfig {
+    /// The channel flow around a cylinder (scene index `0`).
+    fn default() -> Self {
+        let grid = GridConfig::default();
+        return SceneConfig::channel(idx!(grid.dim[0], grid.dim[1]));
+    }
+}
+
+impl SceneConfig {
+    /// The channel flow around a cylinder with `dim` cells.
+    pub fn channel(dim: Index2) -> Self {
+        let height = 1.0;
+        let cell_width = height / dim.y as Scalar;
+        let width = dim.x as Scalar * cell_width;
+        let obstacle_size_rel = 0.3;
+
+        // The smoke bar on the left border (border cells included in `y`).
+        let dim_y = dim.y + 2;
+        let center = dim_y / 2;
+        let half = (1.1 * obstacle_size_rel * dim_y as Scalar) as usize / 2;
+
+        return SceneConfig {
+            grid: GridConfig {
+                dim: [dim.x, dim.y],
+                cell_width: None,
+            },
+            solver: SolverConfig::default(),
+            time: TimeConfig::default(),
+            boundary: BoundaryConfig::default(),
+            obstacles: vec![ObstacleConfig {
+                center: [width * 0.25, height * 0.5],
+                radius: obstacle_size_rel * height / 2.0,
+                region: None,
+                velocity: None,
+            }],
+            emitters: vec![EmitterConfig {
+                min: [0, center - half],
+                max: [1, center + half],
+                region: None,
+                smoke: 1.0,
+            }],
+            masks: vec![],
+            initial: vec![],
+            probes: vec![],
+            sections: vec![],
+            scripts: vec![],
+            domains: vec![],
+            couplings: vec![],
+            fields: FieldsConfig::default(),
+            output: OutputConfig::default(),
+            seed: 0,
+        };
+    }
+
+    pub fn dim(&self) -> Index2 {
+        return idx!(self.grid.dim[0], self.grid.dim[1]);
+    }
+
+    pub fn cell_width(&self) -> Scalar {
+        return self
+            .grid
+            .cell_width
+            .unwrap_or(1.0 / self.grid.dim[1] as Scalar);
+    }
+
+    /// The number of inside cells of domain `i` (`0`: the main grid).
+    pub fn domain_dim(&self, i: usize) -> Option<Index2> {
+        return match i {
+            0 => Some(self.dim()),
+            _ => self.domains.get(i - 1).map(|d| idx!(d.dim[0], d.dim[1])),
+        };
+    }
+
+    /// Load a scene from a `.toml`, `.yaml`, `.yml` or `.ron` file.
+    pub fn from_file(file: &str) -> GenericResult<Self> {
+        return SceneConfig::load(file, &[]);
+    }
+
+    /// Load a scene file, resolve its includes and apply the
+    /// overrides `key.path=value` (e.g. `solver.iterations=80`).
+    pub fn load(file: &str, overrides: &[String]) -> GenericResult<Self> {
+        let mut value = load_value(Path::new(file), &mut vec![])
+            .map_err(|e| format!("Scene file '{}': {}", file, e))?;
+
+        apply_overrides(&mut value, overrides)?;
+        return SceneConfig::from_value(value);
+    }
+
+    /// Parse a scene from a string in the format given by the file extension `ext`.
+    /// Includes are not supported.
+    pub fn parse(content: &str, ext: &str) -> GenericResult<Self> {
+        return SceneConfig::from_value(parse_value(content, ext)?);
+    }
+
+    /// Apply overrides `key.path=value` to this configuration.
+    pub fn with_overrides(self, overrides: &[String]) -> GenericResult<Self> {
+        let mut value = serde_json::to_value(&self)?;
+        apply_overrides(&mut value, overrides)?;
+        return SceneConfig::from_value(value);
+    }
+
+    fn from_value(value: Value) -> GenericResult<Self> {
+        let config: SceneConfig = serde_json::from_value(value)?;
+        config.validate()?;
+        return Ok(config);
+    }
+
+    pub fn validate(&self) -> SimpleResult<()> {
+        if self.grid.dim[0] == 0 || self.grid.dim[1] == 0 {
+            bail!("Grid dimension must be positive: {:?}", self.grid.dim);
+        }
+
+        if self.cell_width() <= 0.0 {
+            bail!("Cell width must be positive: {}", self.cell_width());
+        }
+
+        if self.time.dt <= 0.0 {
+            bail!("Timestep must be positive: {}", self.time.dt);
+        }
+
+        if self.solver.over_relaxation <= 0.0 || self.solver.over_relaxation >= 2.0 {
+            bail!(
+                "Overrelaxation must be in (0, 2): {}",
+                self.solver.over_relaxation
+            );
+        }
+
+        if self.solver.density <= 0.0 {
+            bail!("Density must be positive: {}", self.solver.density);
+        }
+
+        if self.solver.deterministic && self.solver.backend != Backend::Cpu {
+            bail!("Deterministic mode needs the backend 'Cpu'.");
+        }
+
+        for i in self.boundary.inflows.iter() {
+            if self.boundary.given(i.side) == BoundaryCondition::Periodic {
+                bail!("Boundary {:?} is periodic and an inflow.", i.side);
+            }
+        }
+
+        for side in Side::ALL {
+            let condition = self.boundary.condition(side);
+            let opposite = self.boundary.condition(side.opposite());
+            let is_periodic = |c: BoundaryCondition| c == BoundaryCondition::Periodic;
+            if is_periodic(condition) && !is_periodic(opposite) {
+                bail!("Boundary {:?} is periodic but not the opposite side.", side);
+            }
+
+            if is_periodic(condition) && self.solver.activity_threshold > 0.0 {
+                bail!("Periodic boundaries need the activity threshold 0.");
+            }
+
+            if condition.needs_cpu() && self.solver.backend != Backend::Cpu {
+                bail!("Boundary {:?} ({:?}) needs the backend 'Cpu'.", side, condition);
+            }
+
+            if let Some(v) = self.boundary.inflow(side) {
+                v.validate()?;
+            }
+        }
+
+        for m in self.masks.iter() {
+            if m.threshold < 0.0 || m.threshold > 1.0 {
+                bail!("Mask threshold must be in [0, 1]: {}", m.threshold);
+            }
+        }
+
+        for (i, p) in self.probes.iter().enumerate() {
+            if p.name.is_empty() || p.name.contains(',') {
+                bail!("Probe name '{}' must be non-empty without commas.", p.name);
+            }
+
+            if self.probes[..i].iter().any(|o| o.name == p.name) {
+                bail!("Probe name '{}' is not unique.", p.name);
+            }
+
+            if let Some(s) = p.size {
+                if s[0] <= 0.0 || s[1] <= 0.0 {
+                    bail!("Probe '{}' size must be positive: {:?}", p.name, s);
+                }
+            }
+        }
+
+        for (i, c) in self.sections.iter().enumerate() {
+            if c.name.is_empty() || c.name.contains(',') {
+                bail!("Section name '{}' must be non-empty without commas.", c.name);
+            }
+
+            if self.sections[..i].iter().any(|o| o.name == c.name) {
+                bail!("Section name '{}' is not unique.", c.name);
+            }
+
+            if c.start == c.end {
+                bail!("Section '{}' has no length: {:?}", c.name, c.start);
+            }
+        }
+
+        for e in self.emitters.iter() {
+            match e.region {
+                Some(ref r) => r.validate()?,
+                None if e.min[0] >= e.max[0] || e.min[1] >= e.max[1] => {
+                    bail!("Emitter range [{:?}, {:?}) is empty.", e.min, e.max);
+                }
+                None => {}
+            }
+        }
+
+        let obstacles = self.domains.iter().flat_map(|d| d.obstacles.iter());
+        for o in self.obstacles.iter().chain(obstacles) {
+            o.shape().validate()?;
+            if let Some(ref v) = o.velocity {
+                v.validate()?;
+            }
+        }
+
+        for i in self.initial.iter() {
+            i.region.validate()?;
+        }
+
+        for s in self.scripts.iter() {
+            if s.file.is_some() == s.source.is_some() {
+                bail!("Script needs either a 'file' or a 'source'.");
+            }
+        }
+
+        for d in self.domains.iter() {
+            if d.dim[0] == 0 || d.dim[1] == 0 {
+                bail!("Domain size must be non-zero: {:?}", d.dim);
+            }
+        }
+
+        // The sweeps of the parallel modes need pairs of cells (see `Grid::check_execution_mode`).
+        let mode = self.solver.execution_mode;
+        if matches!(mode, ExecutionMode::Parallel | ExecutionMode::ParallelUnsafe) {
+            for dim in (0..=self.domains.len()).filter_map(|i| self.domain_dim(i)) {
+                if dim.x % 2 != 0 || dim.y % 2 != 0 {
+                    bail!("Execution mode '{:?}' needs even grid dimensions: {}", mode, dim);
+                }
+            }
+        }
+
+        if !self.domains.is_empty() && self.solver.backend != Backend::Cpu {
+            bail!("Several domains need the backend 'Cpu'.");
+        }
+
+        for c in self.couplings.iter() {
+            let (from, to) = match (self.domain_dim(c.from), self.domain_dim(c.to)) {
+                (Some(from), Some(to)) if c.from != c.to => (from, to),
+                _ => bail!("Coupling of the domains {} and {} is invalid.", c.from, c.to),
+            };
+
+            // The number of cells along the side.
+            let along = |dim: Index2| match c.side {
+                Side::Left | Side::Right => dim.y,
+                Side::Bottom | Side::Top => dim.x,
+            };
+
+            if c.length == 0
+                || c.offset + c.length > along(from)
+                || c.to_offset + c.length > along(to)
+            {
+                bail!(
+                    "Coupling of the domains {} and {} is outside of their sides.",
+                    c.from,
+                    c.to
+                );
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+fn parse_value(content: &str, ext: &str) -> GenericResult<Value> {
+    return Ok(match ext {
+        "toml" => toml::from_str(content)?,
+        "yaml" | "yml" => serde_yaml::from_str(content)?,
+        "ron" => ron::from_str(content)?,
+        _ => return Err(format!("Unknown scene format '{}'.", ext).into()),
+    });
+}
+
+/// Load a file into a generic value and merge all its includes recursively.
+/// The `stack` guards against cyclic includes.
+fn load_value(file: &Path, stack: &mut Vec<std::path::PathBuf>) -> GenericResult<Value> {
+    let canonical = file
+        .canonicalize()
+        .map_err(|e| format!("Could not read '{}': {}", file.display(), e))?;
+
+    if stack.contains(&canonical) {
+        return Err(format!("Cyclic include of '{}'.", file.display()).into());
+    }
+
+    let content = std::fs::read_to_string(file)?;
+    let ext = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    let mut value = parse_value(&content, ext.as_deref().unwrap_or(""))?;
+
+    let includes = match value.as_object_mut().and_then(|o| o.remove(INCLUDE_KEY)) {
+        None => vec![],
+        Some(Value::String(s)) => vec![s],
+        Some(Value::Array(a)) => a
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                _ => Err(format!("Include '{}' is not a string.", v)),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(v) => return Err(format!("Include '{}' is not a string or list.", v).into()),
+    };
+
+    stack.push(canonical);
+
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(Default::default());
+    for include in includes {
+        merge(&mut merged, load_value(&dir.join(include), stack)?);
+    }
+    merge(&mut merged, value);
+
+    stack.pop();
+
+    return Ok(merged);
+}
+
+/// Deep merge `other` into `value`. Maps are merged, everything else is replaced.
+fn merge(value: &mut Value, other: Value) {
+    match (value, other) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, v) in b {
+                merge(a.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (a, b) => *a = b,
+    }
+}
+
+/// Apply overrides `key.path=value`. The value is parsed as JSON
+/// (numbers, booleans, lists) and otherwise taken as a string.
+/// Numeric path segments index into lists.
+fn apply_overrides(value: &mut Value, overrides: &[String]) -> GenericResult<()> {
+    for o in overrides {
+        let (path, v) = o
+            .split_once('=')
+            .ok_or_else(|| format!("Override '{}' needs the form 'key.path=value'.", o))?;
+
+        let v = serde_json::from_str::<Value>(v.trim())
+            .unwrap_or_else(|_| Value::String(v.trim().to_string()));
+
+        let mut curr = &mut *value;
+        for key in path.trim().split('.') {
+            curr = match curr {
+                Value::Array(a) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| a.get_mut(i))
+                    .ok_or_else(|| format!("Override '{}': no list entry '{}'.", o, key))?,
+                Value::Object(m) => m.entry(key).or_insert(Value::Object(Default::default())),
+                _ => return Err(format!("Override '{}': '{}' is not a section.", o, key).into()),
+            };
+        }
+
+        *curr = v;
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,164 @@
+use crate::log::{debug, warn, Logger};
+use crate::scene::cell::CellTypes;
+use crate::scene::config::{CouplingConfig, Side};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::timestepper::{Integrate, Manipulator};
+use crate::types::*;
+
+use std::any::Any;
+
+/// An opening between two domains (grids of the objects of a timestepper) through
+/// which the fluid and the smoke flow: the right (top) side of the domain `lower` and
+/// the left (bottom) side of the domain `upper`.
+///
+/// The border cells of both sides are fluid along the opening and act as ghost cells:
+/// before each step they take the velocity and the smoke of the first inside cells of
+/// the other domain, and the velocity on the shared faces is set to the mean of both
+/// domains. Each domain is projected on its own, so the coupling is explicit with a lag
+/// of one step, which is small for openings much wider than a cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DomainCoupling {
+    pub lower: usize,
+    pub upper: usize,
+
+    /// `0`: the opening is vertical (`lower` on the left), `1`: horizontal.
+    pub axis: usize,
+
+    /// The first inside cell of the opening along the side of each domain.
+    pub lower_offset: usize,
+    pub upper_offset: usize,
+    pub length: usize,
+}
+
+impl DomainCoupling {
+    pub fn from(config: &CouplingConfig) -> Self {
+        let axis = match config.side {
+            Side::Left | Side::Right => 0,
+            Side::Bottom | Side::Top => 1,
+        };
+
+        let (from, to) = ((config.from, config.offset), (config.to, config.to_offset));
+        let (lower, upper) = match config.side {
+            Side::Right | Side::Top => (from, to),
+            Side::Left | Side::Bottom => (to, from),
+        };
+
+        return DomainCoupling {
+            lower: lower.0,
+            upper: upper.0,
+            axis,
+            lower_offset: lower.1,
+            upper_offset: upper.1,
+            length: config.length,
+        };
+    }
+
+    /// The cell at `normal` across and `tangent` along the side.
+    fn index(&self, normal: usize, tangent: usize) -> Index2 {
+        return if self.axis == 0 {
+            idx!(normal, tangent)
+        } else {
+            idx!(tangent, normal)
+        };
+    }
+
+    /// The border cells of the opening in `lower` and `upper` and the inside cells
+    /// next to them: `(ghost, inside)` for each cell along the opening.
+    fn cells(&self, lower: &Grid) -> Vec<[(Index2, Index2); 2]> {
+        let n = lower.dim[self.axis];
+
+        return (0..self.length)
+            .map(|k| {
+                let (l, u) = (self.lower_offset + 1 + k, self.upper_offset + 1 + k);
+                return [
+                    (self.index(n - 1, l), self.index(n - 2, l)),
+                    (self.index(0, u), self.index(1, u)),
+                ];
+            })
+            .collect();
+    }
+
+    /// Make the border cells of the opening fluid.
+    /// Fails if the domains are the same or not grids of `objects`.
+    pub fn open(&self, objects: &mut [Box<dyn Integrate>]) -> FluidResult<()> {
+        let (lower, upper) = grids_mut(objects, self.lower, self.upper)?;
+
+        for [(l, _), (u, _)] in self.cells(lower) {
+            lower.cell_mut(l).mode = CellTypes::Fluid;
+            upper.cell_mut(u).mode = CellTypes::Fluid;
+        }
+        return Ok(());
+    }
+
+    /// Copy the velocity and the smoke across the opening.
+    /// Fails if the domains are the same or not grids of `objects`.
+    pub fn exchange(&self, objects: &mut [Box<dyn Integrate>]) -> FluidResult<()> {
+        let (lower, upper) = grids_mut(objects, self.lower, self.upper)?;
+        let (a, t) = (self.axis, 1 - self.axis);
+
+        for [(lg, li), (ug, ui)] in self.cells(lower) {
+            // The shared face is the lower face of the ghost cell of `lower` and of the
+            // first inside cell of `upper`.
+            let faces = [lower.cell(lg).velocity.back[a], upper.cell(ui).velocity.back[a]];
+            let face = 0.5 * (faces[0] + faces[1]);
+
+            let (inside, smoke) = (upper.cell(ui).velocity.back, upper.cell(ui).smoke());
+            let ghost = lower.cell_mut(lg);
+            ghost.velocity.back[a] = face;
+            ghost.velocity.back[t] = inside[t];
+            ghost.set_smoke(smoke);
+
+            let (inside, smoke) = (lower.cell(li).velocity.back, lower.cell(li).smoke());
+            let ghost = upper.cell_mut(ug);
+            ghost.velocity.back = inside;
+            ghost.set_smoke(smoke);
+
+            upper.cell_mut(ui).velocity.back[a] = face;
+        }
+    }
+}
+
+impl Manipulator for DomainCoupling {
+    fn manipulate(
+        &self,
+        log: &Logger,
+        t: Scalar,
+        _dt: Scalar,
+        objects: &mut Vec<Box<dyn Integrate>>,
+    ) {
+        debug!(log, "Couple the domains {} and {} at {}.", self.lower, self.upper, t);
+        if let Err(e) = self.exchange(objects) {
+            warn!(log, "Domains {} and {} are not coupled: {}", self.lower, self.upper, e);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The grids `a` and `b` (`a != b`) of `objects`.
+fn grids_mut(
+    objects: &mut [Box<dyn Integrate>],
+    a: usize,
+    b: usize,
+) -> FluidResult<(&mut Grid, &mut Grid)> {
+    if a == b {
+        return Err(Error::Scene(format!("Domain {} is coupled to itself.", a)));
+    }
+    if a.max(b) >= objects.len() {
+        return Err(Error::NotAGrid { index: a.max(b) });
+    }
+
+    fn grid(o: &mut Box<dyn Integrate>, index: usize) -> FluidResult<&mut Grid> {
+        return o.as_any_mut().downcast_mut::<Grid>().ok_or(Error::NotAGrid { index });
+    }
+
+    let (low, high) = objects.split_at_mut(a.max(b));
+    let (first, second) = (&mut low[a.min(b)], &mut high[0]);
+    return if a < b {
+        Ok((grid(first, a)?, grid(second, b)?))
+    } else {
+        Ok((grid(second, a)?, grid(first, b)?))
+    };
+}
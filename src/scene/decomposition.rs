@@ -0,0 +1,542 @@
+use crate::log::{debug, Logger};
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::config::Side;
+use crate::scene::grid::{Grid, JacobiSync};
+use crate::scene::solver_params::SolverParams;
+use crate::scene::timestepper::Integrate;
+use crate::types::*;
+
+use std::ops::Range;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// The exchange of values between the ranks of a domain decomposition, e.g. the
+/// processes of an MPI job (`MpiCommunicator`, feature `mpi`) or threads of one
+/// process (`LocalCommunicator`).
+pub trait Communicator {
+    fn rank(&self) -> usize;
+    fn size(&self) -> usize;
+
+    /// Send `to_lower` to rank `rank - 1` and `to_upper` to rank `rank + 1` and return
+    /// the values received from them (empty for a missing neighbor).
+    /// Fails if a neighbor is disconnected.
+    fn exchange(
+        &mut self,
+        to_lower: &[Scalar],
+        to_upper: &[Scalar],
+    ) -> FluidResult<(Vec<Scalar>, Vec<Scalar>)>;
+
+    /// The values of all ranks in the order of the ranks on rank `0`, `None` on the others.
+    /// Fails if a rank is disconnected.
+    fn gather(&mut self, values: &[Scalar]) -> FluidResult<Option<Vec<Vec<Scalar>>>>;
+}
+
+/// The ranks of one process connected by channels, e.g. for tests or to run a
+/// decomposition on threads.
+pub struct LocalCommunicator {
+    rank: usize,
+    senders: Vec<Sender<(usize, Vec<Scalar>)>>,
+    inbox: Receiver<(usize, Vec<Scalar>)>,
+
+    /// Received messages of other ranks than the expected one.
+    pending: Vec<(usize, Vec<Scalar>)>,
+}
+
+impl LocalCommunicator {
+    /// The connected communicators of `size` ranks.
+    pub fn group(size: usize) -> Vec<LocalCommunicator> {
+        let (senders, inboxes): (Vec<_>, Vec<_>) = (0..size).map(|_| channel()).unzip();
+
+        return inboxes
+            .into_iter()
+            .enumerate()
+            .map(|(rank, inbox)| LocalCommunicator {
+                rank,
+                senders: senders.clone(),
+                inbox,
+                pending: vec![],
+            })
+            .collect();
+    }
+
+    fn disconnected(rank: usize) -> Error {
+        return Error::Other(format!("Rank {} is disconnected.", rank).into());
+    }
+
+    fn send(&self, to: usize, values: &[Scalar]) -> FluidResult<()> {
+        return self.senders[to]
+            .send((self.rank, values.to_vec()))
+            .map_err(|_| Self::disconnected(to));
+    }
+
+    fn receive(&mut self, from: usize) -> FluidResult<Vec<Scalar>> {
+        if let Some(i) = self.pending.iter().position(|(r, _)| *r == from) {
+            return Ok(self.pending.remove(i).1);
+        }
+
+        loop {
+            let (r, values) = self.inbox.recv().map_err(|_| Self::disconnected(from))?;
+            if r == from {
+                return Ok(values);
+            }
+            self.pending.push((r, values));
+        }
+    }
+}
+
+impl Communicator for LocalCommunicator {
+    fn rank(&self) -> usize {
+        return self.rank;
+    }
+
+    fn size(&self) -> usize {
+        return self.senders.len();
+    }
+
+    fn exchange(
+        &mut self,
+        to_lower: &[Scalar],
+        to_upper: &[Scalar],
+    ) -> FluidResult<(Vec<Scalar>, Vec<Scalar>)> {
+        let (lower, upper) = (self.rank > 0, self.rank + 1 < self.size());
+
+        if lower {
+            self.send(self.rank - 1, to_lower)?;
+        }
+        if upper {
+            self.send(self.rank + 1, to_upper)?;
+        }
+
+        let from_lower = if lower {
+            self.receive(self.rank - 1)?
+        } else {
+            vec![]
+        };
+        let from_upper = if upper {
+            self.receive(self.rank + 1)?
+        } else {
+            vec![]
+        };
+        return Ok((from_lower, from_upper));
+    }
+
+    fn gather(&mut self, values: &[Scalar]) -> FluidResult<Option<Vec<Vec<Scalar>>>> {
+        if self.rank != 0 {
+            self.send(0, values)?;
+            return Ok(None);
+        }
+
+        let mut all = vec![values.to_vec()];
+        for r in 1..self.size() {
+            all.push(self.receive(r)?);
+        }
+        return Ok(Some(all));
+    }
+}
+
+/// The processes of an MPI job (feature `mpi`), e.g. started with `mpirun -n 4`.
+#[cfg(feature = "mpi")]
+pub struct MpiCommunicator {
+    world: mpi::topology::SimpleCommunicator,
+}
+
+#[cfg(feature = "mpi")]
+impl MpiCommunicator {
+    /// The communicator of all processes. `universe` has to live until the end of the run.
+    pub fn new(universe: &mpi::environment::Universe) -> Self {
+        return MpiCommunicator {
+            world: universe.world(),
+        };
+    }
+}
+
+#[cfg(feature = "mpi")]
+impl Communicator for MpiCommunicator {
+    fn rank(&self) -> usize {
+        use mpi::topology::Communicator as _;
+        return self.world.rank() as usize;
+    }
+
+    fn size(&self) -> usize {
+        use mpi::topology::Communicator as _;
+        return self.world.size() as usize;
+    }
+
+    /// MPI aborts the job if a process fails, so the exchange always succeeds.
+    fn exchange(
+        &mut self,
+        to_lower: &[Scalar],
+        to_upper: &[Scalar],
+    ) -> FluidResult<(Vec<Scalar>, Vec<Scalar>)> {
+        use mpi::point_to_point::{Destination, Source};
+        use mpi::topology::Communicator as _;
+
+        let rank = self.world.rank();
+        let lower = (rank > 0).then(|| self.world.process_at_rank(rank - 1));
+        let upper = (rank + 1 < self.world.size()).then(|| self.world.process_at_rank(rank + 1));
+
+        return Ok(mpi::request::scope(|scope| {
+            let sends = [(&lower, to_lower), (&upper, to_upper)]
+                .into_iter()
+                .filter_map(|(p, v)| p.as_ref().map(|p| p.immediate_send(scope, v)))
+                .collect::<Vec<_>>();
+
+            let received = [&lower, &upper].map(|p| {
+                return p.as_ref().map_or(vec![], |p| p.receive_vec::<Scalar>().0);
+            });
+
+            for s in sends {
+                s.wait();
+            }
+            let [from_lower, from_upper] = received;
+            return (from_lower, from_upper);
+        }));
+    }
+
+    fn gather(&mut self, values: &[Scalar]) -> FluidResult<Option<Vec<Vec<Scalar>>>> {
+        use mpi::point_to_point::Destination;
+        use mpi::point_to_point::Source;
+        use mpi::topology::Communicator as _;
+
+        if self.world.rank() != 0 {
+            self.world.process_at_rank(0).send(values);
+            return Ok(None);
+        }
+
+        let mut all = vec![values.to_vec()];
+        for r in 1..self.world.size() {
+            all.push(self.world.process_at_rank(r).receive_vec::<Scalar>().0);
+        }
+        return Ok(Some(all));
+    }
+}
+
+/// The rows `[start, end)` of the grid of `rows` rows (including the border) owned by
+/// rank `rank` of `ranks` (contiguous slabs of nearly equal size).
+pub fn slab_rows(rows: usize, ranks: usize, rank: usize) -> Range<usize> {
+    let (n, rest) = (rows / ranks, rows % ranks);
+    let start = rank * n + rank.min(rest);
+    return start..start + n + (rank < rest) as usize;
+}
+
+/// The slab of a rank as rows of its local grid.
+#[derive(Clone, Debug)]
+struct Slab {
+    /// The owned rows.
+    owned: Range<usize>,
+    halo: usize,
+
+    /// The number of local rows.
+    rows: usize,
+}
+
+impl Slab {
+    /// The owned rows sent to the lower and the upper neighbor.
+    fn send_rows(&self) -> (Range<usize>, Range<usize>) {
+        let lower = if self.owned.start > 0 { self.halo } else { 0 };
+        let upper = if self.owned.end < self.rows {
+            self.halo
+        } else {
+            0
+        };
+
+        let (start, end) = (self.owned.start, self.owned.end);
+        return (start..start + lower, end - upper..end);
+    }
+
+    /// The halo rows received from the lower and the upper neighbor.
+    fn halo_rows(&self) -> (Range<usize>, Range<usize>) {
+        return (0..self.owned.start, self.owned.end..self.rows);
+    }
+}
+
+/// The slab of rows of a grid computed by one rank of a domain decomposition.
+///
+/// The grid is split into slabs of rows (`slab_rows`). Each rank holds its rows and
+/// `halo` rows of each neighbor, which are exchanged before the integration, the
+/// pressure solve and the advection, and in each iteration of the pressure solve.
+/// The pressure solve is the weighted Jacobi solve (`ExecutionMode::Jacobi`), whose
+/// iterations give the same result as on the whole grid. The advection is exact as
+/// long as no departure point is more than `halo - 1` cells away, i.e. for a halo of
+/// at least the CFL number plus two. Only the first and the last slab keep the bottom
+/// and the top boundary condition of the grid, the other ones are halo rows.
+/// Manipulators, force fields, hooks, active regions and periodic bottom and top sides
+/// are not supported.
+pub struct Subdomain<C: Communicator> {
+    comm: C,
+
+    /// The owned and the halo rows.
+    pub grid: Grid,
+    slab: Slab,
+
+    /// The dimension of the whole grid.
+    global_dim: Index2,
+
+    /// The row of the whole grid of the first local row.
+    offset: usize,
+}
+
+impl<C: Communicator> Subdomain<C> {
+    /// The subdomain of the rank of `comm` with the cells of the whole grid of dimension
+    /// `dim` (including the border) created by `cell` from their index. Each rank only
+    /// creates its rows.
+    pub fn from_fn<F>(
+        comm: C,
+        dim: Index2,
+        cell_width: Scalar,
+        halo: usize,
+        cell: F,
+    ) -> FluidResult<Self>
+    where
+        F: Fn(Index2) -> Cell + Send + Sync,
+    {
+        let (slab, offset) = Self::slab(&comm, dim, halo)?;
+        let grid = Grid::from_fn(idx!(dim.x, slab.rows) - idx!(2, 2), cell_width, |idx| {
+            return cell(idx + idx!(0, offset)).moved_to(idx);
+        });
+
+        return Ok(Subdomain {
+            comm,
+            grid,
+            slab,
+            global_dim: dim,
+            offset,
+        });
+    }
+
+    /// The subdomain of the rank of `comm` cropped from the whole grid `grid`, which
+    /// each rank has to set up the same.
+    pub fn from_grid(comm: C, grid: &Grid, halo: usize) -> FluidResult<Self> {
+        if grid.is_periodic(1) && comm.size() > 1 {
+            let message = "periodic bottom and top sides need a single rank".to_string();
+            return Err(Error::invalid_parameter("grid", message));
+        }
+
+        let dim = grid.dim;
+        let (slab, offset) = Self::slab(&comm, dim, halo)?;
+
+        let mut local = grid.crop(idx!(0, offset), idx!(dim.x, offset + slab.rows));
+        if comm.rank() > 0 {
+            local.clear_boundary(Side::Bottom);
+        }
+        if comm.rank() + 1 < comm.size() {
+            local.clear_boundary(Side::Top);
+        }
+
+        return Ok(Subdomain {
+            comm,
+            grid: local,
+            slab,
+            global_dim: dim,
+            offset,
+        });
+    }
+
+    /// The slab of the rank of `comm` and the row of the whole grid of its first local row.
+    fn slab(comm: &C, dim: Index2, halo: usize) -> FluidResult<(Slab, usize)> {
+        let owned = slab_rows(dim.y, comm.size(), comm.rank());
+        if halo == 0 || owned.len() < halo {
+            let message = format!("{} is not in [1, {}] (rows per rank)", halo, owned.len());
+            return Err(Error::invalid_parameter("halo", message));
+        }
+
+        let offset = owned.start.saturating_sub(halo);
+        let end = (owned.end + halo).min(dim.y);
+
+        let slab = Slab {
+            owned: owned.start - offset..owned.end - offset,
+            halo,
+            rows: end - offset,
+        };
+        return Ok((slab, offset));
+    }
+
+    pub fn rank(&self) -> usize {
+        return self.comm.rank();
+    }
+
+    /// The rows of the whole grid owned by this rank.
+    pub fn owned_rows(&self) -> Range<usize> {
+        return self.slab.owned.start + self.offset..self.slab.owned.end + self.offset;
+    }
+
+    /// Compute one step of size `dt` on all ranks (collective).
+    /// Fails if a neighbor is disconnected.
+    pub fn step(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) -> FluidResult<()> {
+        debug!(
+            log,
+            "Step of rank {} (rows {:?}).",
+            self.rank(),
+            self.owned_rows()
+        );
+
+        self.exchange_halos()?;
+        self.grid.integrate(log, dt, params);
+
+        self.exchange_halos()?;
+        self.solve_incompressibility(log, dt, params)?;
+
+        self.exchange_halos()?;
+        self.grid.advect(log, dt, params.execution_mode);
+        return Ok(());
+    }
+
+    /// The distributed Jacobi pressure solve with the halos exchanged in each iteration.
+    fn solve_incompressibility(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        params: &SolverParams,
+    ) -> FluidResult<()> {
+        let Subdomain {
+            comm, grid, slab, ..
+        } = self;
+        let stride = grid.dim.x;
+
+        // The iterations after a failed exchange are not synchronized anymore.
+        let mut result = Ok(());
+        grid.jacobi_iterations(
+            log,
+            dt,
+            params.iterations,
+            params.density,
+            |data| {
+                if result.is_err() {
+                    return;
+                }
+
+                result = match data {
+                    JacobiSync::Cells(cells) => {
+                        sync_rows(comm, slab, stride, cells, 2, pack_velocity, unpack_velocity)
+                    }
+                    JacobiSync::Divergence(dn) => {
+                        sync_rows(comm, slab, stride, dn, 1, pack_scalar, unpack_scalar)
+                    }
+                };
+            },
+        );
+
+        grid.compute_stats(log);
+        return result;
+    }
+
+    /// Exchange the velocity, the pressure, the smoke and the mode of the halo rows.
+    /// Fails if a neighbor is disconnected.
+    pub fn exchange_halos(&mut self) -> FluidResult<()> {
+        let stride = self.grid.dim.x;
+        let cells = self.grid.cells_mut();
+        return sync_rows(
+            &mut self.comm,
+            &self.slab,
+            stride,
+            cells,
+            5,
+            pack_cell,
+            unpack_cell,
+        );
+    }
+
+    /// The whole grid with the velocity, the pressure, the smoke and the mode of all
+    /// cells on rank `0`, `None` on the others (collective).
+    /// Fails if a rank is disconnected.
+    pub fn gather(&mut self) -> FluidResult<Option<Grid>> {
+        let stride = self.grid.dim.x;
+        let owned = &self.slab.owned;
+
+        let mut values = vec![];
+        for cell in &self.grid.cells()[owned.start * stride..owned.end * stride] {
+            pack_cell(cell, &mut values);
+        }
+
+        let all = match self.comm.gather(&values)? {
+            Some(all) => all,
+            None => return Ok(None),
+        };
+
+        let dim = self.global_dim;
+        let mut grid = Grid::from_fn(dim - idx!(2, 2), self.grid.cell_width, Cell::new);
+        let values = all.iter().flat_map(|v| v.chunks(5));
+        for (cell, v) in grid.cells_mut().iter_mut().zip(values) {
+            unpack_cell(cell, v);
+        }
+
+        return Ok(Some(grid));
+    }
+}
+
+/// Exchange the values of the rows of `items` (`stride` items per row, `n` values per
+/// item) between the neighbors.
+fn sync_rows<C, T, P, U>(
+    comm: &mut C,
+    slab: &Slab,
+    stride: usize,
+    items: &mut [T],
+    n: usize,
+    pack: P,
+    unpack: U,
+) -> FluidResult<()>
+where
+    C: Communicator,
+    P: Fn(&T, &mut Vec<Scalar>),
+    U: Fn(&mut T, &[Scalar]),
+{
+    let range = |rows: Range<usize>| rows.start * stride..rows.end * stride;
+    let (send_lower, send_upper) = slab.send_rows();
+
+    let (mut to_lower, mut to_upper) = (vec![], vec![]);
+    items[range(send_lower)]
+        .iter()
+        .for_each(|t| pack(t, &mut to_lower));
+    items[range(send_upper)]
+        .iter()
+        .for_each(|t| pack(t, &mut to_upper));
+
+    let (from_lower, from_upper) = comm.exchange(&to_lower, &to_upper)?;
+
+    let (halo_lower, halo_upper) = slab.halo_rows();
+    for (t, v) in items[range(halo_lower)]
+        .iter_mut()
+        .zip(from_lower.chunks(n))
+    {
+        unpack(t, v);
+    }
+    for (t, v) in items[range(halo_upper)]
+        .iter_mut()
+        .zip(from_upper.chunks(n))
+    {
+        unpack(t, v);
+    }
+    return Ok(());
+}
+
+fn pack_scalar(value: &Scalar, out: &mut Vec<Scalar>) {
+    out.push(*value);
+}
+
+fn unpack_scalar(value: &mut Scalar, v: &[Scalar]) {
+    *value = v[0];
+}
+
+fn pack_velocity(cell: &Cell, out: &mut Vec<Scalar>) {
+    out.extend_from_slice(&[cell.velocity.back.x, cell.velocity.back.y]);
+}
+
+fn unpack_velocity(cell: &mut Cell, v: &[Scalar]) {
+    cell.velocity.back = vec2!(v[0], v[1]);
+}
+
+fn pack_cell(cell: &Cell, out: &mut Vec<Scalar>) {
+    pack_velocity(cell, out);
+    let fluid = (cell.mode == CellTypes::Fluid) as u8 as Scalar;
+    out.extend_from_slice(&[cell.pressure, cell.smoke(), fluid]);
+}
+
+fn unpack_cell(cell: &mut Cell, v: &[Scalar]) {
+    unpack_velocity(cell, v);
+    cell.pressure = v[2];
+    cell.set_smoke(v[3]);
+    cell.mode = if v[4] != 0.0 {
+        CellTypes::Fluid
+    } else {
+        CellTypes::Solid
+    };
+}
@@ -0,0 +1,94 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+use std::collections::VecDeque;
+
+/// Scalar health indicators of the simulation after a step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub time: Scalar,
+    pub dt: Scalar,
+
+    /// Maximal absolute divergence of the projected velocity field.
+    pub max_divergence: Scalar,
+
+    /// Kinetic energy per unit density `0.5 * sum(|v|^2) * h^2` over the fluid cells.
+    pub kinetic_energy: Scalar,
+
+    /// Maximal absolute divergence in the last iteration of the pressure solve.
+    pub residual: Scalar,
+}
+
+impl Diagnostics {
+    pub fn from(grid: &Grid, time: Scalar, dt: Scalar) -> Self {
+        let mut max_divergence: Scalar = 0.0;
+        let mut energy = 0.0;
+
+        for row in grid.iter_rows_inside() {
+            let cells = grid.row_cells(&row);
+
+            for ((idx, _), cell) in row.indices().zip(cells) {
+                if cell.mode == CellTypes::Solid {
+                    continue;
+                }
+
+                // Net outflow as in the pressure solve.
+                let pos_nbs = Grid::get_neighbors_indices(idx)[1];
+                let vel = cell.velocity.back;
+                let div = grid.cell(pos_nbs[0]).velocity.back.x - vel.x
+                    + grid.cell(pos_nbs[1]).velocity.back.y - vel.y;
+
+                max_divergence = max_divergence.max(div.abs());
+                energy += grid.cell_center_velocity(idx).norm_squared();
+            }
+        }
+
+        return Diagnostics {
+            time,
+            dt,
+            max_divergence,
+            kinetic_energy: 0.5 * energy * grid.cell_width * grid.cell_width,
+            residual: grid.stats[0].div.abs().max(grid.stats[1].div.abs()),
+        };
+    }
+}
+
+/// The last `capacity` diagnostics of a run.
+pub struct DiagnosticsHistory {
+    capacity: usize,
+    samples: VecDeque<Diagnostics>,
+}
+
+impl DiagnosticsHistory {
+    pub fn new(capacity: usize) -> Self {
+        return DiagnosticsHistory {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        };
+    }
+
+    pub fn push(&mut self, d: Diagnostics) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(d);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.samples.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.samples.is_empty();
+    }
+
+    pub fn last(&self) -> Option<&Diagnostics> {
+        return self.samples.back();
+    }
+
+    /// The points `[time, value]` of the quantity `get`.
+    pub fn points<F: Fn(&Diagnostics) -> Scalar>(&self, get: F) -> Vec<[f64; 2]> {
+        return self.samples.iter().map(|d| [d.time as f64, get(d) as f64]).collect();
+    }
+}
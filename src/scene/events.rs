@@ -0,0 +1,217 @@
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::Integrate;
+use crate::types::*;
+
+/// An event of a timestepper (see `TimeStepper::subscribe`).
+/// `object` is the index of the grid in the objects of the timestepper.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolverEvent {
+    StepStarted {
+        t: Scalar,
+        dt: Scalar,
+    },
+
+    /// `t` is the time at the end of the step.
+    StepFinished {
+        t: Scalar,
+        dt: Scalar,
+    },
+
+    /// The residual of the pressure solve is within `EventChecks::residual_tolerance`.
+    SolverConverged {
+        t: Scalar,
+        object: usize,
+        iterations: u64,
+        residual: Scalar,
+    },
+
+    /// The residual of the pressure solve is above the tolerance or not finite.
+    SolverFailed {
+        t: Scalar,
+        object: usize,
+        iterations: u64,
+        residual: Scalar,
+    },
+
+    /// The fastest velocity moves more than `max_cfl` cells in the step.
+    CflViolated {
+        t: Scalar,
+        object: usize,
+        cfl: Scalar,
+        max_cfl: Scalar,
+    },
+
+    /// The first cell with a velocity, a pressure or a smoke which is not finite.
+    NanDetected {
+        t: Scalar,
+        object: usize,
+        index: Index2,
+    },
+
+    CheckpointWritten {
+        t: Scalar,
+        file: String,
+    },
+}
+
+/// A listener of the events of a timestepper, e.g. a logger, a UI or a scheduler.
+pub trait Observer: Send {
+    fn notify(&mut self, event: &SolverEvent);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&SolverEvent) + Send,
+{
+    fn notify(&mut self, event: &SolverEvent) {
+        self(event);
+    }
+}
+
+/// The id of a subscribed observer (see `Observers::remove`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+/// The thresholds of the checks after each step.
+#[derive(Clone, Copy, Debug)]
+pub struct EventChecks {
+    /// The largest residual of a converged pressure solve. The residual is the one of
+    /// the solver or else the maximal absolute divergence of the grid.
+    pub residual_tolerance: Scalar,
+
+    /// The largest CFL number `max|v| * dt / h` of a step.
+    pub max_cfl: Scalar,
+}
+
+impl Default for EventChecks {
+    fn default() -> Self {
+        return EventChecks {
+            residual_tolerance: Scalar::INFINITY,
+            max_cfl: 1.0,
+        };
+    }
+}
+
+/// The observers of a timestepper.
+///
+/// The grids are only checked (one pass over the cells) if an observer is subscribed.
+/// The solver events of the GPU and CUDA backends report the statistics of the last
+/// solve on the CPU.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<(ObserverId, Box<dyn Observer>)>,
+    next_id: u64,
+
+    pub checks: EventChecks,
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: Box<dyn Observer>) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+
+        self.observers.push((id, observer));
+        return id;
+    }
+
+    /// Remove the observer `id`. Returns `false` if it is not subscribed.
+    pub fn remove(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|(i, _)| *i != id);
+        return self.observers.len() != len;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.observers.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.observers.is_empty();
+    }
+
+    /// Notify all observers in the order they were added.
+    pub fn emit(&mut self, event: SolverEvent) {
+        for (_, observer) in self.observers.iter_mut() {
+            observer.notify(&event);
+        }
+    }
+
+    /// Emit the solver, NaN and CFL events of the grids after a step which ended at `t`.
+    pub(crate) fn check_step(&mut self, t: Scalar, dt: Scalar, objects: &[Box<dyn Integrate>]) {
+        if self.is_empty() {
+            return;
+        }
+
+        for (object, obj) in objects.iter().enumerate() {
+            let grid = match obj.as_any().downcast_ref::<Grid>() {
+                Some(grid) => grid,
+                None => continue,
+            };
+
+            let stats = grid.solve_stats();
+            let residual = stats
+                .residual
+                .unwrap_or(grid.stats[0].div.abs().max(grid.stats[1].div.abs()));
+            let iterations = stats.iterations;
+
+            if residual <= self.checks.residual_tolerance {
+                self.emit(SolverEvent::SolverConverged {
+                    t,
+                    object,
+                    iterations,
+                    residual,
+                });
+            } else {
+                self.emit(SolverEvent::SolverFailed {
+                    t,
+                    object,
+                    iterations,
+                    residual,
+                });
+            }
+
+            let (max_speed, nan) = scan(grid);
+            if let Some(index) = nan {
+                self.emit(SolverEvent::NanDetected { t, object, index });
+            }
+
+            let cfl = max_speed * dt / grid.cell_width;
+            let max_cfl = self.checks.max_cfl;
+            if cfl > max_cfl {
+                self.emit(SolverEvent::CflViolated {
+                    t,
+                    object,
+                    cfl,
+                    max_cfl,
+                });
+            }
+        }
+    }
+}
+
+/// The largest velocity component of the grid and the first cell with a value which is
+/// not finite.
+pub(crate) fn scan(grid: &Grid) -> (Scalar, Option<Index2>) {
+    let mut max_speed: Scalar = 0.0;
+    let mut nan = None;
+
+    for row in grid.iter_rows_inside() {
+        let cells = grid.row_cells(&row);
+
+        for ((idx, _), cell) in row.indices().zip(cells) {
+            let vel = cell.velocity.back;
+            let finite = vel.iter().all(|v| v.is_finite())
+                && cell.pressure.is_finite()
+                && cell.smoke().is_finite();
+
+            if !finite {
+                nan = nan.or(Some(idx));
+                continue;
+            }
+
+            max_speed = max_speed.max(vel.amax());
+        }
+    }
+
+    return (max_speed, nan);
+}
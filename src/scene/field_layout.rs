@@ -0,0 +1,111 @@
+use crate::types::*;
+
+use std::marker::PhantomData;
+
+/// Alignment in bytes of the rows of an `AlignedField` (a cache line, two AVX registers).
+pub const FIELD_ALIGN: usize = 64;
+
+/// The values which an `AlignedField` can hold: plain numbers for which all bit
+/// patterns are valid values and whose size divides `FIELD_ALIGN`.
+pub trait FieldValue: Copy + Send + Sync + 'static {}
+
+impl FieldValue for f32 {}
+impl FieldValue for f64 {}
+impl FieldValue for u32 {}
+
+/// The memory layout of a field over a grid of dimension `dim` with `components`
+/// interleaved values per cell and padded rows: row `y` starts at data index
+/// `y * stride`, the values after the `dim.x * components` values of a row are padding.
+/// The stride of a field with one component is a multiple of `FIELD_ALIGN` bytes,
+/// the stride with `n` components is `n` times that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub dim: Index2,
+    pub components: usize,
+    pub stride: usize,
+}
+
+impl FieldLayout {
+    pub fn new<T: FieldValue>(dim: Index2, components: usize) -> Self {
+        let size = std::mem::size_of::<T>();
+        assert!(
+            components > 0 && FIELD_ALIGN % size == 0,
+            "Field values do not fit the alignment."
+        );
+
+        let row_bytes = (dim.x * size + FIELD_ALIGN - 1) / FIELD_ALIGN * FIELD_ALIGN;
+        return FieldLayout {
+            dim,
+            components,
+            stride: components * row_bytes / size,
+        };
+    }
+
+    /// Number of values including the padding.
+    pub fn size(&self) -> usize {
+        return self.stride * self.dim.y;
+    }
+
+    /// The data index of the first value of cell `idx`.
+    #[inline(always)]
+    pub fn data_index(&self, idx: Index2) -> usize {
+        return idx.x * self.components + idx.y * self.stride;
+    }
+}
+
+#[repr(C, align(64))]
+#[derive(Clone, Copy)]
+struct Block([u8; FIELD_ALIGN]);
+
+/// A zero-initialized field with a `FieldLayout` whose storage starts at a
+/// `FIELD_ALIGN`-byte boundary, hence all rows are aligned and equally long.
+pub struct AlignedField<T: FieldValue> {
+    blocks: Vec<Block>,
+    layout: FieldLayout,
+    value: PhantomData<T>,
+}
+
+impl<T: FieldValue> AlignedField<T> {
+    pub fn new(dim: Index2, components: usize) -> Self {
+        let layout = FieldLayout::new::<T>(dim, components);
+        let bytes = layout.size() * std::mem::size_of::<T>();
+
+        return AlignedField {
+            blocks: vec![Block([0; FIELD_ALIGN]); bytes / FIELD_ALIGN],
+            layout,
+            value: PhantomData,
+        };
+    }
+
+    pub fn layout(&self) -> FieldLayout {
+        return self.layout;
+    }
+
+    /// All values including the padding.
+    pub fn as_slice(&self) -> &[T] {
+        // Safety: the blocks are plain bytes of the size of `layout.size()` values `T`,
+        // aligned for `T`, and all bit patterns are valid values (see `FieldValue`).
+        return unsafe {
+            std::slice::from_raw_parts(self.blocks.as_ptr() as *const T, self.layout.size())
+        };
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // Safety: see `as_slice`.
+        return unsafe {
+            std::slice::from_raw_parts_mut(self.blocks.as_mut_ptr() as *mut T, self.layout.size())
+        };
+    }
+
+    /// The values of the cells of row `y` without the padding.
+    pub fn row(&self, y: usize) -> &[T] {
+        let start = y * self.layout.stride;
+        return &self.as_slice()[start..start + self.layout.dim.x * self.layout.components];
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        let start = y * self.layout.stride;
+        let len = self.layout.dim.x * self.layout.components;
+        return &mut self.as_mut_slice()[start..start + len];
+    }
+}
@@ -0,0 +1,93 @@
+use crate::log::Logger;
+use crate::scene::grid::Grid;
+use crate::scene::memory::MemoryReport;
+use crate::scene::solver_params::SolverParams;
+use crate::scene::timestepper::{ExecutionMode, Integrate};
+use crate::types::*;
+
+use std::any::Any;
+use std::ops::Deref;
+
+/// A grid of `W x H` cells (including the border) whose dimension is known at compile
+/// time, for fixed-size real-time use (e.g. games). The serial pressure solve is
+/// compiled for this dimension, which gives constant loop bounds and strides.
+/// Every dimension is a separate instantiation of the solver.
+///
+/// It behaves like the wrapped `Grid` otherwise: `as_any` returns the grid, such that
+/// the manipulators and the outputs which downcast to `Grid` work unchanged.
+pub struct FixedGrid<const W: usize, const H: usize> {
+    grid: Grid,
+}
+
+impl<const W: usize, const H: usize> FixedGrid<W, H> {
+    pub fn new(cell_width: Scalar) -> Self {
+        assert!(W > 2 && H > 2, "A grid needs inside cells.");
+        return FixedGrid {
+            grid: Grid::new(idx!(W - 2, H - 2), cell_width),
+        };
+    }
+
+    /// Wrap `grid`. Fails if its dimension is not `W x H`.
+    pub fn from_grid(grid: Grid) -> GenericResult<Self> {
+        if grid.dim != idx!(W, H) {
+            return Err(format!("Grid dimension {} is not {}x{}.", grid.dim, W, H).into());
+        }
+        return Ok(FixedGrid { grid });
+    }
+
+    /// The grid to modify. Its dimension must not change.
+    pub fn grid_mut(&mut self) -> &mut Grid {
+        return &mut self.grid;
+    }
+
+    pub fn into_grid(self) -> Grid {
+        return self.grid;
+    }
+}
+
+impl<const W: usize, const H: usize> Deref for FixedGrid<W, H> {
+    type Target = Grid;
+
+    fn deref(&self) -> &Grid {
+        return &self.grid;
+    }
+}
+
+impl<const W: usize, const H: usize> Integrate for FixedGrid<W, H> {
+    fn reset(&mut self, log: &Logger) {
+        self.grid.reset(log);
+    }
+
+    fn integrate(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        self.grid.integrate(log, dt, params);
+    }
+
+    fn solve_incompressibility(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        params: &SolverParams,
+    ) -> FluidResult<()> {
+        return self.grid.solve_incompressibility_fixed::<W, H>(log, dt, params);
+    }
+
+    fn advect(&mut self, log: &Logger, dt: Scalar, execution_mode: ExecutionMode) {
+        self.grid.advect(log, dt, execution_mode);
+    }
+
+    fn advect_and_integrate(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        self.grid.advect_and_integrate(log, dt, params);
+    }
+
+    fn memory_report(&self) -> MemoryReport {
+        return self.grid.memory_report();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self.grid.as_any();
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self.grid.as_any_mut();
+    }
+}
@@ -0,0 +1,188 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::config::SectionConfig;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::probes::sample_centered;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// A named line segment in world coordinates, e.g. across a doorway, through which the
+/// flux of the fluid and of the smoke is measured.
+///
+/// The flux is positive from the left to the right side of the segment looking from
+/// `start` to `end`, e.g. in `+x` for a segment upwards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrossSection {
+    pub name: String,
+    pub start: Vector2,
+    pub end: Vector2,
+}
+
+/// The flux through a `CrossSection` in one step and accumulated up to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FluxSample {
+    pub step: u64,
+    pub time: Scalar,
+
+    /// The volume per time `∫ v·n ds` and the smoke per time `∫ s v·n ds` after the step.
+    pub volume_flux: Scalar,
+    pub smoke_flux: Scalar,
+
+    /// The fluxes integrated over the steps up to this one.
+    pub volume: Scalar,
+    pub smoke: Scalar,
+}
+
+impl CrossSection {
+    pub fn new(name: &str, start: Vector2, end: Vector2) -> Self {
+        return CrossSection {
+            name: name.to_string(),
+            start,
+            end,
+        };
+    }
+
+    pub fn from_config(config: &SectionConfig) -> Self {
+        return CrossSection::new(
+            &config.name,
+            vec2!(config.start[0], config.start[1]),
+            vec2!(config.end[0], config.end[1]),
+        );
+    }
+
+    pub fn length(&self) -> Scalar {
+        return (self.end - self.start).norm();
+    }
+
+    /// The unit normal in the direction of a positive flux.
+    pub fn normal(&self) -> Vector2 {
+        let d = (self.end - self.start) / self.length();
+        return vec2!(d.y, -d.x);
+    }
+
+    /// The volume flux and the smoke flux through the section in `grid` (midpoint rule
+    /// with two samples per cell width). Parts outside of the grid or in solid cells
+    /// do not contribute.
+    pub fn flux(&self, grid: &Grid) -> (Scalar, Scalar) {
+        let length = self.length();
+        let n = ((2.0 * length / grid.cell_width).ceil() as usize).max(1);
+        let (normal, ds) = (self.normal(), length / n as Scalar);
+
+        let (mut volume, mut smoke) = (0.0, 0.0);
+        for k in 0..n {
+            let pos = self.start + (self.end - self.start) * ((k as Scalar + 0.5) / n as Scalar);
+
+            let is_fluid = grid
+                .cell_index_at(pos)
+                .map_or(false, |idx| grid.cell(idx).mode != CellTypes::Solid);
+            if !is_fluid {
+                continue;
+            }
+
+            let v_n = grid.sample_velocity(pos).dot(&normal) * ds;
+            volume += v_n;
+            smoke += sample_centered(grid, pos, |c| c.smoke()) * v_n;
+        }
+
+        return (volume, smoke);
+    }
+}
+
+/// Measures the flux through all cross-sections each step and keeps the time series
+/// in memory over the run.
+pub struct FluxMeter {
+    sections: Vec<CrossSection>,
+    series: Vec<Vec<FluxSample>>,
+}
+
+impl FluxMeter {
+    pub fn new(sections: Vec<CrossSection>) -> Self {
+        let series = vec![vec![]; sections.len()];
+        return FluxMeter { sections, series };
+    }
+
+    pub fn from_config(configs: &[SectionConfig]) -> Self {
+        return FluxMeter::new(configs.iter().map(CrossSection::from_config).collect());
+    }
+
+    /// Add `section` and return its index. It is measured from the next step on.
+    pub fn add(&mut self, section: CrossSection) -> usize {
+        self.sections.push(section);
+        self.series.push(vec![]);
+        return self.sections.len() - 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.sections.is_empty();
+    }
+
+    pub fn sections(&self) -> &[CrossSection] {
+        return &self.sections;
+    }
+
+    /// The index of the section `name`.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        return self.sections.iter().position(|s| s.name == name);
+    }
+
+    /// The measured samples of section `i`.
+    pub fn series(&self, i: usize) -> &[FluxSample] {
+        return &self.series[i];
+    }
+
+    /// The last sample of section `i` with the accumulated volume and smoke.
+    pub fn latest(&self, i: usize) -> Option<&FluxSample> {
+        return self.series[i].last();
+    }
+
+    /// Measure all sections after step `step` of size `dt` which ended at `time`.
+    pub fn record(&mut self, step: u64, time: Scalar, dt: Scalar, grid: &Grid) {
+        for (section, series) in self.sections.iter().zip(self.series.iter_mut()) {
+            let (volume_flux, smoke_flux) = section.flux(grid);
+            let last = series.last().copied().unwrap_or_default();
+
+            series.push(FluxSample {
+                step,
+                time,
+                volume_flux,
+                smoke_flux,
+                volume: last.volume + volume_flux * dt,
+                smoke: last.smoke + smoke_flux * dt,
+            });
+        }
+    }
+
+    /// Discard the samples after step `step`, e.g. after a rewind.
+    pub fn truncate(&mut self, step: u64) {
+        for series in self.series.iter_mut() {
+            series.retain(|s| s.step <= step);
+        }
+    }
+
+    /// Write all samples as CSV with one row per step and section.
+    pub fn write_csv(&self, file: &str) -> GenericResult<()> {
+        let mut w = BufWriter::new(File::create(file)?);
+
+        writeln!(w, "section,step,time,volume_flux,smoke_flux,volume,smoke")?;
+
+        for (section, series) in self.sections.iter().zip(self.series.iter()) {
+            for s in series.iter() {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{}",
+                    section.name,
+                    s.step,
+                    s.time,
+                    s.volume_flux,
+                    s.smoke_flux,
+                    s.volume,
+                    s.smoke
+                )?;
+            }
+        }
+
+        w.flush()?;
+        return Ok(());
+    }
+}
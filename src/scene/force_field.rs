@@ -0,0 +1,146 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::rng::RngStream;
+use crate::types::*;
+
+/// The state of a cell as seen by a force field.
+#[derive(Clone, Debug)]
+pub struct CellView {
+    pub index: Index2,
+    pub mode: CellTypes,
+
+    /// The velocity interpolated to the center of the cell.
+    pub velocity: Vector2,
+    pub pressure: Scalar,
+    pub smoke: Scalar,
+}
+
+/// A body force of the fluid (see `TimeStepper::add_force_field`).
+///
+/// The forces of all fields are evaluated at the centers of the fluid cells from the
+/// velocity at the start of the step and added with explicit Euler before the gravity
+/// and the vorticity confinement of the grid are integrated.
+pub trait ForceField: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The acceleration at the position `pos` at time `t` of the cell `cell`.
+    fn force(&self, pos: Vector2, t: Scalar, cell: &CellView) -> Vector2;
+}
+
+impl<F> ForceField for F
+where
+    F: Fn(Vector2, Scalar, &CellView) -> Vector2 + Send + Sync,
+{
+    fn name(&self) -> &str {
+        return "closure";
+    }
+
+    fn force(&self, pos: Vector2, t: Scalar, cell: &CellView) -> Vector2 {
+        return self(pos, t, cell);
+    }
+}
+
+/// A constant acceleration, e.g. a gravity in addition to the one of the solver.
+#[derive(Clone, Copy, Debug)]
+pub struct Gravity(pub Vector2);
+
+impl ForceField for Gravity {
+    fn name(&self) -> &str {
+        return "gravity";
+    }
+
+    fn force(&self, _pos: Vector2, _t: Scalar, _cell: &CellView) -> Vector2 {
+        return self.0;
+    }
+}
+
+/// A drag towards the velocity `velocity` of the wind, proportional to the
+/// relative velocity.
+#[derive(Clone, Copy, Debug)]
+pub struct Wind {
+    pub velocity: Vector2,
+    pub drag: Scalar,
+}
+
+impl ForceField for Wind {
+    fn name(&self) -> &str {
+        return "wind";
+    }
+
+    fn force(&self, _pos: Vector2, _t: Scalar, cell: &CellView) -> Vector2 {
+        return self.drag * (self.velocity - cell.velocity);
+    }
+}
+
+/// A lift upwards (positive `y`) proportional to the smoke, e.g. of hot gas.
+#[derive(Clone, Copy, Debug)]
+pub struct Buoyancy {
+    pub lift: Scalar,
+}
+
+impl ForceField for Buoyancy {
+    fn name(&self) -> &str {
+        return "buoyancy";
+    }
+
+    fn force(&self, _pos: Vector2, _t: Scalar, cell: &CellView) -> Vector2 {
+        return vec2!(0.0, self.lift * cell.smoke);
+    }
+}
+
+/// A divergence-free turbulence: the curl of a stream function made of
+/// `CurlNoise::WAVES` plane waves with the wavelength `scale`, whose directions
+/// and phases are given by `seed`. The waves travel with the phase speed `speed`.
+#[derive(Clone, Copy, Debug)]
+pub struct CurlNoise {
+    pub amplitude: Scalar,
+    pub scale: Scalar,
+    pub speed: Scalar,
+    pub seed: u64,
+}
+
+const TAU: Scalar = std::f64::consts::TAU as Scalar;
+
+impl CurlNoise {
+    pub const WAVES: usize = 4;
+
+    /// The name of the random stream of the seeds (see `Rng::stream`).
+    pub const STREAM: &'static str = "curl-noise";
+
+    /// The noise with the next seed of `rng`.
+    pub fn seeded(amplitude: Scalar, scale: Scalar, speed: Scalar, rng: &mut RngStream) -> Self {
+        return CurlNoise {
+            amplitude,
+            scale,
+            speed,
+            seed: rng.next_u64(),
+        };
+    }
+
+    /// The direction and the phase of wave `i` (a low-discrepancy sequence).
+    fn wave(&self, i: usize) -> (Vector2, Scalar) {
+        let u = ((self.seed as Scalar + 1.0) * 0.618033988749895 + i as Scalar * 0.754877666246693)
+            .fract();
+        let angle = TAU * u;
+        return (vec2!(angle.cos(), angle.sin()), TAU * (7.0 * u).fract());
+    }
+}
+
+impl ForceField for CurlNoise {
+    fn name(&self) -> &str {
+        return "curl-noise";
+    }
+
+    fn force(&self, pos: Vector2, t: Scalar, _cell: &CellView) -> Vector2 {
+        let k = TAU / self.scale;
+
+        // The stream function `sum(sin(k * d.p + phase - k * speed * t)) / k`.
+        let mut f = Vector2::zeros();
+        for i in 0..CurlNoise::WAVES {
+            let (d, phase) = self.wave(i);
+            let arg = k * (d.dot(&pos) - self.speed * t) + phase;
+            f += arg.cos() * vec2!(d.y, -d.x);
+        }
+
+        return self.amplitude / CurlNoise::WAVES as Scalar * f;
+    }
+}
@@ -0,0 +1,104 @@
+use crate::scene::run_control::RunControl;
+use crate::scene::simulation::Simulation;
+use crate::scene::snapshot::Snapshot;
+use crate::types::*;
+
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::executor::block_on;
+use futures::{SinkExt, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+
+/// The frames of a simulation computed on a worker thread as an async stream
+/// (feature `async`, see `Simulation::frames`):
+/// ```ignore
+/// let mut frames = Simulation::new(config)?.frames(Steps(10), 4)?;
+/// while let Some(frame) = frames.next().await {
+///     send(frame?).await;
+/// }
+/// ```
+/// The first frame is the state before the first step, each next one follows after
+/// `every` steps. At most `buffer` frames are computed ahead of the consumer, then the
+/// worker waits until a frame is taken (back-pressure). A failed step is the last
+/// item. The stream ends when the runs are stopped with `control()`.
+/// Dropping the stream stops the worker after its current steps.
+pub struct FrameStream {
+    frames: Receiver<FluidResult<Snapshot>>,
+    control: RunControl,
+    worker: Option<JoinHandle<Simulation<'static>>>,
+}
+
+impl FrameStream {
+    pub(crate) fn new(sim: Simulation<'static>, every: Steps, buffer: usize) -> Self {
+        let (sender, frames) = channel(buffer);
+        let control = sim.control();
+
+        let worker = std::thread::spawn(move || {
+            return FrameStream::run(sim, every, sender);
+        });
+
+        return FrameStream {
+            frames,
+            control,
+            worker: Some(worker),
+        };
+    }
+
+    fn run(
+        mut sim: Simulation<'static>,
+        every: Steps,
+        mut sender: Sender<FluidResult<Snapshot>>,
+    ) -> Simulation<'static> {
+        let mut frame = sim.grid().map(|grid| Snapshot::new(grid, sim.time().value()));
+
+        loop {
+            let failed = frame.is_err();
+            if block_on(sender.send(frame)).is_err() || failed {
+                return sim;
+            }
+
+            frame = match sim.run_for(every) {
+                Ok(steps) if steps < every => return sim,
+                Ok(_) => sim.grid().map(|grid| Snapshot::new(grid, sim.time().value())),
+                Err(e) => Err(e),
+            };
+        }
+    }
+
+    /// The handle to pause, resume or stop the worker (see `Simulation::control`).
+    pub fn control(&self) -> RunControl {
+        return self.control.clone();
+    }
+
+    /// Stop the worker after its current steps and return the simulation.
+    /// Frames which are not taken yet are dropped.
+    pub fn into_simulation(mut self) -> Simulation<'static> {
+        self.control.stop();
+        self.frames.close();
+
+        let worker = self.worker.take().expect("Worker is joined once.");
+        let sim = worker.join().expect("Frame worker panicked.");
+
+        // The stop only ends the stream, the simulation runs again.
+        sim.resume();
+        return sim;
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = FluidResult<Snapshot>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        return Pin::new(&mut self.frames).poll_next(cx);
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        if self.worker.is_some() {
+            self.control.stop();
+            self.frames.close();
+        }
+    }
+}
@@ -1,30 +1,95 @@
 use crate::log::{debug, info, warn, Logger};
 use crate::math::*;
+use crate::scene::active_region::ActiveRegion;
+use crate::scene::advection::{AdvectedField, AdvectionScheme, SemiLagrangian};
+use crate::scene::boundary::BoundaryCondition;
 use crate::scene::cell::*;
 use crate::scene::cell_stats::*;
+use crate::scene::config::Side;
+use crate::scene::force_field::{CellView, ForceField};
 use crate::scene::grid_stencil;
 use crate::scene::grid_stencil::PosStencilMut;
 use crate::scene::grid_stencil_unsafe;
+use crate::scene::integrator::Integrator;
+use crate::scene::memory::{MemoryLocation, MemoryReport};
+use crate::scene::pressure_solver::{PressureSolver, SolveParams, SolveStats, SorSolver};
+use crate::scene::region::Region;
+use crate::scene::scratch::ScratchPool;
+use crate::scene::smoke_region::SmokeRegion;
+use crate::scene::solver_params::SolverParams;
+use crate::scene::stencil_table::StencilTable;
 use crate::scene::timestepper::{ExecutionMode, Integrate};
 use crate::types::*;
 
-use itertools::Itertools;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::num::Wrapping;
+use std::ops::Range;
 
+#[derive(Serialize, Deserialize)]
 pub struct Grid {
     pub cell_width: Scalar,
     pub dim: Index2,
 
     pub stats: [Stats; 2], //Min and max. accumulator statistics.
 
+    /// Overrelaxation factor of the pressure solve.
+    pub over_relaxation: Scalar,
+
+    /// Strength of the vorticity confinement force (0: disabled).
+    pub vorticity_confinement: Scalar,
+
     cells: Vec<Cell>,
 
     extent: Vector2,
 
+    /// Index of cell `(0, 0)` in the simulated domain (non-zero for cropped grids).
+    origin: Index2,
+
     // Grid offsets for each axis of the velocity in the cells..
     offsets: [Vector2; 2],
+
+    /// The boundary conditions of the sides (indexed by `Side`).
+    boundary: [BoundaryCondition; 4],
+
+    /// Temporary fields of the steps.
+    #[serde(skip)]
+    scratch: ScratchPool,
+
+    /// The moving parts of the grid if the quiescent cells are skipped.
+    #[serde(skip)]
+    active: Option<ActiveRegion>,
+
+    /// The precomputed stencils of the sequential pressure solve if enabled.
+    #[serde(skip)]
+    stencil_table: Option<StencilTable>,
+
+    /// The part of the grid with smoke if only this part is advected.
+    #[serde(skip)]
+    smoke_region: Option<SmokeRegion>,
+
+    /// The pressure solve of the steps (`SorSolver` if not set).
+    #[serde(skip)]
+    pressure_solver: Option<Box<dyn PressureSolver>>,
+
+    /// The statistics of the last pressure solve.
+    #[serde(skip)]
+    solve_stats: SolveStats,
+
+    /// The advection scheme of each `AdvectedField` (`SemiLagrangian` if not set).
+    #[serde(skip)]
+    advection: [Option<Box<dyn AdvectionScheme>>; 2],
+
+    /// The integrators of the body forces and of the backtracing of the advection.
+    #[serde(skip)]
+    integrator: Integrator,
+    #[serde(skip)]
+    backtrace: Integrator,
+
+    /// Organize the parallel work independent of the number of threads.
+    #[serde(skip)]
+    deterministic: bool,
 }
 
 #[derive(Clone)]
@@ -37,10 +102,16 @@ pub struct GridIndexIterator {
 
 impl GridIndexIterator {
     pub fn new(dim: Index2) -> GridIndexIterator {
+        return GridIndexIterator::new_range(idx!(0, 0), dim);
+    }
+
+    /// Iterator over all indices in the rectangular window `[min, max)`.
+    /// First dimension is iterated first (column-major).
+    pub fn new_range(min: Index2, max: Index2) -> GridIndexIterator {
         return GridIndexIterator {
-            curr: idx!(0, 0),
-            min: idx!(0, 0),
-            max: dim,
+            curr: min,
+            min,
+            max,
         };
     }
 }
@@ -67,28 +138,211 @@ impl Iterator for GridIndexIterator {
     }
 }
 
+/// A row of a rectangular window of the grid: the cells `x` of row `y` which lie
+/// contiguously at the data slots `slots` (the first dimension is the fastest).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridRow {
+    pub y: usize,
+    pub x: Range<usize>,
+    pub slots: Range<usize>,
+}
+
+impl GridRow {
+    /// The indices of the row with their data slots.
+    #[inline(always)]
+    pub fn indices(self) -> impl Iterator<Item = (Index2, usize)> {
+        let y = self.y;
+        return self.x.zip(self.slots).map(move |(x, k)| (idx!(x, y), k));
+    }
+}
+
+/// Iterator over the rows of the rectangular window `[min, max)`.
+/// Sweeps loop over the rows and then over the contiguous cells of a row,
+/// which avoids the per-cell wrap-around and index computation of `GridIndexIterator`.
+#[derive(Clone)]
+pub struct GridRowIterator {
+    y: usize,
+    min: Index2,
+    max: Index2,
+    stride: usize,
+}
+
+impl GridRowIterator {
+    /// The rows of the window `[min, max)` of a grid with row length `stride`.
+    pub fn new_range(min: Index2, max: Index2, stride: usize) -> GridRowIterator {
+        assert!(min <= max && max.x <= stride, "Invalid window.");
+        return GridRowIterator {
+            y: min.y,
+            min,
+            max,
+            stride,
+        };
+    }
+}
+
+impl Iterator for GridRowIterator {
+    type Item = GridRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y >= self.max.y {
+            return None;
+        }
+
+        let y = self.y;
+        self.y += 1;
+
+        let start = self.min.x + y * self.stride;
+        return Some(GridRow {
+            y,
+            x: self.min.x..self.max.x,
+            slots: start..start + self.max.x - self.min.x,
+        });
+    }
+}
+
+/// A face of the staggered velocity grid.
+/// The velocity component `dir` of cell `index` lives on this face.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Face {
+    /// The axis of the face normal (0: `u`-face, 1: `v`-face).
+    pub dir: usize,
+
+    /// The cell index which stores the velocity of this face.
+    pub index: Index2,
+
+    /// The world position of the face center.
+    pub pos: Vector2,
+
+    /// The adjacent cells `[negative, positive]` along `dir`.
+    pub cells: [Index2; 2],
+}
+
+/// The data of a Jacobi iteration passed to the `sync` of `Grid::jacobi_iterations`.
+pub(crate) enum JacobiSync<'a> {
+    /// The cells before the divergence is computed.
+    Cells(&'a mut [Cell]),
+
+    /// The divergences normalized with the fluid factors (one per cell).
+    Divergence(&'a mut [Scalar]),
+}
+
+/// Relaxation of the Jacobi pressure solve. All cells are updated at once, hence
+/// over-relaxation diverges and even `1.0` does not damp the checkerboard modes.
+pub const JACOBI_RELAXATION: Scalar = 0.8;
+
+/// Cells per side of the tiles of the tiled pressure solve.
+/// A tile of `48 x 48` cells (about 120 bytes each) fits into 512 KiB of L2 cache.
+pub const TILE_SIZE: usize = 48;
+
+/// Gauss-Seidel sweeps over a tile before moving to the next tile.
+pub const TILE_SWEEPS: u64 = 4;
+
+/// Extra sweeps over the cells at the tile seams after the tiled sweeps.
+pub const SEAM_SWEEPS: u64 = 2;
+
+/// Rows per band of the `ParallelBands` pressure solve in deterministic mode, which
+/// otherwise sizes the bands by the number of threads (see `Grid::set_deterministic`).
+pub const DETERMINISTIC_BAND: usize = 8;
+
+/// Number of positions `Grid::sample_field_batch` processes at once.
+pub const SAMPLE_LANES: usize = 8;
+
+/// The cells and weights of a bilinear sample.
+#[derive(Clone, Copy, Default)]
+struct SampleStencil {
+    /// Storage offsets of the cells `(0,1), (0,0), (1,1), (1,0)` relative to the sample.
+    cells: [usize; 4],
+
+    /// The interpolation weights along `x` and `y`.
+    alpha: [Scalar; 2],
+}
+
+impl SampleStencil {
+    #[inline(always)]
+    fn interpolate(&self, v: [Scalar; 4]) -> Scalar {
+        let [ax, ay] = self.alpha;
+        let top = mul_add(v[2], ax, v[0] * (1.0 - ax));
+        let bottom = mul_add(v[3], ax, v[1] * (1.0 - ax));
+        return mul_add(ay, top, (1.0 - ay) * bottom);
+    }
+}
+
+#[derive(Clone)]
+pub struct FaceIterator {
+    it: GridIndexIterator,
+    dir: usize,
+    cell_width: Scalar,
+    offset: Vector2,
+}
+
+impl Iterator for FaceIterator {
+    type Item = Face;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.it.next().map(|index| {
+            let mut neg = index;
+            neg[self.dir] -= 1;
+
+            return Face {
+                dir: self.dir,
+                index,
+                pos: index.cast::<Scalar>() * self.cell_width + self.offset,
+                cells: [neg, index],
+            };
+        });
+    }
+}
+
 impl Grid {
-    pub fn new(mut dim: Index2, cell_width: Scalar) -> Self {
+    pub fn new(dim: Index2, cell_width: Scalar) -> Self {
+        return Grid::from_fn(dim, cell_width, Cell::new);
+    }
+
+    /// A grid with `dim` inside cells whose cells (including the border) are
+    /// constructed in place by `f` from their index, in parallel.
+    pub fn from_fn<F>(mut dim: Index2, cell_width: Scalar, f: F) -> Self
+    where
+        F: Fn(Index2) -> Cell + Send + Sync,
+    {
         dim.x += 2;
         dim.y += 2;
 
         let h_2 = cell_width as Scalar * 0.5;
         let extent = dim.cast::<Scalar>() * cell_width;
 
+        let mut cells = Vec::new();
+        (0..dim.x * dim.y)
+            .into_par_iter()
+            .map(|i| f(idx!(i % dim.x, i / dim.x)))
+            .collect_into_vec(&mut cells);
+
         return Grid {
             dim,
             cell_width,
 
-            cells: GridIndexIterator::new(dim)
-                .map(|it| Cell::new(it))
-                .collect(),
+            cells,
 
             stats: [Stats::min_identity(), Stats::max_identity()],
 
+            over_relaxation: 1.9,
+            vorticity_confinement: 0.0,
+
             extent,
+            origin: Index2::zeros(),
             // `x`-values lie at offset `(0, h/2)` and
             // `y`-values at `(h/2, 0)`.
             offsets: [vec2!(0.0, h_2), vec2!(h_2, 0.0)],
+            boundary: Default::default(),
+            scratch: ScratchPool::default(),
+            active: None,
+            stencil_table: None,
+            smoke_region: None,
+            pressure_solver: None,
+            solve_stats: SolveStats::default(),
+            advection: [None, None],
+            integrator: Integrator::Euler,
+            backtrace: Integrator::Euler,
+            deterministic: false,
         };
     }
 
@@ -96,14 +350,69 @@ impl Grid {
         return GridIndexIterator::new(self.dim);
     }
 
-    pub fn iter_index_inside(&self) -> GridIndexIterator {
-        return GridIndexIterator {
-            curr: idx!(1, 1),
-            min: idx!(1, 1),
-            max: self.dim - idx!(1, 1),
+    /// Iterate over all indices inside the border cells.
+    pub fn iter_inside(&self) -> GridIndexIterator {
+        return self.iter_region(idx!(1, 1), self.dim - idx!(1, 1));
+    }
+
+    /// Iterate over all indices in the rectangular window `[min, max)`.
+    /// The window is clamped to the grid dimensions.
+    pub fn iter_region(&self, min: Index2, max: Index2) -> GridIndexIterator {
+        let max = clamp_to_range(Index2::zeros(), self.dim, max);
+        let min = clamp_to_range(Index2::zeros(), max, min);
+
+        return GridIndexIterator::new_range(min, max);
+    }
+
+    /// Iterate over the rows of the rectangular window `[min, max)`.
+    /// The window is clamped to the grid dimensions.
+    pub fn iter_rows(&self, min: Index2, max: Index2) -> GridRowIterator {
+        let max = clamp_to_range(Index2::zeros(), self.dim, max);
+        let min = clamp_to_range(Index2::zeros(), max, min);
+
+        return GridRowIterator::new_range(min, max, self.dim.x);
+    }
+
+    /// Iterate over the rows of the cells inside the border cells.
+    pub fn iter_rows_inside(&self) -> GridRowIterator {
+        return self.iter_rows(idx!(1, 1), self.dim - idx!(1, 1));
+    }
+
+    /// The cells of a row from `iter_rows`.
+    pub fn row_cells(&self, row: &GridRow) -> &[Cell] {
+        return &self.cells[row.slots.clone()];
+    }
+
+    pub fn row_cells_mut(&mut self, row: &GridRow) -> &mut [Cell] {
+        return &mut self.cells[row.slots.clone()];
+    }
+
+    /// Iterate over all faces with normal along `dir` which have
+    /// an adjacent cell on both sides.
+    pub fn iter_faces(&self, dir: usize) -> FaceIterator {
+        assert!(dir < 2, "Direction must be 0 or 1.");
+
+        let mut min = idx!(0, 0);
+        min[dir] = 1;
+
+        return FaceIterator {
+            it: self.iter_region(min, self.dim),
+            dir,
+            cell_width: self.cell_width,
+            offset: self.offsets[dir],
         };
     }
 
+    /// Iterate over all `u`-faces (storing `x`-velocities).
+    pub fn iter_u_faces(&self) -> FaceIterator {
+        return self.iter_faces(0);
+    }
+
+    /// Iterate over all `v`-faces (storing `y`-velocities).
+    pub fn iter_v_faces(&self) -> FaceIterator {
+        return self.iter_faces(1);
+    }
+
     pub fn is_inside_range(min: Index2, max: Index2, index: Index2) -> bool {
         return index < max && index >= min;
     }
@@ -129,187 +438,950 @@ impl Grid {
         ];
     }
 
-    pub fn set_obstacle(&mut self, pos: Vector2, radius: f64, velocity: Option<Vector2>) {
+    /// The world position of the center of cell `index`.
+    pub fn cell_center(&self, index: Index2) -> Vector2 {
+        return (index.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+    }
+
+    /// The velocity interpolated from the staggered faces to the center of cell `index`.
+    /// Faces on the positive border are clamped.
+    pub fn cell_center_velocity(&self, index: Index2) -> Vector2 {
+        let nbs = Grid::get_neighbors_indices(index);
+        let vel = self.cell(index).velocity.back;
+
+        let mut v = Vector2::zeros();
+        for dir in 0..2 {
+            let pos_vel = self
+                .cell_opt(nbs[1][dir])
+                .map_or(vel[dir], |c| c.velocity.back[dir]);
+            v[dir] = 0.5 * (vel[dir] + pos_vel);
+        }
+
+        return v;
+    }
+
+    pub fn set_obstacle(&mut self, pos: Vector2, radius: Scalar, velocity: Option<Vector2>) {
         let vel = velocity.unwrap_or(Vector2::zeros());
 
-        for idx in self.iter_index_inside() {
-            let c = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+        for row in self.iter_rows_inside() {
+            for (idx, k) in row.indices() {
+                let c = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+                let cell = &mut self.cells[k];
 
-            if (c - pos).norm_squared() <= radius * radius {
-                let c = self.cell_mut(idx);
-                c.mode = CellTypes::Solid;
-                c.velocity.back = vel;
-            } else {
-                self.cell_mut(idx).mode = CellTypes::Fluid;
+                if (c - pos).norm_squared() <= radius * radius {
+                    cell.mode = CellTypes::Solid;
+                    cell.velocity.back = vel;
+                } else {
+                    cell.mode = CellTypes::Fluid;
+                }
             }
         }
     }
 
-    fn compute_stats(&mut self, log: &Logger) {
-        // Parallelized accumulation of statistics.
-        self.stats[0] = self
-            .cells
-            .par_iter()
-            .map(|c| Stats::from(c))
-            .reduce(|| Stats::identity::<0>(), |a, b| Stats::min(&a, &b));
+    /// Mark all inside cells within the circle as solid with velocity `velocity`.
+    /// Other cells are left untouched, such that several obstacles can be combined.
+    pub fn add_obstacle(&mut self, pos: Vector2, radius: Scalar, velocity: Option<Vector2>) {
+        self.add_solid(&Region::circle(pos, radius), velocity);
+    }
 
-        self.stats[1] = self
-            .cells
-            .par_iter()
-            .map(|c| Stats::from(c))
-            .reduce(|| Stats::identity::<1>(), |a, b| Stats::max(&a, &b));
+    /// Make the inside cells with their center in `region` solid, moving with `velocity`.
+    pub fn add_solid(&mut self, region: &Region, velocity: Option<Vector2>) {
+        let vel = velocity.unwrap_or(Vector2::zeros());
+        let (min, max) = (idx!(1, 1), self.dim - idx!(1, 1));
 
-        info!(
-            log,
-            "Divergence range: {:.4?}, {:.4?}", self.stats[0].div, self.stats[1].div
-        );
-        info!(
-            log,
-            "Pressure range: {:.4?}, {:.4?}", self.stats[0].pressure, self.stats[1].pressure
-        );
-        info!(
-            log,
-            "Velocity range: {:.4?}, {:.4?}",
-            self.stats[0].velocity_norm,
-            self.stats[1].velocity_norm
-        );
+        self.for_each_in(region, |idx, c| {
+            if Grid::is_inside_range(min, max, idx) {
+                c.mode = CellTypes::Solid;
+                c.velocity.back = vel;
+            }
+        });
     }
-}
 
-pub trait CellGetter<'a, I> {
-    type Item: 'a;
+    /// The cells (with the border cells) which have their center in `region`.
+    pub fn cells_in(&self, region: &Region) -> Vec<Index2> {
+        return self
+            .iter_rows_region(region)
+            .flat_map(GridRow::indices)
+            .map(|(idx, _)| idx)
+            .filter(|idx| region.contains(self.cell_center(*idx)))
+            .collect();
+    }
 
-    type Output = &'a Self::Item;
-    type OutputMut = &'a mut Self::Item;
+    /// Call `f` for all cells (with the border cells) which have their center in `region`.
+    pub fn for_each_in<F: FnMut(Index2, &mut Cell)>(&mut self, region: &Region, mut f: F) {
+        for (idx, k) in self.iter_rows_region(region).flat_map(GridRow::indices) {
+            if region.contains(self.cell_center(idx)) {
+                f(idx, &mut self.cells[k]);
+            }
+        }
+    }
 
-    fn cell(&'a self, index: I) -> Self::Output;
-    fn cell_mut(&'a mut self, index: I) -> Self::OutputMut;
+    /// Set the velocity of the faces between two fluid cells which both have their
+    /// center in `region`.
+    pub fn set_velocity_in(&mut self, region: &Region, velocity: Vector2) {
+        for idx in self.cells_in(region) {
+            for dir in 0..2 {
+                if idx[dir] == 0 {
+                    continue;
+                }
 
-    type OutputOpt = Option<&'a Self::Item>;
-    type OutputMutOpt = Option<&'a mut Self::Item>;
+                let mut n = idx;
+                n[dir] -= 1;
 
-    fn cell_opt(&'a self, index: Index2) -> Self::OutputOpt;
-    fn cell_mut_opt(&'a mut self, index: Index2) -> Self::OutputMutOpt;
-}
+                let is_fluid = |i: Index2| self.cell(i).mode == CellTypes::Fluid;
+                if is_fluid(idx) && is_fluid(n) && region.contains(self.cell_center(n)) {
+                    self.cell_mut(idx).velocity.back[dir] = velocity[dir];
+                }
+            }
+        }
+    }
 
-impl<'t> CellGetter<'t, Index2> for Grid {
-    type Item = Cell;
+    /// The rows of the cells in the bounds of `region`.
+    fn iter_rows_region(&self, region: &Region) -> GridRowIterator {
+        let (min, max) = match region.bounds() {
+            Some(bounds) => bounds,
+            None => return self.iter_rows(Index2::zeros(), self.dim),
+        };
 
-    fn cell(&self, index: Index2) -> &Cell {
-        return &self.cells[index.x + index.y * self.dim.x];
+        let index = |p: Vector2| {
+            return Index2::from_iterator(
+                (p / self.cell_width).iter().map(|v| v.max(0.0) as usize),
+            );
+        };
+        return self.iter_rows(index(min), index(max) + idx!(1, 1));
     }
 
-    fn cell_mut(&mut self, index: Index2) -> &mut Cell {
-        return &mut self.cells[index.x + index.y * self.dim.x];
+    pub fn boundary(&self, side: Side) -> BoundaryCondition {
+        return self.boundary[side as usize];
     }
 
-    fn cell_opt(&self, index: Index2) -> Option<&Cell> {
-        return Grid::is_inside_range(Index2::zeros(), self.dim, index).then(|| self.cell(index));
+    /// Set the condition of `side` and make its border cells solid or fluid (the corners
+    /// are solid if one of their sides is). Opposite sides are both periodic or not.
+    pub fn set_boundary(&mut self, side: Side, condition: BoundaryCondition) {
+        self.boundary[side as usize] = condition;
+
+        let last = self.dim - idx!(1, 1);
+        for idx in self.side_cells(side) {
+            let solid = Side::ALL.iter().any(|s| {
+                let at = if s.is_lower() { 0 } else { last[s.axis()] };
+                return idx[s.axis()] == at && self.boundary(*s).is_solid();
+            });
+
+            self.cell_mut(idx).mode = if solid {
+                CellTypes::Solid
+            } else {
+                CellTypes::Fluid
+            };
+        }
+
+        self.apply_boundary();
     }
 
-    fn cell_mut_opt(&mut self, index: Index2) -> Option<&mut Cell> {
-        return Grid::is_inside_range(Index2::zeros(), self.dim, index)
-            .then(|| self.cell_mut(index));
+    /// Remove the condition of `side` without changing its border cells, e.g. of the
+    /// rows of a slab which are the halo of another slab (see `Subdomain`).
+    pub(crate) fn clear_boundary(&mut self, side: Side) {
+        self.boundary[side as usize] = BoundaryCondition::Solid;
     }
-}
 
-impl Grid {
-    pub fn modify_cells<F, const N: usize>(&mut self, indices: [usize; N], mut f: F) -> ()
-    where
-        F: FnMut([&mut Cell; N]),
-    {
-        let refs = self.cells.get_many_mut(indices).expect("Wrong indices.");
-        f(refs);
+    /// Change the velocity of the inflow on the solid `side`, e.g. each step for an
+    /// inflow which varies over time. Unlike `set_boundary` the cells keep their modes
+    /// and the faces take the velocity when the boundary is applied next.
+    pub fn set_inflow(&mut self, side: Side, velocity: Vector2) {
+        debug_assert!(self.boundary(side).is_solid(), "Side {:?} is not solid.", side);
+        self.boundary[side as usize] = BoundaryCondition::Inflow { velocity };
     }
-}
 
-impl Integrate for Grid {
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// The cells of `side` at the border cells, `0` or `dim - 1` along the
+    /// axis of the side.
+    fn side_cells(&self, side: Side) -> impl Iterator<Item = Index2> {
+        let (a, dim) = (side.axis(), self.dim);
+        let at = if side.is_lower() { 0 } else { dim[a] - 1 };
+
+        return (0..dim[1 - a]).map(move |t| side_index(a, at, t));
     }
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self
+    /// Apply the boundary conditions to the border cells, e.g. after changing the cells
+    /// from outside of a step. The steps apply them after the body forces.
+    pub fn apply_boundary(&mut self) {
+        for side in Side::ALL {
+            let (a, n) = (side.axis(), self.dim[side.axis()]);
+
+            match self.boundary(side) {
+                BoundaryCondition::Solid => {}
+                BoundaryCondition::Inflow { velocity } => {
+                    // The faces of the side: of the first inside cells or of the border cells.
+                    let at = if side.is_lower() { 1 } else { n - 1 };
+                    for t in 1..self.dim[1 - a] - 1 {
+                        self.cell_mut(side_index(a, at, t)).velocity.back[a] = velocity[a];
+                    }
+                }
+                BoundaryCondition::Outflow => {
+                    for idx in self.side_cells(side) {
+                        if self.cell(idx).mode == CellTypes::Solid {
+                            continue;
+                        }
+
+                        for dir in 0..2 {
+                            let pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
+
+                            // Just sample on the inside grid by clamping.
+                            self.cell_mut(idx).velocity.back[dir] = self.sample_field(
+                                idx!(1, 1),
+                                self.dim - idx!(1, 1),
+                                pos,
+                                Some(dir),
+                                |cell: &Cell| cell.velocity.back[dir],
+                            );
+                        }
+                    }
+                }
+                BoundaryCondition::Open => {
+                    for idx in self.side_cells(side) {
+                        let cell = self.cell_mut(idx);
+                        if cell.mode == CellTypes::Solid {
+                            continue;
+                        }
+
+                        // The face to the inside of an upper side belongs to the border cell.
+                        let face = cell.velocity.back[a];
+                        cell.velocity.back = Vector2::zeros();
+                        if !side.is_lower() {
+                            cell.velocity.back[a] = face;
+                        }
+                        cell.set_smoke(0.0);
+                    }
+                }
+                BoundaryCondition::Periodic if side.is_lower() => self.exchange_periodic(a),
+                BoundaryCondition::Periodic => {}
+            }
+        }
     }
 
-    fn reset(&mut self, log: &Logger) {
-        info!(log, "Reset stats.");
-        self.stats = [Stats::min_identity(), Stats::max_identity()];
+    /// Copy the velocity and the smoke of the inside cells next to each side of `axis`
+    /// into the border cells of the opposite side and average the faces of both sides.
+    fn exchange_periodic(&mut self, axis: usize) {
+        let (a, t) = (axis, 1 - axis);
+        let n = self.dim[a];
+
+        for k in 1..self.dim[t] - 1 {
+            let at = |i: usize| side_index(a, i, k);
+
+            let state = |c: &Cell| (c.velocity.back, c.smoke());
+            let (first, last) = (state(self.cell(at(1))), state(self.cell(at(n - 2))));
+
+            // The first inside cell and the upper border cell share the face of both sides.
+            let face = 0.5 * (first.0[a] + self.cell(at(n - 1)).velocity.back[a]);
+            self.cell_mut(at(1)).velocity.back[a] = face;
+
+            let ghost = self.cell_mut(at(n - 1));
+            ghost.velocity.back[a] = face;
+            ghost.velocity.back[t] = first.0[t];
+            ghost.set_smoke(first.1);
+
+            let (inside, smoke) = last;
+            let ghost = self.cell_mut(at(0));
+            ghost.velocity.back = inside;
+            ghost.set_smoke(smoke);
+        }
     }
 
-    fn integrate(&mut self, log: &Logger, dt: Scalar, gravity: Vector2) {
-        debug!(log, "Integrate grid.");
+    /// The sides of `axis` are periodic.
+    pub fn is_periodic(&self, axis: usize) -> bool {
+        return self.boundary[2 * axis] == BoundaryCondition::Periodic;
+    }
 
-        for cell in self.cells.iter_mut() {
-            cell.integrate(log, dt, gravity); // integrate
+    /// The position `p` along `axis` wrapped into the inside cells if the axis is periodic.
+    #[inline(always)]
+    fn wrap(&self, p: Scalar, axis: usize) -> Scalar {
+        if !self.is_periodic(axis) {
+            return p;
         }
 
-        // Extrapolate to fluid cells on border.
-        let ranges = [
-            [idx!(0, 1), idx!(0, self.dim.y)],
-            [idx!(self.dim.x - 1, self.dim.x), idx!(0, self.dim.y)],
-            [idx!(0, self.dim.x), idx!(0, 1)],
-            [idx!(0, self.dim.x), idx!(self.dim.y - 1, self.dim.y)],
-        ];
+        let h = self.cell_width;
+        return h + (p - h).rem_euclid(self.extent[axis] - 2.0 * h);
+    }
 
-        debug!(log, "Extrapolate border.");
+    /// Mark the inside cells as solid where `mask` is set.
+    /// The mask has one value per inside cell (row-major, without the border cells).
+    pub fn add_solid_mask(&mut self, mask: &[bool]) -> FluidResult<()> {
+        return self.set_inside(mask, |c, m| {
+            if *m {
+                c.mode = CellTypes::Solid;
+                c.velocity.back = Vector2::zeros();
+            }
+        });
+    }
 
-        for range in ranges {
-            let xr = range[0];
-            let yr = range[1];
+    /// Set the staggered velocities from cell-centered `values` with one value per
+    /// inside cell (row-major, without the border cells). Each face between two fluid
+    /// cells gets the average of both cells (border cells take the adjacent inside value).
+    pub fn set_inside_velocity(&mut self, values: &[Vector2]) -> FluidResult<()> {
+        let dim = self.check_inside_values(values.len())?;
 
-            for (i, j) in (xr[0]..xr[1]).cartesian_product(yr[0]..yr[1]) {
-                let idx = idx!(i, j);
+        let center = |idx: Index2| {
+            let i = idx.x.clamp(1, dim.x) - 1;
+            let j = idx.y.clamp(1, dim.y) - 1;
+            return values[j * dim.x + i];
+        };
 
-                if self.cell(idx).mode == CellTypes::Solid {
+        for (idx, k) in self.iter_rows(Index2::zeros(), self.dim).flat_map(GridRow::indices) {
+            for dir in 0..2 {
+                if idx[dir] == 0 {
                     continue;
                 }
 
-                for dir in 0..2 {
-                    let pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
+                let mut n = idx;
+                n[dir] -= 1;
 
-                    // Just sample on the inside grid by clamping.
-                    self.cell_mut(idx).velocity.back[dir] = self.sample_field(
-                        idx!(1, 1),
-                        self.dim - idx!(1, 1),
-                        pos,
-                        Some(dir),
-                        |cell: &Cell| cell.velocity.back[dir],
-                    );
+                let is_fluid = |i: Index2| self.cell(i).mode == CellTypes::Fluid;
+                let touches_inside = self.is_inside_border(idx) || self.is_inside_border(n);
+
+                if touches_inside && is_fluid(idx) && is_fluid(n) {
+                    self.cells[k].velocity.back[dir] = 0.5 * (center(idx)[dir] + center(n)[dir]);
                 }
             }
         }
+
+        return Ok(());
     }
 
-    fn solve_incompressibility(
+    /// Skip the projection and advection of the blocks of cells in which the velocity
+    /// and the smoke change by less than `threshold` per step (`0`: disabled).
+    /// The parallel stencil modes always compute all cells.
+    pub fn set_activity_threshold(&mut self, threshold: Scalar) {
+        self.active = if threshold > 0.0 {
+            Some(ActiveRegion::new(self.dim, threshold))
+        } else {
+            None
+        };
+    }
+
+    /// Advect the smoke only in the bounding box of the cells with more smoke than
+    /// `threshold` and a halo around it (`0`: disabled). Smoke below the threshold
+    /// outside of the box is not moved.
+    pub fn set_smoke_threshold(&mut self, threshold: Scalar) {
+        self.smoke_region = if threshold > 0.0 {
+            Some(SmokeRegion::new(threshold))
+        } else {
+            None
+        };
+    }
+
+    /// The pressure solve of `Integrate::solve_incompressibility` for a grid of `W x H`
+    /// cells (including the border). In the `Single` execution mode the dimension is
+    /// a compile-time constant of the sweeps (see `FixedGrid`).
+    /// Fails if the dimension of the grid is not `W x H`.
+    pub fn solve_incompressibility_fixed<const W: usize, const H: usize>(
         &mut self,
         log: &Logger,
         dt: Scalar,
-        iterations: u64,
-        density: Scalar,
-        execution_mode: ExecutionMode,
-    ) {
-        match execution_mode {
-            ExecutionMode::Parallel => {
-                self.solve_incompressibility_parallel(log, dt, iterations, density, false);
-            }
-            ExecutionMode::ParallelUnsafe => {
-                self.solve_incompressibility_parallel(log, dt, iterations, density, true);
-            }
-            ExecutionMode::Single => {
-                self.solve_incompressibility_sequential(log, dt, iterations, density);
-            }
+        params: &SolverParams,
+    ) -> FluidResult<()> {
+        if self.dim != idx!(W, H) {
+            return Err(Error::DimensionMismatch {
+                dim: self.dim,
+                expected: idx!(W, H),
+            });
         }
-        self.compute_stats(&log);
-    }
 
-    fn advect(&mut self, log: &slog::Logger, dt: Scalar) {
-        self.advect_velocity(log, dt);
-        self.advect_smoke(log, dt);
-    }
-}
+        let single = matches!(params.execution_mode, ExecutionMode::Single);
+        if !single || self.pressure_solver.is_some() {
+            return self.solve_incompressibility(log, dt, params);
+        }
+
+        let (iterations, density) = (params.iterations, params.density);
+
+        if let Some(ref mut active) = self.active {
+            active.update(&self.cells);
+        }
+        self.solve_incompressibility_sequential(log, dt, iterations, density, idx!(W, H));
+        self.solve_stats = SolveStats {
+            iterations,
+            residual: None,
+        };
+        self.compute_stats(log);
+        return Ok(());
+    }
+
+    /// Replace the pressure solve of the steps (`SorSolver` by default).
+    /// Copies of the grid (e.g. `snapshot`) use the default.
+    pub fn set_pressure_solver(&mut self, solver: Box<dyn PressureSolver>) {
+        self.pressure_solver = Some(solver);
+    }
+
+    /// Fails if the default pressure solve can not run in `execution_mode` on this grid:
+    /// the sweeps of `Parallel` and `ParallelUnsafe` need even dimensions.
+    pub fn check_execution_mode(&self, execution_mode: ExecutionMode) -> FluidResult<()> {
+        let parallel = matches!(
+            execution_mode,
+            ExecutionMode::Parallel | ExecutionMode::ParallelUnsafe
+        );
+        let odd = self.dim.x % 2 != 0 || self.dim.y % 2 != 0;
+        if parallel && odd && self.pressure_solver_name() == "sor" {
+            let dim = self.dim - idx!(2, 2);
+            let message = format!("{:?} needs even grid dimensions, not {}", execution_mode, dim);
+            return Err(Error::invalid_parameter("execution_mode", message));
+        }
+        return Ok(());
+    }
+
+    pub fn pressure_solver_name(&self) -> &str {
+        return self.pressure_solver.as_ref().map_or("sor", |s| s.name());
+    }
+
+    /// The statistics of the last pressure solve.
+    pub fn solve_stats(&self) -> SolveStats {
+        return self.solve_stats;
+    }
+
+    /// Replace the advection scheme of `field` (`SemiLagrangian` by default).
+    /// Copies of the grid (e.g. `snapshot`) use the default.
+    pub fn set_advection_scheme(&mut self, field: AdvectedField, scheme: Box<dyn AdvectionScheme>) {
+        self.advection[field as usize] = Some(scheme);
+    }
+
+    pub fn advection_scheme_name(&self, field: AdvectedField) -> &str {
+        return self.advection[field as usize]
+            .as_ref()
+            .map_or("semi-lagrangian", |s| s.name());
+    }
+
+    /// Integrate the body forces (the gravity and the vorticity confinement) with
+    /// `integrator`. The gravity alone is constant, for which all integrators are exact,
+    /// hence only the confinement is evaluated in several stages.
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Trace the departure points of the semi-Lagrangian advection with `integrator`.
+    /// Higher orders follow curved streamlines more closely with large time steps.
+    pub fn set_backtrace(&mut self, integrator: Integrator) {
+        self.backtrace = integrator;
+    }
+
+    /// Give bit-identical results for any number of threads. Only the `ParallelBands`
+    /// pressure solve depends on the number of threads otherwise, it then uses bands
+    /// of `DETERMINISTIC_BAND` rows. All other sweeps write each cell from one place in
+    /// a fixed order and the reductions of the statistics are minima and maxima, which
+    /// do not depend on the order.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        return self.deterministic;
+    }
+
+    /// A hash of the bits of the velocity, the pressure, the smoke and the mode of all
+    /// cells, e.g. to compare the state of two runs.
+    pub fn state_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for cell in &self.cells {
+            cell.velocity.back.map(Scalar::to_bits).as_slice().hash(&mut hasher);
+            cell.pressure.to_bits().hash(&mut hasher);
+            cell.smoke().to_bits().hash(&mut hasher);
+            (cell.mode == CellTypes::Fluid).hash(&mut hasher);
+        }
+
+        return hasher.finish();
+    }
+
+    /// Advect `field` with its scheme.
+    fn advect_field(
+        &mut self,
+        log: &Logger,
+        field: AdvectedField,
+        dt: Scalar,
+        execution_mode: ExecutionMode,
+    ) {
+        let mut scheme = self.advection[field as usize].take();
+        match scheme {
+            Some(ref mut s) => s.advect(log, self, field, dt, execution_mode),
+            None => SemiLagrangian.advect(log, self, field, dt, execution_mode),
+        }
+        self.advection[field as usize] = scheme;
+    }
+
+    /// Precompute the stencils of the sequential pressure solve once per step
+    /// instead of evaluating the cell modes in each iteration.
+    pub fn set_stencil_table(&mut self, enabled: bool) {
+        self.stencil_table = if enabled {
+            Some(StencilTable::default())
+        } else {
+            None
+        };
+    }
+
+    pub fn active_region(&self) -> Option<&ActiveRegion> {
+        return self.active.as_ref();
+    }
+
+    pub fn smoke_region(&self) -> Option<&SmokeRegion> {
+        return self.smoke_region.as_ref();
+    }
+
+    /// The bytes of the cell fields (all cells), the scratch fields, the active region
+    /// and the stencil table of the grid.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        Grid::report_cells(&mut report, self.cells.capacity());
+        report.add("scratch", MemoryLocation::Host, self.scratch.bytes());
+
+        if let Some(active) = self.active.as_ref() {
+            report.add("active-region", MemoryLocation::Host, active.bytes());
+        }
+        if let Some(table) = self.stencil_table.as_ref() {
+            report.add("stencil-table", MemoryLocation::Host, table.bytes());
+        }
+
+        return report;
+    }
+
+    /// The bytes of the cell fields of a grid with `dim` inside cells, e.g. to check
+    /// whether a resolution fits into memory before allocating the grid.
+    pub fn estimate_memory(dim: Index2) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        Grid::report_cells(&mut report, (dim.x + 2) * (dim.y + 2));
+        return report;
+    }
+
+    fn report_cells(report: &mut MemoryReport, cells: usize) {
+        for (name, size) in Cell::field_sizes() {
+            report.add(&format!("cells.{}", name), MemoryLocation::Host, cells * size);
+        }
+    }
+
+    /// Is cell `idx` in the active region (always if not tracked).
+    #[inline(always)]
+    fn is_active(&self, idx: Index2) -> bool {
+        return self.active.as_ref().map_or(true, |a| a.is_active(idx));
+    }
+
+    /// The pool of the temporary fields of the steps.
+    pub fn scratch(&self) -> &ScratchPool {
+        return &self.scratch;
+    }
+
+    /// Index of cell `(0, 0)` in the simulated domain.
+    pub fn origin(&self) -> Index2 {
+        return self.origin;
+    }
+
+    /// World position of the lower-left corner of cell `(0, 0)` in the simulated domain.
+    pub fn origin_position(&self) -> Vector2 {
+        return self.origin.cast::<Scalar>() * self.cell_width;
+    }
+
+    /// A copy of the whole grid (e.g. to write it on another thread).
+    pub fn snapshot(&self) -> Grid {
+        return self.crop(Index2::zeros(), self.dim);
+    }
+
+    /// A copy of the cells in the window `[min, max)` (clamped to the grid) as a new grid
+    /// without additional border cells. Positions of the copy are local to the window,
+    /// `origin()` gives its placement in the domain.
+    pub fn crop(&self, min: Index2, max: Index2) -> Grid {
+        let max = clamp_to_range(Index2::zeros(), self.dim, max);
+        let min = clamp_to_range(Index2::zeros(), max, min);
+
+        return self.crop_into(min, max, vec![]);
+    }
+
+    /// The copy of `crop` for a valid window with the cells stored in `cells` (emptied).
+    pub(crate) fn crop_into(&self, min: Index2, max: Index2, mut cells: Vec<Cell>) -> Grid {
+        let dim = max - min;
+
+        cells.clear();
+        cells.extend(
+            self.iter_rows(min, max)
+                .flat_map(GridRow::indices)
+                .map(|(idx, k)| self.cells[k].moved_to(idx - min)),
+        );
+
+        return Grid {
+            dim,
+            cell_width: self.cell_width,
+            cells,
+            stats: self.stats.clone(),
+            over_relaxation: self.over_relaxation,
+            vorticity_confinement: self.vorticity_confinement,
+            extent: dim.cast::<Scalar>() * self.cell_width,
+            origin: self.origin + min,
+            offsets: self.offsets,
+            boundary: self.boundary,
+            scratch: ScratchPool::default(),
+            active: None,
+            stencil_table: None,
+            smoke_region: None,
+            pressure_solver: None,
+            solve_stats: self.solve_stats,
+            advection: [None, None],
+            integrator: self.integrator,
+            backtrace: self.backtrace,
+            deterministic: self.deterministic,
+        };
+    }
+
+    /// Set the cells and the statistics to the ones of `state`, a `snapshot` of this
+    /// grid. The settings of the steps are kept, the active region starts over
+    /// (everything is active in the next step). Fails if the dimensions differ.
+    pub fn restore(&mut self, state: &Grid) -> FluidResult<()> {
+        if state.dim != self.dim {
+            return Err(Error::DimensionMismatch {
+                dim: state.dim,
+                expected: self.dim,
+            });
+        }
+
+        self.cells.clone_from_slice(&state.cells);
+        self.stats = state.stats.clone();
+        self.solve_stats = state.solve_stats;
+
+        if let Some(ref mut active) = self.active {
+            *active = ActiveRegion::new(self.dim, active.threshold);
+        }
+        return Ok(());
+    }
+
+    /// All cells in storage order (rows of `dim.x` cells).
+    pub(crate) fn cells(&self) -> &[Cell] {
+        return &self.cells;
+    }
+
+    pub(crate) fn cells_mut(&mut self) -> &mut [Cell] {
+        return &mut self.cells;
+    }
+
+    /// Move the cells out of the grid, which is left empty.
+    pub(crate) fn take_cells(&mut self) -> Vec<Cell> {
+        return std::mem::take(&mut self.cells);
+    }
+
+    /// Add the velocity `vel` to all fluid cells within `radius` cells around `index`.
+    /// The impulse falls off linearly with the distance.
+    pub fn add_impulse(&mut self, index: Index2, vel: Vector2, radius: Scalar) {
+        self.splat(index, radius, |cell, w| cell.velocity.back += vel * w);
+    }
+
+    /// Add the smoke `amount` to all fluid cells within `radius` cells around `index`
+    /// with the falloff of `add_impulse`.
+    pub fn add_smoke(&mut self, index: Index2, amount: Scalar, radius: Scalar) {
+        self.splat(index, radius, |cell, w| cell.set_smoke(cell.smoke() + amount * w));
+    }
+
+    /// Call `f` with the weight `1 - d / (radius + 1)` for all fluid cells within
+    /// the distance `d <= radius` cells around `index`.
+    fn splat<F: FnMut(&mut Cell, Scalar)>(&mut self, index: Index2, radius: Scalar, mut f: F) {
+        let r = radius.ceil() as usize;
+        let min = idx!(index.x.saturating_sub(r), index.y.saturating_sub(r));
+        let max = index + idx!(r + 1, r + 1);
+
+        for (idx, k) in self.iter_rows(min, max).flat_map(GridRow::indices) {
+            let d = (idx.cast::<Scalar>() - index.cast::<Scalar>()).norm();
+            let cell = &mut self.cells[k];
+
+            if d <= radius && cell.mode == CellTypes::Fluid {
+                f(cell, 1.0 - d / (radius + 1.0));
+            }
+        }
+    }
+
+    /// Set the inside cells from `values` with one value per inside cell
+    /// (row-major, without the border cells).
+    pub fn set_inside<T, F: Fn(&mut Cell, &T)>(&mut self, values: &[T], set: F) -> FluidResult<()> {
+        let dim = self.check_inside_values(values.len())?;
+
+        for row in self.iter_rows_inside() {
+            let values = &values[(row.y - 1) * dim.x..row.y * dim.x];
+            for (c, v) in self.row_cells_mut(&row).iter_mut().zip(values) {
+                set(c, v);
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// The dimension of the inside cells if `len` values are one per inside cell.
+    fn check_inside_values(&self, len: usize) -> FluidResult<Index2> {
+        let dim = self.dim - idx!(2, 2);
+        if len != dim.x * dim.y {
+            let message = format!("{} values for {} x {} inside cells", len, dim.x, dim.y);
+            return Err(Error::invalid_parameter("values", message));
+        }
+
+        return Ok(dim);
+    }
+
+    /// Add the vorticity confinement force `eps * h * (N x w)` which
+    /// counteracts the numerical dissipation of small vortices.
+    fn apply_vorticity_confinement(&mut self, log: &Logger, dt: Scalar) {
+        if self.vorticity_confinement <= 0.0 {
+            return;
+        }
+
+        debug!(log, "Apply vorticity confinement.");
+
+        let h = self.cell_width;
+        let h2_inv = 0.5 / h;
+        let offset = |index: Index2, dir: usize, sign: isize| {
+            let mut i = index;
+            i[dir] = (i[dir] as isize + sign) as usize;
+            return i;
+        };
+
+        let mut curl = self.scratch.take::<Scalar>(self.cells.len());
+        let mut forces = self.scratch.take::<Vector2>(self.cells.len());
+
+        // Curl at the cell centers (zero on the border).
+        for (idx, k) in self.iter_rows_inside().flat_map(GridRow::indices) {
+            let v = |i: Index2| self.cell_center_velocity(i);
+            let dvy_dx = v(offset(idx, 0, 1)).y - v(offset(idx, 0, -1)).y;
+            let dvx_dy = v(offset(idx, 1, 1)).x - v(offset(idx, 1, -1)).x;
+            curl[k] = (dvy_dx - dvx_dy) * h2_inv;
+        }
+
+        let w = |i: Index2| curl[i.x + i.y * self.dim.x];
+        let eps = self.vorticity_confinement;
+
+        for (idx, k) in self.iter_rows_inside().flat_map(GridRow::indices) {
+            if self.cells[k].mode != CellTypes::Fluid {
+                continue;
+            }
+            let grad = vec2!(
+                w(offset(idx, 0, 1)).abs() - w(offset(idx, 0, -1)).abs(),
+                w(offset(idx, 1, 1)).abs() - w(offset(idx, 1, -1)).abs()
+            ) * h2_inv;
+            let n = grad / (grad.norm() + 1e-10);
+
+            forces[k] = eps * h * vec2!(n.y * w(idx), -n.x * w(idx));
+        }
+
+        // Distribute the cell-centered force onto the adjacent faces.
+        for (idx, k) in self.iter_rows_inside().flat_map(GridRow::indices) {
+            if self.cells[k].mode != CellTypes::Fluid {
+                continue;
+            }
+            let f = forces[k];
+            self.cells[k].velocity.back += 0.5 * dt * f;
+
+            for dir in 0..2 {
+                let nb = self.cell_mut(offset(idx, dir, 1));
+                if nb.mode == CellTypes::Fluid {
+                    nb.velocity.back[dir] += 0.5 * dt * f[dir];
+                }
+            }
+        }
+
+        self.scratch.give(curl);
+        self.scratch.give(forces);
+    }
+
+    pub(crate) fn compute_stats(&mut self, log: &Logger) {
+        // Parallelized accumulation of statistics.
+        self.stats[0] = self
+            .cells
+            .par_iter()
+            .map(|c| Stats::from(c))
+            .reduce(|| Stats::identity::<0>(), |a, b| Stats::min(&a, &b));
+
+        self.stats[1] = self
+            .cells
+            .par_iter()
+            .map(|c| Stats::from(c))
+            .reduce(|| Stats::identity::<1>(), |a, b| Stats::max(&a, &b));
+
+        info!(
+            log,
+            "Divergence range: {:.4?}, {:.4?}", self.stats[0].div, self.stats[1].div
+        );
+        info!(
+            log,
+            "Pressure range: {:.4?}, {:.4?}", self.stats[0].pressure, self.stats[1].pressure
+        );
+        info!(
+            log,
+            "Velocity range: {:.4?}, {:.4?}",
+            self.stats[0].velocity_norm,
+            self.stats[1].velocity_norm
+        );
+    }
+}
+
+pub trait CellGetter<'a, I> {
+    type Item: 'a;
+
+    type Output = &'a Self::Item;
+    type OutputMut = &'a mut Self::Item;
+
+    fn cell(&'a self, index: I) -> Self::Output;
+    fn cell_mut(&'a mut self, index: I) -> Self::OutputMut;
+
+    type OutputOpt = Option<&'a Self::Item>;
+    type OutputMutOpt = Option<&'a mut Self::Item>;
+
+    fn cell_opt(&'a self, index: Index2) -> Self::OutputOpt;
+    fn cell_mut_opt(&'a mut self, index: Index2) -> Self::OutputMutOpt;
+}
+
+impl<'t> CellGetter<'t, Index2> for Grid {
+    type Item = Cell;
+
+    fn cell(&self, index: Index2) -> &Cell {
+        return &self.cells[index.x + index.y * self.dim.x];
+    }
+
+    fn cell_mut(&mut self, index: Index2) -> &mut Cell {
+        return &mut self.cells[index.x + index.y * self.dim.x];
+    }
+
+    fn cell_opt(&self, index: Index2) -> Option<&Cell> {
+        return Grid::is_inside_range(Index2::zeros(), self.dim, index).then(|| self.cell(index));
+    }
+
+    fn cell_mut_opt(&mut self, index: Index2) -> Option<&mut Cell> {
+        return Grid::is_inside_range(Index2::zeros(), self.dim, index)
+            .then(|| self.cell_mut(index));
+    }
+}
+
+impl Grid {
+    /// Call `f` with the cells at the distinct storage indices `indices`.
+    pub fn modify_cells<F, const N: usize>(
+        &mut self,
+        indices: [usize; N],
+        mut f: F,
+    ) -> FluidResult<()>
+    where
+        F: FnMut([&mut Cell; N]),
+    {
+        let refs = self.cells.get_many_mut(indices).map_err(|_| {
+            let message = format!("{:?} are not distinct or out of range", indices);
+            return Error::invalid_parameter("indices", message);
+        })?;
+
+        f(refs);
+        return Ok(());
+    }
+}
+
+impl Integrate for Grid {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn memory_report(&self) -> MemoryReport {
+        return Grid::memory_report(self);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn reset(&mut self, log: &Logger) {
+        info!(log, "Reset stats.");
+        self.stats = [Stats::min_identity(), Stats::max_identity()];
+    }
+
+    fn integrate(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        debug!(log, "Integrate grid.");
+
+        let staged = !matches!(self.integrator, Integrator::Euler);
+        if staged && self.vorticity_confinement > 0.0 {
+            // The gravity alone is integrated exactly by Euler.
+            self.integrate_staged(log, dt, params.gravity);
+        } else {
+            self.integrate_euler(log, dt, params);
+        }
+
+        debug!(log, "Apply the boundary conditions.");
+        self.apply_boundary();
+    }
+
+    fn solve_incompressibility(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        params: &SolverParams,
+    ) -> FluidResult<()> {
+        self.check_execution_mode(params.execution_mode)?;
+
+        if let Some(ref mut active) = self.active {
+            active.update(&self.cells);
+            debug!(log, "Active blocks: {:.1} %", 100.0 * active.active_fraction());
+        }
+
+        let params = params.solve_params();
+
+        let mut solver = self.pressure_solver.take();
+        self.solve_stats = match solver {
+            Some(ref mut s) => s.solve(log, self, dt, &params),
+            None => SorSolver.solve(log, self, dt, &params),
+        };
+        self.pressure_solver = solver;
+
+        debug!(log, "Pressure solve '{}': {:?}", self.pressure_solver_name(), self.solve_stats);
+        self.compute_stats(&log);
+        return Ok(());
+    }
+
+    fn advect(&mut self, log: &slog::Logger, dt: Scalar, execution_mode: ExecutionMode) {
+        for field in AdvectedField::ALL {
+            self.advect_field(log, field, dt, execution_mode);
+        }
+
+        if let Some(ref mut active) = self.active {
+            active.store(&self.cells);
+        }
+    }
+
+    fn advect_and_integrate(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        // The confinement and the activity tracking need the velocity without the force.
+        // Only the semi-Lagrangian smoke advection adds the force while writing.
+        let smoke_scheme = &self.advection[AdvectedField::Smoke as usize];
+        if self.vorticity_confinement > 0.0 || self.active.is_some() || smoke_scheme.is_some() {
+            self.advect(log, dt, params.execution_mode);
+            self.integrate(log, dt, params);
+            return;
+        }
+
+        let mode = params.execution_mode;
+        self.advect_field(log, AdvectedField::Velocity, dt, mode);
+        self.advect_smoke(log, dt, Some(dt * params.gravity), mode);
+
+        debug!(log, "Apply the boundary conditions.");
+        self.apply_boundary();
+    }
+}
+
+impl Grid {
+    /// The Gauss-Seidel pressure solve of `SorSolver` in the variant of the execution mode.
+    pub(crate) fn solve_sor(&mut self, log: &Logger, dt: Scalar, params: &SolveParams) {
+        let (iterations, density) = (params.iterations, params.density);
+
+        match params.execution_mode {
+            ExecutionMode::Parallel => {
+                self.solve_incompressibility_parallel(log, dt, iterations, density, false);
+            }
+            ExecutionMode::ParallelUnsafe => {
+                self.solve_incompressibility_parallel(log, dt, iterations, density, true);
+            }
+            ExecutionMode::ParallelBands => {
+                self.solve_incompressibility_bands(log, dt, iterations, density);
+            }
+            ExecutionMode::Tiled => {
+                self.solve_incompressibility_tiled(log, dt, iterations, density);
+            }
+            ExecutionMode::Jacobi => {
+                self.solve_incompressibility_jacobi(log, dt, iterations, density);
+            }
+            ExecutionMode::Single => {
+                self.solve_incompressibility_sequential(log, dt, iterations, density, self.dim);
+            }
+        }
+    }
 
-impl Grid {
     #[inline(always)]
     fn apply_pos_stencils<T>(&mut self, use_unsafe: bool, min: Index2, max: Index2, func: T)
     where
@@ -342,25 +1414,417 @@ impl Grid {
         }
     }
 
-    fn solve_incompressibility_parallel(
+    fn solve_incompressibility_parallel(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+        use_unsafe: bool,
+    ) {
+        // Checked by `check_execution_mode` before each solve.
+        debug_assert!(
+            self.dim.x % 2 == 0 && self.dim.y % 2 == 0,
+            "Internal grid dimensions (dim = {} - 1) must be divisible
+             by 2 in each direction.",
+            self.dim
+        );
+
+        let r = self.over_relaxation;
+        let cp = density * self.cell_width / dt;
+
+        let s_factor = |cell: &mut Cell| {
+            return if cell.mode == CellTypes::Solid {
+                0.0
+            } else {
+                1.0
+            };
+        };
+
+        debug!(log, "Distribute all 's' factors for total sum.");
+        self.apply_pos_stencils(
+            use_unsafe,
+            idx!(0, 0),
+            self.dim,
+            |s: PosStencilMut<Cell>| {
+                // This parallel run runs over all edges affected in the simulation domain.
+                // We also run over some boundary cells
+                // which we will anyway not use later.
+                let cell_s = s_factor(s.cell);
+
+                // This cell (1: pos, 0: x)  <-- s from pos x-neighbor.
+                s.cell.s_nbs[1][0] = s_factor(s.neighbors[0]);
+                // This cell (1: pos, 1: y) <-- s from pos y-neighbor.
+                s.cell.s_nbs[1][1] = s_factor(s.neighbors[1]);
+
+                // Pos. x-neighbor (0: neg, 0: x) <-- s from this cell.
+                s.neighbors[0].s_nbs[0][0] = cell_s;
+                // Pos. x-neighbor (0: neg, 1: y) <-- s from this cell.
+                s.neighbors[1].s_nbs[0][1] = cell_s;
+            },
+        );
+
+        debug!(log, "Sum all 's' factors in all cells.");
+        self.cells.par_iter_mut().for_each(|c: &mut Cell| {
+            if c.mode == CellTypes::Solid {
+                return;
+            }
+
+            // Reset pressure field.
+            c.pressure = 0.0;
+
+            let mut sum = 0.0;
+            c.s_nbs.iter().for_each(|s| {
+                sum += s.sum();
+            });
+
+            // Store the inverse.
+            c.s_tot_inv = if sum != 0.0 {
+                1.0 / sum
+            } else {
+                debug_assert!(
+                    false,
+                    "Cell with index: '{}' [solid: {:?} contains only solid neighbors.",
+                    c.index(),
+                    c.mode,
+                );
+                0.0
+            };
+        });
+
+        for _iter in 0..iterations {
+            self.apply_pos_stencils(
+                use_unsafe,
+                idx!(1, 1),
+                self.dim,
+                |s: PosStencilMut<Cell>| {
+                    // This parallel run runs stencils over the simulation domain:
+                    // The `s.cell` will covers all cells in the simulation domain.
+
+                    if s.cell.mode == CellTypes::Solid {
+                        return;
+                    }
+
+                    debug_assert!(
+                        s.cell.s_tot_inv != 0.0,
+                        "Cell with index: '{}' contains only fluid neighbors.",
+                        s.cell.index()
+                    );
+
+                    s.cell.div = 0.0;
+                    for dir in 0..2 {
+                        s.cell.div +=
+                            s.neighbors[dir].velocity.back[dir] - s.cell.velocity.back[dir]
+                    }
+
+                    let div_normed = s.cell.div * s.cell.s_tot_inv;
+
+                    s.cell.pressure -= cp * div_normed;
+
+                    // Velocity update own cell.
+                    s.cell.velocity.back += r * s.cell.s_nbs[0] * div_normed;
+
+                    // Velocity update neighbors in x-direction.
+                    // Solid cells have s_nbs[_] == 0.
+                    s.neighbors[0].velocity.back[0] -= r * s.cell.s_nbs[1].x * div_normed;
+                    // Velocity update neighbors in y-direction.
+                    s.neighbors[1].velocity.back[1] -= r * s.cell.s_nbs[1].y * div_normed;
+                },
+            );
+        }
+    }
+
+    /// Reset the pressure and compute the fluid factors `s_nbs` and `s_tot_inv`
+    /// of all cells for `relax_cell`.
+    fn prepare_fluid_factors(&mut self, log: &Logger) {
+        let dim = self.dim;
+
+        debug!(log, "Compute the fluid factors 's' of all inside cells.");
+        let mut s_nbs = self.scratch.take::<[Vector2; 2]>(dim.x * dim.y);
+        s_nbs.par_iter_mut().enumerate().for_each(|(i, s_nb)| {
+            let idx = idx!(i % dim.x, i / dim.x);
+            if !self.is_inside_border(idx) {
+                return;
+            }
+
+            let s = |i: Index2| (self.cell(i).mode == CellTypes::Fluid) as u8 as Scalar;
+            let nbs = Grid::get_neighbors_indices(idx);
+            *s_nb = nbs.map(|n| vec2!(s(n[0]), s(n[1])));
+        });
+
+        self.cells.par_iter_mut().zip(&s_nbs).for_each(|(c, s)| {
+            let sum = s[0].sum() + s[1].sum();
+            c.pressure = 0.0;
+            c.s_nbs = *s;
+            c.s_tot_inv = if sum != 0.0 { 1.0 / sum } else { 0.0 };
+        });
+
+        self.scratch.give(s_nbs);
+    }
+
+    /// The Gauss-Seidel update of cell `k` in `cells` (rows of `stride` cells)
+    /// with the fluid factors of `prepare_fluid_factors`.
+    #[inline(always)]
+    fn relax_cell(cells: &mut [Cell], k: usize, stride: usize, cp: Scalar, r: Scalar) {
+        let cell = &cells[k];
+
+        if cell.mode == CellTypes::Solid || cell.s_tot_inv == 0.0 {
+            return;
+        }
+
+        let div = cells[k + 1].velocity.back.x - cell.velocity.back.x
+            + cells[k + stride].velocity.back.y
+            - cell.velocity.back.y;
+        let div_normed = div * cell.s_tot_inv;
+        let s_nbs = cell.s_nbs;
+
+        let cell = &mut cells[k];
+        cell.div = div;
+        cell.pressure -= cp * div_normed;
+        cell.velocity.back += r * s_nbs[0] * div_normed;
+
+        cells[k + 1].velocity.back.x -= r * s_nbs[1].x * div_normed;
+        cells[k + stride].velocity.back.y -= r * s_nbs[1].y * div_normed;
+    }
+
+    /// Gauss-Seidel over tiles of `TILE_SIZE x TILE_SIZE` cells which fit into the L2 cache.
+    /// Each tile is swept `TILE_SWEEPS` times before moving on, hence the neighbors
+    /// across a tile seam lag behind. `SEAM_SWEEPS` extra sweeps over the cells at
+    /// the seams at the end make up for that.
+    fn solve_incompressibility_tiled(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+    ) {
+        let r = self.over_relaxation;
+        let cp = density * self.cell_width / dt;
+        let dim = self.dim;
+
+        self.prepare_fluid_factors(log);
+
+        let active = self.active.as_ref();
+        let is_active = |x: usize, y: usize| active.map_or(true, |a| a.is_active(idx!(x, y)));
+
+        let cells = self.cells.as_mut_slice();
+        let (end_x, end_y) = (dim.x - 1, dim.y - 1);
+
+        let mut done = 0;
+        while done < iterations {
+            let sweeps = TILE_SWEEPS.min(iterations - done);
+            done += sweeps;
+
+            for ty in (1..end_y).step_by(TILE_SIZE) {
+                for tx in (1..end_x).step_by(TILE_SIZE) {
+                    for _ in 0..sweeps {
+                        for y in ty..(ty + TILE_SIZE).min(end_y) {
+                            let xs = tx..(tx + TILE_SIZE).min(end_x);
+                            for x in xs.filter(|x| is_active(*x, y)) {
+                                Grid::relax_cell(cells, x + y * dim.x, dim.x, cp, r);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if iterations == 0 {
+            return;
+        }
+
+        // Cells next to a seam between two tiles.
+        let at_seam = |i: usize, end: usize| {
+            let t = (i - 1) % TILE_SIZE;
+            return (t == 0 && i > 1) || (t == TILE_SIZE - 1 && i + 1 < end);
+        };
+
+        debug!(log, "Sweep the tile seams.");
+        for _ in 0..SEAM_SWEEPS {
+            for y in 1..end_y {
+                let seam_row = at_seam(y, end_y);
+                let is_seam = |x: &usize| (seam_row || at_seam(*x, end_x)) && is_active(*x, y);
+                for x in (1..end_x).filter(is_seam) {
+                    Grid::relax_cell(cells, x + y * dim.x, dim.x, cp, r);
+                }
+            }
+        }
+    }
+
+    /// Gauss-Seidel sweeps over bands of rows. Each band only writes its own cells
+    /// and the `y`-velocity of the row above it, hence all even bands and then all
+    /// odd bands are swept in parallel. The bands are sized to give each thread a few
+    /// (or have `DETERMINISTIC_BAND` rows in deterministic mode).
+    fn solve_incompressibility_bands(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+    ) {
+        let r = self.over_relaxation;
+        let cp = density * self.cell_width / dt;
+        let dim = self.dim;
+
+        self.prepare_fluid_factors(log);
+
+        let band = if self.deterministic {
+            DETERMINISTIC_BAND
+        } else {
+            ((dim.y - 2) / (4 * rayon::current_num_threads())).max(1)
+        };
+        let active = self.active.as_ref();
+        let is_active = |x: usize, y: usize| active.map_or(true, |a| a.is_active(idx!(x, y)));
+
+        let sweep = |(y0, rows): (usize, &mut [Cell])| {
+            for y in y0.max(1)..(y0 + band).min(dim.y - 1) {
+                for x in (1..dim.x - 1).filter(|x| is_active(*x, y)) {
+                    Grid::relax_cell(rows, x + (y - y0) * dim.x, dim.x, cp, r);
+                }
+            }
+        };
+
+        for _iter in 0..iterations {
+            for odd in [false, true] {
+                grid_stencil::row_bands_mut(self.cells.as_mut_slice(), dim, band, odd)
+                    .for_each(&sweep);
+            }
+        }
+    }
+
+    /// Weighted Jacobi iterations. Each iteration first computes the divergence of all
+    /// cells from the old velocities and then lets every cell apply the corrections of
+    /// itself and its left and lower neighbor to its own faces. No cell is written by two
+    /// threads, so the rows are processed in parallel without locks and the result does
+    /// not depend on the number of threads.
+    fn solve_incompressibility_jacobi(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+    ) {
+        self.jacobi_iterations(log, dt, iterations, density, |_| {});
+    }
+
+    /// The iterations of `solve_incompressibility_jacobi`, which call `sync` with the cells
+    /// before and with the normalized divergences after the divergence of each iteration,
+    /// e.g. to exchange the halo rows of a subdomain (see `Subdomain`).
+    pub(crate) fn jacobi_iterations<S>(
+        &mut self,
+        log: &Logger,
+        dt: Scalar,
+        iterations: u64,
+        density: Scalar,
+        mut sync: S,
+    ) where
+        S: FnMut(JacobiSync),
+    {
+        let r = JACOBI_RELAXATION;
+        let cp = density * self.cell_width / dt;
+        let dim = self.dim;
+        let stride = dim.x;
+
+        self.prepare_fluid_factors(log);
+
+        let active = self.active.as_ref();
+        let is_relaxed = |cell: &Cell, x: usize, y: usize| {
+            return x > 0
+                && x < dim.x - 1
+                && y > 0
+                && y < dim.y - 1
+                && cell.mode != CellTypes::Solid
+                && cell.s_tot_inv != 0.0
+                && active.map_or(true, |a| a.is_active(idx!(x, y)));
+        };
+
+        let mut div = self.scratch.take::<Scalar>(dim.x * dim.y);
+        let mut div_normed = self.scratch.take::<Scalar>(dim.x * dim.y);
+
+        for _iter in 0..iterations {
+            sync(JacobiSync::Cells(&mut self.cells));
+
+            let cells = self.cells.as_slice();
+            div.par_chunks_mut(stride)
+                .zip(div_normed.par_chunks_mut(stride))
+                .enumerate()
+                .for_each(|(y, (div, div_normed))| {
+                    for x in 0..stride {
+                        let k = x + y * stride;
+                        let cell = &cells[k];
+
+                        (div[x], div_normed[x]) = if is_relaxed(cell, x, y) {
+                            let d = cells[k + 1].velocity.back.x - cell.velocity.back.x
+                                + cells[k + stride].velocity.back.y
+                                - cell.velocity.back.y;
+                            (d, d * cell.s_tot_inv)
+                        } else {
+                            (0.0, 0.0)
+                        };
+                    }
+                });
+
+            sync(JacobiSync::Divergence(&mut div_normed));
+
+            let (div, dn) = (&div, &div_normed);
+            self.cells
+                .par_chunks_mut(stride)
+                .enumerate()
+                .for_each(|(y, row)| {
+                    for (x, cell) in row.iter_mut().enumerate() {
+                        let k = x + y * stride;
+
+                        if is_relaxed(cell, x, y) {
+                            cell.div = div[k];
+                            cell.pressure -= cp * dn[k];
+                            cell.velocity.back += r * cell.s_nbs[0] * dn[k];
+                        }
+
+                        // The corrections of the left and lower neighbor on the shared faces.
+                        let s = (cell.mode == CellTypes::Fluid) as u8 as Scalar;
+                        if x > 0 {
+                            cell.velocity.back.x -= r * s * dn[k - 1];
+                        }
+                        if y > 0 {
+                            cell.velocity.back.y -= r * s * dn[k - stride];
+                        }
+                    }
+                });
+        }
+
+        self.scratch.give(div);
+        self.scratch.give(div_normed);
+    }
+
+    /// Gauss-Seidel sweeps over the rows of the grid of dimension `dim`.
+    /// Inlined, such that a constant `dim` gives constant loop bounds and strides
+    /// (see `solve_incompressibility_fixed`).
+    #[inline(always)]
+    fn solve_incompressibility_sequential(
         &mut self,
         log: &Logger,
         dt: Scalar,
         iterations: u64,
         density: Scalar,
-        use_unsafe: bool,
+        dim: Index2,
     ) {
-        assert!(
-            self.dim.x % 2 == 0 && self.dim.y % 2 == 0,
-            "Internal grid dimensions (dim = {} - 1) must be divisible
-             by 2 in each direction.",
-            self.dim
-        );
+        if self.stencil_table.is_some() {
+            self.solve_incompressibility_table(log, dt, iterations, density);
+            return;
+        }
+
+        // Set pressure field to zero.
+        self.cells.par_iter_mut().for_each(|c| c.pressure = 0.0);
 
-        let r = 1.9; // Overrelaxation factor.
+        let r = self.over_relaxation;
         let cp = density * self.cell_width / dt;
+        let stride = dim.x;
+        let (end_x, end_y) = (dim.x - 1, dim.y - 1);
 
-        let s_factor = |cell: &mut Cell| {
+        let active = self.active.as_ref();
+        let is_active = |x: usize, y: usize| active.map_or(true, |a| a.is_active(idx!(x, y)));
+
+        let s_factor = |cell: &Cell| {
             return if cell.mode == CellTypes::Solid {
                 0.0
             } else {
@@ -368,308 +1832,522 @@ impl Grid {
             };
         };
 
-        debug!(log, "Distribute all 's' factors for total sum.");
-        self.apply_pos_stencils(
-            use_unsafe,
-            idx!(0, 0),
-            self.dim,
-            |s: PosStencilMut<Cell>| {
-                // This parallel run runs over all edges affected in the simulation domain.
-                // We also run over some boundary cells
-                // which we will anyway not use later.
-                let cell_s = s_factor(s.cell);
-
-                // This cell (1: pos, 0: x)  <-- s from pos x-neighbor.
-                s.cell.s_nbs[1][0] = s_factor(s.neighbors[0]);
-                // This cell (1: pos, 1: y) <-- s from pos y-neighbor.
-                s.cell.s_nbs[1][1] = s_factor(s.neighbors[1]);
-
-                // Pos. x-neighbor (0: neg, 0: x) <-- s from this cell.
-                s.neighbors[0].s_nbs[0][0] = cell_s;
-                // Pos. x-neighbor (0: neg, 1: y) <-- s from this cell.
-                s.neighbors[1].s_nbs[0][1] = cell_s;
-            },
-        );
-
-        debug!(log, "Sum all 's' factors in all cells.");
-        self.cells.par_iter_mut().for_each(|c: &mut Cell| {
-            if c.mode == CellTypes::Solid {
-                return;
-            }
-
-            // Reset pressure field.
-            c.pressure = 0.0;
-
-            let mut sum = 0.0;
-            c.s_nbs.iter().for_each(|s| {
-                sum += s.sum();
-            });
-
-            // Store the inverse.
-            c.s_tot_inv = if sum != 0.0 {
-                1.0 / sum
-            } else {
-                debug_assert!(
-                    false,
-                    "Cell with index: '{}' [solid: {:?} contains only solid neighbors.",
-                    c.index(),
-                    c.mode,
-                );
-                0.0
-            };
-        });
-
         for _iter in 0..iterations {
-            self.apply_pos_stencils(
-                use_unsafe,
-                idx!(1, 1),
-                self.dim,
-                |s: PosStencilMut<Cell>| {
-                    // This parallel run runs stencils over the simulation domain:
-                    // The `s.cell` will covers all cells in the simulation domain.
-
-                    if s.cell.mode == CellTypes::Solid {
-                        return;
+            for y in 1..end_y {
+                // The rows below, at and above `y` as slices of equal length,
+                // such that the compiler can drop the bounds checks of the inner loop.
+                let (lower, upper) = self.cells.split_at_mut(y * stride);
+                let (row, upper) = upper.split_at_mut(stride);
+                let below = &lower[(y - 1) * stride..][..stride];
+                let row = &mut row[..stride];
+                let above = &mut upper[..stride];
+
+                for x in 1..end_x {
+                    if row[x].mode == CellTypes::Solid || !is_active(x, y) {
+                        continue;
                     }
 
-                    debug_assert!(
-                        s.cell.s_tot_inv != 0.0,
-                        "Cell with index: '{}' contains only fluid neighbors.",
-                        s.cell.index()
-                    );
-
-                    s.cell.div = 0.0;
-                    for dir in 0..2 {
-                        s.cell.div +=
-                            s.neighbors[dir].velocity.back[dir] - s.cell.velocity.back[dir]
+                    // Normalization values `s`
+                    // for negative/positive neighbors.
+                    // - 0: solid, 1: fluid.
+                    let s_nbs = [
+                        vec2!(s_factor(&row[x - 1]), s_factor(&below[x])),
+                        vec2!(s_factor(&row[x + 1]), s_factor(&above[x])),
+                    ];
+                    let s = s_nbs[0].sum() + s_nbs[1].sum();
+
+                    if s == 0.0 {
+                        warn!(log, "Fluid in-face count is 0.0 for {:?}", idx!(x, y));
+                        continue;
                     }
 
-                    let div_normed = s.cell.div * s.cell.s_tot_inv;
+                    // Net outflow on this cell.
+                    let vel = row[x].velocity.back;
+                    let div =
+                        (row[x + 1].velocity.back.x - vel.x) + (above[x].velocity.back.y - vel.y);
 
-                    s.cell.pressure -= cp * div_normed;
+                    // Normalize outflow to the cells we can control.
+                    let div_normed = div_by(div, s, NEIGHBOR_COUNT_INV[s as usize]);
 
-                    // Velocity update own cell.
-                    s.cell.velocity.back += r * s.cell.s_nbs[0] * div_normed;
+                    let cell = &mut row[x];
+                    cell.div = div;
+                    cell.pressure -= cp * div_normed;
 
-                    // Velocity update neighbors in x-direction.
+                    // Add outflow-part to inflows to reach net 0-outflow.
+                    // Solid cells have s_nbs[0] == 0.
+                    cell.velocity.back += r * s_nbs[0] * div_normed;
+
+                    // Subtract outflow-part to outflows to iteratively reach net 0-outflow.
                     // Solid cells have s_nbs[_] == 0.
-                    s.neighbors[0].velocity.back[0] -= r * s.cell.s_nbs[1].x * div_normed;
-                    // Velocity update neighbors in y-direction.
-                    s.neighbors[1].velocity.back[1] -= r * s.cell.s_nbs[1].y * div_normed;
-                },
-            );
+                    row[x + 1].velocity.back.x -= r * s_nbs[1].x * div_normed;
+                    above[x].velocity.back.y -= r * s_nbs[1].y * div_normed;
+                }
+            }
         }
     }
 
-    fn solve_incompressibility_sequential(
+    /// The sequential Gauss-Seidel sweeps over the entries of the stencil table,
+    /// with the same results as `solve_incompressibility_sequential`.
+    fn solve_incompressibility_table(
         &mut self,
         log: &Logger,
         dt: Scalar,
         iterations: u64,
         density: Scalar,
     ) {
-        // Set pressure field to zero.
         self.cells.par_iter_mut().for_each(|c| c.pressure = 0.0);
 
-        let r = 1.9; // Overrelaxation factor.
+        let r = self.over_relaxation;
         let cp = density * self.cell_width / dt;
+        let stride = self.dim.x;
+
+        debug!(log, "Build the stencil table.");
+        let table = self.stencil_table.as_mut().expect("No stencil table.");
+        for idx in table.build(&self.cells, self.dim, self.active.as_ref()) {
+            warn!(log, "Fluid in-face count is 0.0 for {:?}", idx);
+        }
 
+        let cells = self.cells.as_mut_slice();
         for _iter in 0..iterations {
-            for idx in self.iter_index_inside() {
-                if self.cell(idx).mode == CellTypes::Solid {
-                    continue;
-                }
+            for e in table.entries() {
+                let k = e.k as usize;
 
-                let s_factor = |index: Index2| {
-                    return if self.cell(index).mode == CellTypes::Solid {
-                        0.0
-                    } else {
-                        1.0
-                    };
-                };
+                let vel = cells[k].velocity.back;
+                let div = (cells[k + 1].velocity.back.x - vel.x)
+                    + (cells[k + stride].velocity.back.y - vel.y);
+                let div_normed = div_by(div, e.s, e.s_inv);
 
-                let nbs = Grid::get_neighbors_indices(idx);
+                let cell = &mut cells[k];
+                cell.div = div;
+                cell.pressure -= cp * div_normed;
+                cell.velocity.back += r * e.s_nbs[0] * div_normed;
 
-                // Normalization values `s`
-                // for negative/positive neighbors.
-                // - 0: solid, 1: fluid.
-                let mut s_nbs = [Vector2::zeros(), Vector2::zeros()];
-                let mut s = 0.0;
+                cells[k + 1].velocity.back.x -= r * e.s_nbs[1].x * div_normed;
+                cells[k + stride].velocity.back.y -= r * e.s_nbs[1].y * div_normed;
+            }
+        }
+    }
 
-                for neg_pos in 0..2 {
-                    s_nbs[neg_pos] = vec2!(s_factor(nbs[neg_pos][0]), s_factor(nbs[neg_pos][1]));
-                    s += s_nbs[neg_pos].sum();
-                }
+    /// Add the forces of `fields` at time `t` over the step `dt` to the fluid cells
+    /// (explicit Euler, see `ForceField`).
+    pub(crate) fn apply_force_fields(
+        &mut self,
+        log: &Logger,
+        fields: &[Box<dyn ForceField>],
+        t: Scalar,
+        dt: Scalar,
+        execution_mode: ExecutionMode,
+    ) {
+        if fields.is_empty() {
+            return;
+        }
 
-                if s == 0.0 {
-                    warn!(log, "Fluid in-face count is 0.0 for {:?}", idx);
-                    continue;
-                }
+        debug!(log, "Apply {} force fields.", fields.len());
 
-                let get_vel = |index: Index2, dir: usize| {
-                    return self.cell(index).velocity.back[dir];
-                };
+        let mut forces = self.scratch.take::<Vector2>(self.cells.len());
+        self.map_cells_into(execution_mode, &mut forces, |idx| {
+            let cell = self.cell(idx);
+            if cell.mode != CellTypes::Fluid {
+                return Vector2::zeros();
+            }
 
-                let mut div: Scalar = 0.0; // Net outflow on this cell.
-                let pos_idx = 1;
-                let pos_nbs = &nbs[pos_idx];
-                for dir in 0..2 {
-                    div += get_vel(pos_nbs[dir], dir) - get_vel(idx, dir)
-                }
+            let view = CellView {
+                index: idx,
+                mode: cell.mode.clone(),
+                velocity: self.cell_center_velocity(idx),
+                pressure: cell.pressure,
+                smoke: cell.smoke(),
+            };
+            let pos = self.cell_center(idx);
+
+            return fields
+                .iter()
+                .fold(Vector2::zeros(), |f, field| f + field.force(pos, t, &view));
+        });
 
-                self.cell_mut(idx).div = div;
+        self.cells
+            .par_iter_mut()
+            .zip(forces.par_iter())
+            .for_each(|(c, f)| c.velocity.back += dt * *f);
+
+        self.scratch.give(forces);
+    }
 
-                // Normalize outflow to the cells we can control.
-                let div_normed = div / s;
-                self.cell_mut(idx).pressure -= cp * div_normed;
+    /// Explicit Euler for the body forces: first the gravity, then the confinement of
+    /// the updated velocity.
+    fn integrate_euler(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        let integrate = |cell: &mut Cell| cell.integrate(log, dt, params);
+        match params.execution_mode {
+            ExecutionMode::Single => self.cells.iter_mut().for_each(integrate),
+            _ => self.cells.par_iter_mut().for_each(integrate),
+        }
 
-                // Add outflow-part to inflows to reach net 0-outflow.
-                // Solid cells have s_nbs[0] == 0.
-                self.cell_mut(idx).velocity.back += r * s_nbs[0] * div_normed;
+        self.apply_vorticity_confinement(log, dt);
+    }
 
-                // Subtract outflow-part to outflows to iteratively reach net 0-outflow (div(v) == 0).
-                // Solid cells have s_nbs[_] == 0.
-                self.cell_mut(nbs[pos_idx][0]).velocity.back.x -= r * s_nbs[pos_idx].x * div_normed;
-                self.cell_mut(nbs[pos_idx][1]).velocity.back.y -= r * s_nbs[pos_idx].y * div_normed;
+    /// The body forces with the stages of `self.integrator`. The slope of a stage is the
+    /// gravity on the fluid cells plus the confinement of the velocity of the stage.
+    fn integrate_staged(&mut self, log: &Logger, dt: Scalar, gravity: Vector2) {
+        let n = self.cells.len();
+        let mut start = self.scratch.take::<Vector2>(n);
+        let mut slope = self.scratch.take::<Vector2>(n);
+        let mut k = self.scratch.take::<Vector2>(n);
+
+        self.cells.par_iter().zip(start.par_iter_mut()).for_each(|(c, v)| *v = c.velocity.back);
+
+        let (offsets, weights) = self.integrator.tableau();
+        for (stage, (c, w)) in offsets.iter().zip(weights.iter()).enumerate() {
+            if stage > 0 {
+                let (c, start, k) = (*c, &start, &k);
+                self.cells.par_iter_mut().enumerate().for_each(|(i, cell)| {
+                    cell.velocity.back = start[i] + c * dt * k[i];
+                });
             }
+
+            // The confinement over a unit step is its acceleration.
+            self.cells.par_iter().zip(k.par_iter_mut()).for_each(|(c, k)| *k = c.velocity.back);
+            self.apply_vorticity_confinement(log, 1.0);
+
+            let w = *w;
+            self.cells
+                .par_iter()
+                .zip(k.par_iter_mut().zip(slope.par_iter_mut()))
+                .for_each(|(cell, (k, slope))| {
+                    *k = cell.velocity.back - *k;
+                    if cell.mode == CellTypes::Fluid {
+                        *k += gravity;
+                    }
+                    *slope = if stage == 0 { w * *k } else { *slope + w * *k };
+                });
         }
+
+        let (v, dv) = (&start, &slope);
+        self.cells.par_iter_mut().enumerate().for_each(|(i, cell)| {
+            cell.velocity.back = v[i] + dt * dv[i];
+        });
+
+        self.scratch.give(start);
+        self.scratch.give(slope);
+        self.scratch.give(k);
     }
 
-    fn advect_velocity(&mut self, log: &slog::Logger, dt: Scalar) {
-        debug!(log, "Advect velocity.");
+    /// Evaluate `f` for all cells into `out` (in storage order),
+    /// in parallel unless the execution mode is `Single`.
+    fn map_cells_into<T, F>(&self, execution_mode: ExecutionMode, out: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(Index2) -> T + Send + Sync,
+    {
+        let dim = self.dim;
+        let f = |(i, v): (usize, &mut T)| *v = f(idx!(i % dim.x, i / dim.x));
 
-        self.cells
-            .par_iter_mut()
-            .for_each(|c| c.velocity.front = c.velocity.back);
+        match execution_mode {
+            ExecutionMode::Single => out.iter_mut().enumerate().for_each(f),
+            _ => out.par_iter_mut().enumerate().for_each(f),
+        }
+    }
+
+    /// The semi-Lagrangian update of the staggered velocity of cell `idx`.
+    /// Only reads the current (back) velocities.
+    fn advected_velocity(&self, idx: Index2, dt: Scalar) -> Vector2 {
+        let mut advected = self.cell(idx).velocity.back;
 
-        for idx in self.iter_index_inside() {
-            if self.cell(idx).mode == CellTypes::Solid {
+        let is_fixed = !self.is_inside_border(idx) || self.cell(idx).mode == CellTypes::Solid;
+        if is_fixed || !self.is_active(idx) {
+            return advected;
+        }
+
+        let nbs = Grid::get_neighbors_indices(idx);
+
+        // Advect the two staggered grids (x and then y-direction).
+        for dir in 0..2 {
+            // Is the negative neighbor a solid cell, then do not advect this velocity.
+            if self.cell(nbs[0][dir]).mode == CellTypes::Solid {
                 continue;
             }
 
-            let nbs = Grid::get_neighbors_indices(idx);
+            let mut pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
+            let mut vel: Vector2 = self.cell(idx).velocity.back;
 
-            // Advect the two staggered grids (x and then y-direction).
-            for dir in 0..2 {
-                // Is the negative neighbor a solid cell, then do not advect this velocity.
-                if self.cell(nbs[0][dir]).mode == CellTypes::Solid {
-                    continue;
-                }
+            let sample = |pos: Vector2, dir: usize| {
+                return self.sample_field(
+                    idx!(1, 1),
+                    self.dim - idx!(1, 1),
+                    pos,
+                    Some(dir),
+                    |cell: &Cell| cell.velocity.back[dir],
+                );
+            };
 
-                let mut pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
-                let mut vel: Vector2 = self.cell(idx).velocity.back;
+            let other_dir = (dir + 1) % 2;
+            vel[other_dir] = sample(pos, other_dir);
 
-                let sample = |pos: Vector2, dir: usize| {
-                    return self.sample_field(
-                        idx!(1, 1),
-                        self.dim - idx!(1, 1),
-                        pos,
-                        Some(dir),
-                        |cell: &Cell| cell.velocity.back[dir],
-                    );
-                };
+            // Get position of particle which reached this position.
+            pos = self.backtrace.departure(pos, vel, dt, |p| self.sample_velocity(p));
+
+            // Set the past velocity at this cell.
+            advected[dir] = sample(pos, dir);
+        }
+
+        return advected;
+    }
+
+    /// The departure point of the smoke at the center of the inside cell `idx`.
+    fn smoke_departure(&self, idx: Index2, dt: Scalar) -> Vector2 {
+        let pos = self.cell_center(idx);
+        let vel = self.cell_center_velocity(idx);
 
-                let other_dir = (dir + 1) % 2;
-                vel[other_dir] = sample(pos, other_dir);
+        return self.backtrace.departure(pos, vel, dt, |p| self.sample_velocity(p));
+    }
 
-                // Get position of particle which reached this position.
-                pos = pos - dt * vel;
-                //debug!(log, "Idx: {}", idx);
+    /// The semi-Lagrangian update of the smoke of the cells `xs` of row `j` into `row`.
+    fn advect_smoke_row(&self, j: usize, xs: Range<usize>, dt: Scalar, row: &mut [Scalar]) {
+        let dim = self.dim;
+        let is_advected = |idx: Index2| {
+            return self.is_inside_border(idx)
+                && self.cell(idx).mode != CellTypes::Solid
+                && self.is_active(idx);
+        };
 
-                // Set the past velocity at this cell.
-                self.cell_mut(idx).velocity.front[dir] = sample(pos, dir);
+        // Cells which are not advected sample at their center and get reset below.
+        // The smoke is at the cell centers (see `probes::sample_centered`).
+        let half = vec2!(0.5, 0.5) * self.cell_width;
+        let mut positions = [Vector2::zeros(); SAMPLE_LANES];
+        let lanes = xs.clone().step_by(SAMPLE_LANES);
+        for (i0, values) in lanes.zip(row.chunks_mut(SAMPLE_LANES)) {
+            for (l, pos) in positions[0..values.len()].iter_mut().enumerate() {
+                let idx = idx!(i0 + l, j);
+                *pos = if is_advected(idx) {
+                    self.smoke_departure(idx, dt) - half
+                } else {
+                    self.cell_center(idx) - half
+                };
             }
+
+            self.sample_field_batch(
+                idx!(0, 0),
+                dim,
+                &positions[0..values.len()],
+                None,
+                |cell: &Cell| cell.smoke(),
+                values,
+            );
         }
 
-        self.cells.par_iter_mut().for_each(|c| c.velocity.swap());
+        for (i, s) in xs.zip(row.iter_mut()) {
+            if !is_advected(idx!(i, j)) {
+                *s = self.cell(idx!(i, j)).smoke();
+            }
+        }
     }
 
-    fn advect_smoke(&mut self, log: &slog::Logger, dt: Scalar) {
-        debug!(log, "Advect smoke.");
+    pub(crate) fn advect_velocity(
+        &mut self,
+        log: &slog::Logger,
+        dt: Scalar,
+        execution_mode: ExecutionMode,
+    ) {
+        debug!(log, "Advect velocity.");
 
-        self.cells
-            .par_iter_mut()
-            .for_each(|c| c.smoke.front = c.smoke.back);
+        // All cells are independent, the result is the same in all execution modes.
+        let mut advected = self.scratch.take::<Vector2>(self.cells.len());
+        self.map_cells_into(execution_mode, &mut advected, |idx| {
+            return self.advected_velocity(idx, dt);
+        });
 
-        for idx in self.iter_index_inside() {
-            if self.cell(idx).mode == CellTypes::Solid {
-                continue;
-            }
+        self.cells.par_iter_mut().zip(&advected).for_each(|(c, v)| {
+            c.velocity.front = *v;
+            c.velocity.swap();
+        });
 
-            let nbs = Grid::get_neighbors_indices(idx);
-            let mut pos = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+        self.scratch.give(advected);
+    }
 
-            let mut vel = Vector2::zeros();
-            for dir in 0..2 {
-                vel += vec2!(
-                    self.cell(nbs[dir][0]).velocity.back.x,
-                    self.cell(nbs[dir][1]).velocity.back.y
-                ) * 0.5;
+    /// Advect the smoke with the current velocity. If `force` is given, it is added to
+    /// the velocity of the fluid cells in the same pass as the smoke is written.
+    pub(crate) fn advect_smoke(
+        &mut self,
+        log: &slog::Logger,
+        dt: Scalar,
+        force: Option<Vector2>,
+        execution_mode: ExecutionMode,
+    ) {
+        debug!(log, "Advect smoke.");
+
+        // Only the window `[min, max)` has smoke to advect (unless it wraps around).
+        let periodic = self.is_periodic(0) || self.is_periodic(1);
+        let (min, max) = match self.smoke_region {
+            Some(ref mut region) if !periodic => {
+                region.update(&self.cells, self.dim);
+                region.window(self.dim, dt, self.cell_width)
             }
+            _ => (Index2::zeros(), self.dim),
+        };
+        let width = max.x - min.x;
 
-            pos = pos - dt * vel;
+        // Rows are sampled in batches, the result is the same in all execution modes.
+        let mut advected = self.scratch.take::<Scalar>(width * (max.y - min.y));
+        let advect_row = |(j, row): (usize, &mut [Scalar])| {
+            self.advect_smoke_row(min.y + j, min.x..max.x, dt, row);
+        };
+        match execution_mode {
+            ExecutionMode::Single => advected
+                .chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(advect_row),
+            _ => advected
+                .par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(advect_row),
+        }
 
-            self.cell_mut(idx).smoke.front = self.sample_field(
-                idx!(0, 0),
-                self.dim - idx!(0, 0),
+        let write = |c: &mut Cell, s: Scalar| {
+            c.smoke.front = RenderScalar::store(s);
+            c.smoke.swap();
+        };
+        self.cells
+            .par_chunks_mut(self.dim.x)
+            .enumerate()
+            .for_each(|(y, row)| {
+                if (min.y..max.y).contains(&y) {
+                    let values = &advected[(y - min.y) * width..][..width];
+                    for (c, s) in row[min.x..max.x].iter_mut().zip(values) {
+                        write(c, *s);
+                    }
+                }
+
+                if let Some(f) = force {
+                    for c in row.iter_mut().filter(|c| c.mode == CellTypes::Fluid) {
+                        c.velocity.back += f;
+                    }
+                }
+            });
+
+        self.scratch.give(advected);
+    }
+
+    /// Sample the (staggered) velocity at the world position `pos`.
+    pub fn sample_velocity(&self, pos: Vector2) -> Vector2 {
+        let sample = |dir: usize| {
+            return self.sample_field(
+                idx!(1, 1),
+                self.dim - idx!(1, 1),
                 pos,
-                None,
-                |cell: &Cell| cell.smoke.back,
+                Some(dir),
+                |cell: &Cell| cell.velocity.back[dir],
             );
+        };
+
+        return vec2!(sample(0), sample(1));
+    }
+
+    /// The index of the cell containing the world position `pos` or `None` if outside.
+    pub fn cell_index_at(&self, pos: Vector2) -> Option<Index2> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
         }
 
-        self.cells.par_iter_mut().for_each(|c| c.smoke.swap());
+        let index = Index2::from_iterator((pos / self.cell_width).iter().map(|v| *v as usize));
+        if !Grid::is_inside_range(idx!(0, 0), self.dim, index) {
+            return None;
+        }
+
+        return Some(index);
     }
 
-    pub fn sample_field<F: Fn(&Cell) -> Scalar>(
+    /// Compute the bilinear interpolation stencil of the world position `pos`
+    /// with all indices clamped to `[min, max)`.
+    /// Branch-free: the clamping compiles to min/max instructions.
+    #[inline(always)]
+    fn sample_stencil(
         &self,
         min: Index2,
         max: Index2,
-        mut pos: Vector2,
+        pos: Vector2,
         dir: Option<usize>,
-        get_val: F,
-    ) -> Scalar {
+    ) -> SampleStencil {
         let h = self.cell_width;
         let h_inv = 1.0 / self.cell_width;
 
         // If `dir` is set, we need some offset.
         // For velocities as they are on a staggered grid.
         let offset = dir.map_or(Vector2::zeros(), |d| self.offsets[d]);
-        pos = pos - offset; // Compute position on staggered grid.
-        pos = clamp_to_range(Vector2::zeros(), self.extent, pos);
-
-        // Compute index.
-        let mut index = Index2::from_iterator((pos * h_inv).iter().map(|v| *v as usize));
-
-        let clamp_index = |i| clamp_to_range(min, max - idx!(1, 1), i);
+        let px = (self.wrap(pos.x, 0) - offset.x).clamp(0.0, self.extent.x);
+        let py = (self.wrap(pos.y, 1) - offset.y).clamp(0.0, self.extent.y);
+
+        // Compute the clamped index and its positive neighbor.
+        let clamp_x = |i: usize| i.max(min.x).min(max.x - 1);
+        let clamp_y = |i: usize| i.max(min.y).min(max.y - 1);
+        let i0 = clamp_x((px * h_inv) as usize);
+        let j0 = clamp_y((py * h_inv) as usize);
+        let (i1, j1) = (clamp_x(i0 + 1), clamp_y(j0 + 1));
+
+        let alpha = [
+            ((px - i0 as Scalar * h) * h_inv).clamp(0.0, 1.0),
+            ((py - j0 as Scalar * h) * h_inv).clamp(0.0, 1.0),
+        ];
 
-        index = clamp_index(index);
-        let pos_cell = pos - index.cast::<Scalar>() * h;
-        let alpha = clamp_to_range(vec2!(0.0, 0.0), vec2!(1.0, 1.0), pos_cell * h_inv);
+        let offset = |i: usize, j: usize| i + j * self.dim.x;
 
-        // debug!(log, "Sample at: {}", index);
+        return SampleStencil {
+            cells: [offset(i0, j1), offset(i0, j0), offset(i1, j1), offset(i1, j0)],
+            alpha,
+        };
+    }
 
-        // Get all neighbor indices (column major).
-        // [ (0,1), (1,1)
-        //   (0,0), (1,0) ]
-        let nbs = [
-            clamp_index(index + Index2::new(0, 1)),
-            index,
-            clamp_index(index + Index2::new(1, 1)),
-            clamp_index(index + Index2::new(1, 0)),
-        ];
+    #[inline]
+    pub fn sample_field<F: Fn(&Cell) -> Scalar>(
+        &self,
+        min: Index2,
+        max: Index2,
+        pos: Vector2,
+        dir: Option<usize>,
+        get_val: F,
+    ) -> Scalar {
+        let stencil = self.sample_stencil(min, max, pos, dir);
+        return stencil.interpolate(stencil.cells.map(|i| get_val(&self.cells[i])));
+    }
 
-        // Get all values on the grid.
-        let m = Matrix2::from_iterator(
-            nbs.map(|i| {
-                return get_val(self.cell(i));
-            })
-            .into_iter(), // Column major order.
+    /// Sample the field at all `positions` into `values` (same results as `sample_field`).
+    /// The stencils of `SAMPLE_LANES` positions are computed before gathering
+    /// their values, which keeps the arithmetic in tight loops the compiler can vectorize.
+    pub fn sample_field_batch<F: Fn(&Cell) -> Scalar>(
+        &self,
+        min: Index2,
+        max: Index2,
+        positions: &[Vector2],
+        dir: Option<usize>,
+        get_val: F,
+        values: &mut [Scalar],
+    ) {
+        assert!(
+            positions.len() == values.len(),
+            "Got {} positions for {} values.",
+            positions.len(),
+            values.len()
         );
 
-        let t1 = vec2!(1.0 - alpha.x, alpha.x);
-        let t2 = vec2!(alpha.y, 1.0 - alpha.y);
+        for (pos, vals) in positions
+            .chunks(SAMPLE_LANES)
+            .zip(values.chunks_mut(SAMPLE_LANES))
+        {
+            let mut stencils = [SampleStencil::default(); SAMPLE_LANES];
+            for (s, p) in stencils.iter_mut().zip(pos) {
+                *s = self.sample_stencil(min, max, *p, dir);
+            }
 
-        return t2.dot(&(m * t1));
+            for (v, s) in vals.iter_mut().zip(stencils.iter()) {
+                *v = s.interpolate(s.cells.map(|i| get_val(&self.cells[i])));
+            }
+        }
     }
 }
+
+/// The cell at `normal` along `axis` and at `tangent` along the other axis.
+fn side_index(axis: usize, normal: usize, tangent: usize) -> Index2 {
+    return if axis == 0 {
+        idx!(normal, tangent)
+    } else {
+        idx!(tangent, normal)
+    };
+}
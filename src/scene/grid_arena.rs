@@ -0,0 +1,101 @@
+use crate::math::*;
+use crate::scene::cell::Cell;
+use crate::scene::grid::Grid;
+use crate::types::*;
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+/// Reuses the cell storage of short-lived whole-grid copies (e.g. the frame snapshots
+/// of the outputs), keyed by the grid dimension.
+///
+/// A copy from `snapshot` or `crop` gives its storage back when it is dropped, also on
+/// another thread. The storage is filled by cloning the cells, so reuse saves both the
+/// allocation and its initialization. At most `limit` buffers per dimension are kept.
+#[derive(Clone)]
+pub struct GridArena {
+    shared: Arc<Mutex<Buffers>>,
+}
+
+struct Buffers {
+    free: HashMap<Index2, Vec<Vec<Cell>>>,
+    limit: usize,
+    allocations: usize,
+}
+
+/// A grid whose cell storage returns to its `GridArena` when dropped.
+pub struct PooledGrid {
+    grid: Grid,
+    arena: GridArena,
+}
+
+impl GridArena {
+    pub fn new(limit: usize) -> Self {
+        return GridArena {
+            shared: Arc::new(Mutex::new(Buffers {
+                free: HashMap::new(),
+                limit,
+                allocations: 0,
+            })),
+        };
+    }
+
+    /// A copy of the whole `grid`.
+    pub fn snapshot(&self, grid: &Grid) -> PooledGrid {
+        return self.crop(grid, Index2::zeros(), grid.dim);
+    }
+
+    /// A copy of the window `[min, max)` of `grid` as `Grid::crop`.
+    pub fn crop(&self, grid: &Grid, min: Index2, max: Index2) -> PooledGrid {
+        let max = clamp_to_range(Index2::zeros(), grid.dim, max);
+        let min = clamp_to_range(Index2::zeros(), max, min);
+
+        return PooledGrid {
+            grid: grid.crop_into(min, max, self.take(max - min)),
+            arena: self.clone(),
+        };
+    }
+
+    /// Number of buffers which had to be allocated.
+    pub fn allocations(&self) -> usize {
+        return self.shared.lock().unwrap().allocations;
+    }
+
+    /// An empty buffer for a grid of dimension `dim`.
+    fn take(&self, dim: Index2) -> Vec<Cell> {
+        let mut buffers = self.shared.lock().unwrap();
+
+        if let Some(cells) = buffers.free.get_mut(&dim).and_then(|f| f.pop()) {
+            return cells;
+        }
+
+        buffers.allocations += 1;
+        return Vec::with_capacity(dim.x * dim.y);
+    }
+
+    fn give(&self, dim: Index2, mut cells: Vec<Cell>) {
+        let mut buffers = self.shared.lock().unwrap();
+        let limit = buffers.limit;
+
+        let free = buffers.free.entry(dim).or_default();
+        if free.len() < limit {
+            cells.clear();
+            free.push(cells);
+        }
+    }
+}
+
+impl Deref for PooledGrid {
+    type Target = Grid;
+
+    fn deref(&self) -> &Grid {
+        return &self.grid;
+    }
+}
+
+impl Drop for PooledGrid {
+    fn drop(&mut self) {
+        self.arena.give(self.grid.dim, self.grid.take_cells());
+    }
+}
@@ -67,6 +67,35 @@ where
         });
 }
 
+/// Bands of `band` rows together with the row above each band
+/// (first dimension stored first). Only the bands with an even (or `odd`) band index
+/// are returned, which do not overlap and can be processed in parallel.
+/// Each item is the row index of the first band row and the band's rows.
+pub fn row_bands_mut<T>(
+    data: &mut [T],
+    dim: Index2,
+    band: usize,
+    odd: bool,
+) -> impl ParallelIterator<Item = (usize, &mut [T])>
+where
+    T: Send + Sync,
+{
+    assert!(
+        band > 0 && dim.iter().fold(1, std::ops::Mul::mul) == data.len(),
+        "Wrong dimensions."
+    );
+
+    let first = if odd { band.min(dim.y) } else { 0 };
+
+    return data[first * dim.x..]
+        .par_chunks_mut(2 * band * dim.x)
+        .enumerate()
+        .map(move |(i, chunk)| {
+            let rows = (band + 1).min(chunk.len() / dim.x);
+            return (first + 2 * i * band, &mut chunk[..rows * dim.x]);
+        });
+}
+
 mod test {
     use crate::scene::grid_stencil::*;
 
@@ -120,6 +149,22 @@ mod test {
         assert!(v[(2, 1)] == 6);
     }
 
+    #[test]
+    fn test_row_bands() {
+        // 7 rows with one value per row, bands of 2 rows.
+        let mut v: Vec<usize> = (0..7).collect();
+
+        let even: Vec<(usize, Vec<usize>)> = row_bands_mut(&mut v, idx!(1, 7), 2, false)
+            .map(|(y, rows)| (y, rows.to_vec()))
+            .collect();
+        assert!(even == [(0, vec![0, 1, 2]), (4, vec![4, 5, 6])], "{:?}", even);
+
+        let odd: Vec<(usize, Vec<usize>)> = row_bands_mut(&mut v, idx!(1, 7), 2, true)
+            .map(|(y, rows)| (y, rows.to_vec()))
+            .collect();
+        assert!(odd == [(2, vec![2, 3, 4]), (6, vec![6])], "{:?}", odd);
+    }
+
     #[test]
     fn test_without_shift() {
         // Grid:
@@ -0,0 +1,136 @@
+use crate::scene::grid::Grid;
+use crate::scene::grid_arena::{GridArena, PooledGrid};
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+
+/// A past state of a simulation in a `StateHistory`.
+pub struct HistoryEntry {
+    /// The number of steps computed up to this state.
+    pub steps: u64,
+    pub time: Scalar,
+
+    /// The grids of all domains.
+    grids: Vec<PooledGrid>,
+}
+
+impl HistoryEntry {
+    /// The main grid at this state, e.g. to show it while scrubbing.
+    pub fn grid(&self) -> &Grid {
+        return &self.grids[0];
+    }
+
+    /// The grid of domain `i` at this state.
+    pub fn domain(&self, i: usize) -> Option<&Grid> {
+        return self.grids.get(i).map(|g| g.deref());
+    }
+
+    /// Set the grids and the time of `timestepper` to this state. The solver parameters,
+    /// the manipulators and the hooks are kept. Fails if a grid has another dimension
+    /// than at this state.
+    pub fn restore(&self, timestepper: &mut TimeStepper) -> FluidResult<()> {
+        let grids = timestepper
+            .objects
+            .iter_mut()
+            .filter_map(|o| o.as_any_mut().downcast_mut::<Grid>());
+        for (grid, state) in grids.zip(self.grids.iter()) {
+            grid.restore(state)?;
+        }
+
+        let mut state = timestepper.state();
+        state.t = self.time;
+        timestepper.set_state(state);
+        return Ok(());
+    }
+}
+
+/// The states of the grids after the last steps in memory (oldest first), to scrub
+/// backwards or to rewind and branch off from a past state.
+///
+/// Each state is a full copy of the grids. The copies reuse the storage of the
+/// discarded states (see `GridArena`), so a long run does not allocate per step.
+pub struct StateHistory {
+    capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+    arena: GridArena,
+}
+
+impl StateHistory {
+    /// A history of at most `capacity` states.
+    pub fn new(capacity: usize) -> Self {
+        return StateHistory {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            arena: GridArena::new(capacity),
+        };
+    }
+
+    /// Add the state of `timestepper` after `steps` steps, the oldest state is
+    /// discarded if the history is full.
+    pub fn push(&mut self, steps: u64, timestepper: &TimeStepper) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        let grids = timestepper
+            .objects
+            .iter()
+            .filter_map(|o| o.as_any().downcast_ref::<Grid>())
+            .map(|g| self.arena.snapshot(g))
+            .collect();
+
+        self.entries.push_back(HistoryEntry {
+            steps,
+            time: timestepper.time(),
+            grids,
+        });
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    /// The state `back` entries before the latest one (`0`: the latest).
+    pub fn get(&self, back: usize) -> Option<&HistoryEntry> {
+        let i = self.entries.len().checked_sub(back + 1)?;
+        return self.entries.get(i);
+    }
+
+    pub fn latest(&self) -> Option<&HistoryEntry> {
+        return self.entries.back();
+    }
+
+    /// The states, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> {
+        return self.entries.iter();
+    }
+
+    /// Discard the latest `back` states and return the state which is now the latest.
+    /// Returns `None` and keeps all states if there are not enough.
+    pub fn rewind(&mut self, back: usize) -> Option<&HistoryEntry> {
+        if back >= self.entries.len() {
+            return None;
+        }
+
+        self.entries.truncate(self.entries.len() - back);
+        return self.entries.back();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,110 @@
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::Integrate;
+use crate::types::*;
+
+/// The points of a step at which hooks are called, in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookPoint {
+    /// After the manipulators, before the integration.
+    PreStep = 0,
+    PostIntegrate = 1,
+    PostProject = 2,
+
+    /// After the advection (the end of the step).
+    PostAdvect = 3,
+}
+
+impl HookPoint {
+    pub const ALL: [HookPoint; 4] = [
+        HookPoint::PreStep,
+        HookPoint::PostIntegrate,
+        HookPoint::PostProject,
+        HookPoint::PostAdvect,
+    ];
+}
+
+/// The step in which a hook is called.
+#[derive(Clone, Copy, Debug)]
+pub struct HookInfo {
+    pub point: HookPoint,
+
+    /// The time at the start of the step.
+    pub t: Scalar,
+    pub dt: Scalar,
+}
+
+/// A closure called at a point of each step with mutable access to a grid.
+pub type Hook = Box<dyn FnMut(&HookInfo, &mut Grid) + Send>;
+
+/// The id of a registered hook (see `StepHooks::remove`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+/// The hooks of a timestepper (see `TimeStepper::add_hook`).
+///
+/// The hooks of a point run in the order they were added, on every grid of the
+/// timestepper. On the GPU and CUDA backends, which compute stages together, the
+/// hooks of these stages run in order after them.
+#[derive(Default)]
+pub struct StepHooks {
+    hooks: [Vec<(HookId, Hook)>; 4],
+    next_id: u64,
+}
+
+impl StepHooks {
+    pub fn add(&mut self, point: HookPoint, hook: Hook) -> HookId {
+        let id = HookId(self.next_id);
+        self.next_id += 1;
+
+        self.hooks[point as usize].push((id, hook));
+        return id;
+    }
+
+    /// Remove the hook `id`. Returns `false` if it is not registered.
+    pub fn remove(&mut self, id: HookId) -> bool {
+        for hooks in self.hooks.iter_mut() {
+            if let Some(pos) = hooks.iter().position(|(i, _)| *i == id) {
+                hooks.remove(pos);
+                return true;
+            }
+        }
+
+        return false;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.hooks.iter().map(|h| h.len()).sum();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    pub fn clear(&mut self) {
+        for hooks in self.hooks.iter_mut() {
+            hooks.clear();
+        }
+    }
+
+    pub(crate) fn call(
+        &mut self,
+        point: HookPoint,
+        t: Scalar,
+        dt: Scalar,
+        objects: &mut [Box<dyn Integrate>],
+    ) {
+        let hooks = &mut self.hooks[point as usize];
+        if hooks.is_empty() {
+            return;
+        }
+
+        let info = HookInfo { point, t, dt };
+        for obj in objects.iter_mut() {
+            if let Some(grid) = obj.as_any_mut().downcast_mut::<Grid>() {
+                for (_, hook) in hooks.iter_mut() {
+                    hook(&info, grid);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+
+/// A time integrator of a step, used for the body forces of a grid
+/// (see `Grid::set_integrator`) and for the backtracing of the advection
+/// (see `Grid::set_backtrace`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    /// Explicit Euler (one stage).
+    #[default]
+    Euler,
+
+    /// Explicit midpoint rule (two stages).
+    Midpoint,
+
+    /// Classical Runge-Kutta of fourth order (four stages).
+    Rk4,
+}
+
+impl Integrator {
+    /// The offsets of the stages (as fraction of the step) and the weights of their
+    /// slopes, for `dv/dt = f(v)`: stage `i` evaluates `f` at
+    /// `v + offsets[i] * dt * k[i - 1]` and the step is `v + dt * sum(weights[i] * k[i])`.
+    pub fn tableau(&self) -> (&'static [Scalar], &'static [Scalar]) {
+        return match self {
+            Integrator::Euler => (&[0.0], &[1.0]),
+            Integrator::Midpoint => (&[0.0, 0.5], &[0.0, 1.0]),
+            Integrator::Rk4 => (
+                &[0.0, 0.5, 0.5, 1.0],
+                &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+            ),
+        };
+    }
+
+    /// The point from which a particle moves to `pos` over the step `dt`, where it has
+    /// the velocity `vel`. The later stages sample the velocity field `velocity`.
+    #[inline(always)]
+    pub fn departure<F>(&self, pos: Vector2, vel: Vector2, dt: Scalar, velocity: F) -> Vector2
+    where
+        F: Fn(Vector2) -> Vector2,
+    {
+        if let Integrator::Euler = self {
+            return pos - dt * vel;
+        }
+
+        let (offsets, weights) = self.tableau();
+        let mut k = vel;
+        let mut slope = weights[0] * k;
+        for (c, w) in offsets.iter().zip(weights.iter()).skip(1) {
+            k = velocity(pos - c * dt * k);
+            slope += *w * k;
+        }
+
+        return pos - dt * slope;
+    }
+}
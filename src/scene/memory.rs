@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+/// Where the memory of an entry lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryLocation {
+    /// RAM of the host (including page-locked memory).
+    Host,
+
+    /// Memory of the GPU or CUDA device.
+    Device,
+}
+
+/// The memory of one part of a simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryEntry {
+    pub name: String,
+    pub location: MemoryLocation,
+    pub bytes: usize,
+}
+
+/// The bytes used by the fields, the auxiliary structures and the backend buffers of a
+/// simulation (see `TimeStepper::memory_report`).
+///
+/// The sizes are those of the allocations (capacities), not of the heap blocks the
+/// allocator reserves for them. Buffers which are only allocated in the first step
+/// (e.g. the scratch fields) are missing before that step.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    entries: Vec<MemoryEntry>,
+}
+
+impl MemoryReport {
+    pub fn add(&mut self, name: &str, location: MemoryLocation, bytes: usize) {
+        self.entries.push(MemoryEntry {
+            name: name.to_string(),
+            location,
+            bytes,
+        });
+    }
+
+    /// Add the entries of `other` with names prefixed by `prefix`.
+    pub fn append(&mut self, prefix: &str, other: MemoryReport) {
+        for e in other.entries {
+            self.entries.push(MemoryEntry {
+                name: format!("{}{}", prefix, e.name),
+                ..e
+            });
+        }
+    }
+
+    pub fn entries(&self) -> &[MemoryEntry] {
+        return &self.entries;
+    }
+
+    /// The bytes of all entries at `location`.
+    pub fn total(&self, location: MemoryLocation) -> usize {
+        return self
+            .entries
+            .iter()
+            .filter(|e| e.location == location)
+            .map(|e| e.bytes)
+            .sum();
+    }
+
+    /// Format the report as table with the totals of the host and the device.
+    pub fn format_table(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "{:<32} {:>8} {:>12}", "name", "location", "size");
+        for e in self.entries.iter() {
+            let _ = writeln!(
+                out,
+                "{:<32} {:>8} {:>12}",
+                e.name,
+                format!("{:?}", e.location).to_lowercase(),
+                format_bytes(e.bytes)
+            );
+        }
+
+        for location in [MemoryLocation::Host, MemoryLocation::Device] {
+            let _ = writeln!(
+                out,
+                "{:<32} {:>8} {:>12}",
+                "total",
+                format!("{:?}", location).to_lowercase(),
+                format_bytes(self.total(location))
+            );
+        }
+
+        return out;
+    }
+}
+
+/// Format `bytes` with a binary unit, e.g. `1.50 MiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        return format!("{} B", bytes);
+    }
+
+    return format!("{:.2} {}", value, units[unit]);
+}
@@ -1,13 +1,54 @@
+pub mod active_region;
+pub mod advection;
+pub mod bench;
+pub mod boundary;
 pub mod cell;
 pub mod cell_stats;
+pub mod config;
+pub mod coupling;
+pub mod decomposition;
+pub mod diagnostics;
+pub mod events;
+pub mod field_layout;
+pub mod fixed_grid;
+pub mod flux;
+pub mod force_field;
+#[cfg(feature = "async")]
+pub mod frames;
 
 pub mod grid;
+pub mod grid_arena;
 pub mod grid_stencil;
 pub mod grid_stencil_unsafe;
 
+pub mod history;
+pub mod hooks;
+pub mod integrator;
+pub mod memory;
+pub mod pressure_solver;
+pub mod probes;
+pub mod profile;
+pub mod profiler;
+pub mod region;
+pub mod reload;
+pub mod replay;
+pub mod rng;
+pub mod run_control;
+pub mod scratch;
+#[cfg(feature = "scripting")]
+pub mod script;
 pub mod setup;
+pub mod simulation;
+pub mod smoke_region;
+pub mod solver_params;
+pub mod step_stats;
+pub mod snapshot;
+pub mod stencil_table;
+pub mod streamlines;
+pub mod sweep;
 pub mod timestepper;
 
+#[cfg(feature = "plotting")]
 pub mod visualization;
 
 pub mod tests;
@@ -0,0 +1,64 @@
+use crate::log::Logger;
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::ExecutionMode;
+use crate::types::*;
+
+/// The parameters of a pressure solve.
+#[derive(Clone, Copy, Debug)]
+pub struct SolveParams {
+    pub iterations: u64,
+    pub density: Scalar,
+    pub execution_mode: ExecutionMode,
+}
+
+/// The statistics of a pressure solve.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolveStats {
+    /// The number of iterations which were run.
+    pub iterations: u64,
+
+    /// The residual at the end if the solver tracks it (e.g. to stop early).
+    pub residual: Option<Scalar>,
+}
+
+/// Makes the velocity of a grid divergence-free (the projection of a step) and stores
+/// the pressure in the cells. The active region and the statistics of the grid are
+/// updated by the grid around the solve (see `Grid::set_pressure_solver`).
+pub trait PressureSolver: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn solve(
+        &mut self,
+        log: &Logger,
+        grid: &mut Grid,
+        dt: Scalar,
+        params: &SolveParams,
+    ) -> SolveStats;
+}
+
+/// The Gauss-Seidel solve with over-relaxation `Grid::over_relaxation` in the variant
+/// of the execution mode (the default solver of a grid).
+/// It always runs all iterations and does not track the residual.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SorSolver;
+
+impl PressureSolver for SorSolver {
+    fn name(&self) -> &str {
+        return "sor";
+    }
+
+    fn solve(
+        &mut self,
+        log: &Logger,
+        grid: &mut Grid,
+        dt: Scalar,
+        params: &SolveParams,
+    ) -> SolveStats {
+        grid.solve_sor(log, dt, params);
+
+        return SolveStats {
+            iterations: params.iterations,
+            residual: None,
+        };
+    }
+}
@@ -0,0 +1,191 @@
+use crate::scene::cell::{Cell, CellTypes};
+use crate::scene::config::ProbeConfig;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::region::Region;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Debug)]
+pub enum ProbeShape {
+    Point(Vector2),
+
+    /// Averaged over all fluid cells with their center in the region.
+    Region(Region),
+}
+
+/// A named sensor in world coordinates.
+#[derive(Clone, Debug)]
+pub struct Probe {
+    pub name: String,
+    pub shape: ProbeShape,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProbeSample {
+    pub step: u64,
+    pub time: Scalar,
+    pub velocity: Vector2,
+    pub pressure: Scalar,
+    pub smoke: Scalar,
+}
+
+/// What the solver computed at a world position (see `Simulation::inspect`), e.g. to
+/// debug a scene or for the tooltips of a viewer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellProbe {
+    pub position: Vector2,
+
+    /// The cell containing `position` and its type, `None` outside of the grid.
+    pub index: Option<Index2>,
+    pub cell_type: Option<CellTypes>,
+
+    /// Interpolated at `position` (clamped to the grid).
+    pub velocity: Vector2,
+    pub pressure: Scalar,
+    pub smoke: Scalar,
+
+    /// The divergence of the cell in the last pressure solve (`0` outside of the grid).
+    pub divergence: Scalar,
+}
+
+impl CellProbe {
+    pub fn at(grid: &Grid, pos: Vector2) -> Self {
+        let index = grid.cell_index_at(pos);
+        let cell = index.map(|idx| grid.cell(idx));
+
+        return CellProbe {
+            position: pos,
+            index,
+            cell_type: cell.map(|c| c.mode.clone()),
+            velocity: grid.sample_velocity(pos),
+            pressure: sample_centered(grid, pos, |c| c.pressure),
+            smoke: sample_centered(grid, pos, |c| c.smoke()),
+            divergence: cell.map_or(0.0, |c| c.div),
+        };
+    }
+}
+
+/// Interpolate the cell-centered field `get` at the world position `pos`.
+pub(crate) fn sample_centered<F: Fn(&Cell) -> Scalar>(grid: &Grid, pos: Vector2, get: F) -> Scalar {
+    let h_2 = 0.5 * grid.cell_width;
+    return grid.sample_field(idx!(0, 0), grid.dim, pos - vec2!(h_2, h_2), None, get);
+}
+
+impl Probe {
+    pub fn from_config(config: &ProbeConfig) -> Self {
+        let pos = vec2!(config.position[0], config.position[1]);
+
+        let shape = match config.size {
+            Some(s) => ProbeShape::Region(Region::rect(pos, pos + vec2!(s[0], s[1]))),
+            None => ProbeShape::Point(pos),
+        };
+
+        return Probe {
+            name: config.name.clone(),
+            shape,
+        };
+    }
+
+    /// Sample velocity, pressure and smoke at the probe.
+    pub fn sample(&self, grid: &Grid) -> ProbeSample {
+        let point = |pos: Vector2| ProbeSample {
+            velocity: grid.sample_velocity(pos),
+            pressure: sample_centered(grid, pos, |c| c.pressure),
+            smoke: sample_centered(grid, pos, |c| c.smoke()),
+            ..Default::default()
+        };
+
+        let region = match self.shape {
+            ProbeShape::Point(pos) => return point(pos),
+            ProbeShape::Region(ref region) => region,
+        };
+
+        let mut sum = ProbeSample::default();
+        let mut n = 0;
+
+        for idx in grid.cells_in(region) {
+            let cell = grid.cell(idx);
+            if cell.mode == CellTypes::Solid {
+                continue;
+            }
+
+            sum.velocity += grid.cell_center_velocity(idx);
+            sum.pressure += cell.pressure;
+            sum.smoke += cell.smoke();
+            n += 1;
+        }
+
+        // Regions smaller than a cell fall back to the center of their bounds.
+        if n == 0 {
+            let (min, max) = region.bounds().unwrap_or_default();
+            return point((min + max) * 0.5);
+        }
+
+        let n = n as Scalar;
+        return ProbeSample {
+            velocity: sum.velocity / n,
+            pressure: sum.pressure / n,
+            smoke: sum.smoke / n,
+            ..Default::default()
+        };
+    }
+}
+
+/// Records the samples of all probes in memory over the run.
+pub struct Probes {
+    pub probes: Vec<Probe>,
+    series: Vec<Vec<ProbeSample>>,
+}
+
+impl Probes {
+    pub fn new(probes: Vec<Probe>) -> Self {
+        let series = vec![vec![]; probes.len()];
+        return Probes { probes, series };
+    }
+
+    pub fn from_config(configs: &[ProbeConfig]) -> Self {
+        return Probes::new(configs.iter().map(Probe::from_config).collect());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.probes.is_empty();
+    }
+
+    /// The recorded samples of probe `i`.
+    pub fn series(&self, i: usize) -> &[ProbeSample] {
+        return &self.series[i];
+    }
+
+    /// Sample all probes after step `step` at time `time`.
+    pub fn record(&mut self, step: u64, time: Scalar, grid: &Grid) {
+        for (probe, series) in self.probes.iter().zip(self.series.iter_mut()) {
+            series.push(ProbeSample {
+                step,
+                time,
+                ..probe.sample(grid)
+            });
+        }
+    }
+
+    /// Write all samples as CSV with one row per step and probe.
+    pub fn write_csv(&self, file: &str) -> GenericResult<()> {
+        let mut w = BufWriter::new(File::create(file)?);
+
+        writeln!(w, "probe,step,time,vel_x,vel_y,pressure,smoke")?;
+
+        for (probe, series) in self.probes.iter().zip(self.series.iter()) {
+            for s in series.iter() {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{}",
+                    probe.name, s.step, s.time, s.velocity.x, s.velocity.y, s.pressure, s.smoke
+                )?;
+            }
+        }
+
+        w.flush()?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::log::{debug, Logger};
+use crate::scene::cell::CellTypes;
+use crate::scene::config::{Side, VelocityConfig, VelocityProfileConfig};
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::region::Region;
+use crate::scene::timestepper::{Integrate, Manipulator};
+use crate::types::*;
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+const TAU: Scalar = std::f64::consts::TAU as Scalar;
+
+/// A velocity as a function of the time.
+pub type VelocityFn = Arc<dyn Fn(Scalar) -> Vector2 + Send + Sync>;
+
+/// A velocity which varies over time, e.g. of an inflow or a moving obstacle. It is
+/// evaluated at the start of each step (see `InflowDriver` and `ObstacleDriver`).
+#[derive(Clone)]
+pub enum VelocityProfile {
+    Constant(Vector2),
+
+    /// `offset + amplitude sin(2π frequency t + phase)`.
+    Sine {
+        amplitude: Vector2,
+        frequency: Scalar,
+        phase: Scalar,
+        offset: Vector2,
+    },
+
+    /// Linear between the points `(t, v)` with increasing `t`, constant before the
+    /// first and after the last point.
+    Table(Vec<(Scalar, Vector2)>),
+
+    Function(VelocityFn),
+}
+
+impl VelocityProfile {
+    pub fn constant(velocity: Vector2) -> Self {
+        return VelocityProfile::Constant(velocity);
+    }
+
+    pub fn sine(amplitude: Vector2, frequency: Scalar) -> Self {
+        return VelocityProfile::Sine {
+            amplitude,
+            frequency,
+            phase: 0.0,
+            offset: Vector2::zeros(),
+        };
+    }
+
+    pub fn table(points: Vec<(Scalar, Vector2)>) -> Self {
+        return VelocityProfile::Table(points);
+    }
+
+    pub fn function<F: Fn(Scalar) -> Vector2 + Send + Sync + 'static>(f: F) -> Self {
+        return VelocityProfile::Function(Arc::new(f));
+    }
+
+    pub fn from_config(config: &VelocityConfig) -> Self {
+        return match config {
+            VelocityConfig::Constant(v) => VelocityProfile::constant(vec2!(v[0], v[1])),
+            VelocityConfig::Profile(VelocityProfileConfig::Sine {
+                amplitude,
+                frequency,
+                phase,
+                offset,
+            }) => VelocityProfile::Sine {
+                amplitude: vec2!(amplitude[0], amplitude[1]),
+                frequency: *frequency,
+                phase: *phase,
+                offset: vec2!(offset[0], offset[1]),
+            },
+            VelocityConfig::Profile(VelocityProfileConfig::Table(rows)) => {
+                VelocityProfile::table(rows.iter().map(|r| (r[0], vec2!(r[1], r[2]))).collect())
+            }
+        };
+    }
+
+    /// The profile does not change over time.
+    pub fn is_constant(&self) -> bool {
+        return matches!(self, VelocityProfile::Constant(_));
+    }
+
+    /// The velocity at time `t`.
+    pub fn at(&self, t: Scalar) -> Vector2 {
+        return match self {
+            VelocityProfile::Constant(v) => *v,
+            VelocityProfile::Sine {
+                amplitude,
+                frequency,
+                phase,
+                offset,
+            } => offset + amplitude * (TAU * frequency * t + phase).sin(),
+            VelocityProfile::Table(points) => match points.partition_point(|(s, _)| *s <= t) {
+                _ if points.is_empty() => Vector2::zeros(),
+                0 => points[0].1,
+                k if k == points.len() => points[k - 1].1,
+                k => {
+                    let ((t0, v0), (t1, v1)) = (points[k - 1], points[k]);
+                    v0 + (v1 - v0) * ((t - t0) / (t1 - t0))
+                }
+            },
+            VelocityProfile::Function(f) => f(t),
+        };
+    }
+}
+
+impl fmt::Debug for VelocityProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            VelocityProfile::Constant(v) => write!(f, "Constant({:?})", v),
+            VelocityProfile::Sine {
+                amplitude,
+                frequency,
+                phase,
+                offset,
+            } => write!(
+                f,
+                "Sine({:?}, {}, {}, {:?})",
+                amplitude, frequency, phase, offset
+            ),
+            VelocityProfile::Table(points) => write!(f, "Table({:?})", points),
+            VelocityProfile::Function(_) => write!(f, "Function"),
+        };
+    }
+}
+
+/// Sets the velocity of the inflow on the solid `side` of the grid `domain` at the
+/// start of each step (see `Grid::set_inflow`).
+pub struct InflowDriver {
+    pub domain: usize,
+    pub side: Side,
+    pub velocity: VelocityProfile,
+}
+
+impl Manipulator for InflowDriver {
+    fn manipulate(
+        &self,
+        log: &Logger,
+        t: Scalar,
+        _dt: Scalar,
+        objects: &mut Vec<Box<dyn Integrate>>,
+    ) {
+        let velocity = self.velocity.at(t);
+        debug!(log, "Set the inflow {:?} to {:?} at {}.", self.side, velocity, t);
+
+        grid_mut(objects, self.domain).set_inflow(self.side, velocity);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Sets the velocity of the solid `cells` of the grid `domain`, e.g. of a moving
+/// obstacle, at the start of each step. Cells which became fluid are skipped.
+pub struct ObstacleDriver {
+    pub domain: usize,
+    pub cells: Vec<Index2>,
+    pub velocity: VelocityProfile,
+}
+
+impl ObstacleDriver {
+    /// The driver of the solid inside cells of `grid` (the grid `domain`) in `region`.
+    pub fn new(domain: usize, grid: &Grid, region: &Region, velocity: VelocityProfile) -> Self {
+        let (min, max) = (idx!(1, 1), grid.dim - idx!(1, 1));
+        let cells = grid
+            .cells_in(region)
+            .into_iter()
+            .filter(|idx| Grid::is_inside_range(min, max, *idx))
+            .filter(|idx| grid.cell(*idx).mode == CellTypes::Solid)
+            .collect();
+
+        return ObstacleDriver {
+            domain,
+            cells,
+            velocity,
+        };
+    }
+}
+
+impl Manipulator for ObstacleDriver {
+    fn manipulate(
+        &self,
+        log: &Logger,
+        t: Scalar,
+        _dt: Scalar,
+        objects: &mut Vec<Box<dyn Integrate>>,
+    ) {
+        let velocity = self.velocity.at(t);
+        debug!(log, "Set the obstacle velocity to {:?} at {}.", velocity, t);
+
+        let grid = grid_mut(objects, self.domain);
+        for idx in self.cells.iter() {
+            let cell = grid.cell_mut(*idx);
+            if cell.mode == CellTypes::Solid {
+                cell.velocity.back = velocity;
+            }
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn grid_mut(objects: &mut [Box<dyn Integrate>], i: usize) -> &mut Grid {
+    return objects[i]
+        .as_any_mut()
+        .downcast_mut::<Grid>()
+        .expect("Not a grid");
+}
@@ -0,0 +1,204 @@
+use crate::scene::timestepper::StageTimings;
+use crate::types::*;
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::{Duration, Instant};
+
+/// The stages of a step of a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    /// The manipulators (emitters, obstacles, boundary conditions).
+    Constraints,
+    Integrate,
+    Project,
+    Advect,
+    /// Writing the frames, time series and checkpoints.
+    Output,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Constraints,
+        Stage::Integrate,
+        Stage::Project,
+        Stage::Advect,
+        Stage::Output,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Stage::Constraints => "constraints",
+            Stage::Integrate => "integrate",
+            Stage::Project => "project",
+            Stage::Advect => "advect",
+            Stage::Output => "output",
+        };
+    }
+}
+
+/// A complete event of the Chrome trace format (`chrome://tracing`, Perfetto).
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    /// Start in microseconds.
+    ts: u64,
+    /// Duration in microseconds.
+    dur: u64,
+    pid: u32,
+    tid: u32,
+    args: TraceArgs,
+}
+
+#[derive(Serialize)]
+struct TraceArgs {
+    step: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: &'a [TraceEvent],
+}
+
+/// Accumulates the wall-clock time of the stages of a run.
+pub struct Profiler {
+    origin: Instant,
+    steps: u64,
+
+    /// The stages of the current step.
+    step: [Duration; 5],
+    total: [Duration; 5],
+
+    /// The events for the Chrome trace if enabled.
+    events: Option<Vec<TraceEvent>>,
+}
+
+impl Profiler {
+    /// Records the trace events if `trace` is set.
+    pub fn new(trace: bool) -> Self {
+        return Profiler {
+            origin: Instant::now(),
+            steps: 0,
+            step: Default::default(),
+            total: Default::default(),
+            events: if trace { Some(vec![]) } else { None },
+        };
+    }
+
+    /// Add the time of `stage` which started at `start`.
+    pub fn record(&mut self, stage: Stage, start: Instant, duration: Duration) {
+        self.step[stage as usize] += duration;
+
+        if let Some(ref mut events) = self.events {
+            events.push(TraceEvent {
+                name: stage.name(),
+                cat: "step",
+                ph: "X",
+                ts: start.saturating_duration_since(self.origin).as_micros() as u64,
+                dur: duration.as_micros() as u64,
+                pid: 1,
+                tid: 1,
+                args: TraceArgs { step: self.steps },
+            });
+        }
+    }
+
+    /// Add the solver stages of `TimeStepper::compute_step_timed` which started at `start`.
+    /// The stages ran one after the other.
+    pub fn record_step(&mut self, start: Instant, timings: &StageTimings) {
+        let stages = [
+            (Stage::Constraints, timings.manipulate),
+            (Stage::Integrate, timings.integrate),
+            (Stage::Project, timings.solve),
+            (Stage::Advect, timings.advect),
+        ];
+
+        let mut start = start;
+        for (stage, duration) in stages {
+            self.record(stage, start, duration);
+            start += duration;
+        }
+    }
+
+    /// Finish the current step and return its stages.
+    pub fn end_step(&mut self) -> [Duration; 5] {
+        let step = std::mem::take(&mut self.step);
+        for (t, s) in self.total.iter_mut().zip(step) {
+            *t += s;
+        }
+        self.steps += 1;
+
+        return step;
+    }
+
+    /// The accumulated time of `stage` over all finished steps.
+    pub fn total(&self, stage: Stage) -> Duration {
+        return self.total[stage as usize];
+    }
+
+    pub fn steps(&self) -> u64 {
+        return self.steps;
+    }
+
+    /// A single line with the time of each stage of a step in milliseconds.
+    pub fn format_step(step: &[Duration; 5]) -> String {
+        let ms = |s: &Stage| 1e3 * step[*s as usize].as_secs_f64();
+        return Stage::ALL
+            .iter()
+            .map(|s| format!("{}: {:.3} ms", s.name(), ms(s)))
+            .collect::<Vec<_>>()
+            .join(", ");
+    }
+
+    /// The table of the total and average time per step and the share of each stage.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let sum: Duration = self.total.iter().sum();
+
+        let _ = writeln!(
+            out,
+            "{:<12} {:>11} {:>11} {:>7}",
+            "stage", "total [ms]", "step [ms]", "share"
+        );
+
+        let mut row = |name: &str, d: Duration| {
+            let ms = 1e3 * d.as_secs_f64();
+            let share = 100.0 * d.as_secs_f64() / sum.as_secs_f64().max(1e-12);
+            let _ = writeln!(
+                out,
+                "{:<12} {:>11.3} {:>11.3} {:>6.1}%",
+                name,
+                ms,
+                ms / self.steps.max(1) as f64,
+                share
+            );
+        };
+
+        for s in Stage::ALL {
+            row(s.name(), self.total(s));
+        }
+        row("total", sum);
+
+        return out;
+    }
+
+    /// Write the recorded stages as Chrome trace JSON.
+    pub fn write_trace(&self, file: &str) -> GenericResult<()> {
+        let events = match self.events {
+            Some(ref e) => e,
+            None => bail!("Tracing is not enabled."),
+        };
+
+        let trace = Trace {
+            trace_events: events,
+        };
+        serde_json::to_writer(BufWriter::new(File::create(file)?), &trace)?;
+
+        return Ok(());
+    }
+}
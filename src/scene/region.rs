@@ -0,0 +1,200 @@
+use crate::io::bitmap::load_mask;
+use crate::scene::config::RegionConfig;
+use crate::types::*;
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A signed distance function, negative inside.
+pub type SdfFn = Arc<dyn Fn(Vector2) -> Scalar + Send + Sync>;
+
+/// A shape in world coordinates. The features which act on a part of the grid
+/// (obstacles, emitters and sinks, initial values, probes) take the cells with their
+/// center in a region (see `Grid::for_each_in`).
+#[derive(Clone)]
+pub enum Region {
+    /// The axis-aligned box `[min, max]`.
+    Box { min: Vector2, max: Vector2 },
+
+    Circle { center: Vector2, radius: Scalar },
+
+    /// A closed polygon (even-odd rule).
+    Polygon(Vec<Vector2>),
+
+    /// An image of `dim` pixels (row-major, the first row at the bottom) stretched over
+    /// the box `[min, max]` with the set pixels inside.
+    Mask {
+        min: Vector2,
+        max: Vector2,
+        dim: Index2,
+        values: Arc<Vec<bool>>,
+    },
+
+    /// The points where the signed distance is not positive.
+    Sdf(SdfFn),
+
+    Union(Vec<Region>),
+    Intersection(Vec<Region>),
+
+    /// The points of the first region which are not in the second.
+    Difference(Box<Region>, Box<Region>),
+}
+
+impl Region {
+    pub fn rect(min: Vector2, max: Vector2) -> Self {
+        return Region::Box { min, max };
+    }
+
+    pub fn circle(center: Vector2, radius: Scalar) -> Self {
+        return Region::Circle { center, radius };
+    }
+
+    pub fn polygon(points: Vec<Vector2>) -> Self {
+        return Region::Polygon(points);
+    }
+
+    pub fn sdf<F: Fn(Vector2) -> Scalar + Send + Sync + 'static>(f: F) -> Self {
+        return Region::Sdf(Arc::new(f));
+    }
+
+    pub fn union(self, other: Region) -> Self {
+        return Region::Union(vec![self, other]);
+    }
+
+    pub fn intersect(self, other: Region) -> Self {
+        return Region::Intersection(vec![self, other]);
+    }
+
+    pub fn subtract(self, other: Region) -> Self {
+        return Region::Difference(Box::new(self), Box::new(other));
+    }
+
+    /// The region of `config` on a grid with `dim` inside cells of width `cell_width`,
+    /// which loads the images of masks.
+    pub fn from_config(
+        config: &RegionConfig,
+        dim: Index2,
+        cell_width: Scalar,
+    ) -> GenericResult<Self> {
+        let all = |regions: &[RegionConfig]| {
+            return regions
+                .iter()
+                .map(|r| Region::from_config(r, dim, cell_width))
+                .collect::<GenericResult<Vec<_>>>();
+        };
+
+        return Ok(match config {
+            RegionConfig::Box { min, max } => {
+                Region::rect(vec2!(min[0], min[1]), vec2!(max[0], max[1]))
+            }
+            RegionConfig::Circle { center, radius } => {
+                Region::circle(vec2!(center[0], center[1]), *radius)
+            }
+            RegionConfig::Polygon { points } => {
+                Region::polygon(points.iter().map(|p| vec2!(p[0], p[1])).collect())
+            }
+            RegionConfig::Mask {
+                file,
+                threshold,
+                invert,
+            } => Region::Mask {
+                min: vec2!(cell_width, cell_width),
+                max: (dim + idx!(1, 1)).cast::<Scalar>() * cell_width,
+                dim,
+                values: Arc::new(load_mask(file, dim, *threshold, *invert, true)?),
+            },
+            RegionConfig::Union { regions } => Region::Union(all(regions)?),
+            RegionConfig::Intersection { regions } => Region::Intersection(all(regions)?),
+            RegionConfig::Difference { region, minus } => Region::Difference(
+                Box::new(Region::from_config(region, dim, cell_width)?),
+                Box::new(Region::from_config(minus, dim, cell_width)?),
+            ),
+        });
+    }
+
+    pub fn contains(&self, p: Vector2) -> bool {
+        return match self {
+            Region::Box { min, max } => {
+                p.x >= min.x && p.y >= min.y && p.x <= max.x && p.y <= max.y
+            }
+            Region::Circle { center, radius } => (p - center).norm_squared() <= radius * radius,
+            Region::Polygon(points) => {
+                // Count the edges crossed by the ray from `p` in `+x`.
+                let mut inside = false;
+                for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                    if (a.y > p.y) != (b.y > p.y) {
+                        let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                        inside ^= p.x < x;
+                    }
+                }
+                inside
+            }
+            Region::Mask {
+                min,
+                max,
+                dim,
+                values,
+            } => {
+                if p.x < min.x || p.y < min.y || p.x >= max.x || p.y >= max.y {
+                    return false;
+                }
+
+                let rel = (p - min).component_div(&(max - min));
+                let i = ((rel.x * dim.x as Scalar) as usize).min(dim.x - 1);
+                let j = ((rel.y * dim.y as Scalar) as usize).min(dim.y - 1);
+                values[j * dim.x + i]
+            }
+            Region::Sdf(f) => f(p) <= 0.0,
+            Region::Union(regions) => regions.iter().any(|r| r.contains(p)),
+            Region::Intersection(regions) => regions.iter().all(|r| r.contains(p)),
+            Region::Difference(a, b) => a.contains(p) && !b.contains(p),
+        };
+    }
+
+    /// A box `(min, max)` containing the region, `None` if it is not bounded
+    /// (signed distance functions).
+    pub fn bounds(&self) -> Option<(Vector2, Vector2)> {
+        return match self {
+            Region::Box { min, max } | Region::Mask { min, max, .. } => Some((*min, *max)),
+            Region::Circle { center, radius } => {
+                let r = vec2!(*radius, *radius);
+                Some((center - r, center + r))
+            }
+            Region::Polygon(points) => points.iter().map(|p| (*p, *p)).reduce(hull),
+            Region::Sdf(_) => None,
+            Region::Union(regions) => regions
+                .iter()
+                .map(|r| r.bounds())
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .reduce(hull),
+            Region::Intersection(regions) => regions
+                .iter()
+                .filter_map(|r| r.bounds())
+                .reduce(|(lo, hi), (min, max)| (lo.sup(&min), hi.inf(&max))),
+            Region::Difference(a, _) => a.bounds(),
+        };
+    }
+}
+
+/// The box containing the boxes `a` and `b`.
+fn hull(a: (Vector2, Vector2), b: (Vector2, Vector2)) -> (Vector2, Vector2) {
+    return (a.0.inf(&b.0), a.1.sup(&b.1));
+}
+
+impl fmt::Debug for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Region::Box { min, max } => write!(f, "Box({:?}, {:?})", min, max),
+            Region::Circle { center, radius } => write!(f, "Circle({:?}, {})", center, radius),
+            Region::Polygon(points) => write!(f, "Polygon({:?})", points),
+            Region::Mask { min, max, dim, .. } => {
+                write!(f, "Mask({:?}, {:?}, {:?})", min, max, dim)
+            }
+            Region::Sdf(_) => write!(f, "Sdf"),
+            Region::Union(regions) => write!(f, "Union({:?})", regions),
+            Region::Intersection(regions) => write!(f, "Intersection({:?})", regions),
+            Region::Difference(a, b) => write!(f, "Difference({:?}, {:?})", a, b),
+        };
+    }
+}
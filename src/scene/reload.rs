@@ -0,0 +1,201 @@
+use crate::scene::config::SceneConfig;
+use crate::scene::setup::SmokeEmitter;
+use crate::scene::solver_params::SolverParams;
+use crate::scene::timestepper::{ExecutionMode, TimeStepper};
+use crate::types::*;
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// The fields of a scene which a reload applies to a running simulation
+/// (`[]` stands for any emitter). All other changes need a restart.
+pub const RELOADABLE_FIELDS: [&str; 6] = [
+    "emitters[].smoke",
+    "solver.gravity",
+    "solver.iterations",
+    "solver.over_relaxation",
+    "solver.vorticity_confinement",
+    "output.interval",
+];
+
+/// Watches a scene file during a run and reloads it when it is modified
+/// (see `SceneReload`). Only the file itself is watched, not its includes.
+pub struct SceneWatcher {
+    file: String,
+    overrides: Vec<String>,
+    modified: Option<SystemTime>,
+
+    /// The scene of the last reload.
+    config: SceneConfig,
+}
+
+impl SceneWatcher {
+    /// The watcher of the scene `file` loaded with `overrides` into `config`.
+    pub fn new(file: &str, overrides: &[String], config: SceneConfig) -> Self {
+        return SceneWatcher {
+            file: file.to_string(),
+            overrides: overrides.to_vec(),
+            modified: SceneWatcher::modified(file),
+            config,
+        };
+    }
+
+    fn modified(file: &str) -> Option<SystemTime> {
+        return std::fs::metadata(file).and_then(|m| m.modified()).ok();
+    }
+
+    pub fn file(&self) -> &str {
+        return &self.file;
+    }
+
+    /// The changes of the scene if the file was modified since the last poll, `None` if
+    /// not. An invalid scene is returned as error and keeps the last valid scene.
+    pub fn poll(&mut self) -> Option<GenericResult<SceneReload>> {
+        let modified = SceneWatcher::modified(&self.file);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let load = || -> GenericResult<SceneConfig> {
+            let config = SceneConfig::load(&self.file, &self.overrides)?;
+            config.validate()?;
+            SolverParams::from_config(&config.solver)?;
+            return Ok(config);
+        };
+
+        return Some(load().map(|config| {
+            let reload = SceneReload::new(&self.config, &config);
+            self.config = config;
+            return reload;
+        }));
+    }
+}
+
+/// The changes between two versions of a scene: the reloadable fields (see
+/// `RELOADABLE_FIELDS`), which `apply` sets on the running simulation, and the ones
+/// which need a restart.
+pub struct SceneReload {
+    pub config: SceneConfig,
+
+    /// The changed fields which are applied, e.g. `solver.iterations`.
+    pub applied: Vec<String>,
+
+    /// The changed fields which need a restart, e.g. `grid.dim`.
+    pub restart: Vec<String>,
+}
+
+impl SceneReload {
+    pub fn new(old: &SceneConfig, new: &SceneConfig) -> Self {
+        let (old_fields, new_fields) = (flatten(old), flatten(new));
+        let emitters_kept = old.emitters.len() == new.emitters.len();
+
+        let mut changed: Vec<String> = old_fields
+            .iter()
+            .filter(|(k, v)| new_fields.get(*k) != Some(v))
+            .map(|(k, _)| k.clone())
+            .chain(
+                new_fields
+                    .keys()
+                    .filter(|k| !old_fields.contains_key(*k))
+                    .cloned(),
+            )
+            .map(|k| field_name(&k))
+            .collect();
+        changed.sort();
+        changed.dedup();
+
+        let is_reloadable = |f: &String| {
+            let is_emitter = f.starts_with("emitters");
+            return RELOADABLE_FIELDS.contains(&f.as_str()) && (emitters_kept || !is_emitter);
+        };
+        let (applied, restart): (Vec<String>, Vec<String>) =
+            changed.into_iter().partition(is_reloadable);
+
+        return SceneReload {
+            config: new.clone(),
+            applied,
+            restart,
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.applied.is_empty() && self.restart.is_empty();
+    }
+
+    /// Set the reloadable solver parameters and emitter rates on `timestepper` from its
+    /// next step on. The output interval is applied by the caller.
+    pub fn apply(&self, timestepper: &mut TimeStepper) -> GenericResult<()> {
+        let solver = &self.config.solver;
+        let new = SolverParams::from_config(solver)?;
+
+        let mut params = timestepper.params().clone();
+        params.gravity = new.gravity;
+        params.iterations = new.iterations;
+        params.vorticity_confinement = new.vorticity_confinement;
+        if !matches!(params.execution_mode, ExecutionMode::Jacobi) {
+            params.over_relaxation = solver.over_relaxation;
+        }
+        timestepper.set_params(params);
+
+        if self.applied.iter().any(|f| f.starts_with("emitters")) {
+            let emitters = timestepper
+                .manipulators
+                .iter_mut()
+                .filter_map(|m| m.as_any_mut().downcast_mut::<SmokeEmitter>());
+
+            for (emitter, config) in emitters.zip(self.config.emitters.iter()) {
+                emitter.smoke = config.smoke;
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// The leaf values of `config` by their path, e.g. `emitters.0.smoke`.
+fn flatten(config: &SceneConfig) -> BTreeMap<String, Value> {
+    fn add(path: String, value: Value, fields: &mut BTreeMap<String, Value>) {
+        let join = |key: &dyn std::fmt::Display| {
+            return if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", path, key)
+            };
+        };
+
+        match value {
+            Value::Object(m) => m.into_iter().for_each(|(k, v)| add(join(&k), v, fields)),
+            Value::Array(a) => a
+                .into_iter()
+                .enumerate()
+                .for_each(|(i, v)| add(join(&i), v, fields)),
+            v => {
+                fields.insert(path, v);
+            }
+        }
+    }
+
+    let mut fields = BTreeMap::new();
+    add(
+        String::new(),
+        serde_json::to_value(config).unwrap_or_default(),
+        &mut fields,
+    );
+    return fields;
+}
+
+/// The name of the field of the leaf `path`: the emitters as `emitters[].<field>` and
+/// all others up to the second level, e.g. `solver.gravity` for `solver.gravity.1`.
+fn field_name(path: &str) -> String {
+    let parts: Vec<&str> = path.split('.').collect();
+
+    return match parts.as_slice() {
+        ["emitters", i, field, ..] if i.parse::<usize>().is_ok() => {
+            format!("emitters[].{}", field)
+        }
+        [section, field, ..] => format!("{}.{}", section, field),
+        _ => path.to_string(),
+    };
+}
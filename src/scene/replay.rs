@@ -0,0 +1,237 @@
+use crate::scene::config::SceneConfig;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::setup::SmokeEmitter;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Runtime-tunable simulation parameters.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameters {
+    pub gravity: [Scalar; 2],
+    pub over_relaxation: Scalar,
+    pub iterations: u64,
+    pub emitter_smoke: Scalar,
+    pub vorticity_confinement: Scalar,
+}
+
+impl Parameters {
+    /// Read the current parameters from the simulation.
+    pub fn from(timestepper: &mut TimeStepper) -> Self {
+        let gravity = timestepper.gravity();
+        let iterations = timestepper.incompress_iters();
+
+        let emitter_smoke = timestepper
+            .manipulators
+            .iter_mut()
+            .find_map(|m| m.as_any_mut().downcast_mut::<SmokeEmitter>().map(|e| e.smoke))
+            .unwrap_or(0.0);
+
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        return Parameters {
+            gravity: [gravity.x, gravity.y],
+            over_relaxation: grid.over_relaxation,
+            iterations,
+            emitter_smoke,
+            vorticity_confinement: grid.vorticity_confinement,
+        };
+    }
+
+    /// Apply the parameters to the simulation.
+    pub fn apply(&self, timestepper: &mut TimeStepper) {
+        timestepper.set_gravity(vec2!(self.gravity[0], self.gravity[1]));
+        timestepper.set_incompress_iters(self.iterations);
+
+        for m in timestepper.manipulators.iter_mut() {
+            if let Some(e) = m.as_any_mut().downcast_mut::<SmokeEmitter>() {
+                e.smoke = self.emitter_smoke;
+            }
+        }
+
+        let grid = timestepper.objects[0]
+            .as_any_mut()
+            .downcast_mut::<Grid>()
+            .expect("Not a grid");
+
+        grid.over_relaxation = self.over_relaxation;
+        grid.vorticity_confinement = self.vorticity_confinement;
+    }
+}
+
+/// A runtime interaction with the simulation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interaction {
+    Parameters(Parameters),
+    Impulse {
+        index: [usize; 2],
+        velocity: [Scalar; 2],
+        radius: Scalar,
+    },
+}
+
+impl Interaction {
+    pub fn apply(&self, timestepper: &mut TimeStepper) {
+        match self {
+            Interaction::Parameters(p) => p.apply(timestepper),
+            Interaction::Impulse {
+                index,
+                velocity,
+                radius,
+            } => {
+                let grid = timestepper.objects[0]
+                    .as_any_mut()
+                    .downcast_mut::<Grid>()
+                    .expect("Not a grid");
+
+                grid.add_impulse(
+                    idx!(index[0], index[1]),
+                    vec2!(velocity[0], velocity[1]),
+                    *radius,
+                );
+            }
+        }
+    }
+}
+
+/// An interaction applied before the step with index `step`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub step: u64,
+    pub interaction: Interaction,
+}
+
+/// Everything needed to reproduce a run bit-for-bit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub config: SceneConfig,
+    pub dt: Scalar,
+
+    /// Number of computed steps.
+    pub steps: u64,
+    pub events: Vec<Event>,
+
+    /// Checksum of the grid after the last step.
+    pub checksum: Option<u64>,
+}
+
+impl Recording {
+    pub fn load(file: &str) -> GenericResult<Self> {
+        let recording: Recording = serde_json::from_reader(BufReader::new(File::open(file)?))?;
+
+        if recording.version != REPLAY_VERSION {
+            return Err(format!(
+                "Replay file version {} is not supported (expected {}).",
+                recording.version,
+                REPLAY_VERSION
+            )
+            .into());
+        }
+
+        return Ok(recording);
+    }
+
+    pub fn save(&self, file: &str) -> GenericResult<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(file)?), self)?;
+        return Ok(());
+    }
+
+    /// Apply all interactions recorded before step `step`.
+    pub fn apply(&self, step: u64, timestepper: &mut TimeStepper) {
+        // Events are ordered by step.
+        let start = self.events.partition_point(|e| e.step < step);
+        for e in self.events[start..].iter().take_while(|e| e.step == step) {
+            e.interaction.apply(timestepper);
+        }
+    }
+
+    /// Check the grid against the recorded checksum.
+    pub fn verify(&self, grid: &Grid) -> GenericResult<()> {
+        if let Some(expected) = self.checksum {
+            let actual = grid_checksum(grid);
+            if actual != expected {
+                return Err(format!(
+                    "Replay diverged: checksum {:016x} does not match the recorded {:016x}.",
+                    actual,
+                    expected
+                )
+                .into());
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Records the interactions of a run.
+pub struct Recorder {
+    pub recording: Recording,
+    last: Option<Parameters>,
+}
+
+impl Recorder {
+    pub fn new(config: &SceneConfig, dt: Scalar) -> Self {
+        return Recorder {
+            recording: Recording {
+                version: REPLAY_VERSION,
+                config: config.clone(),
+                dt,
+                steps: 0,
+                events: vec![],
+                checksum: None,
+            },
+            last: None,
+        };
+    }
+
+    pub fn record(&mut self, step: u64, interaction: Interaction) {
+        self.recording.events.push(Event { step, interaction });
+    }
+
+    /// Record the parameters if they changed since the last call.
+    pub fn record_parameters(&mut self, step: u64, params: &Parameters) {
+        if self.last.as_ref() != Some(params) {
+            self.record(step, Interaction::Parameters(params.clone()));
+            self.last = Some(params.clone());
+        }
+    }
+
+    /// Store the number of steps and the final checksum and write the recording.
+    pub fn finish(&mut self, steps: u64, grid: &Grid, file: &str) -> GenericResult<()> {
+        self.recording.steps = steps;
+        self.recording.checksum = Some(grid_checksum(grid));
+        return self.recording.save(file);
+    }
+}
+
+/// FNV-1a hash over the bits of all cell fields.
+pub fn grid_checksum(grid: &Grid) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    let mut add = |v: Scalar| {
+        for b in v.to_bits().to_le_bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    for idx in grid.iter_index() {
+        let c = grid.cell(idx);
+        add(c.velocity.back.x);
+        add(c.velocity.back.y);
+        add(c.pressure);
+        add(c.smoke());
+    }
+
+    return hash;
+}
@@ -0,0 +1,63 @@
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+
+/// The seeded random numbers of a simulation (`SceneConfig::seed`). Each stochastic
+/// subsystem draws from its own stream (e.g. `stream("curl-noise")`), which only depends
+/// on the seed and the name of the stream. More draws in one subsystem therefore do not
+/// change the numbers of the others, and one seed reproduces a whole run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rng {
+    seed: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        return Rng { seed };
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    /// The stream of the subsystem `name`, from its start.
+    pub fn stream(&self, name: &str) -> RngStream {
+        // FNV-1a, which unlike `DefaultHasher` is the same in all Rust versions.
+        let hash = name.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+            return (h ^ b as u64).wrapping_mul(0x100000001b3);
+        });
+
+        let mut mix = RngStream { state: self.seed };
+        return RngStream {
+            state: mix.next_u64() ^ hash,
+        };
+    }
+}
+
+/// A stream of random numbers (SplitMix64). Not suited for cryptography.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngStream {
+    state: u64,
+}
+
+impl RngStream {
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        return z ^ (z >> 31);
+    }
+
+    /// A uniform number in `[0, 1)`, exactly representable in `Scalar`.
+    pub fn next_scalar(&mut self) -> Scalar {
+        let bits = Scalar::MANTISSA_DIGITS;
+        return (self.next_u64() >> (64 - bits)) as Scalar / (1u64 << bits) as Scalar;
+    }
+
+    /// A uniform number in `[min, max)`.
+    pub fn range(&mut self, min: Scalar, max: Scalar) -> Scalar {
+        return min + (max - min) * self.next_scalar();
+    }
+}
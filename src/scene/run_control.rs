@@ -0,0 +1,112 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+/// The requested run state of a simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+
+    /// The runs wait until resumed, only the requested single steps are computed.
+    Paused,
+
+    /// The runs return, until resumed.
+    Stopped,
+}
+
+struct Shared {
+    state: RunState,
+
+    /// The single steps requested while paused.
+    pending_steps: u64,
+}
+
+/// A handle to pause, resume, single-step or stop the runs of a simulation
+/// (see `Simulation::control`) from any thread, e.g. a UI thread while the
+/// simulation runs on a worker. All clones control the same simulation.
+///
+/// The state is checked before each step of `Simulation::run_for` and
+/// `Simulation::run_until`, a running step is never interrupted.
+#[derive(Clone)]
+pub struct RunControl {
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+}
+
+impl Default for RunControl {
+    fn default() -> Self {
+        let shared = Shared {
+            state: RunState::Running,
+            pending_steps: 0,
+        };
+
+        return RunControl {
+            shared: Arc::new((Mutex::new(shared), Condvar::new())),
+        };
+    }
+}
+
+impl RunControl {
+    pub fn state(&self) -> RunState {
+        return self.shared.0.lock().unwrap().state;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.state() == RunState::Paused;
+    }
+
+    /// The requested single steps which are not computed yet.
+    pub fn pending_steps(&self) -> u64 {
+        return self.shared.0.lock().unwrap().pending_steps;
+    }
+
+    /// Let the runs wait before their next step.
+    pub fn pause(&self) {
+        self.update(|s| s.state = RunState::Paused);
+    }
+
+    /// Continue the runs after a `pause` or a `stop`. Unused single steps are dropped.
+    pub fn resume(&self) {
+        self.update(|s| {
+            s.state = RunState::Running;
+            s.pending_steps = 0;
+        });
+    }
+
+    /// Let a paused run compute one more step. Has no effect if not paused.
+    pub fn step_once(&self) {
+        self.update(|s| {
+            if s.state == RunState::Paused {
+                s.pending_steps += 1;
+            }
+        });
+    }
+
+    /// Let the runs return before their next step, also if paused.
+    /// Later runs return at once until `resume` is called.
+    pub fn stop(&self) {
+        self.update(|s| s.state = RunState::Stopped);
+    }
+
+    fn update<F: FnOnce(&mut Shared)>(&self, f: F) {
+        let (lock, changed) = &*self.shared;
+        f(&mut lock.lock().unwrap());
+        changed.notify_all();
+    }
+
+    /// Block while paused without a requested step.
+    /// Returns `false` if the run has to stop.
+    pub(crate) fn wait_for_step(&self) -> bool {
+        let (lock, changed) = &*self.shared;
+        let mut s = lock.lock().unwrap();
+
+        loop {
+            match s.state {
+                RunState::Running => return true,
+                RunState::Stopped => return false,
+                RunState::Paused if s.pending_steps > 0 => {
+                    s.pending_steps -= 1;
+                    return true;
+                }
+                RunState::Paused => s = changed.wait(s).unwrap(),
+            }
+        }
+    }
+}
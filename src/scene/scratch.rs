@@ -0,0 +1,76 @@
+use crate::types::*;
+
+/// Element types which the `ScratchPool` keeps buffers for.
+pub trait ScratchElement: Copy + Default + Send + Sync {
+    fn buffers(pool: &mut ScratchPool) -> &mut Vec<Vec<Self>>;
+}
+
+/// Temporary per-cell fields of the solver which are reused across steps.
+///
+/// `take` hands out a buffer of the requested length and `give` returns it to the pool.
+/// After the first step all buffers have their final size and no further heap
+/// allocations happen (see `allocations`).
+#[derive(Default)]
+pub struct ScratchPool {
+    scalars: Vec<Vec<Scalar>>,
+    vectors: Vec<Vec<Vector2>>,
+    vector_pairs: Vec<Vec<[Vector2; 2]>>,
+
+    allocations: usize,
+}
+
+impl ScratchElement for Scalar {
+    fn buffers(pool: &mut ScratchPool) -> &mut Vec<Vec<Self>> {
+        return &mut pool.scalars;
+    }
+}
+
+impl ScratchElement for Vector2 {
+    fn buffers(pool: &mut ScratchPool) -> &mut Vec<Vec<Self>> {
+        return &mut pool.vectors;
+    }
+}
+
+impl ScratchElement for [Vector2; 2] {
+    fn buffers(pool: &mut ScratchPool) -> &mut Vec<Vec<Self>> {
+        return &mut pool.vector_pairs;
+    }
+}
+
+impl ScratchPool {
+    /// A buffer of `len` default values.
+    /// Reuses the largest returned buffer and only allocates if it is too small.
+    pub fn take<T: ScratchElement>(&mut self, len: usize) -> Vec<T> {
+        let mut buffer = T::buffers(self).pop().unwrap_or_default();
+
+        if buffer.capacity() < len {
+            self.allocations += 1;
+        }
+
+        buffer.clear();
+        buffer.resize(len, T::default());
+        return buffer;
+    }
+
+    /// Return a buffer from `take` for reuse.
+    pub fn give<T: ScratchElement>(&mut self, buffer: Vec<T>) {
+        let buffers = T::buffers(self);
+        buffers.push(buffer);
+        // Keep the largest buffer on top.
+        buffers.sort_by_key(|b| b.capacity());
+    }
+
+    /// Bytes of the buffers in the pool (the buffers handed out are not counted).
+    pub fn bytes(&self) -> usize {
+        fn bytes<T>(buffers: &[Vec<T>]) -> usize {
+            return buffers.iter().map(|b| b.capacity() * std::mem::size_of::<T>()).sum();
+        }
+
+        return bytes(&self.scalars) + bytes(&self.vectors) + bytes(&self.vector_pairs);
+    }
+
+    /// Number of times `take` had to allocate.
+    pub fn allocations(&self) -> usize {
+        return self.allocations;
+    }
+}
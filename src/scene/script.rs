@@ -0,0 +1,224 @@
+use crate::log::{warn, Logger};
+use crate::scene::cell::CellTypes;
+use crate::scene::config::ScriptConfig;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::timestepper::{Integrate, Manipulator};
+use crate::types::*;
+
+use rhai::{Engine, Scope, AST, FLOAT, INT};
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+
+/// A change of the grid requested by a script.
+enum Command {
+    SetSmoke {
+        min: Index2,
+        max: Index2,
+        smoke: Scalar,
+    },
+    SetVelocity {
+        min: Index2,
+        max: Index2,
+        velocity: Vector2,
+    },
+    Obstacle {
+        center: Vector2,
+        radius: Scalar,
+        velocity: Vector2,
+    },
+}
+
+/// A Rhai script of a scene which runs before each step (feature `scripting`).
+///
+/// The script sees the constants `t` and `dt` and changes the grid with
+/// - `emit(x0, y0, x1, y1, smoke)`: set the smoke in the cell index window `[min, max)`,
+/// - `set_velocity(x0, y0, x1, y1, vx, vy)`: set the velocity in the window,
+/// - `obstacle(x, y, radius)` or `obstacle(x, y, radius, vx, vy)`: a circular obstacle in
+///   world coordinates during this step.
+///
+/// The script has no state between the steps, everything derives from `t`, e.g.
+/// `if t < 3.0 { obstacle(0.5, 0.5, 0.1) }` opens a vent at `t = 3`. The obstacles of a
+/// step are removed before the next step, hence they can move. The changes are applied
+/// after the script finished. Failing scripts are logged and skipped.
+pub struct SceneScript {
+    name: String,
+
+    engine: Engine,
+    ast: AST,
+    commands: Arc<Mutex<Vec<Command>>>,
+
+    /// The fluid cells which are solid during this step.
+    obstacle_cells: Mutex<Vec<Index2>>,
+}
+
+impl SceneScript {
+    /// Compile the script `source` with the name `name` (used in the errors).
+    pub fn new(name: &str, source: &str) -> GenericResult<Self> {
+        let commands = Arc::new(Mutex::new(vec![]));
+
+        let mut engine = Engine::new();
+        register(&mut engine, &commands);
+
+        let ast = engine
+            .compile(source)
+            .map_err(|e| format!("Script '{}': {}", name, e))?;
+
+        return Ok(SceneScript {
+            name: name.to_string(),
+            engine,
+            ast,
+            commands,
+            obstacle_cells: Mutex::new(vec![]),
+        });
+    }
+
+    pub fn from_config(config: &ScriptConfig) -> GenericResult<Self> {
+        return match (&config.file, &config.source) {
+            (Some(file), None) => {
+                let source = std::fs::read_to_string(file)
+                    .map_err(|e| format!("Script '{}': {}", file, e))?;
+                SceneScript::new(file, &source)
+            }
+            (None, Some(source)) => SceneScript::new("inline", source),
+            _ => bail!("Script needs either a 'file' or a 'source'."),
+        };
+    }
+
+    /// Run the script for the step from `t` over `dt` and apply its changes to `grid`.
+    pub fn run(&self, grid: &mut Grid, t: Scalar, dt: Scalar) -> GenericResult<()> {
+        let mut scope = Scope::new();
+        scope.push_constant("t", t as FLOAT);
+        scope.push_constant("dt", dt as FLOAT);
+
+        let result = self.engine.run_ast_with_scope(&mut scope, &self.ast);
+        let commands = std::mem::take(&mut *self.commands.lock().unwrap());
+        result.map_err(|e| format!("Script '{}' at t: '{:0.3}': {}", self.name, t, e))?;
+
+        let mut obstacle_cells = self.obstacle_cells.lock().unwrap();
+        for idx in obstacle_cells.drain(..) {
+            grid.cell_mut(idx).mode = CellTypes::Fluid;
+        }
+
+        for command in commands {
+            match command {
+                Command::SetSmoke { min, max, smoke } => {
+                    for row in grid.iter_rows(min, max) {
+                        for c in grid.row_cells_mut(&row) {
+                            c.set_smoke(smoke);
+                        }
+                    }
+                }
+                Command::SetVelocity { min, max, velocity } => {
+                    for row in grid.iter_rows(min, max) {
+                        for c in grid.row_cells_mut(&row) {
+                            c.velocity.back = velocity;
+                        }
+                    }
+                }
+                Command::Obstacle {
+                    center,
+                    radius,
+                    velocity,
+                } => {
+                    let end = grid.dim - idx!(1, 1);
+                    for idx in grid.iter_index() {
+                        let is_inside = Grid::is_inside_range(idx!(1, 1), end, idx);
+                        let c = grid.cell_center(idx);
+                        if !is_inside || (c - center).norm_squared() > radius * radius {
+                            continue;
+                        }
+
+                        let cell = grid.cell_mut(idx);
+                        if cell.mode == CellTypes::Fluid {
+                            cell.mode = CellTypes::Solid;
+                            obstacle_cells.push(idx);
+                        }
+                        cell.velocity.back = velocity;
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl Manipulator for SceneScript {
+    fn manipulate(
+        &self,
+        log: &Logger,
+        t: Scalar,
+        dt: Scalar,
+        objects: &mut Vec<Box<dyn Integrate>>,
+    ) {
+        let grid = objects
+            .get_mut(0)
+            .expect("No objects.")
+            .as_mut()
+            .as_any_mut()
+            .downcast_mut::<Grid>()
+            .expect("Not a grid");
+
+        if let Err(e) = self.run(grid, t, dt) {
+            warn!(log, "{}", e);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The cell index window `[min, max)` of script coordinates (clamped at zero).
+fn window(x0: INT, y0: INT, x1: INT, y1: INT) -> (Index2, Index2) {
+    let i = |v: INT| v.max(0) as usize;
+    return (idx!(i(x0), i(y0)), idx!(i(x1), i(y1)));
+}
+
+/// Register the functions of the scripts which push their commands to `commands`.
+fn register(engine: &mut Engine, commands: &Arc<Mutex<Vec<Command>>>) {
+    let c = commands.clone();
+    engine.register_fn(
+        "emit",
+        move |x0: INT, y0: INT, x1: INT, y1: INT, smoke: FLOAT| {
+            let (min, max) = window(x0, y0, x1, y1);
+            let smoke = smoke as Scalar;
+            c.lock()
+                .unwrap()
+                .push(Command::SetSmoke { min, max, smoke });
+        },
+    );
+
+    let c = commands.clone();
+    engine.register_fn(
+        "set_velocity",
+        move |x0: INT, y0: INT, x1: INT, y1: INT, vx: FLOAT, vy: FLOAT| {
+            let (min, max) = window(x0, y0, x1, y1);
+            let velocity = vec2!(vx as Scalar, vy as Scalar);
+            c.lock()
+                .unwrap()
+                .push(Command::SetVelocity { min, max, velocity });
+        },
+    );
+
+    let c = commands.clone();
+    engine.register_fn(
+        "obstacle",
+        move |x: FLOAT, y: FLOAT, radius: FLOAT, vx: FLOAT, vy: FLOAT| {
+            c.lock().unwrap().push(Command::Obstacle {
+                center: vec2!(x as Scalar, y as Scalar),
+                radius: radius as Scalar,
+                velocity: vec2!(vx as Scalar, vy as Scalar),
+            });
+        },
+    );
+
+    let c = commands.clone();
+    engine.register_fn("obstacle", move |x: FLOAT, y: FLOAT, radius: FLOAT| {
+        c.lock().unwrap().push(Command::Obstacle {
+            center: vec2!(x as Scalar, y as Scalar),
+            radius: radius as Scalar,
+            velocity: Vector2::zeros(),
+        });
+    });
+}
@@ -1,12 +1,30 @@
 use std::fmt::Debug;
 use std::str::FromStr;
 
+use crate::io::bitmap::{load_field, load_mask};
+use crate::io::velocity::load_velocity;
+use crate::io::output::{ExportRegion, OutputInterval, OverwritePolicy};
 use crate::log::*;
+use crate::render::{Colormap, ValueRange};
+use crate::scene::bench::BenchScene;
+use crate::scene::boundary::BoundaryCondition;
 use crate::scene::cell::CellTypes;
-use crate::scene::grid::{CellGetter, Grid};
-use crate::scene::timestepper::{Integrate, Manipulator, TimeStepper, ExecutionMode};
+use crate::scene::config::{
+    FieldImageConfig, MaskConfig, ObstacleConfig, ProbeConfig, SceneConfig, ScriptConfig,
+    SectionConfig, Side, VelocityFieldConfig,
+};
+use crate::scene::coupling::DomainCoupling;
+use crate::scene::grid::Grid;
+use crate::scene::profile::{InflowDriver, ObstacleDriver, VelocityProfile};
+use crate::scene::region::Region;
+use crate::scene::solver_params::SolverParams;
+use crate::scene::sweep::SweepParam;
+use crate::scene::timestepper::{
+    Backend, ExecutionMode, GpuSchedule, Integrate, Manipulator, TimeStepper,
+};
 use crate::types::*;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use std::any::Any;
 use nalgebra as na;
 
 fn parse_vector<T, const DIM: usize>(s: &str) -> Result<na::SVector<T, DIM>, String>
@@ -31,10 +49,436 @@ where
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Run a scene.
+    Run(CLIArgs),
+
+    /// Resume a run from a checkpoint.
+    /// The scene must be the same as the one the checkpoint was written with.
+    Resume {
+        checkpoint: String,
+
+        #[command(flatten)]
+        args: CLIArgs,
+    },
+
+    /// Replay a recording written with `--record` and check that the
+    /// final state matches bit-for-bit.
+    Replay {
+        recording: String,
+
+        #[command(flatten)]
+        args: CLIArgs,
+    },
+
+    /// Render the grid of a checkpoint to images.
+    Render(RenderArgs),
+
+    /// Print the resolved scene configuration.
+    Info(CLIArgs),
+
+    /// Run the standard benchmark scenes and print the timings per stage.
+    Bench(BenchArgs),
+
+    /// Run a scene for all combinations of parameter values and print the diagnostics
+    /// of each run as one table.
+    Sweep(SweepArgs),
+
+    /// Show a scene live in a window (feature `viewer`).
+    #[cfg(feature = "viewer")]
+    View(CLIArgs),
+}
+
+/// Options for the image renderers.
+#[derive(Args, Debug, Clone)]
+pub struct RenderOptions {
+    /// Pixels per cell for the rendered images.
+    #[arg(long = "render-scale", default_value_t = 4)]
+    pub render_scale: usize,
+
+    #[arg(long = "render-gamma", default_value_t = 1.0)]
+    pub render_gamma: Scalar,
+
+    #[arg(long = "render-grayscale", default_value_t = false)]
+    pub render_grayscale: bool,
+
+    /// Colormap for the smoke: `turbo`, `viridis`, `magma`, `coolwarm`, `rdbu`,
+    /// `grayscale` or custom stops, e.g. `0:#000000,0.7:#ff0000,1:#ffffff`.
+    #[arg(long = "colormap")]
+    pub colormap: Option<Colormap>,
+
+    /// Value range of the smoke: `auto`, `running` or `min,max`.
+    #[arg(long = "smoke-range", default_value = "0,1")]
+    pub smoke_range: ValueRange,
+
+    /// Colormap for the heatmaps (default `turbo`, `rdbu` for the divergence).
+    #[arg(long = "heatmap-colormap")]
+    pub heatmap_colormap: Option<Colormap>,
+
+    /// Draw a velocity arrow every `n` cells.
+    #[arg(long = "quiver-stride", default_value_t = 8)]
+    pub quiver_stride: usize,
+
+    /// Draw the velocity arrows over the pressure instead of the smoke.
+    #[arg(long = "quiver-pressure", default_value_t = false)]
+    pub quiver_pressure: bool,
+
+    /// Smoke values of the SVG iso-contours, e.g. `0.2,0.5,0.8`.
+    #[arg(long = "contour-levels", value_delimiter = ',', default_value = "0.25,0.5,0.75")]
+    pub contour_levels: Vec<Scalar>,
+
+    /// Seed a streamline every `n` cells.
+    #[arg(long = "streamline-stride", default_value_t = 5)]
+    pub streamline_stride: usize,
+
+    /// Maximal number of integration steps per streamline (half a cell each).
+    #[arg(long = "streamline-length", default_value_t = 30)]
+    pub streamline_length: usize,
+
+    /// Value range of the heatmaps: `auto`, `symmetric`, `running` or `min,max`.
+    #[arg(long = "heatmap-range", default_value = "auto")]
+    pub heatmap_range: ValueRange,
+
+    /// Number of character columns for the terminal renderer.
+    #[arg(long = "terminal-columns", default_value_t = 80)]
+    pub terminal_columns: usize,
+
+    /// Draw a velocity arrow every `n` characters in the terminal (`0` disables them).
+    #[arg(long = "terminal-quiver", default_value_t = 0)]
+    pub terminal_quiver: usize,
+
+    /// Use Unicode shades instead of ANSI colors in the terminal.
+    #[arg(long = "terminal-no-color", default_value_t = false)]
+    pub terminal_no_color: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    pub checkpoint: String,
+
+    /// Output file for the rendered smoke.
+    #[arg(short = 'o', long, default_value_t = String::from("render.png"))]
+    pub output: String,
+
+    #[arg(long = "output-quiver")]
+    pub output_quiver: Option<String>,
+
+    #[arg(long = "output-streamlines")]
+    pub output_streamlines: Option<String>,
+
+    #[arg(long = "output-svg")]
+    pub output_svg: Option<String>,
+
+    #[arg(long = "output-pressure-map")]
+    pub output_pressure_map: Option<String>,
+
+    #[arg(long = "output-divergence-map")]
+    pub output_divergence_map: Option<String>,
+
+    #[arg(long = "output-vtk")]
+    pub output_vtk: Option<String>,
+
+    /// Print the smoke field to the terminal.
+    #[arg(long = "terminal", default_value_t = false)]
+    pub terminal: bool,
+
+    #[command(flatten)]
+    pub render: RenderOptions,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Number of steps per run.
+    #[arg(short = 'n', long, default_value_t = 50)]
+    pub steps: u64,
+
+    /// Resolutions to run (repeatable), e.g. `--dim 200,100`.
+    #[arg(long = "dim", value_parser = parse_vector::<usize, 2>,
+          default_values = ["100,50", "200,100", "400,200"])]
+    pub dims: Vec<Index2>,
+
+    /// Scenes to run (repeatable): `empty-box`, `channel` or `dense-emitters`.
+    #[arg(long = "scene", default_values = ["empty-box", "channel", "dense-emitters"])]
+    pub scenes: Vec<BenchScene>,
+
+    #[arg(long = "parallel", default_value_t = false)]
+    pub parallel: bool,
+
+    #[arg(long = "parallel-unsafe", default_value_t = false)]
+    pub parallel_unsafe: bool,
+
+    /// Parallel pressure solve over bands of rows.
+    #[arg(long = "parallel-bands", default_value_t = false)]
+    pub parallel_bands: bool,
+
+    /// Serial pressure solve over cache-sized tiles.
+    #[arg(long = "tiled", default_value_t = false)]
+    pub tiled: bool,
+
+    /// Parallel Jacobi pressure solve, deterministic for any number of threads.
+    #[arg(long = "jacobi", default_value_t = false)]
+    pub jacobi: bool,
+
+    /// Number of worker threads (default: all cores).
+    #[arg(long = "threads")]
+    pub threads: Option<usize>,
+}
+
+impl BenchArgs {
+    pub fn execution_mode(&self) -> ExecutionMode {
+        return if self.parallel {
+            ExecutionMode::Parallel
+        } else if self.parallel_unsafe {
+            ExecutionMode::ParallelUnsafe
+        } else if self.parallel_bands {
+            ExecutionMode::ParallelBands
+        } else if self.tiled {
+            ExecutionMode::Tiled
+        } else if self.jacobi {
+            ExecutionMode::Jacobi
+        } else {
+            ExecutionMode::Single
+        };
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SweepArgs {
+    /// The base scene file (default: the channel scene).
+    pub scene: Option<String>,
+
+    /// The values of a parameter (repeatable), e.g. `--param solver.iterations=20,40,80`
+    /// or `--param grid.dim=[100,50],[200,100]`.
+    #[arg(long = "param", required = true)]
+    pub params: Vec<SweepParam>,
+
+    /// Number of steps per run.
+    #[arg(short = 'n', long, default_value_t = 50)]
+    pub steps: u64,
+
+    /// Number of processes which run the combinations in parallel.
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Only run the combinations `k, k + n, ...` and print their rows (`k/n`),
+    /// used by the processes of `--jobs`.
+    #[arg(long = "shard", value_parser = parse_shard, hide = true)]
+    pub shard: Option<(usize, usize)>,
+}
+
+fn parse_shard(s: &str) -> Result<(usize, usize), String> {
+    let shard = s
+        .split_once('/')
+        .and_then(|(k, n)| Some((k.parse().ok()?, n.parse().ok()?)))
+        .filter(|(k, n)| k < n);
+
+    return shard.ok_or_else(|| format!("Shard '{}' needs the form 'k/n' with k < n.", s));
+}
+
+#[derive(Args, Debug)]
 pub struct CLIArgs {
+    /// Scene file (`.toml`, `.yaml`, `.ron`). Its time and output settings
+    /// replace the command line defaults.
+    pub scene: Option<String>,
+
+    /// Directory prepended to all relative output patterns.
+    #[arg(long = "output-dir")]
+    pub output_dir: Option<String>,
+
+    /// Write frames every `n` steps or every `t` simulation time with `ts`, e.g. `0.1s`.
+    #[arg(long = "output-interval")]
+    pub output_interval: Option<OutputInterval>,
+
+    /// What to do with existing frames: `overwrite`, `skip` or `error`.
+    #[arg(long = "overwrite")]
+    pub overwrite: Option<OverwritePolicy>,
+
+    /// Export only the cell window `i0,j0,i1,j1` (end exclusive, grid indices with border).
+    #[arg(long = "export-window", value_parser = ExportRegion::parse_cells, conflicts_with = "export_rect")]
+    pub export_window: Option<ExportRegion>,
+
+    /// Export only the cells overlapping the world rectangle `x0,y0,x1,y1`.
+    #[arg(long = "export-rect", value_parser = ExportRegion::parse_world)]
+    pub export_rect: Option<ExportRegion>,
+
+    /// Append to existing time series files (default when resuming).
+    #[arg(long = "append", default_value_t = false)]
+    pub append: bool,
+
+    /// Output pattern for the plots. All patterns contain `{}` or `%06d` for the frame number.
     #[arg(short = 'o', long, default_value_t = String::from("./frames/frame-{}.png"))]
     pub output: String,
 
+    /// Output pattern for VTK ImageData files, e.g. `./frames/frame-{}.vti`.
+    #[arg(long = "output-vtk")]
+    pub output_vtk: Option<String>,
+
+    /// Output pattern for rendered smoke images, e.g. `./frames/render-{}.png`.
+    #[arg(long = "output-render")]
+    pub output_render: Option<String>,
+
+    /// Output pattern for velocity arrow images, e.g. `./frames/quiver-{}.png`.
+    #[arg(long = "output-quiver")]
+    pub output_quiver: Option<String>,
+
+    /// Output pattern for pressure heatmaps, e.g. `./frames/pressure-{}.png`.
+    #[arg(long = "output-pressure-map")]
+    pub output_pressure_map: Option<String>,
+
+    /// Output pattern for streamline images, e.g. `./frames/stream-{}.png`.
+    /// The background is chosen with `--quiver-pressure`.
+    #[arg(long = "output-streamlines")]
+    pub output_streamlines: Option<String>,
+
+    /// Output pattern for SVG contours, obstacle outlines and streamlines, e.g. `./frames/frame-{}.svg`.
+    #[arg(long = "output-svg")]
+    pub output_svg: Option<String>,
+
+    /// Output pattern for divergence heatmaps, e.g. `./frames/div-{}.png`.
+    #[arg(long = "output-divergence-map")]
+    pub output_divergence_map: Option<String>,
+
+    /// Encode the rendered smoke into a video file (`.gif` or e.g. `.mp4` over `ffmpeg`).
+    #[arg(long = "output-video")]
+    pub output_video: Option<String>,
+
+    #[arg(long = "video-fps", default_value_t = 30)]
+    pub video_fps: u32,
+
+    /// Output pattern for per-cell CSV files, e.g. `./frames/fields-{}.csv`.
+    #[arg(long = "output-fields-csv")]
+    pub output_fields_csv: Option<String>,
+
+    /// Output file for the probe time series as CSV, written at the end of the run.
+    #[arg(long = "output-probes")]
+    pub output_probes: Option<String>,
+
+    /// Output file for the flux through the cross-sections as CSV, written at the end of
+    /// the run.
+    #[arg(long = "output-flux")]
+    pub output_flux: Option<String>,
+
+    /// Output file for per-step diagnostics as CSV.
+    #[arg(long = "output-series-csv")]
+    pub output_series_csv: Option<String>,
+
+    /// Output prefix pattern for `.npy` files, e.g. `./frames/frame-{}`.
+    #[arg(long = "output-npy")]
+    pub output_npy: Option<String>,
+
+    /// Output pattern for `.npz` files, e.g. `./frames/frame-{}.npz`.
+    #[arg(long = "output-npz")]
+    pub output_npz: Option<String>,
+
+    /// Output file for the HDF5 time series (feature `hdf5`).
+    #[cfg(feature = "hdf5")]
+    #[arg(long = "output-hdf5")]
+    pub output_hdf5: Option<String>,
+
+    /// Output pattern for the smoke surface as binary glTF line meshes
+    /// with per-vertex velocities, e.g. `./frames/surface-{}.glb`.
+    #[arg(long = "output-gltf")]
+    pub output_gltf: Option<String>,
+
+    /// Smoke value of the extracted surface.
+    #[arg(long = "surface-level", default_value_t = 0.5)]
+    pub surface_level: Scalar,
+
+    /// Output pattern for 32-bit float OpenEXR images, e.g. `./frames/frame-{}.exr`.
+    #[arg(long = "output-exr")]
+    pub output_exr: Option<String>,
+
+    /// Output pattern for Tecplot ASCII files, e.g. `./frames/frame-{}.dat`.
+    #[arg(long = "output-tecplot")]
+    pub output_tecplot: Option<String>,
+
+    /// Write nodal Tecplot zones on the cell centers instead of cell-centered values.
+    #[arg(long = "tecplot-node-centered", default_value_t = false)]
+    pub tecplot_node_centered: bool,
+
+    /// Output pattern for OpenVDB volumes, e.g. `./frames/frame-{}.vdb`.
+    #[arg(long = "output-vdb")]
+    pub output_vdb: Option<String>,
+
+    /// Output pattern for compact binary state dumps, e.g. `./dumps/dump-{}.rfd`.
+    /// Use `--output-interval 1` to dump every step.
+    #[arg(long = "output-dump")]
+    pub output_dump: Option<String>,
+
+    /// Store the dumped fields as 32-bit floats.
+    #[arg(long = "dump-f32", default_value_t = false)]
+    pub dump_f32: bool,
+
+    /// Write a checkpoint at the end of the run (and every `--checkpoint-interval` steps).
+    #[arg(long = "output-checkpoint")]
+    pub output_checkpoint: Option<String>,
+
+    #[arg(long = "checkpoint-interval", default_value_t = 0)]
+    pub checkpoint_interval: u64,
+
+    /// Number of frames which are queued for writing in the background
+    /// before the simulation waits.
+    #[arg(long = "output-queue", default_value_t = 4)]
+    pub output_queue: usize,
+
+    /// Compress the checkpoints with `zstd` at this level (`1` to `22`).
+    #[arg(
+        long = "checkpoint-compression",
+        value_parser = clap::value_parser!(i32).range(1..=22)
+    )]
+    pub checkpoint_compression: Option<i32>,
+
+    /// Write only a full checkpoint every this many checkpoints and in between deltas
+    /// with the changed tiles (to `<output-checkpoint>.delta`).
+    #[arg(long = "checkpoint-key-interval")]
+    pub checkpoint_key_interval: Option<u64>,
+
+    /// Tiles of a delta checkpoint whose cells changed by at most this much
+    /// since the full checkpoint are not stored.
+    #[arg(long = "checkpoint-tolerance", default_value_t = 0.0)]
+    pub checkpoint_tolerance: Scalar,
+
+    /// Stream frames over a WebSocket on this address, e.g. `127.0.0.1:9001` (feature `stream`).
+    #[cfg(feature = "stream")]
+    #[arg(long = "stream")]
+    pub stream: Option<String>,
+
+    /// Stream every `n`-th step.
+    #[cfg(feature = "stream")]
+    #[arg(long = "stream-interval", default_value_t = 1)]
+    pub stream_interval: u64,
+
+    /// Wait for the first client before starting the simulation.
+    #[cfg(feature = "stream")]
+    #[arg(long = "stream-wait", default_value_t = false)]
+    pub stream_wait: bool,
+
+    /// Draw the smoke field in the terminal every `n` steps (`0` disables it).
+    #[arg(long = "terminal-interval", default_value_t = 0)]
+    pub terminal_interval: u64,
+
+    /// Measure the stages of each step and print a breakdown at the end of the run.
+    #[arg(long = "profile", default_value_t = false)]
+    pub profile: bool,
+
+    /// Write the stages of all steps as Chrome trace JSON (implies `--profile`).
+    #[arg(long = "profile-trace")]
+    pub profile_trace: Option<String>,
+
+    /// Record the scene and all runtime interactions into this replay file.
+    #[arg(long = "record")]
+    pub record: Option<String>,
+
+    /// Resume from this checkpoint file (set by `resume`).
+    #[arg(skip)]
+    pub resume: Option<String>,
+
     #[arg(short = 'e', long = "time-end", default_value_t = 5.0)]
     pub time_end: Scalar,
 
@@ -56,6 +500,42 @@ pub struct CLIArgs {
     #[arg(long = "incompress-iters", default_value_t = 100)]
     pub incompress_iter: u64,
 
+    /// Black/white image whose dark pixels become solid cells (repeatable).
+    #[arg(long = "obstacle-mask")]
+    pub obstacle_masks: Vec<String>,
+
+    /// Luminance threshold in `[0, 1]` for `--obstacle-mask`.
+    #[arg(long = "mask-threshold", default_value_t = 0.5)]
+    pub mask_threshold: Scalar,
+
+    /// Probe recording velocity, pressure and smoke each step (repeatable):
+    /// `name=x,y` for a point or `name=x,y,width,height` for a region (world coordinates).
+    #[arg(long = "probe")]
+    pub probes: Vec<ProbeConfig>,
+
+    /// Cross-section measuring the flux of the fluid and the smoke each step
+    /// (repeatable): `name=x0,y0,x1,y1` (world coordinates).
+    #[arg(long = "section")]
+    pub sections: Vec<SectionConfig>,
+
+    /// Initial cell-centered velocity field from a `.npy` (`(ny, nx, 2)`) or
+    /// `.csv` (columns `i`, `j`, `vel_x`, `vel_y`) file, resampled to the grid.
+    #[arg(long = "initial-velocity")]
+    pub initial_velocity: Option<String>,
+
+    /// Grayscale image for the initial smoke (white is `1`).
+    #[arg(long = "initial-smoke")]
+    pub initial_smoke: Option<String>,
+
+    /// Scene parameter overrides, e.g. `--set solver.iterations=80`.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Watch the scene file and apply changes of the emitter rates, the forces, the
+    /// solver iterations and the output interval on the next step.
+    #[arg(long = "watch", default_value_t = false, requires = "scene")]
+    pub watch: bool,
+
     #[arg(long = "scene-index", default_value_t = 0)]
     pub scene_idx: usize,
 
@@ -80,20 +560,65 @@ pub struct CLIArgs {
     #[arg(long = "parallel-unsafe", default_value_t = false)]
     pub parallel_unsafe: bool,
 
+    /// Parallel pressure solve over bands of rows.
+    #[arg(long = "parallel-bands", default_value_t = false)]
+    pub parallel_bands: bool,
+
+    /// Serial pressure solve over cache-sized tiles.
+    #[arg(long = "tiled", default_value_t = false)]
+    pub tiled: bool,
+
+    /// Parallel Jacobi pressure solve, deterministic for any number of threads.
+    #[arg(long = "jacobi", default_value_t = false)]
+    pub jacobi: bool,
+
+    /// Bit-identical results for any number of threads in all execution modes.
+    #[arg(long = "deterministic", default_value_t = false)]
+    pub deterministic: bool,
+
+    /// Number of worker threads (default: all cores).
+    #[arg(long = "threads")]
+    pub threads: Option<usize>,
+
+    /// Pin the worker threads to these cores, e.g. `0,2,4,6`.
+    #[arg(long = "cores", value_delimiter = ',')]
+    pub cores: Option<Vec<usize>>,
+
+    /// Compute the steps on the GPU (feature `gpu`), falls back to the CPU if not available.
+    #[arg(long = "gpu", default_value_t = false, conflicts_with = "cuda")]
+    pub gpu: bool,
+
+    /// Only integrate and project on the GPU, advect on the CPU.
+    #[arg(long = "gpu-projection", default_value_t = false, requires = "gpu")]
+    pub gpu_projection: bool,
+
+    /// Solve and advect on a CUDA device (feature `cuda`), falls back to the CPU if not available.
+    #[arg(long = "cuda", default_value_t = false)]
+    pub cuda: bool,
+
     #[arg(long = "show-progress", default_value_t = false)]
     pub show_progress: bool,
+
+    /// Write the log as JSON lines with the step and the simulation time into this file
+    /// instead of the terminal.
+    #[arg(long = "log-json", value_name = "FILE")]
+    pub log_json: Option<String>,
+
+    #[command(flatten)]
+    pub render: RenderOptions,
 }
 
-pub fn parse_args() -> CLIArgs {
-    return CLIArgs::parse();
+pub fn parse_args() -> Cli {
+    return Cli::parse();
 }
 
-struct AddSmokeBar {
-    pub center: Index2,
-    pub height: usize,
+/// Sets the smoke in the cells with their center in `region` every step.
+pub struct SmokeEmitter {
+    pub region: Region,
+    pub smoke: Scalar,
 }
 
-impl Manipulator for AddSmokeBar {
+impl Manipulator for SmokeEmitter {
     fn manipulate(
         &self,
         log: &Logger,
@@ -111,95 +636,270 @@ impl Manipulator for AddSmokeBar {
             .downcast_mut::<Grid>()
             .expect("");
 
-        // Setup smoke on border.
-        let y_range = [
-            self.center.y - (self.height / 2),
-            self.center.y + (self.height / 2),
-        ];
-
-        (y_range[0]..y_range[1]).for_each(|y| {
-            let idx = idx!(0, y);
-            if let Some(cell) = grid.cell_mut_opt(idx) {
-                cell.smoke.back = 1.0;
-            }
+        grid.for_each_in(&self.region, |_, c| c.set_smoke(self.smoke));
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn load_script(config: &ScriptConfig) -> GenericResult<Box<dyn Manipulator>> {
+    return Ok(Box::new(crate::scene::script::SceneScript::from_config(config)?));
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load_script(_config: &ScriptConfig) -> GenericResult<Box<dyn Manipulator>> {
+    bail!("Scene scripts need the feature 'scripting'.");
+}
+
+/// Load the scene configuration given by the command line:
+/// either the scene file or the built-in scene index.
+pub fn scene_config(cli: &CLIArgs) -> GenericResult<SceneConfig> {
+    let mut config = match cli.scene {
+        Some(ref file) => SceneConfig::load(file, &cli.overrides)?,
+        None => builtin_scene_config(cli)?,
+    };
+
+    config.masks.extend(cli.obstacle_masks.iter().map(|f| MaskConfig {
+        file: f.clone(),
+        threshold: cli.mask_threshold,
+        invert: false,
+        resample: true,
+    }));
+    if let Some(threads) = cli.threads {
+        config.solver.threads = threads;
+    }
+    if cli.deterministic {
+        config.solver.deterministic = true;
+    }
+    if let Some(ref cores) = cli.cores {
+        config.solver.cores = cores.clone();
+    }
+    if cli.gpu {
+        config.solver.backend = Backend::Gpu;
+        if cli.gpu_projection {
+            config.solver.gpu_schedule = GpuSchedule::Projection;
+        }
+    } else if cli.cuda {
+        config.solver.backend = Backend::Cuda;
+    }
+    config.probes.extend(cli.probes.iter().cloned());
+    config.sections.extend(cli.sections.iter().cloned());
+    if let Some(ref file) = cli.initial_smoke {
+        config.fields.smoke = Some(FieldImageConfig {
+            file: file.clone(),
+            scale: 1.0,
+            offset: 0.0,
+            invert: false,
+            resample: true,
         });
     }
+
+    if let Some(ref file) = cli.initial_velocity {
+        config.fields.velocity = Some(VelocityFieldConfig {
+            file: file.clone(),
+            scale: 1.0,
+            resample: true,
+        });
+    }
+
+    config.validate()?;
+
+    return Ok(config);
 }
 
-pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<TimeStepper<'t>>> {
-    let velocity_in = vec2!(2.0, 0.0);
-    let height = 1.0;
-    let cell_width = height / cli.dim.y as Scalar;
-    let width = cli.dim.x as Scalar * cell_width;
+fn builtin_scene_config(cli: &CLIArgs) -> GenericResult<SceneConfig> {
+    if cli.scene_idx != 0 {
+        return Err(format!("Not implemented scene index '{}'.", cli.scene_idx).into());
+    }
+
+    let mut config = SceneConfig::channel(cli.dim);
+    config.solver.density = cli.density;
+    config.solver.iterations = cli.incompress_iter;
+    config.solver.execution_mode = if cli.parallel {
+        ExecutionMode::Parallel
+    } else if cli.parallel_unsafe {
+        ExecutionMode::ParallelUnsafe
+    } else if cli.parallel_bands {
+        ExecutionMode::ParallelBands
+    } else if cli.tiled {
+        ExecutionMode::Tiled
+    } else if cli.jacobi {
+        ExecutionMode::Jacobi
+    } else {
+        ExecutionMode::Single
+    };
+    config.time.dt = cli.dt;
+    config.time.end = cli.time_end;
+
+    return config.with_overrides(&cli.overrides);
+}
+
+/// Apply the time and output settings of the scene to the command line arguments.
+/// Outputs given on the command line take precedence.
+pub fn apply_scene_config(cli: &mut CLIArgs, config: &SceneConfig) {
+    cli.dt = config.time.dt;
+    cli.time_end = config.time.end;
+
+    let out = &config.output;
+    if let Some(ref frames) = out.frames {
+        cli.output = frames.clone();
+    }
+    cli.output_vtk = cli.output_vtk.take().or(out.vtk.clone());
+    cli.output_render = cli.output_render.take().or(out.render.clone());
+    cli.output_video = cli.output_video.take().or(out.video.clone());
+    cli.output_checkpoint = cli.output_checkpoint.take().or(out.checkpoint.clone());
+    cli.output_interval = cli.output_interval.or(out.interval);
+    cli.overwrite = cli.overwrite.or(out.overwrite);
+    if cli.export_window.is_none() && cli.export_rect.is_none() {
+        cli.export_window = out.region;
+    }
+}
 
-    let obstacle_size_rel = 0.3;
-    let obstacle_size = obstacle_size_rel * height;
+/// Add the obstacle `o` in `region` to `grid` (the grid `domain`) with its velocity at
+/// the start and a driver in `drivers` if the velocity varies over time.
+fn add_obstacle(
+    grid: &mut Grid,
+    domain: usize,
+    region: &Region,
+    o: &ObstacleConfig,
+    drivers: &mut Vec<Box<dyn Manipulator>>,
+) {
+    let velocity = o.velocity.as_ref().map(VelocityProfile::from_config);
+    grid.add_solid(region, velocity.as_ref().map(|v| v.at(0.0)));
+
+    if let Some(velocity) = velocity.filter(|v| !v.is_constant()) {
+        drivers.push(Box::new(ObstacleDriver::new(domain, grid, region, velocity)));
+    }
+}
+
+pub fn setup_scene<'t>(log: &'t Logger, config: &SceneConfig) -> GenericResult<Box<TimeStepper<'t>>> {
+    let dim = config.dim();
+    let cell_width = config.cell_width();
+    let width = dim.x as Scalar * cell_width;
+    let height = dim.y as Scalar * cell_width;
 
     info!(
         log,
         "Grid: {:.2} x {:.2}, [dim-x: {}, dim-y: {}, cell-width: {}]",
         width,
         height,
-        cli.dim.x,
-        cli.dim.y,
+        dim.x,
+        dim.y,
         cell_width
     );
 
-    let mut grid = Box::new(Grid::new(cli.dim, cell_width));
+    let mut grid = Box::new(Grid::new(dim, cell_width));
+    for side in Side::ALL {
+        grid.set_boundary(side, config.boundary.condition(side));
+    }
+    let params = SolverParams::from_config(&config.solver)?;
+    params.apply(&mut grid);
+    grid.set_activity_threshold(config.solver.activity_threshold);
+    grid.set_smoke_threshold(config.solver.smoke_threshold);
+    grid.set_stencil_table(config.solver.stencil_table);
+
+    // The inflows and obstacles which vary over time are set before each step.
+    let mut drivers: Vec<Box<dyn Manipulator>> = vec![];
+    for side in Side::ALL {
+        let velocity = config.boundary.inflow(side).map(VelocityProfile::from_config);
+        if let Some(velocity) = velocity.filter(|v| !v.is_constant()) {
+            drivers.push(Box::new(InflowDriver {
+                domain: 0,
+                side,
+                velocity,
+            }));
+        }
+    }
+
+    // Setup obstacles.
+    for o in config.obstacles.iter() {
+        let region = Region::from_config(&o.shape(), dim, cell_width)?;
+        add_obstacle(&mut grid, 0, &region, o, &mut drivers);
+    }
+
+    for m in config.masks.iter() {
+        let mask = load_mask(&m.file, config.dim(), m.threshold, m.invert, m.resample)?;
+        grid.add_solid_mask(&mask)?;
+    }
 
-    if cli.scene_idx == 0 {
-        for idx in grid.iter_index() {
-            let is_inside = grid.is_inside_border(idx);
+    if let Some(ref f) = config.fields.smoke {
+        let smoke = load_field(&f.file, config.dim(), f.scale, f.offset, f.invert, f.resample)?;
+        grid.set_inside(&smoke, |c, s| c.set_smoke(*s))?;
+    }
 
-            // Set walls.
-            if idx.x == 0 || idx.y == 0 || idx.y == grid.dim.y - 1 {
-                grid.cell_mut(idx).mode = CellTypes::Solid;
-            }
+    if let Some(ref f) = config.fields.velocity {
+        let velocity: Vec<Vector2> = load_velocity(&f.file, config.dim(), f.resample)?
+            .into_iter()
+            .map(|v| v * f.scale)
+            .collect();
+        grid.set_inside_velocity(&velocity)?;
+    }
 
-            if is_inside && idx.x == 1 {
-                grid.cell_mut(idx).velocity.back = velocity_in;
-            }
+    for i in config.initial.iter() {
+        let region = Region::from_config(&i.region, dim, cell_width)?;
+        if let Some(smoke) = i.smoke {
+            grid.for_each_in(&region, |_, c| {
+                if c.mode == CellTypes::Fluid {
+                    c.set_smoke(smoke);
+                }
+            });
+        }
+        if let Some(v) = i.velocity {
+            grid.set_velocity_in(&region, vec2!(v[0], v[1]));
         }
-    } else {
-        bail!("Not implemented scene index '{}'.", cli.scene_idx);
     }
 
-    let grav = if cli.scene_idx == 0 {
-        Vector2::zeros()
-    } else {
-        cli.gravity
-    };
+    // Set manipulators (for smoke).
+    let mut manips: Vec<Box<dyn Manipulator>> = vec![];
+    for e in config.emitters.iter() {
+        manips.push(Box::new(SmokeEmitter {
+            region: Region::from_config(&e.shape(cell_width), dim, cell_width)?,
+            smoke: e.smoke,
+        }));
+    }
 
-    // Setup obstacle.
-    let p = vec2!(width * 0.25, height * 0.5);
-    grid.set_obstacle(p, obstacle_size / 2.0, None);
+    // The scripts run after the emitters.
+    for s in config.scripts.iter() {
+        manips.push(load_script(s)?);
+    }
 
-    // Set manipulator (for smoke).
-    let smoke_adder = Box::new(AddSmokeBar {
-        center: idx!(1, grid.dim.y / 2),
-        height: (1.1 * obstacle_size_rel * grid.dim.y as Scalar) as usize,
-    });
+    let mut objs: Vec<Box<dyn Integrate>> = vec![grid];
 
-    let manips: Vec<Box<dyn Manipulator>> = vec![smoke_adder];
-    let objs: Vec<Box<dyn Integrate>> = vec![grid];
+    for (i, d) in config.domains.iter().enumerate() {
+        let dim = idx!(d.dim[0], d.dim[1]);
 
+        let mut domain = Box::new(Grid::new(dim, cell_width));
+        for side in Side::ALL {
+            domain.set_boundary(side, BoundaryCondition::Solid);
+        }
+        params.apply(&mut domain);
+        domain.set_activity_threshold(config.solver.activity_threshold);
+        domain.set_smoke_threshold(config.solver.smoke_threshold);
 
-    let exec_mode = if cli.parallel {
-            ExecutionMode::Parallel
-        } else if cli.parallel_unsafe {
-            ExecutionMode::ParallelUnsafe
-        } else {
-            ExecutionMode::Single
-        };
-    let timestepper = Box::new(TimeStepper::new(
-        &log,
-        cli.density,
-        grav,
-        cli.incompress_iter,
-        exec_mode,
-        objs,
-        manips,
-    ));
+        for o in d.obstacles.iter() {
+            let region = Region::from_config(&o.shape(), dim, cell_width)?;
+            add_obstacle(&mut domain, i + 1, &region, o, &mut drivers);
+        }
+        objs.push(domain);
+    }
+
+    // The couplings run before the scripts, which see the exchanged cells.
+    for (i, c) in config.couplings.iter().enumerate() {
+        let coupling = DomainCoupling::from(c);
+        coupling.open(&mut objs)?;
+        manips.insert(config.emitters.len() + i, Box::new(coupling));
+    }
+    manips.splice(0..0, drivers);
+
+    let s = &config.solver;
+    let mut timestepper = Box::new(TimeStepper::new(&log, params, objs, manips));
+    timestepper.set_thread_pool(s.threads, &s.cores)?;
+    timestepper.set_fused_integration(s.fused_integration);
+    timestepper.set_gpu_schedule(s.gpu_schedule);
+    timestepper.set_backend(s.backend);
 
     return Ok(timestepper);
 }
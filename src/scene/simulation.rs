@@ -0,0 +1,581 @@
+use crate::io::checkpoint::Compression;
+use crate::io::state::{decode_state, encode_state};
+use crate::log::Logger;
+use crate::scene::config::{
+    BoundaryConfig, EmitterConfig, ObstacleConfig, OutputConfig, SceneConfig, Side,
+};
+use crate::scene::diagnostics::{Diagnostics, DiagnosticsHistory};
+use crate::scene::events::{Observer, ObserverId};
+use crate::scene::flux::{CrossSection, FluxMeter};
+use crate::scene::force_field::{CurlNoise, ForceField};
+#[cfg(feature = "async")]
+use crate::scene::frames::FrameStream;
+use crate::scene::grid::Grid;
+use crate::scene::history::StateHistory;
+use crate::scene::hooks::{HookId, HookInfo, HookPoint};
+use crate::scene::probes::CellProbe;
+use crate::scene::profile::{InflowDriver, ObstacleDriver, VelocityProfile};
+use crate::scene::region::Region;
+use crate::scene::rng::{Rng, RngStream};
+use crate::scene::run_control::{RunControl, RunState};
+use crate::scene::setup::setup_scene;
+use crate::scene::solver_params::SolverParams;
+use crate::scene::step_stats::StepStats;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// A logger which discards all records, for simulations without a logger.
+fn discard_logger() -> &'static Logger {
+    static LOG: OnceLock<Logger> = OnceLock::new();
+    return LOG.get_or_init(|| slog::Logger::root(slog::Discard, slog::o!()));
+}
+
+/// A localized injection of a `Simulation` in cell units.
+enum Injection {
+    Impulse {
+        index: Index2,
+        velocity: Vector2,
+        radius: Scalar,
+    },
+    Smoke {
+        index: Index2,
+        amount: Scalar,
+        radius: Scalar,
+    },
+}
+
+/// A simulation of a scene: the grid with the emitters, obstacles and boundary
+/// conditions of the `SceneConfig`, the solver parameters, the step size and the
+/// diagnostics of the last steps. The single entry point for applications which
+/// step a scene without wiring the grid, the timestepper and the logger themselves.
+///
+/// The outputs are not written by the simulation, `output()` only holds the settings.
+/// Invalid scenes and parameters, failed I/O and diverged steps are returned as `Error`.
+pub struct Simulation<'l> {
+    config: SceneConfig,
+    timestepper: Box<TimeStepper<'l>>,
+
+    dt: Scalar,
+    steps: u64,
+
+    diagnostics: Option<DiagnosticsHistory>,
+
+    /// The states of the last steps to rewind to.
+    history: Option<StateHistory>,
+
+    /// The flux through the cross-sections of the scene and the added ones.
+    fluxes: FluxMeter,
+
+    /// Fail a step which leaves a value which is not finite.
+    check_finite: bool,
+
+    /// Pauses, resumes or stops the runs, also from other threads.
+    control: RunControl,
+
+    /// The injections applied at the start of the next step.
+    injections: Vec<Injection>,
+
+    /// The random numbers of the stochastic features and their used streams.
+    rng: Rng,
+    streams: BTreeMap<String, RngStream>,
+}
+
+impl Simulation<'static> {
+    /// The simulation of `config` which discards its log.
+    pub fn new(config: SceneConfig) -> FluidResult<Self> {
+        return Simulation::with_logger(discard_logger(), config);
+    }
+
+    /// Load a scene file with overrides `key.path=value` (see `SceneConfig::load`).
+    pub fn from_file(file: &str, overrides: &[String]) -> FluidResult<Self> {
+        let config =
+            SceneConfig::load(file, overrides).map_err(|e| Error::Scene(e.to_string()))?;
+        return Simulation::new(config);
+    }
+
+    /// The frames every `every` steps as an async stream, computed on a worker thread
+    /// at most `buffer` frames ahead of the consumer (feature `async`, see `FrameStream`).
+    #[cfg(feature = "async")]
+    pub fn frames(self, every: Steps, buffer: usize) -> FluidResult<FrameStream> {
+        if every.0 == 0 {
+            let message = "must be at least 1".to_string();
+            return Err(Error::invalid_parameter("every", message));
+        }
+
+        return Ok(FrameStream::new(self, every, buffer));
+    }
+}
+
+impl<'l> Simulation<'l> {
+    /// The simulation of `config` which logs to `log`.
+    pub fn with_logger(log: &'l Logger, config: SceneConfig) -> FluidResult<Self> {
+        config.validate().map_err(|e| Error::Scene(e.to_string()))?;
+        let timestepper = setup_scene(log, &config)?;
+        let rng = Rng::new(config.seed);
+        let fluxes = FluxMeter::from_config(&config.sections);
+
+        return Ok(Simulation {
+            dt: config.time.dt,
+            config,
+            timestepper,
+            steps: 0,
+            diagnostics: None,
+            history: None,
+            fluxes,
+            check_finite: true,
+            control: RunControl::default(),
+            injections: vec![],
+            rng,
+            streams: BTreeMap::new(),
+        });
+    }
+
+    /// Record the `Diagnostics` of the last `capacity` steps (`0`: disabled).
+    /// Each record is an extra pass over the grid.
+    pub fn set_diagnostics(&mut self, capacity: usize) {
+        self.diagnostics = (capacity > 0).then(|| DiagnosticsHistory::new(capacity));
+    }
+
+    /// Keep the states of the last `steps` steps in memory (`0`: disabled), starting
+    /// with the current state, to look at them (`history`) or to `rewind`.
+    /// Each state is a copy of the grids after the step.
+    pub fn set_history(&mut self, steps: usize) {
+        self.history = (steps > 0).then(|| {
+            let mut history = StateHistory::new(steps + 1);
+            history.push(self.steps, &self.timestepper);
+            return history;
+        });
+    }
+
+    /// The states of the last steps if recorded (see `set_history`).
+    pub fn history(&self) -> Option<&StateHistory> {
+        return self.history.as_ref();
+    }
+
+    /// Go back `n` steps to a state of the `history`: the grid, the time and the number
+    /// of steps. The states after it are discarded. The solver parameters, the step size,
+    /// the hooks and the force fields are the current ones, so the next steps branch off
+    /// from the past state, e.g. with a changed parameter.
+    pub fn rewind(&mut self, n: Steps) -> FluidResult<()> {
+        let history = self.history.as_mut().ok_or_else(|| {
+            let message = "needs a history (see `set_history`)".to_string();
+            return Error::invalid_parameter("n", message);
+        })?;
+
+        let recorded = history.len() - 1;
+        let entry = history.rewind(n.0 as usize).ok_or_else(|| {
+            let message = format!("{} is more than the {} recorded steps", n.0, recorded);
+            return Error::invalid_parameter("n", message);
+        })?;
+
+        entry.restore(&mut self.timestepper)?;
+        self.steps = entry.steps;
+        self.fluxes.truncate(self.steps);
+        return Ok(());
+    }
+
+    /// Check after each step that all values are finite (default, see
+    /// `StepStats::nan_count`). A step which diverged returns `Error::Diverged`.
+    pub fn set_check_finite(&mut self, check: bool) {
+        self.check_finite = check;
+    }
+
+    /// Compute one step of size `dt()` and return its statistics, which are one pass
+    /// over the grid.
+    pub fn step(&mut self) -> FluidResult<StepStats> {
+        return self.step_with(self.dt);
+    }
+
+    /// Compute one step of size `dt()`, also if paused (e.g. to single-step a paused
+    /// simulation on its own thread, see `RunControl::step_once` for other threads).
+    pub fn step_once(&mut self) -> FluidResult<StepStats> {
+        return self.step();
+    }
+
+    /// Compute `n` steps of size `dt()`. Waits before a step while paused and returns
+    /// early when stopped (see `control`). Returns the number of computed steps.
+    pub fn run_for(&mut self, n: Steps) -> FluidResult<Steps> {
+        let mut steps = 0;
+        while steps < n.0 && self.control.wait_for_step() {
+            self.step()?;
+            steps += 1;
+        }
+
+        return Ok(Steps(steps));
+    }
+
+    /// Compute steps of size `dt()` until the time `t` is reached. The last step is
+    /// shortened to end at `t`. Waits and stops like `run_for`.
+    /// Returns the number of computed steps.
+    pub fn run_until(&mut self, t: Time) -> FluidResult<Steps> {
+        if !t.is_finite() {
+            return Err(Error::invalid_parameter("t", format!("{} is not finite", t)));
+        }
+
+        let (t, mut steps) = (t.value(), 0);
+
+        // Steps shorter than this are rounding errors of the previous steps.
+        let eps = 1e-6 * self.dt;
+        while self.timestepper.time() < t - eps && self.control.wait_for_step() {
+            self.step_with(self.dt.min(t - self.timestepper.time()))?;
+            steps += 1;
+        }
+
+        return Ok(Steps(steps));
+    }
+
+    /// The handle to pause, resume or stop the runs from any thread.
+    pub fn control(&self) -> RunControl {
+        return self.control.clone();
+    }
+
+    pub fn run_state(&self) -> RunState {
+        return self.control.state();
+    }
+
+    pub fn pause(&self) {
+        self.control.pause();
+    }
+
+    pub fn resume(&self) {
+        self.control.resume();
+    }
+
+    /// Add the velocity `velocity` around the position `pos` (world coordinates) within
+    /// the distance `radius` at the start of the next step. The impulse falls off
+    /// linearly with the distance (see `Grid::add_impulse`).
+    pub fn add_impulse(
+        &mut self,
+        pos: Vector2,
+        velocity: Vector2,
+        radius: Length,
+    ) -> FluidResult<()> {
+        let (index, radius) = self.injection_cells(pos, radius)?;
+        self.injections.push(Injection::Impulse {
+            index,
+            velocity,
+            radius,
+        });
+        return Ok(());
+    }
+
+    /// Add the smoke `amount` around the position `pos` (world coordinates) within the
+    /// distance `radius` at the start of the next step (see `Grid::add_smoke`).
+    pub fn add_smoke(&mut self, pos: Vector2, amount: Scalar, radius: Length) -> FluidResult<()> {
+        let (index, radius) = self.injection_cells(pos, radius)?;
+        self.injections.push(Injection::Smoke {
+            index,
+            amount,
+            radius,
+        });
+        return Ok(());
+    }
+
+    /// The cell at `pos` and `radius` in cells.
+    fn injection_cells(&self, pos: Vector2, radius: Length) -> FluidResult<(Index2, Scalar)> {
+        if !(radius.value() >= 0.0 && radius.is_finite()) {
+            return Err(Error::invalid_parameter("radius", format!("{} is negative", radius)));
+        }
+
+        let grid = self.grid()?;
+        let index = grid.cell_index_at(pos).ok_or_else(|| {
+            let message = format!("{:?} is outside of the grid", pos);
+            return Error::invalid_parameter("pos", message);
+        })?;
+
+        return Ok((index, radius.in_cells(Length(grid.cell_width)).value()));
+    }
+
+    fn step_with(&mut self, dt: Scalar) -> FluidResult<StepStats> {
+        let injections = std::mem::take(&mut self.injections);
+        let grid = self.grid_mut()?;
+        for injection in injections {
+            match injection {
+                Injection::Impulse {
+                    index,
+                    velocity,
+                    radius,
+                } => grid.add_impulse(index, velocity, radius),
+                Injection::Smoke {
+                    index,
+                    amount,
+                    radius,
+                } => grid.add_smoke(index, amount, radius),
+            }
+        }
+
+        self.timestepper.compute_step(dt)?;
+        self.steps += 1;
+
+        let t = self.timestepper.time();
+        let grid = self.timestepper.objects.get(0).and_then(|o| o.as_any().downcast_ref::<Grid>());
+        let grid = grid.ok_or(Error::NotAGrid { index: 0 })?;
+
+        let stats = StepStats::from(grid, dt);
+        if self.check_finite {
+            if let Some(index) = stats.first_nan {
+                return Err(Error::Diverged { t, index });
+            }
+        }
+
+        if let Some(ref mut history) = self.diagnostics {
+            history.push(Diagnostics::from(grid, t, dt));
+        }
+
+        if !self.fluxes.is_empty() {
+            self.fluxes.record(self.steps, t, dt, grid);
+        }
+
+        // A diverged step is not recorded, so it can be rewound.
+        if let Some(ref mut history) = self.history {
+            history.push(self.steps, &self.timestepper);
+        }
+
+        return Ok(stats);
+    }
+
+    pub fn time(&self) -> Time {
+        return Time(self.timestepper.time());
+    }
+
+    pub fn dt(&self) -> Time {
+        return Time(self.dt);
+    }
+
+    pub fn set_dt(&mut self, dt: Time) -> FluidResult<()> {
+        if !(dt.value() > 0.0 && dt.is_finite()) {
+            return Err(Error::invalid_parameter("dt", format!("{} is not positive", dt)));
+        }
+
+        self.dt = dt.value();
+        return Ok(());
+    }
+
+    /// The number of computed steps.
+    pub fn steps(&self) -> Steps {
+        return Steps(self.steps);
+    }
+
+    /// The main grid. Fails if the first object of the timestepper was replaced by
+    /// another object (see `timestepper_mut`).
+    pub fn grid(&self) -> FluidResult<&Grid> {
+        return self.domain(0).ok_or(Error::NotAGrid { index: 0 });
+    }
+
+    /// The interpolated velocity, pressure and smoke, the cell and its type at the world
+    /// position `pos` of the main grid.
+    pub fn inspect(&self, pos: Vector2) -> FluidResult<CellProbe> {
+        return Ok(CellProbe::at(self.grid()?, pos));
+    }
+
+    /// Measure the flux of the fluid and the smoke through the segment from `start` to
+    /// `end` (world coordinates of the main grid) from the next step on (see
+    /// `CrossSection`). Returns the index of the section in `fluxes()`.
+    pub fn add_cross_section(
+        &mut self,
+        name: &str,
+        start: Vector2,
+        end: Vector2,
+    ) -> FluidResult<usize> {
+        if self.fluxes.find(name).is_some() {
+            let message = format!("'{}' is not unique", name);
+            return Err(Error::invalid_parameter("name", message));
+        }
+
+        if start == end {
+            let message = format!("{:?} is the start of the section", end);
+            return Err(Error::invalid_parameter("end", message));
+        }
+
+        return Ok(self.fluxes.add(CrossSection::new(name, start, end)));
+    }
+
+    /// Let the inflow on the solid `side` of the main grid follow `velocity` from the
+    /// next step on, e.g. `VelocityProfile::function(|t| vec2!(2.0 * t.sin(), 0.0))`.
+    /// It replaces an inflow set before on the side.
+    pub fn set_inflow(&mut self, side: Side, velocity: VelocityProfile) -> FluidResult<()> {
+        let boundary = self.grid()?.boundary(side);
+        if !boundary.is_solid() {
+            let message = format!("{:?} is not solid", boundary);
+            return Err(Error::invalid_parameter("side", message));
+        }
+
+        let t = self.time().value();
+        self.grid_mut()?.set_inflow(side, velocity.at(t));
+
+        let manipulators = &mut self.timestepper.manipulators;
+        manipulators.retain_mut(|m| {
+            return m
+                .as_any_mut()
+                .downcast_mut::<InflowDriver>()
+                .map_or(true, |d| d.domain != 0 || d.side != side);
+        });
+        manipulators.insert(
+            0,
+            Box::new(InflowDriver {
+                domain: 0,
+                side,
+                velocity,
+            }),
+        );
+        return Ok(());
+    }
+
+    /// Add the solid cells in `region` of the main grid as an obstacle which moves with
+    /// `velocity` (the velocity of its cells, the cells do not move).
+    pub fn add_moving_obstacle(
+        &mut self,
+        region: &Region,
+        velocity: VelocityProfile,
+    ) -> FluidResult<()> {
+        let t = self.time().value();
+        self.grid_mut()?.add_solid(region, Some(velocity.at(t)));
+
+        let driver = ObstacleDriver::new(0, self.grid()?, region, velocity);
+        self.timestepper.manipulators.insert(0, Box::new(driver));
+        return Ok(());
+    }
+
+    /// The flux through the cross-sections in each step and accumulated over the steps.
+    pub fn fluxes(&self) -> &FluxMeter {
+        return &self.fluxes;
+    }
+
+    /// The grid of domain `i` (`0`: the main grid, see `SceneConfig::domains`).
+    pub fn domain(&self, i: usize) -> Option<&Grid> {
+        return self.timestepper.objects.get(i)?.as_any().downcast_ref::<Grid>();
+    }
+
+    pub fn domain_mut(&mut self, i: usize) -> Option<&mut Grid> {
+        return self.timestepper.objects.get_mut(i)?.as_any_mut().downcast_mut::<Grid>();
+    }
+
+    pub fn grid_mut(&mut self) -> FluidResult<&mut Grid> {
+        return self.domain_mut(0).ok_or(Error::NotAGrid { index: 0 });
+    }
+
+    /// Call `hook` with the grid at `point` of each step (see `TimeStepper::add_hook`).
+    pub fn add_hook<F>(&mut self, point: HookPoint, hook: F) -> HookId
+    where
+        F: FnMut(&HookInfo, &mut Grid) + Send + 'static,
+    {
+        return self.timestepper.add_hook(point, hook);
+    }
+
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        return self.timestepper.remove_hook(id);
+    }
+
+    /// Use the solver parameters `params` from the next step on
+    /// (see `TimeStepper::set_params`).
+    pub fn set_params(&mut self, params: SolverParams) {
+        self.timestepper.set_params(params);
+    }
+
+    /// Add the body force `field` in each step (see `TimeStepper::add_force_field`).
+    pub fn add_force_field<F: ForceField + 'static>(&mut self, field: F) {
+        self.timestepper.add_force_field(field);
+    }
+
+    /// Add a `CurlNoise` whose seed is drawn from the stream `CurlNoise::STREAM`.
+    pub fn add_curl_noise(&mut self, amplitude: Scalar, scale: Scalar, speed: Scalar) {
+        let rng = self.rng_stream(CurlNoise::STREAM);
+        let noise = CurlNoise::seeded(amplitude, scale, speed, rng);
+        self.add_force_field(noise);
+    }
+
+    /// The seeded random numbers of the stochastic features (see `SceneConfig::seed`).
+    pub fn rng(&self) -> Rng {
+        return self.rng;
+    }
+
+    /// The stream `name` of `rng()`, which continues where its last use stopped.
+    pub fn rng_stream(&mut self, name: &str) -> &mut RngStream {
+        let rng = self.rng;
+        return self
+            .streams
+            .entry(name.to_string())
+            .or_insert_with(|| rng.stream(name));
+    }
+
+    /// Notify `observer` of the events of the steps (see `TimeStepper::subscribe`).
+    pub fn subscribe<O: Observer + 'static>(&mut self, observer: O) -> ObserverId {
+        return self.timestepper.subscribe(observer);
+    }
+
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        return self.timestepper.unsubscribe(id);
+    }
+
+    /// The timestepper, e.g. to change the solver parameters during the run.
+    pub fn timestepper(&self) -> &TimeStepper<'l> {
+        return &self.timestepper;
+    }
+
+    pub fn timestepper_mut(&mut self) -> &mut TimeStepper<'l> {
+        return &mut self.timestepper;
+    }
+
+    /// The scene the simulation was set up with.
+    pub fn config(&self) -> &SceneConfig {
+        return &self.config;
+    }
+
+    pub fn emitters(&self) -> &[EmitterConfig] {
+        return &self.config.emitters;
+    }
+
+    pub fn obstacles(&self) -> &[ObstacleConfig] {
+        return &self.config.obstacles;
+    }
+
+    pub fn boundary(&self) -> &BoundaryConfig {
+        return &self.config.boundary;
+    }
+
+    pub fn output(&self) -> &OutputConfig {
+        return &self.config.output;
+    }
+
+    /// The diagnostics of the last steps if recorded (see `set_diagnostics`).
+    pub fn diagnostics(&self) -> Option<&DiagnosticsHistory> {
+        return self.diagnostics.as_ref();
+    }
+
+    pub fn save_checkpoint(&mut self, file: &str, compression: Compression) -> FluidResult<()> {
+        return Ok(self.timestepper.save_checkpoint(file, compression)?);
+    }
+
+    /// Continue from the checkpoint `file` of the same scene.
+    pub fn load_checkpoint(&mut self, file: &str) -> FluidResult<()> {
+        return Ok(self.timestepper.load_checkpoint(file)?);
+    }
+
+    /// The time, the step size, the number of steps, all grids and the random number
+    /// streams as a versioned, platform-independent byte buffer (see
+    /// `io::state::encode_state`), e.g. to send the simulation to another process or to a
+    /// remote viewer. The force fields are not part of it (see `load_state`).
+    pub fn save_state(&self, compression: Compression) -> FluidResult<Vec<u8>> {
+        let (steps, dt) = (self.steps, self.dt);
+        return Ok(encode_state(&self.timestepper, &self.streams, steps, dt, compression)?);
+    }
+
+    /// Continue from the buffer `bytes` of `save_state` of the same scene. The fluxes
+    /// measured after its step are discarded. The force fields of this simulation are
+    /// kept, they have to be added as on the saving side.
+    pub fn load_state(&mut self, bytes: &[u8]) -> FluidResult<()> {
+        let (_, mut state) = decode_state(bytes)?;
+        let (steps, dt) = (state.steps, state.dt);
+        let streams = std::mem::take(&mut state.streams);
+
+        state.restore(&mut self.timestepper)?;
+        self.steps = steps;
+        self.dt = dt;
+        self.streams = streams;
+        self.fluxes.truncate(steps);
+        return Ok(());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::scene::cell::Cell;
+use crate::types::*;
+
+use rayon::prelude::*;
+
+/// Tracks the bounding box of the cells with smoke, so that the smoke advection can
+/// skip the empty parts of the domain.
+///
+/// A cell has smoke if its smoke exceeds `threshold` in magnitude. The box is updated
+/// before each advection, hence it grows with the plume and with new emitters.
+/// Cells at most the fastest velocity times `dt` away from the box (the halo) are
+/// advected too, since their departure points can lie inside the box.
+pub struct SmokeRegion {
+    pub threshold: Scalar,
+
+    /// The box `[min, max)` of the cells with smoke (`None` if there is no smoke).
+    bounds: Option<(Index2, Index2)>,
+
+    /// The largest velocity component of all cells.
+    max_velocity: Scalar,
+}
+
+impl SmokeRegion {
+    pub fn new(threshold: Scalar) -> Self {
+        return SmokeRegion {
+            threshold,
+            bounds: None,
+            max_velocity: 0.0,
+        };
+    }
+
+    /// Recompute the box and the largest velocity of the `cells` (rows of `dim.x`).
+    pub fn update(&mut self, cells: &[Cell], dim: Index2) {
+        assert!(cells.len() == dim.x * dim.y, "Cells do not match the dimension.");
+
+        let thr = self.threshold;
+        let merge = |a: Option<(Index2, Index2)>, b: Option<(Index2, Index2)>| {
+            return match (a, b) {
+                (Some(a), Some(b)) => Some((a.0.inf(&b.0), a.1.sup(&b.1))),
+                (a, None) => a,
+                (None, b) => b,
+            };
+        };
+
+        let (bounds, max_velocity) = cells
+            .par_chunks(dim.x)
+            .enumerate()
+            .map(|(y, row)| {
+                let mut x_range: Option<(usize, usize)> = None;
+                let mut vel: Scalar = 0.0;
+
+                for (x, c) in row.iter().enumerate() {
+                    vel = vel.max(c.velocity.back.amax());
+                    if c.smoke().abs() > thr {
+                        x_range = Some((x_range.map_or(x, |r| r.0), x + 1));
+                    }
+                }
+
+                let bounds = x_range.map(|(x0, x1)| (idx!(x0, y), idx!(x1, y + 1)));
+                return (bounds, vel);
+            })
+            .reduce(|| (None, 0.0), |a, b| (merge(a.0, b.0), a.1.max(b.1)));
+
+        self.bounds = bounds;
+        self.max_velocity = max_velocity;
+    }
+
+    /// The box `[min, max)` of the cells with smoke at the last update.
+    pub fn bounds(&self) -> Option<(Index2, Index2)> {
+        return self.bounds;
+    }
+
+    /// The window `[min, max)` of the cells to advect over a step `dt` on a grid of
+    /// dimension `dim` with cells of width `cell_width`: the box plus the halo.
+    /// The window is empty (`min == max`) if there is no smoke.
+    pub fn window(&self, dim: Index2, dt: Scalar, cell_width: Scalar) -> (Index2, Index2) {
+        let (min, max) = match self.bounds {
+            Some(b) => b,
+            None => return (Index2::zeros(), Index2::zeros()),
+        };
+
+        let cells = (self.max_velocity * dt / cell_width).ceil();
+        let halo = cells.min((dim.x + dim.y) as Scalar) as usize + 1;
+        let min = min.map(|v| v.saturating_sub(halo));
+        let max = (max + idx!(halo, halo)).inf(&dim);
+
+        return (min, max);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::render::{render_smoke, RenderParams};
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use image::RgbaImage;
+
+/// A copy of the cell fields at one point in time, taken on demand
+/// independent of the output interval.
+/// All fields include the border cells and are stored row by row (`x` varies fastest).
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub time: Scalar,
+    pub dim: Index2,
+    pub cell_width: Scalar,
+
+    pub pressure: Vec<Scalar>,
+    pub smoke: Vec<Scalar>,
+    pub divergence: Vec<Scalar>,
+
+    /// The velocity interpolated to the cell centers.
+    pub velocity: Vec<Vector2>,
+    pub solid: Vec<bool>,
+}
+
+fn grid<'g>(timestepper: &'g TimeStepper) -> &'g Grid {
+    return timestepper.objects[0]
+        .as_any()
+        .downcast_ref::<Grid>()
+        .expect("Not a grid");
+}
+
+impl Snapshot {
+    pub fn new(grid: &Grid, time: Scalar) -> Self {
+        let field = |get: &dyn Fn(Index2) -> Scalar| -> Vec<Scalar> {
+            return grid.iter_index().map(get).collect();
+        };
+
+        return Snapshot {
+            time,
+            dim: grid.dim,
+            cell_width: grid.cell_width,
+            pressure: field(&|idx| grid.cell(idx).pressure),
+            smoke: field(&|idx| grid.cell(idx).smoke()),
+            divergence: field(&|idx| grid.cell(idx).div),
+            velocity: grid
+                .iter_index()
+                .map(|idx| grid.cell_center_velocity(idx))
+                .collect(),
+            solid: grid
+                .iter_index()
+                .map(|idx| grid.cell(idx).mode == CellTypes::Solid)
+                .collect(),
+        };
+    }
+
+    /// The snapshot of the simulation's grid at the current time.
+    pub fn of(timestepper: &TimeStepper) -> Self {
+        return Snapshot::new(grid(timestepper), timestepper.time());
+    }
+
+    /// The position of cell `index` in the fields.
+    pub fn offset(&self, index: Index2) -> usize {
+        return index.x + index.y * self.dim.x;
+    }
+}
+
+/// Render the smoke of the simulation's grid at the current time.
+pub fn screenshot(timestepper: &TimeStepper, params: &RenderParams) -> RgbaImage {
+    return render_smoke(grid(timestepper), params);
+}
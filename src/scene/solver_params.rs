@@ -0,0 +1,188 @@
+use crate::scene::config::SolverConfig;
+use crate::scene::grid::Grid;
+use crate::scene::integrator::Integrator;
+use crate::scene::pressure_solver::SolveParams;
+use crate::scene::timestepper::ExecutionMode;
+use crate::types::*;
+
+use derive_builder::UninitializedFieldError;
+
+/// The parameters of the solver of a timestepper, validated when built:
+/// ```ignore
+/// let params = SolverParamsBuilder::default()
+///     .gravity(vec2!(0.0, -9.81))
+///     .iterations(40)
+///     .build()?;
+/// ```
+/// The gravity, the density, the iterations and the execution mode are passed to
+/// `Integrate::integrate` and `Integrate::solve_incompressibility` in each step. The
+/// over-relaxation, the vorticity confinement, the schemes and the deterministic mode
+/// are settings of the grids, which `TimeStepper::set_params` copies to them
+/// (see `SolverParams::apply`). The threads and the cores set up the thread pool of the
+/// timestepper before its next step (see `TimeStepper::set_thread_pool`).
+#[derive(Builder, Clone, Debug)]
+#[builder(
+    pattern = "mutable",
+    build_fn(validate = "Self::validate", error = "Error")
+)]
+pub struct SolverParams {
+    #[builder(default = "Vector2::zeros()")]
+    pub gravity: Vector2,
+
+    #[builder(default = "1000.0")]
+    pub density: Scalar,
+
+    /// The over-relaxation of the Gauss-Seidel pressure solve in `(0, 2)`.
+    /// The `Jacobi` execution mode has a fixed relaxation (`JACOBI_RELAXATION`).
+    #[builder(default = "1.9")]
+    pub over_relaxation: Scalar,
+
+    /// The iterations of the pressure solve (at least one).
+    #[builder(default = "100")]
+    pub iterations: u64,
+
+    /// The largest CFL number `max|v| * dt / h` of a step, reported with
+    /// `SolverEvent::CflViolated`.
+    #[builder(default = "1.0")]
+    pub max_cfl: Scalar,
+
+    #[builder(default = "0.0")]
+    pub vorticity_confinement: Scalar,
+
+    #[builder(default = "ExecutionMode::Single")]
+    pub execution_mode: ExecutionMode,
+
+    /// The integrator of the body forces (see `Grid::set_integrator`).
+    #[builder(default)]
+    pub integrator: Integrator,
+
+    /// The integrator of the departure points of the advection (see `Grid::set_backtrace`).
+    #[builder(default)]
+    pub backtrace: Integrator,
+
+    /// Bit-identical results for any number of threads (see `Grid::set_deterministic`).
+    #[builder(default = "false")]
+    pub deterministic: bool,
+
+    /// The number of worker threads of the own pool of the timestepper (`0`: rayon's
+    /// global pool, or one worker per core if `cores` is not empty).
+    #[builder(default = "0")]
+    pub threads: usize,
+
+    /// Pin the workers round-robin to these cores (empty: no pinning).
+    #[builder(default)]
+    pub cores: Vec<usize>,
+}
+
+impl Default for SolverParams {
+    fn default() -> Self {
+        return SolverParamsBuilder::default()
+            .build()
+            .expect("Default parameters are valid.");
+    }
+}
+
+impl SolverParams {
+    /// The parameters of the solver settings of a scene.
+    pub fn from_config(config: &SolverConfig) -> FluidResult<Self> {
+        let mut builder = SolverParamsBuilder::default();
+        builder
+            .gravity(vec2!(config.gravity[0], config.gravity[1]))
+            .density(config.density)
+            .iterations(config.iterations)
+            .vorticity_confinement(config.vorticity_confinement)
+            .execution_mode(config.execution_mode)
+            .integrator(config.integrator)
+            .backtrace(config.backtrace)
+            .deterministic(config.deterministic)
+            .threads(config.threads)
+            .cores(config.cores.clone());
+
+        // The scenes always have an over-relaxation, which `Jacobi` does not use.
+        if !matches!(config.execution_mode, ExecutionMode::Jacobi) {
+            builder.over_relaxation(config.over_relaxation);
+        }
+
+        return builder.build();
+    }
+
+    /// The parameters of the pressure solve.
+    pub fn solve_params(&self) -> SolveParams {
+        return SolveParams {
+            iterations: self.iterations,
+            density: self.density,
+            execution_mode: self.execution_mode,
+        };
+    }
+
+    /// Copy the settings of the grids to `grid`.
+    pub fn apply(&self, grid: &mut Grid) {
+        grid.over_relaxation = self.over_relaxation;
+        grid.vorticity_confinement = self.vorticity_confinement;
+        grid.set_integrator(self.integrator);
+        grid.set_backtrace(self.backtrace);
+        grid.set_deterministic(self.deterministic);
+    }
+}
+
+impl SolverParamsBuilder {
+    fn validate(&self) -> FluidResult<()> {
+        let invalid = |name, message: String| Err(Error::invalid_parameter(name, message));
+
+        if let Some(g) = self.gravity {
+            if !g.iter().all(|v| v.is_finite()) {
+                return invalid("gravity", format!("{:?} is not finite", g));
+            }
+        }
+
+        if let Some(d) = self.density {
+            if !(d > 0.0 && d.is_finite()) {
+                return invalid("density", format!("{} is not positive", d));
+            }
+        }
+
+        if let Some(r) = self.over_relaxation {
+            if !(r > 0.0 && r < 2.0) {
+                return invalid("over_relaxation", format!("{} is not in (0, 2)", r));
+            }
+
+            if let Some(ExecutionMode::Jacobi) = self.execution_mode {
+                return invalid(
+                    "over_relaxation",
+                    "is not used by the execution mode 'Jacobi'".to_string(),
+                );
+            }
+        }
+
+        if self.iterations == Some(0) {
+            return invalid("iterations", "must be at least 1".to_string());
+        }
+
+        if let Some(c) = self.max_cfl {
+            if c.is_nan() || c <= 0.0 {
+                return invalid("max_cfl", format!("{} is not positive", c));
+            }
+        }
+
+        if let Some(v) = self.vorticity_confinement {
+            if !(v >= 0.0 && v.is_finite()) {
+                return invalid("vorticity_confinement", format!("{} is negative", v));
+            }
+        }
+
+        // Pinning is not supported on all platforms, then any core is accepted.
+        if let (Some(cores), Some(ids)) = (&self.cores, core_affinity::get_core_ids()) {
+            if let Some(c) = cores.iter().find(|c| !ids.iter().any(|id| id.id == **c)) {
+                return invalid("cores", format!("core '{}' is not available", c));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl From<UninitializedFieldError> for Error {
+    fn from(e: UninitializedFieldError) -> Self {
+        return Error::invalid_parameter("params", e.to_string());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::scene::active_region::ActiveRegion;
+use crate::scene::cell::{Cell, CellTypes};
+use crate::types::*;
+
+/// The precomputed stencil of a cell which the pressure solve relaxes.
+#[derive(Clone, Copy, Debug)]
+pub struct StencilEntry {
+    /// The data index of the cell.
+    pub k: u32,
+
+    /// The number of non-solid neighbors and its reciprocal.
+    pub s: Scalar,
+    pub s_inv: Scalar,
+
+    /// The factors of the negative/positive neighbors (0: solid, 1: fluid).
+    pub s_nbs: [Vector2; 2],
+}
+
+/// The stencils of all cells of a step which the sequential pressure solve relaxes,
+/// in sweep order. The table is built once per step, such that the iterations are
+/// pure arithmetic over the cells without checking the cell modes, the border
+/// or the active region.
+#[derive(Default)]
+pub struct StencilTable {
+    entries: Vec<StencilEntry>,
+}
+
+impl StencilTable {
+    /// Rebuild the table for the `cells` (rows of `dim.x` cells).
+    /// Returns the indices of the non-solid cells without non-solid neighbors.
+    pub fn build(
+        &mut self,
+        cells: &[Cell],
+        dim: Index2,
+        active: Option<&ActiveRegion>,
+    ) -> Vec<Index2> {
+        assert!(cells.len() == dim.x * dim.y, "Cells do not match the dimension.");
+        assert!(cells.len() <= u32::MAX as usize, "Too many cells for the table.");
+
+        let s_factor = |k: usize| (cells[k].mode != CellTypes::Solid) as u8 as Scalar;
+        let stride = dim.x;
+        let mut isolated = vec![];
+
+        self.entries.clear();
+        for y in 1..dim.y - 1 {
+            for x in 1..dim.x - 1 {
+                let k = x + y * stride;
+                if cells[k].mode == CellTypes::Solid
+                    || !active.map_or(true, |a| a.is_active(idx!(x, y)))
+                {
+                    continue;
+                }
+
+                let s_nbs = [
+                    vec2!(s_factor(k - 1), s_factor(k - stride)),
+                    vec2!(s_factor(k + 1), s_factor(k + stride)),
+                ];
+                let s = s_nbs[0].sum() + s_nbs[1].sum();
+
+                if s == 0.0 {
+                    isolated.push(idx!(x, y));
+                    continue;
+                }
+
+                self.entries.push(StencilEntry {
+                    k: k as u32,
+                    s,
+                    s_inv: 1.0 / s,
+                    s_nbs,
+                });
+            }
+        }
+
+        return isolated;
+    }
+
+    pub fn bytes(&self) -> usize {
+        return self.entries.capacity() * std::mem::size_of::<StencilEntry>();
+    }
+
+    pub fn entries(&self) -> &[StencilEntry] {
+        return &self.entries;
+    }
+}
@@ -0,0 +1,89 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::Grid;
+use crate::types::*;
+
+/// The statistics of one step of a `Simulation` (see `Simulation::step`), e.g. to adapt
+/// the step size to the CFL number or to stop on a stalled pressure solve.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StepStats {
+    /// The size of the step, shorter than `Simulation::dt` for the last step of
+    /// `run_until`.
+    pub dt: Scalar,
+
+    /// The CFL number `max_velocity * dt / h` of the step.
+    pub cfl: Scalar,
+
+    /// The iterations of the pressure solve.
+    pub iterations: u64,
+
+    /// The residual of the pressure solve, or else the maximal absolute divergence of
+    /// the grid (as `SolverEvent::SolverConverged`).
+    pub residual: Scalar,
+
+    /// The largest absolute velocity component after the step.
+    pub max_velocity: Scalar,
+
+    /// The smallest and largest smoke of the cells which are not solid.
+    pub smoke_min: Scalar,
+    pub smoke_max: Scalar,
+
+    /// The number of cells with a velocity, a pressure or a smoke which is not finite.
+    /// These cells are left out of the values above.
+    pub nan_count: usize,
+
+    /// The first cell which is not finite.
+    pub first_nan: Option<Index2>,
+}
+
+impl StepStats {
+    /// The statistics of `grid` after a step of size `dt` (one pass over the cells).
+    pub fn from(grid: &Grid, dt: Scalar) -> Self {
+        let mut stats = StepStats {
+            dt,
+            smoke_min: Scalar::MAX,
+            smoke_max: Scalar::MIN,
+            ..Default::default()
+        };
+
+        for row in grid.iter_rows_inside() {
+            let cells = grid.row_cells(&row);
+
+            for ((idx, _), cell) in row.indices().zip(cells) {
+                let vel = cell.velocity.back;
+                let finite = vel.iter().all(|v| v.is_finite())
+                    && cell.pressure.is_finite()
+                    && cell.smoke().is_finite();
+
+                if !finite {
+                    stats.nan_count += 1;
+                    stats.first_nan = stats.first_nan.or(Some(idx));
+                    continue;
+                }
+
+                stats.max_velocity = stats.max_velocity.max(vel.amax());
+                if cell.mode != CellTypes::Solid {
+                    stats.smoke_min = stats.smoke_min.min(cell.smoke());
+                    stats.smoke_max = stats.smoke_max.max(cell.smoke());
+                }
+            }
+        }
+
+        if stats.smoke_min > stats.smoke_max {
+            stats.smoke_min = 0.0;
+            stats.smoke_max = 0.0;
+        }
+
+        let solve = grid.solve_stats();
+        stats.iterations = solve.iterations;
+        stats.residual = solve
+            .residual
+            .unwrap_or(grid.stats[0].div.abs().max(grid.stats[1].div.abs()));
+        stats.cfl = stats.max_velocity * dt / grid.cell_width;
+
+        return stats;
+    }
+
+    pub fn is_finite(&self) -> bool {
+        return self.nan_count == 0;
+    }
+}
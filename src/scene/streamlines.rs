@@ -0,0 +1,93 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct StreamlineParams {
+    /// Seed a streamline every `seed_stride` cells in each direction.
+    #[builder(default = "5")]
+    pub seed_stride: usize,
+
+    /// Maximal number of integration steps per streamline.
+    #[builder(default = "30")]
+    pub max_steps: usize,
+
+    /// Length of one integration step in cell widths.
+    #[builder(default = "0.5")]
+    pub step_length: Scalar,
+
+    /// Also integrate backwards from the seed such that the seed lies in the middle.
+    #[builder(default = "true")]
+    pub both_directions: bool,
+}
+
+/// Integrate a streamline from `seed` (world coordinates) with the midpoint method
+/// with fixed steps along the normalized velocity (`sign` = -1 for backwards).
+/// Stops at solid cells, the domain border or stagnation points.
+pub fn trace_streamline(grid: &Grid, seed: Vector2, params: &StreamlineParams, sign: Scalar) -> Vec<Vector2> {
+    let h = params.step_length * grid.cell_width;
+    let eps = 1e-9;
+
+    let is_fluid = |p: Vector2| {
+        return grid.cell_index_at(p).map_or(false, |idx| {
+            grid.is_inside_border(idx) && grid.cell(idx).mode == CellTypes::Fluid
+        });
+    };
+
+    let direction = |p: Vector2| {
+        let v = grid.sample_velocity(p) * sign;
+        let n = v.norm();
+        return if n > eps { Some(v / n) } else { None };
+    };
+
+    let mut line = vec![seed];
+    let mut p = seed;
+
+    for _ in 0..params.max_steps {
+        let d1 = match direction(p) {
+            Some(d) => d,
+            None => break,
+        };
+        let d2 = match direction(p + 0.5 * h * d1) {
+            Some(d) => d,
+            None => break,
+        };
+
+        let next = p + h * d2;
+        if !is_fluid(next) {
+            break;
+        }
+
+        line.push(next);
+        p = next;
+    }
+
+    return line;
+}
+
+/// Trace streamlines seeded at the fluid cell centers every `seed_stride` cells.
+pub fn compute_streamlines(grid: &Grid, params: &StreamlineParams) -> Vec<Vec<Vector2>> {
+    assert!(params.seed_stride >= 1, "Stride must be at least 1.");
+
+    return grid
+        .iter_inside()
+        .filter(|idx| idx.x % params.seed_stride == 0 && idx.y % params.seed_stride == 0)
+        .filter(|idx| grid.cell(*idx).mode == CellTypes::Fluid)
+        .map(|idx| {
+            let seed = grid.cell_center(idx);
+            let mut line = trace_streamline(grid, seed, params, 1.0);
+
+            if params.both_directions {
+                let mut back = trace_streamline(grid, seed, params, -1.0);
+                back.reverse();
+                back.pop(); // The seed.
+                back.append(&mut line);
+                line = back;
+            }
+
+            return line;
+        })
+        .filter(|line| line.len() >= 2)
+        .collect();
+}
@@ -0,0 +1,216 @@
+use crate::scene::config::SceneConfig;
+use crate::scene::diagnostics::Diagnostics;
+use crate::scene::simulation::Simulation;
+use crate::types::*;
+
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// The values of one scene parameter of a sweep: `key.path=v1,v2,...` with the keys of
+/// the overrides (see `SceneConfig::load`), e.g. `solver.iterations=20,40,80` or
+/// `grid.dim=[100,50],[200,100]` (commas inside brackets do not split).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepParam {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for SweepParam {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, values) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Sweep parameter '{}' needs the form 'key.path=v1,v2'.", s))?;
+
+        let mut split = vec![String::new()];
+        let mut depth = 0;
+        for c in values.chars() {
+            match c {
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    split.push(String::new());
+                    continue;
+                }
+                _ => {}
+            }
+            split.last_mut().unwrap().push(c);
+        }
+
+        let values: Vec<String> = split.iter().map(|v| v.trim().to_string()).collect();
+        if key.trim().is_empty() || values.iter().any(|v| v.is_empty()) {
+            return Err(format!(
+                "Sweep parameter '{}' has an empty key or value.",
+                s
+            ));
+        }
+
+        return Ok(SweepParam {
+            key: key.trim().to_string(),
+            values,
+        });
+    }
+}
+
+/// The overrides of all combinations of the values of `params`,
+/// with the values of the last parameter changing fastest.
+pub fn sweep_overrides(params: &[SweepParam]) -> Vec<Vec<String>> {
+    let mut runs = vec![vec![]];
+    for p in params {
+        runs = runs
+            .into_iter()
+            .flat_map(|run: Vec<String>| {
+                return p.values.iter().map(move |v| {
+                    let mut run = run.clone();
+                    run.push(format!("{}={}", p.key, v));
+                    return run;
+                });
+            })
+            .collect();
+    }
+
+    return runs;
+}
+
+/// The outcome of one combination of a sweep.
+pub struct SweepResult {
+    /// The position of the combination in `sweep_overrides`.
+    pub index: usize,
+    pub overrides: Vec<String>,
+    pub steps: u64,
+
+    /// The wall time of the steps.
+    pub seconds: Scalar,
+
+    /// The diagnostics after the last step.
+    pub diagnostics: Option<Diagnostics>,
+
+    /// The reason of an invalid scene or a failed step.
+    pub error: Option<String>,
+}
+
+/// Run the scene `base` with `overrides` for `steps` steps.
+/// An invalid scene or a failed step is recorded in the result.
+pub fn run_sweep_point(
+    base: &SceneConfig,
+    index: usize,
+    overrides: &[String],
+    steps: u64,
+) -> SweepResult {
+    let mut result = SweepResult {
+        index,
+        overrides: overrides.to_vec(),
+        steps: 0,
+        seconds: 0.0,
+        diagnostics: None,
+        error: None,
+    };
+
+    let sim = base
+        .clone()
+        .with_overrides(overrides)
+        .map_err(|e| Error::Scene(e.to_string()))
+        .and_then(Simulation::new);
+
+    let mut sim = match sim {
+        Ok(sim) => sim,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    sim.set_diagnostics(1);
+
+    let start = Instant::now();
+    for _ in 0..steps {
+        if let Err(e) = sim.step() {
+            result.error = Some(e.to_string());
+            break;
+        }
+        result.steps += 1;
+    }
+
+    result.seconds = start.elapsed().as_secs_f64() as Scalar;
+    result.diagnostics = sim.diagnostics().and_then(|d| d.last()).copied();
+    return result;
+}
+
+/// Run all combinations of `params` on the scene `base` for `steps` steps each, one
+/// after the other. With `shard = (k, n)` only the combinations `k, k + n, ...` are run,
+/// e.g. by `n` processes.
+pub fn run_sweep(
+    base: &SceneConfig,
+    params: &[SweepParam],
+    steps: u64,
+    shard: Option<(usize, usize)>,
+) -> Vec<SweepResult> {
+    let (k, n) = shard.unwrap_or((0, 1));
+
+    return sweep_overrides(params)
+        .iter()
+        .enumerate()
+        .skip(k)
+        .step_by(n.max(1))
+        .map(|(i, overrides)| run_sweep_point(base, i, overrides, steps))
+        .collect();
+}
+
+/// The header of the summary table of a sweep over `params`.
+pub fn format_sweep_header(params: &[SweepParam]) -> String {
+    let mut out = format!("{:>5}", "run");
+    for p in params {
+        let _ = write!(out, " {:>w$}", p.key, w = column_width(p));
+    }
+
+    let _ = writeln!(
+        out,
+        " {:>6} {:>10} {:>12} {:>12} {:>12}  {}",
+        "steps", "time [s]", "max div", "energy", "residual", "status"
+    );
+    return out;
+}
+
+/// The rows of the summary table with the values of the `params` and the diagnostics
+/// after the last step, one line per result starting with its index.
+pub fn format_sweep_rows(params: &[SweepParam], results: &[SweepResult]) -> String {
+    let mut out = String::new();
+
+    for r in results {
+        let _ = write!(out, "{:>5}", r.index);
+        for (p, o) in params.iter().zip(r.overrides.iter()) {
+            let value = o.split_once('=').map_or("", |(_, v)| v);
+            let _ = write!(out, " {:>w$}", value, w = column_width(p));
+        }
+
+        let d = r.diagnostics.unwrap_or_default();
+        let _ = writeln!(
+            out,
+            " {:>6} {:>10.3} {:>12.4e} {:>12.4e} {:>12.4e}  {}",
+            r.steps,
+            r.seconds,
+            d.max_divergence,
+            d.kinetic_energy,
+            d.residual,
+            r.error.as_deref().unwrap_or("ok").replace('\n', " ")
+        );
+    }
+
+    return out;
+}
+
+/// Format the results as table with one row per combination.
+pub fn format_sweep_table(params: &[SweepParam], results: &[SweepResult]) -> String {
+    return format_sweep_header(params) + &format_sweep_rows(params, results);
+}
+
+fn column_width(p: &SweepParam) -> usize {
+    return p
+        .values
+        .iter()
+        .map(|v| v.len())
+        .chain([p.key.len()])
+        .max()
+        .unwrap_or(0);
+}
@@ -1,9 +1,47 @@
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::scene::grid::*;
+    use crate::types::*;
+
+    /// A cone of smoke in a uniform flow, which is translated by `velocity * t`.
+    pub(crate) fn create_blob(velocity: Vector2) -> Grid {
+        let mut grid = Grid::new(dim!(40, 30), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let r = (grid.cell_center(idx) - vec2!(1.5, 1.2)).norm();
+            let c = grid.cell_mut(idx);
+            c.velocity.back = velocity;
+            c.set_smoke((1.0 - r / 0.4).max(0.0));
+        }
+        return grid;
+    }
+
+    /// The center of the smoke.
+    pub(crate) fn centroid(grid: &Grid) -> Vector2 {
+        let (mut sum, mut moment) = (0.0, Vector2::zeros());
+        for idx in grid.iter_index() {
+            sum += grid.cell(idx).smoke();
+            moment += grid.cell(idx).smoke() * grid.cell_center(idx);
+        }
+        return moment / sum;
+    }
+
+    /// The largest velocity component of the inside cells, the scale of velocity errors.
+    pub(crate) fn max_speed(grid: &Grid) -> Scalar {
+        return grid
+            .iter_inside()
+            .map(|i| grid.cell(i).velocity.back.amax())
+            .fold(0.0, Scalar::max);
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use crate::log::*;
     use crate::scene::grid::*;
+    use crate::scene::boundary::BoundaryCondition;
     use crate::scene::cell::*;
+    use crate::scene::config::*;
     use crate::types::*;
     use float_cmp::approx_eq;
 
@@ -59,4 +97,2365 @@ mod tests {
         assert!(approx_eq!(Scalar, val, 0.0, epsilon = 1e-6), "Val: {}", val);
     }
 
+    #[test]
+    fn check_grid_iter_region() {
+        let grid = Grid::new(dim!(4, 3), 1.0);
+
+        let indices: Vec<Index2> = grid.iter_region(idx!(1, 2), idx!(3, 4)).collect();
+        assert!(
+            indices == vec![idx!(1, 2), idx!(2, 2), idx!(1, 3), idx!(2, 3)],
+            "Indices: {:?}",
+            indices
+        );
+
+        // Window is clamped to the grid.
+        let count = grid.iter_region(idx!(4, 3), idx!(100, 100)).count();
+        assert!(count == 2 * 2, "Count: {}", count);
+
+        let count = grid.iter_inside().count();
+        assert!(count == 4 * 3, "Count: {}", count);
+
+        let count = grid.iter_region(idx!(2, 2), idx!(2, 4)).count();
+        assert!(count == 0, "Count: {}", count);
+    }
+
+    #[test]
+    fn check_grid_arena() {
+        use crate::scene::grid_arena::GridArena;
+
+        let mut grid = Grid::new(dim!(6, 4), 0.5);
+        grid.cell_mut(idx!(2, 3)).set_smoke(0.5);
+
+        let arena = GridArena::new(1);
+        for _ in 0..3 {
+            let copy = arena.snapshot(&grid);
+            assert!(copy.dim == grid.dim && copy.cell(idx!(2, 3)).smoke() == 0.5);
+        }
+        assert!(arena.allocations() == 1, "Allocations: {}", arena.allocations());
+
+        // The storage returns from another thread.
+        let copy = arena.crop(&grid, idx!(1, 2), idx!(4, 4));
+        assert!(copy.dim == idx!(3, 2) && copy.cell(idx!(1, 1)).smoke() == 0.5);
+        std::thread::spawn(move || drop(copy)).join().unwrap();
+
+        let copy = arena.crop(&grid, idx!(1, 2), idx!(4, 4));
+        assert!(copy.origin() == idx!(1, 2));
+        assert!(arena.allocations() == 2, "Allocations: {}", arena.allocations());
+
+        let (a, b) = (arena.snapshot(&grid), arena.snapshot(&grid));
+        assert!(arena.allocations() == 3, "Allocations: {}", arena.allocations());
+        drop((a, b));
+    }
+
+    #[test]
+    fn check_grid_iter_rows() {
+        let grid = Grid::new(dim!(4, 3), 1.0);
+
+        // Same order as `iter_region` with the data slots of the indices.
+        let windows = [
+            (idx!(1, 2), idx!(3, 4)),
+            (idx!(4, 3), idx!(100, 100)),
+            (idx!(2, 2), idx!(2, 4)),
+        ];
+        for (min, max) in windows {
+            let rows: Vec<_> = grid.iter_rows(min, max).flat_map(GridRow::indices).collect();
+            let indices: Vec<Index2> = grid.iter_region(min, max).collect();
+
+            assert!(rows.iter().map(|r| r.0).eq(indices), "Rows: {:?}", rows);
+            assert!(rows.iter().all(|(i, k)| *k == i.x + i.y * grid.dim.x), "Rows: {:?}", rows);
+        }
+
+        let inside: usize = grid.iter_rows_inside().map(|r| grid.row_cells(&r).len()).sum();
+        assert!(inside == 4 * 3, "Count: {}", inside);
+    }
+
+    #[test]
+    fn check_grid_from_fn() {
+        let grid = Grid::from_fn(dim!(5, 3), 0.5, |i| {
+            let mut cell = Cell::new(i);
+            cell.pressure = (i.x + 10 * i.y) as Scalar;
+            return cell;
+        });
+
+        assert!(grid.dim == idx!(7, 5) && grid.cell_width == 0.5);
+        for i in grid.iter_index() {
+            let cell = grid.cell(i);
+            assert!(cell.index() == i, "Index {} at {}", cell.index(), i);
+            assert!(cell.pressure == (i.x + 10 * i.y) as Scalar, "Pressure at {}", i);
+        }
+    }
+
+    #[test]
+    fn check_field_layout() {
+        use crate::scene::field_layout::*;
+
+        for dim in [idx!(1, 3), idx!(16, 2), idx!(17, 5), idx!(102, 52)] {
+            for components in [1, 2] {
+                let mut field = AlignedField::<f32>::new(dim, components);
+                let layout = field.layout();
+
+                assert!((layout.stride * 4 / components) % FIELD_ALIGN == 0);
+                assert!(layout.stride >= dim.x * components);
+                assert!(layout.size() == layout.stride * dim.y);
+                assert!(field.as_slice().as_ptr() as usize % FIELD_ALIGN == 0);
+
+                field.row_mut(dim.y - 1)[components * (dim.x - 1)] = 1.0;
+                let k = layout.data_index(idx!(dim.x - 1, dim.y - 1));
+                assert!(field.as_slice()[k] == 1.0);
+                assert!(field.row(dim.y - 1).len() == dim.x * components);
+                assert!(field.as_slice().iter().sum::<f32>() == 1.0);
+            }
+        }
+
+        let layout = FieldLayout::new::<f64>(idx!(9, 2), 1);
+        assert!(layout.stride == 16 && layout.data_index(idx!(3, 1)) == 19);
+    }
+
+    #[test]
+    fn check_grid_faces() {
+        let grid = Grid::new(dim!(2, 1), 2.0);
+
+        // Internal dim is (4, 3): u-faces in x: 1..4, y: 0..3.
+        let faces: Vec<Face> = grid.iter_u_faces().collect();
+        assert!(faces.len() == 3 * 3, "Count: {}", faces.len());
+
+        let f = &faces[0];
+        assert!(f.index == idx!(1, 0) && f.cells == [idx!(0, 0), idx!(1, 0)]);
+        assert!(f.pos == vec2!(2.0, 1.0), "Pos: {}", f.pos);
+
+        let faces: Vec<Face> = grid.iter_v_faces().collect();
+        assert!(faces.len() == 4 * 2, "Count: {}", faces.len());
+
+        let f = &faces[0];
+        assert!(f.index == idx!(0, 1) && f.cells == [idx!(0, 0), idx!(0, 1)]);
+        assert!(f.pos == vec2!(1.0, 2.0), "Pos: {}", f.pos);
+    }
+
+    #[test]
+    fn check_scene_config() {
+        let toml = r#"
+            [grid]
+            dim = [40, 20]
+
+            [solver]
+            gravity = [0.0, -9.81]
+            iterations = 80
+
+            [[obstacles]]
+            center = [0.5, 0.5]
+            radius = 0.1
+        "#;
+
+        let config = SceneConfig::parse(toml, "toml").unwrap();
+        assert!(config.dim() == idx!(40, 20));
+        assert!(config.cell_width() == 1.0 / 20.0);
+        assert!(config.solver.iterations == 80);
+        assert!(config.solver.density == 1000.0);
+        assert!(config.obstacles.len() == 1);
+        assert!(config.boundary.left == BoundaryCondition::Solid);
+        assert!(config.boundary.right == BoundaryCondition::Outflow);
+
+        let yaml = "
+            time:
+              dt: 0.01
+            emitters:
+              - min: [0, 2]
+                max: [1, 5]
+        ";
+
+        let config = SceneConfig::parse(yaml, "yaml").unwrap();
+        assert!(config.time.dt == 0.01);
+        assert!(config.emitters[0].smoke == 1.0);
+
+        assert!(SceneConfig::parse("[grid]\nsize = 3", "toml").is_err());
+        assert!(SceneConfig::parse("[time]\ndt = -1.0", "toml").is_err());
+    }
+
+    #[test]
+    fn check_scene_config_includes() {
+        let dir = std::env::temp_dir().join("rustofluid-test-scenes");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(
+            dir.join("base.toml"),
+            "[grid]\ndim = [40, 20]\n[solver]\niterations = 50\ndensity = 10.0\n",
+        )
+        .unwrap();
+
+        std::fs::write(
+            dir.join("variant.ron"),
+            r#"(include: ["base.toml"], solver: (iterations: 60), obstacles: [(center: (0.5, 0.5), radius: 0.1)])"#,
+        )
+        .unwrap();
+
+        let file = dir.join("variant.ron");
+        let config = SceneConfig::load(
+            file.to_str().unwrap(),
+            &[
+                "solver.iterations=80".to_string(),
+                "obstacles.0.radius=0.2".to_string(),
+            ],
+        )
+        .unwrap();
+
+        assert!(config.dim() == idx!(40, 20));
+        assert!(config.solver.density == 10.0);
+        assert!(config.solver.iterations == 80);
+        assert!(config.obstacles[0].radius == 0.2);
+
+        let res = SceneConfig::load(file.to_str().unwrap(), &["solver.iter=80".to_string()]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn check_bench() {
+        use crate::scene::bench::*;
+        use crate::scene::timestepper::ExecutionMode;
+
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let results: Vec<BenchResult> = BenchScene::ALL
+            .iter()
+            .map(|s| run_bench(&log, *s, idx!(20, 10), 2, ExecutionMode::Single).unwrap())
+            .collect();
+
+        assert!(results.iter().all(|r| r.throughput() > 0.0));
+
+        let table = format_bench_table(&results);
+        assert!(table.lines().count() == 1 + BenchScene::ALL.len());
+        assert!(table.contains("dense-emitters") && table.contains("20x10"));
+
+        assert!("channel".parse::<BenchScene>() == Ok(BenchScene::Channel));
+        assert!("box".parse::<BenchScene>().is_err());
+    }
+
+    #[test]
+    fn check_streamlines() {
+        use crate::scene::streamlines::*;
+
+        let mut grid = Grid::new(dim!(20, 10), 1.0);
+        for idx in grid.iter_index() {
+            grid.cell_mut(idx).velocity.back = vec2!(1.0, 0.0);
+        }
+
+        let mut builder = StreamlineParamsBuilder::default();
+        builder.max_steps(30).step_length(0.5);
+
+        let line = trace_streamline(&grid, vec2!(5.5, 5.5), &builder.build().unwrap(), 1.0);
+        assert!(line.len() == 31, "Wrong length {}.", line.len());
+        let eps = if cfg!(feature = "f32") { 1e-4 } else { 1e-9 };
+        assert!(line.iter().all(|p| approx_eq!(Scalar, p.y, 5.5, epsilon = eps)));
+        assert!(approx_eq!(Scalar, line[30].x, 20.5, epsilon = eps));
+
+        // Backwards the line stops before the left border cell.
+        let line = trace_streamline(&grid, vec2!(5.5, 5.5), &builder.build().unwrap(), -1.0);
+        assert!(line.last().unwrap().x >= 1.0);
+
+        // Solid cells stop the streamline.
+        grid.cell_mut(idx!(8, 5)).mode = CellTypes::Solid;
+        let line = trace_streamline(&grid, vec2!(5.5, 5.5), &builder.build().unwrap(), 1.0);
+        assert!(line.last().unwrap().x < 8.0);
+
+        let lines = compute_streamlines(&grid, &builder.seed_stride(5).build().unwrap());
+        assert!(!lines.is_empty());
+    }
+
+    #[test]
+    fn check_image_mask_and_field() {
+        use crate::io::bitmap::load_mask;
+
+        // A 4x2 image with a black pixel at the top left and a dark gray one at the bottom right.
+        let mut img = image::GrayImage::from_pixel(4, 2, image::Luma([255]));
+        img.put_pixel(0, 0, image::Luma([0]));
+        img.put_pixel(3, 1, image::Luma([100]));
+
+        let file = std::env::temp_dir().join("rustofluid-test-mask.png");
+        img.save(&file).unwrap();
+        let file = file.to_str().unwrap();
+
+        let mask = load_mask(file, idx!(4, 2), 0.5, false, false).unwrap();
+        assert!(mask == [false, false, false, true, true, false, false, false], "{:?}", mask);
+
+        let mask = load_mask(file, idx!(4, 2), 0.3, true, false).unwrap();
+        assert!(mask.iter().filter(|m| **m).count() == 7);
+
+        assert!(load_mask(file, idx!(8, 4), 0.5, false, false).is_err());
+        assert!(load_mask(file, idx!(8, 4), 0.5, false, true).unwrap().len() == 32);
+
+        let smoke = crate::io::bitmap::load_field(file, idx!(4, 2), 2.0, 1.0, false, false).unwrap();
+        assert!(smoke[0] == 3.0 && smoke[4] == 1.0);
+
+        let mut grid = Grid::new(dim!(4, 2), 1.0);
+        grid.set_inside(&smoke, |c, s| c.set_smoke(*s)).unwrap();
+        assert!(grid.cell(idx!(1, 2)).smoke() == 1.0 && grid.cell(idx!(1, 1)).smoke() == 3.0);
+
+        grid.add_solid_mask(&load_mask(file, idx!(4, 2), 0.5, false, false).unwrap()).unwrap();
+        assert!(grid.cell(idx!(1, 2)).mode == CellTypes::Solid);
+        assert!(grid.cell(idx!(4, 1)).mode == CellTypes::Solid);
+        assert!(grid.cell(idx!(2, 2)).mode == CellTypes::Fluid);
+    }
+
+    #[test]
+    fn check_record_and_replay() {
+        use crate::scene::replay::*;
+        use crate::scene::setup::setup_scene;
+
+        let (log, _) = create_logger();
+        let config = SceneConfig::channel(dim!(20, 10));
+        let dt = config.time.dt;
+
+        let checksum = |ts: &crate::scene::timestepper::TimeStepper| {
+            let g = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            return grid_checksum(g);
+        };
+
+        // Record a run with an impulse and a parameter change.
+        let mut recorder = Recorder::new(&config, dt);
+        let mut ts = setup_scene(&log, &config).unwrap();
+
+        for step in 0..10 {
+            if step == 3 {
+                let impulse = Interaction::Impulse {
+                    index: [5, 5],
+                    velocity: [1.0, -2.0],
+                    radius: 2.0,
+                };
+                impulse.apply(&mut ts);
+                recorder.record(step, impulse);
+            }
+            if step == 6 {
+                let mut p = Parameters::from(&mut ts);
+                p.gravity = [0.0, -9.81];
+                p.apply(&mut ts);
+                recorder.record_parameters(step, &p);
+                recorder.record_parameters(step, &p);
+            }
+            ts.compute_step(dt).unwrap();
+        }
+
+        assert!(recorder.recording.events.len() == 2, "Unchanged parameters recorded twice.");
+
+        let file = std::env::temp_dir().join("rustofluid-test-replay.json");
+        let file = file.to_str().unwrap();
+        let g = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+        recorder.finish(10, g, file).unwrap();
+        let expected = checksum(&ts);
+
+        // Replay it.
+        let recording = Recording::load(file).unwrap();
+        let mut ts = setup_scene(&log, &recording.config).unwrap();
+        for step in 0..recording.steps {
+            recording.apply(step, &mut ts);
+            ts.compute_step(recording.dt).unwrap();
+        }
+
+        assert!(checksum(&ts) == expected, "Replay is not bit-for-bit identical.");
+        let g = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+        assert!(recording.verify(g).is_ok());
+
+        // Without the impulse the run diverges.
+        let mut ts = setup_scene(&log, &recording.config).unwrap();
+        for _ in 0..recording.steps {
+            ts.compute_step(recording.dt).unwrap();
+        }
+        let g = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+        assert!(recording.verify(g).is_err());
+    }
+
+    #[test]
+    fn check_probes() {
+        use crate::scene::probes::*;
+
+        let mut grid = Grid::new(dim!(10, 10), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let c = grid.cell_mut(idx);
+            c.velocity.back = vec2!(1.0, -2.0);
+            c.pressure = idx.x as Scalar;
+            c.set_smoke(0.5);
+        }
+
+        let point: ProbeConfig = "p=0.5,0.5".parse().unwrap();
+        let region: ProbeConfig = "r=0.2,0.2,0.3,0.3".parse().unwrap();
+        assert!(point.size.is_none() && region.size == Some([0.3, 0.3]));
+        assert!("p=1".parse::<ProbeConfig>().is_err());
+        assert!("1,2".parse::<ProbeConfig>().is_err());
+
+        let mut probes = Probes::from_config(&[point, region]);
+        probes.record(0, 0.1, &grid);
+        probes.record(1, 0.2, &grid);
+
+        let s = probes.series(0);
+        assert!(s.len() == 2 && s[1].step == 1 && s[1].time == 0.2);
+        assert!((s[0].velocity - vec2!(1.0, -2.0)).norm() < 1e-12, "{}", s[0].velocity);
+        assert!((s[0].smoke - 0.5).abs() < 1e-12);
+
+        // Cell centers at `x = 0.5` lie between cells `4` and `5`.
+        assert!((s[0].pressure - 4.5).abs() < 1e-9, "{}", s[0].pressure);
+
+        // Region over the cells `2..=4` (centers `0.25, 0.35, 0.45`).
+        let r = probes.series(1)[0];
+        assert!((r.pressure - 3.0).abs() < 1e-9, "{}", r.pressure);
+
+        let file = std::env::temp_dir().join("rustofluid-test-probes.csv");
+        probes.write_csv(file.to_str().unwrap()).unwrap();
+        let content = std::fs::read_to_string(&file).unwrap();
+        assert!(content.lines().count() == 1 + 4);
+        assert!(content.lines().nth(3).unwrap().starts_with("r,0,0.1,"));
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.probes = vec!["a=0.1,0.1".parse().unwrap(), "a=0.2,0.2".parse().unwrap()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn check_parallel_bands() {
+        use crate::scene::setup::setup_scene;
+        use crate::scene::tests::fixtures::max_speed;
+        use crate::scene::timestepper::ExecutionMode;
+
+        let (log, _) = create_logger();
+
+        // Odd inside dimensions are allowed. All sweep orders converge to the same
+        // projection, hence the bands agree with the serial solve up to the residual.
+        let mut config = SceneConfig::channel(dim!(31, 17));
+        config.solver.iterations = 200;
+
+        let mut run = |mode: ExecutionMode, threads: usize| {
+            config.solver.execution_mode = mode;
+            config.solver.threads = threads;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            assert!(threads == 0 || ts.threads() == threads);
+            for _ in 0..5 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+
+            return ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid").snapshot();
+        };
+
+        let serial = run(ExecutionMode::Single, 0);
+        let tolerance = 1e-3 * max_speed(&serial);
+        for threads in [1, 3] {
+            let bands = run(ExecutionMode::ParallelBands, threads);
+            for idx in serial.iter_inside() {
+                let dv = serial.cell(idx).velocity.back - bands.cell(idx).velocity.back;
+                assert!(dv.amax() < tolerance, "Velocity differs at {}: {}.", idx, dv);
+            }
+        }
+    }
+
+    #[test]
+    fn check_smoke_region() {
+        use crate::scene::setup::setup_scene;
+
+        let (log, _) = create_logger();
+        let mut config = SceneConfig::channel(dim!(60, 20));
+
+        let mut run = |threshold: Scalar| {
+            config.solver.smoke_threshold = threshold;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            let mut bounds = vec![];
+            for _ in 0..5 {
+                ts.compute_step(config.time.dt).unwrap();
+
+                let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+                bounds.push(grid.smoke_region().and_then(|r| r.bounds()));
+            }
+
+            let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            let smoke: Vec<Scalar> = grid.iter_index().map(|i| grid.cell(i).smoke()).collect();
+            return (smoke, bounds);
+        };
+
+        let (full, _) = run(0.0);
+        let (sparse, bounds) = run(1e-20);
+        assert!(full == sparse, "The sparse advection differs.");
+
+        // The plume starts at the left border and grows.
+        let width = |b: &Option<(Index2, Index2)>| b.map_or(0, |(min, max)| max.x - min.x);
+        assert!(width(&bounds[0]) > 0 && width(&bounds[0]) < 60, "Bounds: {:?}", bounds[0]);
+        assert!(bounds.windows(2).all(|b| width(&b[0]) <= width(&b[1])), "Bounds: {:?}", bounds);
+    }
+
+    #[test]
+    fn check_fixed_grid() {
+        use crate::scene::fixed_grid::FixedGrid;
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::Integrate;
+
+        let (log, _) = create_logger();
+
+        let create = || {
+            let mut grid = Grid::new(dim!(14, 8), 0.1);
+            for idx in grid.iter_inside().collect::<Vec<_>>() {
+                let p = idx.cast::<Scalar>();
+                grid.cell_mut(idx).velocity.back = vec2!((0.7 * p.x).sin(), (0.4 * p.y).cos());
+            }
+            grid.add_obstacle(vec2!(0.6, 0.4), 0.15, None);
+            return grid;
+        };
+
+        let mut grid = create();
+        let mut fixed = FixedGrid::<16, 10>::from_grid(create()).unwrap();
+        assert!(FixedGrid::<16, 11>::from_grid(create()).is_err());
+
+        let params = SolverParamsBuilder::default().iterations(20).build().unwrap();
+        for _ in 0..3 {
+            grid.solve_incompressibility(&log, 0.01, &params).unwrap();
+            fixed.solve_incompressibility(&log, 0.01, &params).unwrap();
+        }
+
+        let same = grid.iter_index().all(|i| {
+            let (a, b) = (grid.cell(i), fixed.cell(i));
+            return a.velocity.back == b.velocity.back && a.pressure == b.pressure;
+        });
+        assert!(same, "The fixed grid differs.");
+
+        let mut other = FixedGrid::<16, 10>::new(0.1);
+        *other.grid_mut() = Grid::new(dim!(4, 4), 0.1);
+        let result = other.solve_incompressibility(&log, 0.01, &params);
+        assert!(matches!(result, Err(Error::DimensionMismatch { .. })), "Dimension changed.");
+
+        // Downcasts see the wrapped grid.
+        let object: Box<dyn Integrate> = Box::new(fixed);
+        assert!(object.as_any().downcast_ref::<Grid>().is_some());
+    }
+
+    #[test]
+    fn check_stencil_table() {
+        use crate::scene::setup::setup_scene;
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(23, 13));
+        config.solver.iterations = 30;
+
+        let mut run = |table: bool, threshold: Scalar| {
+            config.solver.stencil_table = table;
+            config.solver.activity_threshold = threshold;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            for _ in 0..4 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+
+            let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            return grid
+                .iter_index()
+                .map(|i| grid.cell(i))
+                .map(|c| (c.velocity.back, c.pressure, c.div))
+                .collect::<Vec<_>>();
+        };
+
+        for threshold in [0.0, 1e-3] {
+            assert!(run(false, threshold) == run(true, threshold), "Threshold {}", threshold);
+        }
+    }
+
+    #[test]
+    fn check_jacobi_deterministic() {
+        use crate::scene::setup::setup_scene;
+        use crate::scene::timestepper::ExecutionMode;
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(31, 17));
+        config.solver.execution_mode = ExecutionMode::Jacobi;
+
+        let mut run = |threads: usize, iterations: u64| {
+            config.solver.threads = threads;
+            config.solver.iterations = iterations;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            for _ in 0..3 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+
+            let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            let bits: Vec<_> = grid
+                .iter_index()
+                .map(|i| grid.cell(i))
+                .map(|c| (c.velocity.back.map(Scalar::to_bits), c.pressure.to_bits()))
+                .collect();
+            let max_div = grid
+                .iter_inside()
+                .filter(|i| grid.cell(*i).mode == CellTypes::Fluid)
+                .map(|i| grid.cell(i).div.abs())
+                .fold(0.0, Scalar::max);
+
+            return (bits, max_div);
+        };
+
+        let (single, converged) = run(1, 400);
+        let (multi, _) = run(3, 400);
+        assert!(single == multi, "Jacobi depends on the number of threads.");
+
+        let (_, few) = run(3, 5);
+        assert!(
+            converged.is_finite() && converged < few,
+            "Jacobi does not converge: {} vs. {}",
+            converged,
+            few
+        );
+    }
+
+    #[test]
+    fn check_smoke_storage() {
+        let mut cell = Cell::new(idx!(1, 1));
+        for s in [0.0, 0.25, 0.5, 1.0] {
+            cell.set_smoke(s);
+            assert!(cell.smoke() == s, "Smoke {} is not exact.", s);
+        }
+
+        cell.set_smoke(0.1234);
+        assert!((cell.smoke() - 0.1234).abs() < 1e-3, "Smoke {} is not close.", cell.smoke());
+    }
+
+    #[test]
+    fn check_fused_integration() {
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::{Integrate, TimeStepper};
+
+        let (log, _) = create_logger();
+
+        let mut grid = Grid::new(dim!(30, 15), 1.0);
+        for idx in grid.iter_inside() {
+            let c = grid.cell_mut(idx);
+            c.velocity.back = vec2!(1.0 + 0.1 * idx.y as Scalar, 0.0);
+            c.set_smoke(if idx.x < 10 { 1.0 } else { 0.0 });
+        }
+        let params = SolverParamsBuilder::default()
+            .gravity(vec2!(0.0, -9.81))
+            .iterations(40)
+            .build()
+            .unwrap();
+
+        // The separate passes in the order of the fused step.
+        let (dt, mut separate) = (0.05, grid.snapshot());
+        for _ in 0..5 {
+            separate.advect(&log, dt, params.execution_mode);
+            separate.integrate(&log, dt, &params);
+            separate.solve_incompressibility(&log, dt, &params).unwrap();
+        }
+
+        let objs: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+        let mut ts = TimeStepper::new(&log, params, objs, vec![]);
+        ts.set_fused_integration(true);
+        for _ in 0..5 {
+            ts.compute_step(dt).unwrap();
+        }
+
+        // The fused pass adds the same force, and the projection is the last pass.
+        let fused = ts.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+        for idx in separate.iter_index() {
+            let (a, b) = (separate.cell(idx), fused.cell(idx));
+            assert!(a.velocity.back == b.velocity.back, "Velocity differs at {}.", idx);
+            assert!(a.smoke() == b.smoke(), "Smoke differs at {}.", idx);
+        }
+    }
+
+    #[test]
+    fn check_thread_pool_cores() {
+        use crate::scene::setup::setup_scene;
+
+        use crate::scene::solver_params::SolverParamsBuilder;
+
+        let (log, _) = create_logger();
+        let mut config = SceneConfig::channel(dim!(20, 10));
+
+        let ids = match core_affinity::get_core_ids() {
+            Some(ids) if !ids.is_empty() => ids,
+            _ => {
+                eprintln!("Skipped, pinning is not supported.");
+                return;
+            }
+        };
+
+        config.solver.cores = vec![ids[0].id];
+        let ts = setup_scene(&log, &config).unwrap();
+        assert!(ts.threads() == 1, "One worker per core.");
+
+        config.solver.threads = 2;
+        let mut ts = setup_scene(&log, &config).unwrap();
+        assert!(ts.threads() == 2, "Workers are pinned round-robin.");
+
+        // The solver parameters set up a new pool before the next step.
+        let params = SolverParamsBuilder::default().threads(3).build().unwrap();
+        ts.set_params(params);
+        ts.compute_step(config.time.dt).unwrap();
+        assert!(ts.threads() == 3 && ts.params().cores.is_empty(), "Pool is not replaced.");
+
+        let unknown = ids.iter().map(|id| id.id).max().unwrap() + 1;
+        config.solver.cores = vec![unknown];
+        assert!(setup_scene(&log, &config).is_err(), "Unknown core is accepted.");
+
+        let params = SolverParamsBuilder::default().cores(vec![unknown]).build();
+        assert!(matches!(params, Err(Error::InvalidParameter { name: "cores", .. })));
+    }
+
+    #[test]
+    fn check_profiler() {
+        use crate::scene::profiler::{Profiler, Stage};
+        use crate::scene::timestepper::StageTimings;
+        use std::time::{Duration, Instant};
+
+        let ms = Duration::from_millis;
+        let mut profiler = Profiler::new(true);
+
+        for _ in 0..2 {
+            let timings = StageTimings {
+                manipulate: ms(1),
+                integrate: ms(2),
+                solve: ms(3),
+                advect: ms(4),
+            };
+            profiler.record_step(Instant::now(), &timings);
+            profiler.record(Stage::Output, Instant::now(), ms(5));
+
+            let step = profiler.end_step();
+            assert!(step == [ms(1), ms(2), ms(3), ms(4), ms(5)], "{:?}", step);
+        }
+
+        assert!(profiler.steps() == 2);
+        assert!(profiler.total(Stage::Project) == ms(6));
+
+        let report = profiler.report();
+        assert!(report.contains("project") && report.contains("30.000"), "{}", report);
+
+        let file = std::env::temp_dir().join("rustofluid-test-trace.json");
+        profiler.write_trace(file.to_str().unwrap()).unwrap();
+
+        let trace: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert!(events.len() == 10, "Events: {}", events.len());
+        assert!(events[2]["name"] == "project" && events[2]["dur"] == 3000);
+        assert!(events[9]["args"]["step"] == 1);
+
+        // The solver stages follow each other.
+        let end = |e: &serde_json::Value| e["ts"].as_u64().unwrap() + e["dur"].as_u64().unwrap();
+        assert!(end(&events[0]) == events[1]["ts"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn check_active_region() {
+        use crate::scene::bench::BenchScene;
+        use crate::scene::setup::setup_scene;
+        use crate::scene::timestepper::TimeStepper;
+
+        let (log, _) = create_logger();
+
+        // A still tank.
+        let mut config = BenchScene::EmptyBox.config(dim!(128, 64));
+        config.solver.gravity = [0.0, 0.0];
+        config.solver.activity_threshold = 1e-4;
+
+        let mut ts = setup_scene(&log, &config).unwrap();
+        let fraction = |ts: &TimeStepper| {
+            let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            return grid.active_region().expect("Not tracked").active_fraction();
+        };
+
+        // All blocks are active until a step is stored.
+        ts.compute_step(config.time.dt).unwrap();
+        assert!(fraction(&ts) == 1.0, "Fraction: {}", fraction(&ts));
+        ts.compute_step(config.time.dt).unwrap();
+        assert!(fraction(&ts) == 0.0, "Fraction: {}", fraction(&ts));
+
+        // Only the blocks around the impulse and their halo.
+        let grid = ts.objects[0].as_any_mut().downcast_mut::<Grid>().expect("Not a grid");
+        grid.add_impulse(idx!(8, 8), vec2!(1.0, 0.5), 2.0);
+        ts.compute_step(config.time.dt).unwrap();
+
+        let f = fraction(&ts);
+        assert!(f > 0.0 && f < 0.25, "Fraction: {}", f);
+
+        let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+        let far = grid.cell(idx!(120, 60)).velocity.back;
+        assert!(far == Vector2::zeros(), "Quiescent cell changed: {}", far);
+    }
+
+    #[test]
+    fn check_scratch_pool() {
+        use crate::scene::scratch::ScratchPool;
+
+        let mut pool = ScratchPool::default();
+
+        let a = pool.take::<Scalar>(10);
+        assert!(a.len() == 10 && a.iter().all(|v| *v == 0.0), "Buffer not cleared.");
+        pool.give(a);
+
+        let mut b = pool.take::<Scalar>(5);
+        b[0] = 3.0;
+        pool.give(b);
+        assert!(pool.take::<Scalar>(8)[0] == 0.0, "Buffer not cleared.");
+        assert!(pool.allocations() == 1, "Reused buffer allocated again.");
+
+        pool.take::<Vector2>(4);
+        assert!(pool.allocations() == 2, "Element types share buffers.");
+    }
+
+    #[test]
+    fn check_scratch_no_allocations_after_first_step() {
+        use crate::scene::setup::setup_scene;
+        use crate::scene::timestepper::{ExecutionMode, TimeStepper};
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.solver.vorticity_confinement = 0.3;
+
+        for mode in [ExecutionMode::Single, ExecutionMode::ParallelBands] {
+            config.solver.execution_mode = mode;
+            let mut ts = setup_scene(&log, &config).unwrap();
+
+            let allocations = |ts: &TimeStepper| {
+                let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+                return grid.scratch().allocations();
+            };
+
+            ts.compute_step(config.time.dt).unwrap();
+            let first = allocations(&ts);
+            for _ in 0..3 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+            assert!(allocations(&ts) == first, "Steps allocate scratch buffers.");
+        }
+    }
+
+    #[test]
+    fn check_tiled_pressure_solve() {
+        use crate::scene::grid::TILE_SIZE;
+        use crate::scene::setup::setup_scene;
+        use crate::scene::tests::fixtures::max_speed;
+        use crate::scene::timestepper::ExecutionMode;
+
+        let (log, _) = create_logger();
+
+        // More than one tile in each direction. All sweep orders converge to the same
+        // projection, hence the tiles agree with the serial solve up to the residual.
+        let mut config = SceneConfig::channel(dim!(TILE_SIZE + 13, TILE_SIZE + 7));
+        config.solver.iterations = 300;
+
+        let mut run = |mode: ExecutionMode| {
+            config.solver.execution_mode = mode;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            for _ in 0..3 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+
+            return ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid").snapshot();
+        };
+
+        let serial = run(ExecutionMode::Single);
+        let tiled = run(ExecutionMode::Tiled);
+        let tolerance = 1e-3 * max_speed(&serial);
+        for idx in serial.iter_inside() {
+            let dv = serial.cell(idx).velocity.back - tiled.cell(idx).velocity.back;
+            assert!(dv.amax() < tolerance, "Velocity differs at {}: {}.", idx, dv);
+        }
+    }
+
+    #[test]
+    fn check_backend_fallback() {
+        use crate::scene::setup::setup_scene;
+        use crate::scene::timestepper::Backend;
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.solver.backend = Backend::Gpu;
+
+        // Without feature `gpu` or a GPU the steps run on the CPU.
+        let mut ts = setup_scene(&log, &config).unwrap();
+        if !cfg!(feature = "gpu") {
+            assert!(ts.backend() == Backend::Cpu, "Expected the CPU fallback.");
+        }
+
+        ts.compute_step(config.time.dt).unwrap();
+        assert!(ts.time() == config.time.dt);
+
+        assert!(ts.set_backend(Backend::Cpu) == Backend::Cpu);
+        assert!(ts.backend() == Backend::Cpu);
+    }
+
+    #[test]
+    fn check_parallel_advection_is_identical() {
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::{ExecutionMode, Integrate};
+
+        let (log, _) = create_logger();
+
+        let create = || {
+            let mut grid = Grid::new(dim!(23, 11), 0.1);
+            for idx in grid.iter_index().collect::<Vec<_>>() {
+                let p = idx.cast::<Scalar>();
+                let c = grid.cell_mut(idx);
+                c.velocity.back = vec2!((0.3 * p.x).sin(), (0.7 * p.y).cos());
+                c.set_smoke((0.1 * (p.x + 2.0 * p.y)).fract());
+            }
+            grid.add_obstacle(vec2!(0.8, 0.6), 0.2, None);
+            return grid;
+        };
+
+        let mut serial = create();
+        let mut parallel = create();
+
+        let runs = [
+            (&mut serial, ExecutionMode::Single),
+            (&mut parallel, ExecutionMode::Parallel),
+        ];
+        for (grid, mode) in runs {
+            let params = SolverParamsBuilder::default()
+                .gravity(vec2!(0.0, -9.81))
+                .execution_mode(mode)
+                .build()
+                .unwrap();
+            grid.integrate(&log, 0.01, &params);
+            grid.advect(&log, 0.01, mode);
+        }
+
+        for idx in serial.iter_index() {
+            let (a, b) = (serial.cell(idx), parallel.cell(idx));
+            assert!(a.velocity.back == b.velocity.back, "Velocity differs at {}.", idx);
+            assert!(a.velocity.front == b.velocity.front, "Velocity differs at {}.", idx);
+            assert!(a.smoke().to_bits() == b.smoke().to_bits(), "Smoke differs at {}.", idx);
+        }
+    }
+
+    #[test]
+    fn check_grid_sample_batch() {
+        let mut grid = Grid::new(dim!(13, 7), 0.5);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let p = idx.cast::<Scalar>();
+            grid.cell_mut(idx).velocity.back = vec2!((0.4 * p.x).sin(), p.x * p.y);
+        }
+
+        // Include positions outside the grid and a length which is not a multiple of the lanes.
+        let positions = (0..3 * SAMPLE_LANES + 3)
+            .map(|i| {
+                let t = i as Scalar;
+                return vec2!(-1.0 + 0.37 * t, 8.0 - 0.29 * t);
+            })
+            .collect::<Vec<_>>();
+
+        for dir in 0..2 {
+            let get = |cell: &Cell| cell.velocity.back[dir];
+            let (min, max) = (idx!(1, 1), grid.dim - idx!(1, 1));
+
+            let mut values = vec![0.0; positions.len()];
+            grid.sample_field_batch(min, max, &positions, Some(dir), get, &mut values);
+
+            for (pos, v) in positions.iter().zip(values) {
+                let expected = grid.sample_field(min, max, *pos, Some(dir), get);
+                assert!(
+                    v.to_bits() == expected.to_bits(),
+                    "Batch sample {} differs from {} at {}.",
+                    v,
+                    expected,
+                    pos
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_snapshot() {
+        use crate::render::RenderParamsBuilder;
+        use crate::scene::setup::setup_scene;
+        use crate::scene::snapshot::*;
+
+        let (log, _) = create_logger();
+        let config = SceneConfig::channel(dim!(20, 10));
+        let mut ts = setup_scene(&log, &config).unwrap();
+        ts.compute_step(config.time.dt).unwrap();
+
+        let s = Snapshot::of(&ts);
+        let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+        let idx = idx!(3, 4);
+
+        assert!(s.time == ts.time() && s.dim == grid.dim);
+        assert!(s.smoke.len() == grid.dim.x * grid.dim.y);
+        assert!(s.smoke[s.offset(idx)] == grid.cell(idx).smoke());
+        assert!(s.velocity[s.offset(idx)] == grid.cell_center_velocity(idx));
+
+        // The snapshot is a copy.
+        ts.compute_step(config.time.dt).unwrap();
+        assert!(s.time < ts.time());
+
+        let params = RenderParamsBuilder::default().scale(2).build().unwrap();
+        let img = screenshot(&ts, &params);
+        assert!(img.width() as usize == 2 * s.dim.x && img.height() as usize == 2 * s.dim.y);
+    }
+
+    #[test]
+    fn check_diagnostics() {
+        use crate::scene::diagnostics::*;
+
+        let mut grid = Grid::new(dim!(4, 4), 0.5);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).velocity.back = vec2!(1.0, 0.0);
+        }
+
+        let d = Diagnostics::from(&grid, 0.1, 0.01);
+        assert!(d.max_divergence == 0.0);
+        assert!((d.kinetic_energy - 0.5 * 16.0 * 0.25).abs() < 1e-12, "{}", d.kinetic_energy);
+
+        grid.cell_mut(idx!(3, 2)).velocity.back.x = 3.0;
+        let d = Diagnostics::from(&grid, 0.2, 0.01);
+        assert!(d.max_divergence == 2.0, "{}", d.max_divergence);
+
+        let mut history = DiagnosticsHistory::new(2);
+        for t in [0.1, 0.2, 0.3] {
+            history.push(Diagnostics { time: t, ..d });
+        }
+        assert!(history.len() == 2 && history.last().unwrap().time == 0.3);
+        assert!(history.points(|d| d.dt) == [[0.2, 0.01], [0.3, 0.01]]);
+    }
+
+    #[test]
+    fn check_memory_report() {
+        use crate::scene::memory::MemoryLocation;
+        use crate::scene::setup::setup_scene;
+
+        let (log, _) = create_logger();
+        let config = SceneConfig::channel(dim!(23, 13));
+
+        let mut ts = setup_scene(&log, &config).unwrap();
+        ts.compute_step(config.time.dt).unwrap();
+
+        let report = ts.memory_report();
+        let cells: usize = report
+            .entries()
+            .iter()
+            .filter(|e| e.name.starts_with("objects[0].cells."))
+            .map(|e| e.bytes)
+            .sum();
+        let n = 25 * 15 * std::mem::size_of::<Cell>();
+
+        assert!(cells >= n, "Cells use {} bytes, expected at least {}", cells, n);
+        assert!(
+            Grid::estimate_memory(dim!(23, 13)).total(MemoryLocation::Host) == n,
+            "Estimate does not match the cells."
+        );
+        assert!(
+            report.entries().iter().any(|e| e.name == "objects[0].scratch"),
+            "Scratch fields not reported."
+        );
+    }
+
+    #[test]
+    fn check_pressure_solver() {
+        use crate::scene::pressure_solver::*;
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::Integrate;
+
+        struct Counting {
+            calls: u64,
+        }
+
+        impl PressureSolver for Counting {
+            fn name(&self) -> &str {
+                return "counting";
+            }
+
+            fn solve(
+                &mut self,
+                log: &Logger,
+                grid: &mut Grid,
+                dt: Scalar,
+                params: &SolveParams,
+            ) -> SolveStats {
+                self.calls += 1;
+                let stats = SorSolver.solve(log, grid, dt, params);
+                return SolveStats {
+                    residual: Some(self.calls as Scalar),
+                    ..stats
+                };
+            }
+        }
+
+        let (log, _) = create_logger();
+        let setup = || {
+            let mut grid = Grid::new(dim!(12, 8), 1.0);
+            grid.cell_mut(idx!(5, 4)).velocity.back = vec2!(1.0, -0.5);
+            return grid;
+        };
+
+        let mut grid = setup();
+        let mut custom = setup();
+        custom.set_pressure_solver(Box::new(Counting { calls: 0 }));
+
+        let params = SolverParamsBuilder::default().iterations(20).build().unwrap();
+        for _ in 0..2 {
+            grid.solve_incompressibility(&log, 0.01, &params).unwrap();
+            custom.solve_incompressibility(&log, 0.01, &params).unwrap();
+        }
+
+        let stats = custom.solve_stats();
+        assert!(custom.pressure_solver_name() == "counting");
+        assert!(grid.pressure_solver_name() == "sor");
+        assert!(stats.iterations == 20 && stats.residual == Some(2.0), "Stats: {:?}", stats);
+        assert!(grid.solve_stats().residual.is_none());
+
+        let fields = |g: &Grid| {
+            return g
+                .iter_index()
+                .map(|i| (g.cell(i).velocity.back, g.cell(i).pressure))
+                .collect::<Vec<_>>();
+        };
+        assert!(fields(&grid) == fields(&custom), "Delegating solver differs.");
+    }
+
+    #[test]
+    fn check_advection_scheme() {
+        use crate::scene::advection::*;
+        use crate::scene::timestepper::{ExecutionMode, Integrate};
+        use std::sync::{Arc, Mutex};
+
+        struct Recording {
+            fields: Arc<Mutex<Vec<AdvectedField>>>,
+        }
+
+        impl AdvectionScheme for Recording {
+            fn name(&self) -> &str {
+                return "recording";
+            }
+
+            fn advect(
+                &mut self,
+                log: &Logger,
+                grid: &mut Grid,
+                field: AdvectedField,
+                dt: Scalar,
+                execution_mode: ExecutionMode,
+            ) {
+                self.fields.lock().unwrap().push(field);
+                SemiLagrangian.advect(log, grid, field, dt, execution_mode);
+            }
+        }
+
+        let (log, _) = create_logger();
+        let setup = || {
+            let mut grid = Grid::new(dim!(12, 8), 1.0);
+            grid.cell_mut(idx!(5, 4)).velocity.back = vec2!(1.0, -0.5);
+            grid.cell_mut(idx!(5, 4)).set_smoke(1.0);
+            return grid;
+        };
+
+        let fields = Arc::new(Mutex::new(vec![]));
+        let mut grid = setup();
+        let mut custom = setup();
+        for field in AdvectedField::ALL {
+            let scheme = Recording {
+                fields: fields.clone(),
+            };
+            custom.set_advection_scheme(field, Box::new(scheme));
+        }
+
+        grid.advect(&log, 0.1, ExecutionMode::Single);
+        custom.advect(&log, 0.1, ExecutionMode::Single);
+
+        custom.advect(&log, 0.1, ExecutionMode::Single);
+
+        let order = [AdvectedField::Velocity, AdvectedField::Smoke];
+        assert!(*fields.lock().unwrap() == [order, order].concat(), "Wrong fields.");
+        assert!(custom.advection_scheme_name(AdvectedField::Smoke) == "recording");
+        assert!(grid.advection_scheme_name(AdvectedField::Velocity) == "semi-lagrangian");
+
+        let state = |g: &Grid| {
+            return g
+                .iter_index()
+                .map(|i| (g.cell(i).velocity.back, g.cell(i).smoke()))
+                .collect::<Vec<_>>();
+        };
+        grid.advect(&log, 0.1, ExecutionMode::Single);
+        assert!(state(&grid) == state(&custom), "Delegating scheme differs.");
+    }
+
+    #[test]
+    fn check_smoke_translation() {
+        use crate::scene::tests::fixtures::{centroid, create_blob};
+        use crate::scene::timestepper::{ExecutionMode, Integrate};
+
+        let (log, _) = create_logger();
+        let (velocity, dt, steps) = (vec2!(1.0, 0.5), 0.03, 10);
+
+        for mode in [ExecutionMode::Single, ExecutionMode::Parallel] {
+            let mut grid = create_blob(velocity);
+            let expected = centroid(&grid) + velocity * dt * steps as Scalar;
+            for _ in 0..steps {
+                grid.advect(&log, dt, mode);
+            }
+
+            let error = (centroid(&grid) - expected).norm();
+            assert!(error < 0.01 * grid.cell_width, "Smoke is off by {} in {:?}.", error, mode);
+        }
+    }
+
+    #[test]
+    fn check_integrators() {
+        use crate::scene::integrator::Integrator;
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::Integrate;
+
+        // Rotation around the origin, the exact departure point rotates back by `dt`.
+        let velocity = |p: Vector2| vec2!(-p.y, p.x);
+        let (pos, dt): (Vector2, Scalar) = (vec2!(1.0, 0.0), 0.5);
+        let exact = vec2!(dt.cos(), -dt.sin());
+
+        let error = |i: Integrator| (i.departure(pos, velocity(pos), dt, velocity) - exact).norm();
+        let errors = [Integrator::Euler, Integrator::Midpoint, Integrator::Rk4].map(error);
+        assert!(
+            errors[0] > errors[1] && errors[1] > errors[2] && errors[2] < 1e-3,
+            "Errors do not decrease with the order: {:?}",
+            errors
+        );
+
+        // Without confinement all integrators give the Euler step.
+        let (log, _) = create_logger();
+        let run = |integrator: Integrator, confinement: Scalar| {
+            let mut grid = Grid::new(dim!(12, 10), 0.1);
+            grid.vorticity_confinement = confinement;
+            grid.set_integrator(integrator);
+            grid.cell_mut(idx!(5, 5)).velocity.back = vec2!(1.0, 0.5);
+            grid.cell_mut(idx!(6, 5)).velocity.back = vec2!(-0.5, 1.0);
+
+            let params = SolverParamsBuilder::default().gravity(vec2!(0.0, -9.81)).build().unwrap();
+            grid.integrate(&log, 0.01, &params);
+            return grid.iter_index().map(|i| grid.cell(i).velocity.back).collect::<Vec<_>>();
+        };
+
+        let euler = run(Integrator::Euler, 0.0);
+        assert!(run(Integrator::Rk4, 0.0) == euler, "Gravity step differs.");
+
+        let euler = run(Integrator::Euler, 5.0);
+        let rk4 = run(Integrator::Rk4, 5.0);
+        let diff = euler.iter().zip(rk4.iter()).map(|(a, b)| (a - b).amax()).fold(0.0, Scalar::max);
+        assert!(diff < 1e-2, "Confinement steps differ by {}", diff);
+    }
+
+    #[test]
+    fn check_simulation() {
+        use crate::scene::simulation::Simulation;
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        sim.set_dt(Time(0.02)).unwrap();
+        sim.set_diagnostics(4);
+
+        sim.step().unwrap();
+        let steps = sim.run_until(Time(0.11)).unwrap();
+
+        assert!(steps == Steps(5) && sim.steps() == Steps(6), "Steps: {:?}", steps);
+        assert!((sim.time().value() - 0.11).abs() < 1e-9, "Time: {}", sim.time());
+        assert!(sim.run_until(Time(0.05)).unwrap() == Steps(0), "Stepped back in time.");
+
+        let history = sim.diagnostics().unwrap();
+        assert!(history.len() == 4 && (history.last().unwrap().dt - 0.01).abs() < 1e-9);
+        assert!(sim.grid().unwrap().dim == idx!(22, 12));
+    }
+
+    #[test]
+    fn check_step_hooks() {
+        use crate::scene::hooks::HookPoint;
+        use crate::scene::simulation::Simulation;
+        use std::sync::{Arc, Mutex};
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut ids = Vec::new();
+        for point in HookPoint::ALL {
+            let calls = calls.clone();
+            ids.push(sim.add_hook(point, move |info, _| {
+                calls.lock().unwrap().push((info.point, info.t));
+            }));
+        }
+
+        // Smoke set after the advection is not transported anymore in this step.
+        let index = idx!(10, 5);
+        sim.add_hook(HookPoint::PostAdvect, move |_, grid| {
+            grid.cell_mut(index).set_smoke(0.25);
+        });
+
+        sim.step().unwrap();
+        sim.step().unwrap();
+
+        let calls = calls.lock().unwrap().clone();
+        assert!(calls.len() == 8, "Hooks called {} times.", calls.len());
+        for (i, (point, t)) in calls.iter().enumerate() {
+            assert!(*point == HookPoint::ALL[i % 4], "Hook {} at {:?}.", i, point);
+            assert!(*t == (i / 4) as Scalar * sim.dt().value(), "Hook {} at time {}.", i, t);
+        }
+        assert!(sim.grid().unwrap().cell(index).smoke() == 0.25, "Hook did not change the grid.");
+
+        assert!(ids.iter().all(|id| sim.remove_hook(*id)) && !sim.remove_hook(ids[0]));
+        sim.step().unwrap();
+        assert!(sim.timestepper_mut().hooks_mut().len() == 1);
+    }
+
+    #[test]
+    fn check_solver_events() {
+        use crate::io::checkpoint::Compression;
+        use crate::scene::events::SolverEvent;
+        use crate::scene::hooks::HookPoint;
+        use crate::scene::simulation::Simulation;
+        use std::sync::{Arc, Mutex};
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let id = sim.subscribe(move |e: &SolverEvent| sink.lock().unwrap().push(e.clone()));
+
+        sim.step().unwrap();
+        {
+            let events = events.lock().unwrap();
+            assert!(matches!(events[0], SolverEvent::StepStarted { t, .. } if t == 0.0));
+            assert!(matches!(events[1], SolverEvent::SolverConverged { object: 0, .. }));
+            assert!(matches!(events.last().unwrap(), SolverEvent::StepFinished { .. }));
+            let nan = events.iter().any(|e| matches!(e, SolverEvent::NanDetected { .. }));
+            assert!(!nan, "Unexpected events: {:?}", events);
+        }
+
+        // Tight thresholds and a broken cell.
+        let checks = &mut sim.timestepper_mut().observers_mut().checks;
+        checks.residual_tolerance = -1.0;
+        checks.max_cfl = 0.0;
+        let index = idx!(5, 5);
+        sim.add_hook(HookPoint::PostAdvect, move |_, grid| {
+            grid.cell_mut(index).pressure = Scalar::NAN;
+        });
+
+        events.lock().unwrap().clear();
+        let result = sim.step();
+        assert!(matches!(result, Err(Error::Diverged { index: i, .. }) if i == index));
+        {
+            let events = events.lock().unwrap();
+            assert!(matches!(events[1], SolverEvent::SolverFailed { .. }));
+            assert!(matches!(events[2], SolverEvent::NanDetected { index: i, .. } if i == index));
+            assert!(matches!(events[3], SolverEvent::CflViolated { cfl, .. } if cfl > 0.0));
+        }
+
+        let file = std::env::temp_dir().join("rustofluid-test-events.ckpt");
+        let file = file.to_str().unwrap();
+        sim.save_checkpoint(file, Compression::None).unwrap();
+        assert!(matches!(
+            events.lock().unwrap().last().unwrap(),
+            SolverEvent::CheckpointWritten { file: f, .. } if f == file
+        ));
+
+        assert!(sim.unsubscribe(id) && !sim.unsubscribe(id));
+        let count = events.lock().unwrap().len();
+        sim.set_check_finite(false);
+        sim.step().unwrap();
+        assert!(events.lock().unwrap().len() == count, "Unsubscribed observer notified.");
+    }
+
+    #[test]
+    fn check_force_fields() {
+        use crate::scene::force_field::*;
+        use crate::scene::simulation::Simulation;
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.solver.gravity = [0.0, -9.81];
+        let mut with_gravity = Simulation::new(config.clone()).unwrap();
+
+        config.solver.gravity = [0.0, 0.0];
+        let mut with_field = Simulation::new(config).unwrap();
+        with_field.add_force_field(Gravity(vec2!(0.0, -9.81)));
+
+        for _ in 0..5 {
+            with_gravity.step().unwrap();
+            with_field.step().unwrap();
+        }
+
+        let (a, b) = (with_gravity.grid().unwrap(), with_field.grid().unwrap());
+        for idx in a.iter_index() {
+            let dv = a.cell(idx).velocity.back - b.cell(idx).velocity.back;
+            assert!(dv.amax() < 1e-12, "Velocity differs at {}: {}.", idx, dv);
+        }
+
+        // The curl noise is divergence-free.
+        let noise = CurlNoise {
+            amplitude: 2.0,
+            scale: 0.3,
+            speed: 1.0,
+            seed: 7,
+        };
+        let view = CellView {
+            index: idx!(0, 0),
+            mode: CellTypes::Fluid,
+            velocity: Vector2::zeros(),
+            pressure: 0.0,
+            smoke: 1.0,
+        };
+        let h = 1e-5;
+        for pos in [vec2!(0.1, 0.2), vec2!(0.55, 0.7), vec2!(1.3, 0.05)] {
+            let f = |p: Vector2| noise.force(p, 0.4, &view);
+            let div = (f(pos + vec2!(h, 0.0)).x - f(pos - vec2!(h, 0.0)).x
+                + f(pos + vec2!(0.0, h)).y
+                - f(pos - vec2!(0.0, h)).y)
+                / (2.0 * h);
+            assert!(div.abs() < 1e-4, "Divergence at {}: {}.", pos, div);
+            assert!(f(pos).norm() <= 2.0 + 1e-12);
+        }
+
+        let force = |field: &dyn ForceField| field.force(Vector2::zeros(), 0.0, &view);
+        assert!(force(&Buoyancy { lift: 3.0 }) == vec2!(0.0, 3.0));
+        let wind = Wind {
+            velocity: vec2!(1.0, 0.0),
+            drag: 0.5,
+        };
+        assert!(force(&wind) == vec2!(0.5, 0.0));
+        assert!(force(&|_: Vector2, t: Scalar, _: &CellView| vec2!(t, 1.0)) == vec2!(0.0, 1.0));
+    }
+
+    #[test]
+    #[cfg(feature = "scripting")]
+    fn check_scene_script() {
+        use crate::scene::simulation::Simulation;
+
+        let script = |source: &str| ScriptConfig {
+            file: None,
+            source: Some(source.to_string()),
+        };
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.obstacles.clear();
+        config.time.dt = 0.01;
+        config.scripts = vec![script(
+            "emit(2, 2, 4, 4, 0.5); if t < 0.015 { obstacle(1.5, 0.5, 0.15, 0.0, 0.0); }",
+        )];
+        let mut sim = Simulation::new(config.clone()).unwrap();
+
+        let index = idx!(15, 5);
+        sim.step().unwrap();
+        assert!(sim.grid().unwrap().cell(index).mode == CellTypes::Solid, "Obstacle is missing.");
+        assert!(sim.grid().unwrap().cell(idx!(3, 3)).smoke() > 0.0, "No smoke emitted.");
+        sim.step().unwrap();
+        assert!(sim.grid().unwrap().cell(index).mode == CellTypes::Solid);
+        sim.step().unwrap();
+        assert!(sim.grid().unwrap().cell(index).mode == CellTypes::Fluid, "Vent did not open.");
+
+        config.scripts = vec![script("emit(")];
+        assert!(Simulation::new(config.clone()).is_err(), "Syntax error is accepted.");
+
+        config.scripts = vec![ScriptConfig {
+            file: Some("scene.rhai".to_string()),
+            source: Some("emit(0, 0, 1, 1, 1.0);".to_string()),
+        }];
+        assert!(config.validate().is_err(), "File and source are accepted.");
+    }
+
+    #[test]
+    fn check_errors() {
+        use crate::scene::coupling::DomainCoupling;
+        use crate::scene::simulation::Simulation;
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::{ExecutionMode, Integrate};
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.time.dt = 0.0;
+        let result = Simulation::new(config.clone());
+        assert!(matches!(result, Err(Error::Scene(_))), "Invalid scene is accepted.");
+
+        config.time.dt = 0.01;
+        config.masks.push(MaskConfig {
+            file: "does-not-exist.png".to_string(),
+            threshold: 0.5,
+            invert: false,
+            resample: true,
+        });
+        assert!(Simulation::new(config.clone()).is_err(), "Missing mask is accepted.");
+
+        config.masks.clear();
+        let mut sim = Simulation::new(config).unwrap();
+        let result = sim.set_dt(Time(-1.0));
+        assert!(matches!(result, Err(Error::InvalidParameter { name: "dt", .. })));
+        assert!(sim.run_until(Time(Scalar::NAN)).is_err() && sim.steps() == Steps(0));
+
+        let grid = sim.grid_mut().unwrap();
+        assert!(grid.set_inside(&[1.0; 3], |c, s| c.set_smoke(*s)).is_err());
+        assert!(grid.modify_cells([0, 0], |_| {}).is_err(), "Aliased cells are accepted.");
+        assert!(grid.modify_cells([0, 1], |[a, b]| a.pressure = b.pressure).is_ok());
+
+        let result = grid.restore(&Grid::new(dim!(5, 4), 1.0));
+        assert!(matches!(result, Err(Error::DimensionMismatch { .. })), "Dimension differs.");
+
+        let ts = sim.timestepper_mut();
+        for dt in [0.0, -1.0, Scalar::NAN] {
+            let result = ts.compute_step(dt);
+            assert!(matches!(result, Err(Error::InvalidParameter { name: "dt", .. })));
+            assert!(ts.compute_step_timed(dt).is_err() && ts.time() == 0.0);
+        }
+
+        // A simulation whose main grid was removed.
+        ts.objects.clear();
+        assert!(matches!(sim.grid(), Err(Error::NotAGrid { index: 0 })));
+        assert!(sim.step().is_err() && sim.inspect(vec2!(0.5, 0.5)).is_err());
+
+        // The parallel sweeps need even dimensions.
+        let params = SolverParamsBuilder::default()
+            .execution_mode(ExecutionMode::Parallel)
+            .build()
+            .unwrap();
+        let mut odd = Grid::new(dim!(21, 10), 0.1);
+        let result = odd.solve_incompressibility(&log, 0.01, &params);
+        assert!(matches!(result, Err(Error::InvalidParameter { name: "execution_mode", .. })));
+
+        let mut coupling = DomainCoupling {
+            lower: 0,
+            upper: 0,
+            axis: 0,
+            lower_offset: 0,
+            upper_offset: 0,
+            length: 1,
+        };
+        let mut objs: Vec<Box<dyn Integrate>> = vec![Box::new(Grid::new(dim!(4, 4), 1.0))];
+        assert!(coupling.open(&mut objs).is_err(), "Domain is coupled to itself.");
+        coupling.upper = 1;
+        assert!(matches!(coupling.exchange(&mut objs), Err(Error::NotAGrid { index: 1 })));
+    }
+
+    #[test]
+    fn check_solver_params() {
+        use crate::scene::simulation::Simulation;
+        use crate::scene::solver_params::*;
+        use crate::scene::timestepper::ExecutionMode;
+
+        let params = SolverParams::default();
+        assert!(params.over_relaxation == 1.9 && params.iterations == 100);
+
+        let invalid = |r: Result<SolverParams, Error>, param: &str| {
+            return matches!(r, Err(Error::InvalidParameter { name, .. }) if name == param);
+        };
+        fn build(f: impl Fn(&mut SolverParamsBuilder)) -> Result<SolverParams, Error> {
+            let mut builder = SolverParamsBuilder::default();
+            f(&mut builder);
+            return builder.build();
+        }
+
+        assert!(invalid(build(|b| { b.over_relaxation(2.0); }), "over_relaxation"));
+        assert!(invalid(build(|b| { b.density(0.0); }), "density"));
+        assert!(invalid(build(|b| { b.iterations(0); }), "iterations"));
+        assert!(invalid(build(|b| { b.max_cfl(Scalar::NAN); }), "max_cfl"));
+        assert!(invalid(build(|b| { b.gravity(vec2!(0.0, Scalar::INFINITY)); }), "gravity"));
+        assert!(
+            invalid(
+                build(|b| {
+                    b.execution_mode(ExecutionMode::Jacobi).over_relaxation(1.5);
+                }),
+                "over_relaxation"
+            ),
+            "Over-relaxation with 'Jacobi' is accepted."
+        );
+        assert!(build(|b| { b.execution_mode(ExecutionMode::Jacobi); }).is_ok());
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.solver.execution_mode = ExecutionMode::Jacobi;
+        assert!(SolverParams::from_config(&config.solver).is_ok(), "Jacobi scene is rejected.");
+
+        // The parallel sweeps need even dimensions, also of the other domains.
+        let mut config = SceneConfig::channel(dim!(21, 10));
+        config.solver.execution_mode = ExecutionMode::Parallel;
+        let result = Simulation::new(config.clone());
+        assert!(matches!(result, Err(Error::Scene(_))), "Odd grid in parallel mode is accepted.");
+        config.grid.dim = [22, 10];
+        assert!(config.validate().is_ok(), "Even grid in parallel mode is rejected.");
+        config.domains.push(DomainConfig {
+            dim: [5, 4],
+            obstacles: vec![],
+        });
+        assert!(config.validate().is_err(), "Odd domain in parallel mode is accepted.");
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        let params = SolverParamsBuilder::default()
+            .gravity(vec2!(0.0, -1.0))
+            .over_relaxation(1.5)
+            .vorticity_confinement(0.2)
+            .max_cfl(2.0)
+            .build()
+            .unwrap();
+        sim.set_params(params);
+        sim.step().unwrap();
+
+        let grid = sim.grid().unwrap();
+        assert!(grid.over_relaxation == 1.5 && grid.vorticity_confinement == 0.2);
+        assert!(sim.timestepper().gravity() == vec2!(0.0, -1.0));
+    }
+
+    #[test]
+    fn check_run_control() {
+        use crate::scene::run_control::RunState;
+        use crate::scene::simulation::Simulation;
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        let control = sim.control();
+
+        // A paused run on a worker only computes the requested steps until stopped.
+        control.pause();
+        let steps = std::thread::scope(|scope| {
+            let worker = scope.spawn(|| sim.run_for(Steps(10)).unwrap());
+            for _ in 0..3 {
+                control.step_once();
+            }
+            while control.pending_steps() > 0 {
+                std::thread::yield_now();
+            }
+            control.stop();
+            return worker.join().unwrap();
+        });
+        assert!(steps == Steps(3), "Computed {:?} instead of 3 single steps.", steps);
+        assert!(sim.steps() == Steps(3) && sim.run_state() == RunState::Stopped);
+
+        assert!(sim.run_for(Steps(5)).unwrap() == Steps(0), "Stopped simulation runs.");
+        sim.step_once().unwrap();
+        assert!(sim.steps() == Steps(4), "Single step is not computed.");
+
+        sim.resume();
+        assert!(sim.run_for(Steps(2)).unwrap() == Steps(2) && sim.steps() == Steps(6));
+        control.step_once();
+        assert!(control.pending_steps() == 0, "Step is requested while running.");
+    }
+
+    #[test]
+    fn check_injections() {
+        use crate::scene::simulation::Simulation;
+
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.obstacles.clear();
+        config.emitters.clear();
+        let mut sim = Simulation::new(config.clone()).unwrap();
+        let mut reference = Simulation::new(config).unwrap();
+
+        let pos = sim.grid().unwrap().cell_center(idx!(10, 5));
+        let h = Length(sim.grid().unwrap().cell_width);
+        sim.add_smoke(pos, 0.5, 2.0 * h).unwrap();
+        sim.add_impulse(pos, vec2!(0.0, 1.0), 2.0 * h).unwrap();
+        let smoke = sim.grid().unwrap().cell(idx!(10, 5)).smoke();
+        assert!(smoke == 0.0, "Smoke is added before the step.");
+
+        sim.step().unwrap();
+        reference.step().unwrap();
+        assert!(sim.grid().unwrap().cell(idx!(10, 5)).smoke() > 0.0, "No smoke is added.");
+
+        let (a, b) = (sim.grid().unwrap(), reference.grid().unwrap());
+        let differs = a.iter_index().any(|i| {
+            return a.cell(i).velocity.back != b.cell(i).velocity.back;
+        });
+        assert!(differs, "Impulse is not applied.");
+
+        let outside = vec2!(-1.0, 0.5);
+        assert!(sim.add_impulse(outside, vec2!(1.0, 0.0), h).is_err(), "Outside is accepted.");
+        assert!(sim.add_smoke(pos, 1.0, -h).is_err(), "Negative radius is accepted.");
+    }
+
+    #[test]
+    fn check_units() {
+        let h = Length(0.1);
+        let v: Velocity = Length(2.0) / Time(0.5);
+        assert!(v == Velocity(4.0) && v * Time(0.25) == Length(1.0));
+        assert!(Length(0.35).in_cells(h).value() > 3.49 && Cells(2.0).length(h) == 2.0 * h);
+        assert!(Steps(4).duration(Time(0.5)) == Time(2.0));
+        assert!(Length(3.0) / Length(1.5) == 2.0 && format!("{}", Time(1.5)) == "1.5 s");
+    }
+
+    #[test]
+    fn check_decomposition() {
+        use crate::scene::decomposition::{LocalCommunicator, Subdomain};
+        use crate::scene::setup::setup_scene;
+        use crate::scene::solver_params::SolverParamsBuilder;
+        use crate::scene::timestepper::{ExecutionMode, Integrate};
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(24, 16));
+        config.solver.execution_mode = ExecutionMode::Jacobi;
+        let ts = setup_scene(&log, &config).unwrap();
+        let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+
+        let params = SolverParamsBuilder::default()
+            .execution_mode(ExecutionMode::Jacobi)
+            .gravity(vec2!(0.0, -9.81))
+            .iterations(20)
+            .build()
+            .unwrap();
+        let dt = config.time.dt;
+
+        let step = |grid: &Grid| {
+            let mut reference = grid.snapshot();
+            for _ in 0..3 {
+                reference.integrate(&log, dt, &params);
+                reference.solve_incompressibility(&log, dt, &params).unwrap();
+                reference.advect(&log, dt, params.execution_mode);
+            }
+            return reference;
+        };
+
+        let step_ranks = |grid: &Grid, ranks: usize| {
+            let subdomains: Vec<_> = LocalCommunicator::group(ranks)
+                .into_iter()
+                .map(|comm| Subdomain::from_grid(comm, grid, 4).unwrap())
+                .collect();
+            let owned: usize = subdomains.iter().map(|s| s.owned_rows().len()).sum();
+            assert!(owned == grid.dim.y, "The slabs do not cover the grid.");
+
+            let gathered = std::thread::scope(|scope| {
+                let runs: Vec<_> = subdomains
+                    .into_iter()
+                    .map(|mut s| {
+                        let (log, params) = (log.clone(), params.clone());
+                        return scope.spawn(move || {
+                            for _ in 0..3 {
+                                s.step(&log, dt, &params).unwrap();
+                            }
+                            return s.gather().unwrap();
+                        });
+                    })
+                    .collect();
+
+                return runs.into_iter().filter_map(|r| r.join().unwrap()).next();
+            });
+
+            return gathered.expect("Rank 0 gathers no grid.");
+        };
+
+        let compare = |reference: &Grid, gathered: &Grid| {
+            for i in reference.iter_index() {
+                let (a, b) = (reference.cell(i), gathered.cell(i));
+                assert!(
+                    (a.velocity.back - b.velocity.back).norm() < 1e-6
+                        && (a.pressure - b.pressure).abs() < 1e-3
+                        && (a.smoke() - b.smoke()).abs() < 1e-6,
+                    "Cell {:?} differs from the whole grid.",
+                    i
+                );
+            }
+        };
+
+        compare(&step(grid), &step_ranks(grid, 3));
+
+        // The open bottom and top sides only apply to the first and the last slab.
+        let mut open = grid.snapshot();
+        open.set_boundary(Side::Bottom, BoundaryCondition::Open);
+        open.set_boundary(Side::Top, BoundaryCondition::Open);
+        compare(&step(&open), &step_ranks(&open, 2));
+
+        let comm = LocalCommunicator::group(4).pop().unwrap();
+        assert!(Subdomain::from_grid(comm, grid, 5).is_err(), "Too large halo is accepted.");
+
+        let mut periodic = grid.snapshot();
+        periodic.set_boundary(Side::Bottom, BoundaryCondition::Periodic);
+        periodic.set_boundary(Side::Top, BoundaryCondition::Periodic);
+        let comm = LocalCommunicator::group(2).pop().unwrap();
+        assert!(
+            Subdomain::from_grid(comm, &periodic, 4).is_err(),
+            "Periodic bottom and top sides are accepted."
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn check_frame_stream() {
+        use crate::scene::simulation::Simulation;
+        use crate::scene::snapshot::Snapshot;
+        use futures::executor::block_on;
+        use futures::StreamExt;
+
+        // The frames and the errors of the steps are sent from the worker thread.
+        fn is_send<T: Send>() {}
+        is_send::<FluidResult<Snapshot>>();
+
+        let config = SceneConfig::channel(dim!(20, 10));
+        let sim = Simulation::new(config.clone()).unwrap();
+        let dt = sim.dt().value();
+        let mut frames = sim.frames(Steps(2), 1).unwrap();
+
+        let times: Vec<_> = block_on((&mut frames).take(3).map(|f| f.unwrap().time).collect());
+        for (i, t) in times.iter().enumerate() {
+            assert!((t - 2.0 * i as Scalar * dt).abs() < 1e-9, "Frame {} at {}.", i, t);
+        }
+
+        // The worker is at most the buffered frames and one running frame ahead.
+        let sim = frames.into_simulation();
+        assert!(sim.steps() >= Steps(4) && sim.steps() <= Steps(10), "{:?}", sim.steps());
+
+        let sim = Simulation::new(config).unwrap();
+        assert!(sim.frames(Steps(0), 1).is_err(), "No steps between frames are accepted.");
+    }
+
+    #[test]
+    fn check_deterministic_threads() {
+        use crate::scene::setup::setup_scene;
+        use crate::scene::timestepper::{Backend, ExecutionMode};
+
+        let (log, _) = create_logger();
+
+        let mut config = SceneConfig::channel(dim!(30, 16));
+        config.solver.deterministic = true;
+        config.solver.iterations = 20;
+
+        let mut run = |mode: ExecutionMode, threads: usize| {
+            config.solver.execution_mode = mode;
+            config.solver.threads = threads;
+
+            let mut ts = setup_scene(&log, &config).unwrap();
+            for _ in 0..3 {
+                ts.compute_step(config.time.dt).unwrap();
+            }
+
+            let grid = ts.objects[0].as_any().downcast_ref::<Grid>().expect("Not a grid");
+            assert!(grid.is_deterministic(), "Deterministic mode is not set.");
+            return grid.state_hash();
+        };
+
+        let modes = [
+            ExecutionMode::Single,
+            ExecutionMode::Parallel,
+            ExecutionMode::ParallelBands,
+            ExecutionMode::Jacobi,
+        ];
+        for mode in modes {
+            let single = run(mode, 1);
+            assert!(single == run(mode, 3), "{:?} depends on the number of threads.", mode);
+            assert!(single == run(mode, 1), "{:?} differs between runs.", mode);
+        }
+
+        config.solver.backend = Backend::Gpu;
+        assert!(config.validate().is_err(), "Deterministic GPU backend is accepted.");
+    }
+
+    #[test]
+    fn check_simulation_state() {
+        use crate::io::checkpoint::Compression;
+        use crate::scene::force_field::Gravity;
+        use crate::scene::simulation::Simulation;
+
+        let config = SceneConfig::channel(dim!(20, 10));
+        let mut sim = Simulation::new(config.clone()).unwrap();
+        sim.add_force_field(Gravity(vec2!(0.0, -9.81)));
+        sim.rng_stream("test").next_u64();
+        sim.step().unwrap();
+
+        let bytes = sim.save_state(Compression::None).unwrap();
+        let next = sim.rng_stream("test").next_u64();
+        sim.step().unwrap();
+
+        // The streams continue where they stopped, the force fields are not in the state.
+        let mut other = Simulation::new(config).unwrap();
+        other.load_state(&bytes).unwrap();
+        assert!(other.rng_stream("test").next_u64() == next, "Stream starts over.");
+        assert!(other.timestepper().force_fields().is_empty(), "Force field is restored.");
+
+        other.add_force_field(Gravity(vec2!(0.0, -9.81)));
+        other.step().unwrap();
+        let (a, b) = (sim.grid().unwrap(), other.grid().unwrap());
+        for idx in a.iter_index() {
+            let dv = a.cell(idx).velocity.back - b.cell(idx).velocity.back;
+            assert!(dv.amax() < 1e-12, "Velocity differs at {}: {}.", idx, dv);
+        }
+    }
+
+    #[test]
+    fn check_rng_streams() {
+        use crate::scene::rng::Rng;
+        use crate::scene::simulation::Simulation;
+
+        let rng = Rng::new(42);
+        let mut a = rng.stream("a");
+        let draws: Vec<_> = (0..100).map(|_| a.next_scalar()).collect();
+        assert!(draws.iter().all(|u| (0.0..1.0).contains(u)), "Draw is not in [0, 1).");
+        assert!(draws.windows(2).any(|w| w[0] != w[1]), "Stream is constant.");
+
+        assert!(rng.stream("b") == Rng::new(42).stream("b"), "Stream is not reproducible.");
+        assert!(rng.stream("a") != rng.stream("b") && rng.stream("a") != Rng::new(1).stream("a"));
+
+        let run = |seed: u64| {
+            let mut config = SceneConfig::channel(dim!(20, 10));
+            config.seed = seed;
+
+            let mut sim = Simulation::new(config).unwrap();
+            sim.add_curl_noise(0.5, 0.3, 0.1);
+            sim.add_curl_noise(0.5, 0.3, 0.1);
+            sim.run_for(Steps(3)).unwrap();
+            return sim.grid().unwrap().state_hash();
+        };
+        assert!(run(1) == run(1), "The same seed gives different runs.");
+        assert!(run(1) != run(2), "The seed is not used.");
+
+        let config = SceneConfig::default().with_overrides(&["seed=7".to_string()]);
+        assert!(config.unwrap().seed == 7, "Seed is not set in the scene.");
+    }
+
+    #[test]
+    fn check_sweep() {
+        use crate::scene::sweep::*;
+
+        let params: Vec<SweepParam> = ["solver.iterations=10,20", "grid.dim=[20,10],[24,12]"]
+            .iter()
+            .map(|p| p.parse().unwrap())
+            .collect();
+        assert!(params[1].values == ["[20,10]", "[24,12]"], "{:?}", params[1].values);
+        assert!("solver.iterations".parse::<SweepParam>().is_err());
+        assert!("solver.iterations=1,,2".parse::<SweepParam>().is_err());
+
+        let runs = sweep_overrides(&params);
+        assert!(runs.len() == 4 && runs[1] == ["solver.iterations=10", "grid.dim=[24,12]"]);
+
+        let base = SceneConfig::channel(dim!(20, 10));
+        let results = run_sweep(&base, &params, 2, None);
+        assert!(results.len() == 4);
+        assert!(results.iter().all(|r| r.error.is_none() && r.steps == 2));
+        assert!(results.iter().all(|r| r.diagnostics.is_some()), "No diagnostics.");
+
+        let shard = run_sweep(&base, &params, 1, Some((1, 2)));
+        assert!(shard.iter().map(|r| r.index).collect::<Vec<_>>() == [1, 3]);
+
+        let table = format_sweep_table(&params, &results);
+        assert!(table.lines().count() == 1 + 4 && table.contains("grid.dim"));
+
+        let invalid: SweepParam = "solver.over_relaxation=1.5,3.0".parse().unwrap();
+        let results = run_sweep(&base, &[invalid], 1, None);
+        assert!(results[0].error.is_none() && results[1].error.is_some());
+        assert!(format_sweep_rows(&[], &results).lines().nth(1).unwrap().contains("Invalid scene"));
+    }
+
+    #[test]
+    fn check_scene_reload() {
+        use crate::scene::reload::*;
+        use crate::scene::setup::setup_scene;
+        use std::time::{Duration, SystemTime};
+
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let file = std::env::temp_dir().join("rustofluid-test-reload.toml");
+        let scene = |iterations: u64, smoke: f64, dim: [usize; 2]| {
+            let scene = format!(
+                "[grid]\ndim = [{}, {}]\n[solver]\niterations = {}\n\
+                 [[emitters]]\nmin = [0, 4]\nmax = [1, 6]\nsmoke = {:.1}\n",
+                dim[0], dim[1], iterations, smoke
+            );
+            std::fs::write(&file, scene).unwrap();
+        };
+
+        scene(40, 1.0, [20, 10]);
+        let path = file.to_str().unwrap();
+        let config = SceneConfig::load(path, &[]).unwrap();
+        let mut ts = setup_scene(&log, &config).unwrap();
+        let mut watcher = SceneWatcher::new(path, &[], config);
+        assert!(watcher.poll().is_none(), "Unmodified scene is reloaded.");
+
+        // Independent of the resolution of the modification time.
+        let touch = |secs: u64| {
+            let f = std::fs::File::options().write(true).open(&file).unwrap();
+            f.set_modified(SystemTime::now() + Duration::from_secs(secs)).unwrap();
+        };
+
+        scene(80, 0.5, [40, 20]);
+        touch(10);
+        let reload = watcher.poll().expect("Modified scene is not reloaded.").unwrap();
+        assert!(reload.applied == ["emitters[].smoke", "solver.iterations"]);
+        assert!(reload.restart == ["grid.dim"]);
+
+        reload.apply(&mut ts).unwrap();
+        assert!(ts.params().iterations == 80, "Iterations are not reloaded.");
+        assert!(watcher.poll().is_none(), "Scene is reloaded twice.");
+
+        std::fs::write(&file, "[solver]\niterations = 0\n").unwrap();
+        touch(20);
+        assert!(matches!(watcher.poll(), Some(Err(_))), "Invalid scene is reloaded.");
+
+        scene(80, 0.5, [40, 20]);
+        touch(30);
+        let reload = watcher.poll().unwrap().unwrap();
+        assert!(reload.is_empty(), "Invalid scene replaced the last valid one.");
+    }
+
+    #[test]
+    fn check_step_stats() {
+        use crate::scene::hooks::HookPoint;
+        use crate::scene::simulation::Simulation;
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        sim.set_dt(Time(0.02)).unwrap();
+
+        let stats = sim.step().unwrap();
+        let h = sim.grid().unwrap().cell_width;
+        assert!(stats.dt == 0.02 && stats.is_finite(), "Stats: {:?}", stats);
+        assert!(stats.iterations == sim.timestepper().params().iterations);
+        assert!(stats.max_velocity > 0.0, "Inflow is missing: {:?}", stats);
+        assert!((stats.cfl - stats.max_velocity * 0.02 / h).abs() < 1e-6);
+        assert!(stats.smoke_min >= 0.0 && stats.smoke_max <= 1.0 && stats.smoke_max > 0.0);
+
+        let index = idx!(5, 5);
+        sim.add_hook(HookPoint::PostAdvect, move |_, grid| {
+            grid.cell_mut(index).set_smoke(Scalar::NAN);
+        });
+        sim.set_check_finite(false);
+
+        let stats = sim.step().unwrap();
+        assert!(stats.nan_count == 1 && stats.first_nan == Some(index), "Stats: {:?}", stats);
+        assert!(stats.smoke_max.is_finite(), "Cells which are not finite are counted.");
+    }
+
+    #[test]
+    fn check_state_history() {
+        use crate::scene::simulation::Simulation;
+
+        let smoke = |sim: &Simulation| -> Vec<Scalar> {
+            let grid = sim.grid().unwrap();
+            return grid.iter_index().map(|idx| grid.cell(idx).smoke()).collect();
+        };
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        assert!(sim.rewind(Steps(1)).is_err(), "Rewind without a history.");
+
+        sim.set_history(3);
+        sim.run_for(Steps(2)).unwrap();
+        let (time, state) = (sim.time().value(), smoke(&sim));
+        sim.run_for(Steps(3)).unwrap();
+
+        let history = sim.history().unwrap();
+        assert!(history.len() == 4, "History has {} states.", history.len());
+        assert!(history.latest().unwrap().steps == 5 && history.get(3).unwrap().steps == 2);
+        assert!(history.get(1).unwrap().grid().dim == sim.grid().unwrap().dim);
+
+        assert!(sim.rewind(Steps(4)).is_err(), "Rewind beyond the history.");
+        assert!(sim.steps() == Steps(5), "Failed rewind changed the simulation.");
+
+        sim.rewind(Steps(3)).unwrap();
+        assert!(sim.steps() == Steps(2) && sim.time().value() == time);
+        assert!(smoke(&sim) == state, "Grid is not restored.");
+
+        // The next steps branch off.
+        sim.step().unwrap();
+        let history = sim.history().unwrap();
+        assert!(history.len() == 2 && history.latest().unwrap().steps == 3);
+
+        *sim.grid_mut().unwrap() = Grid::new(dim!(5, 5), 1.0);
+        assert!(sim.rewind(Steps(1)).is_err(), "Rewind to another dimension.");
+    }
+
+    #[test]
+    fn check_coupled_domains() {
+        use crate::scene::cell::CellTypes;
+        use crate::scene::config::{CouplingConfig, DomainConfig, Side};
+        use crate::scene::simulation::Simulation;
+
+        let mut config = SceneConfig::channel(dim!(10, 10));
+        config.boundary.right = BoundaryCondition::Solid;
+        config.boundary.inflow_velocity = None;
+        config.obstacles.clear();
+        config.emitters.clear();
+
+        // A corridor of 4 cells height on the right, open over its whole height.
+        config.domains.push(DomainConfig {
+            dim: [6, 4],
+            obstacles: vec![],
+        });
+        let coupling = CouplingConfig {
+            from: 1,
+            to: 0,
+            side: Side::Left,
+            offset: 0,
+            to_offset: 2,
+            length: 4,
+        };
+
+        config.couplings.push(CouplingConfig {
+            length: 5,
+            ..coupling.clone()
+        });
+        assert!(config.validate().is_err(), "Opening larger than the corridor.");
+        config.couplings[0] = coupling;
+
+        let mut sim = Simulation::new(config).unwrap();
+        let opening = 3..7;
+        for j in 0..12 {
+            let open = sim.grid().unwrap().cell(idx!(11, j)).mode == CellTypes::Fluid;
+            assert!(open == opening.contains(&j), "Border cell {} is wrong.", j);
+        }
+
+        // Smoke which flows out of the room on the right.
+        let room = sim.grid_mut().unwrap();
+        for j in opening.clone() {
+            room.cell_mut(idx!(10, j)).set_smoke(1.0);
+            room.cell_mut(idx!(11, j)).velocity.back.x = 1.0;
+        }
+
+        sim.step().unwrap();
+        let corridor = sim.domain(1).unwrap();
+        assert!(corridor.dim == idx!(8, 6) && sim.domain(2).is_none());
+        for j in 1..5 {
+            assert!(corridor.cell(idx!(0, j)).smoke() == 1.0, "Ghost cell {} has no smoke.", j);
+            assert!(corridor.cell(idx!(1, j)).smoke() > 0.0, "No smoke entered in row {}.", j);
+        }
+        assert!(corridor.cell(idx!(0, 5)).mode == CellTypes::Solid);
+    }
+
+    #[test]
+    fn check_inspect() {
+        use crate::scene::cell::CellTypes;
+        use crate::scene::simulation::Simulation;
+
+        let config = SceneConfig::channel(dim!(20, 10));
+        let center = config.obstacles[0].center;
+        let mut sim = Simulation::new(config).unwrap();
+        sim.run_for(Steps(3)).unwrap();
+
+        let grid = sim.grid().unwrap();
+        let index = idx!(15, 4);
+        let pos = grid.cell_center(index);
+        let probe = sim.inspect(pos).unwrap();
+
+        assert!(probe.index == Some(index) && probe.cell_type == Some(CellTypes::Fluid));
+        assert!(probe.velocity == grid.sample_velocity(pos));
+        assert!((probe.smoke - grid.cell(index).smoke()).abs() < 1e-6, "{:?}", probe);
+        let pressure = grid.cell(index).pressure;
+        assert!((probe.pressure - pressure).abs() < 1e-4 * (1.0 + pressure.abs()), "{:?}", probe);
+        assert!(probe.divergence == grid.cell(index).div);
+
+        let obstacle = sim.inspect(vec2!(center[0], center[1])).unwrap();
+        assert!(obstacle.cell_type == Some(CellTypes::Solid), "{:?}", obstacle);
+
+        let outside = sim.inspect(vec2!(-1.0, 0.5)).unwrap();
+        assert!(outside.index.is_none() && outside.cell_type.is_none());
+        assert!(outside.smoke.is_finite(), "Outside is not clamped.");
+    }
+
+    #[test]
+    fn check_flux() {
+        use crate::scene::flux::CrossSection;
+        use crate::scene::simulation::Simulation;
+
+        let mut sim = Simulation::new(SceneConfig::channel(dim!(20, 10))).unwrap();
+        let wake = sim.add_cross_section("wake", vec2!(1.2, 0.0), vec2!(1.2, 1.2)).unwrap();
+        let outlet = sim.add_cross_section("outlet", vec2!(1.6, 0.0), vec2!(1.6, 1.2)).unwrap();
+        assert!(sim.add_cross_section("wake", vec2!(0.0, 0.0), vec2!(1.0, 0.0)).is_err());
+        assert!(sim.add_cross_section("point", vec2!(1.0, 0.5), vec2!(1.0, 0.5)).is_err());
+
+        sim.set_history(5);
+        sim.run_for(Steps(5)).unwrap();
+
+        let fluxes = sim.fluxes();
+        assert!(fluxes.series(wake).len() == 5 && fluxes.series(outlet).len() == 5);
+
+        // The inflow goes through both sections in `+x`.
+        let last = *fluxes.latest(outlet).unwrap();
+        assert!(last.volume_flux > 0.0, "{:?}", last);
+        assert!(fluxes.latest(wake).unwrap().volume_flux > 0.0);
+
+        let dt = sim.dt().value();
+        let volume: Scalar = fluxes.series(outlet).iter().map(|s| s.volume_flux * dt).sum();
+        assert!((last.volume - volume).abs() < 1e-6 * volume.abs(), "{:?}", last);
+        assert!(last.smoke.is_finite(), "{:?}", last);
+
+        let section = &fluxes.sections()[outlet];
+        let reversed = CrossSection::new("reversed", section.end, section.start);
+        let grid = sim.grid().unwrap();
+        let (forward, backward) = (section.flux(grid).0, reversed.flux(grid).0);
+        assert!((forward + backward).abs() < 1e-5 * forward.abs(), "{} {}", forward, backward);
+
+        sim.rewind(Steps(2)).unwrap();
+        assert!(sim.fluxes().series(outlet).len() == 3);
+    }
+
+    #[test]
+    fn check_regions() {
+        use crate::scene::region::Region;
+        use crate::scene::simulation::Simulation;
+
+        let triangle = Region::polygon(vec![vec2!(0.0, 0.0), vec2!(1.0, 0.0), vec2!(0.0, 1.0)]);
+        assert!(triangle.contains(vec2!(0.2, 0.2)) && !triangle.contains(vec2!(0.8, 0.8)));
+        assert!(triangle.bounds() == Some((vec2!(0.0, 0.0), vec2!(1.0, 1.0))));
+
+        let ring = Region::circle(vec2!(0.5, 0.5), 0.4);
+        let ring = ring.subtract(Region::circle(vec2!(0.5, 0.5), 0.2));
+        assert!(ring.contains(vec2!(0.8, 0.5)) && !ring.contains(vec2!(0.5, 0.5)));
+
+        let half = Region::sdf(|p| p.x - 0.5);
+        assert!(half.bounds().is_none() && half.contains(vec2!(0.1, 3.0)));
+        let both = half.clone().intersect(Region::rect(vec2!(0.0, 0.0), vec2!(1.0, 1.0)));
+        assert!(both.bounds() == Some((vec2!(0.0, 0.0), vec2!(1.0, 1.0))));
+        assert!(both.contains(vec2!(0.4, 0.5)) && !both.contains(vec2!(0.6, 0.5)));
+        assert!(!both.contains(vec2!(0.4, 1.5)));
+
+        // The cells of a region are the same with and without its bounds.
+        let grid = Grid::new(dim!(10, 10), 0.1);
+        let cells = grid.cells_in(&both);
+        let all: Vec<Index2> = grid
+            .iter_index()
+            .filter(|idx| both.contains(grid.cell_center(*idx)))
+            .collect();
+        assert!(!cells.is_empty() && cells.len() == all.len(), "{:?}", cells);
+
+        // The obstacles, emitters and initial values of a scene are regions.
+        let toml = r#"
+            [grid]
+            dim = [20, 10]
+
+            [[obstacles]]
+            region = { box = { min = [0.4, 0.4], max = [0.6, 0.7] } }
+
+            [[emitters]]
+            region = { circle = { center = [0.2, 0.5], radius = 0.15 } }
+            smoke = 0.5
+
+            [[initial]]
+            region = { polygon = { points = [[1.2, 0.2], [1.8, 0.2], [1.5, 0.9]] } }
+            smoke = 0.25
+        "#;
+
+        let config = SceneConfig::parse(toml, "toml").unwrap();
+        let mut sim = Simulation::new(config).unwrap();
+
+        let grid = sim.grid().unwrap();
+        let cell_at = |x: Scalar, y: Scalar| grid.cell(grid.cell_index_at(vec2!(x, y)).unwrap());
+        assert!(cell_at(0.5, 0.5).mode == CellTypes::Solid);
+        assert!(cell_at(0.5, 0.9).mode == CellTypes::Fluid);
+        assert!(cell_at(1.5, 0.4).smoke() == 0.25 && cell_at(1.1, 0.8).smoke() == 0.0);
+
+        sim.step().unwrap();
+        let grid = sim.grid().unwrap();
+        let emitted = grid.cell(grid.cell_index_at(vec2!(0.2, 0.5)).unwrap()).smoke();
+        assert!(emitted > 0.0 && emitted <= 0.5 + 1e-6, "{}", emitted);
+
+        let invalid = "[[obstacles]]\nregion = { polygon = { points = [[0.0, 0.0]] } }";
+        assert!(SceneConfig::parse(invalid, "toml").is_err());
+    }
+
+    #[test]
+    fn check_boundary_conditions() {
+        use crate::scene::config::{InitialConfig, RegionConfig, Side};
+        use crate::scene::simulation::Simulation;
+
+        // The bools of older scenes.
+        let toml = "[boundary]\nleft = true\nright = false\ntop = 'periodic'\nbottom = 'periodic'";
+        let config = SceneConfig::parse(toml, "toml").unwrap();
+        let inflow = BoundaryCondition::Inflow {
+            velocity: vec2!(2.0, 0.0),
+        };
+        assert!(config.boundary.condition(Side::Left) == inflow);
+        assert!(config.boundary.right == BoundaryCondition::Outflow);
+        assert!(config.boundary.condition(Side::Top) == BoundaryCondition::Periodic);
+        assert!(SceneConfig::parse("[boundary]\ntop = 'periodic'", "toml").is_err());
+
+        let mut grid = Grid::new(dim!(6, 6), 1.0);
+        grid.set_boundary(Side::Right, BoundaryCondition::Inflow { velocity: vec2!(-1.0, 0.5) });
+        grid.set_boundary(Side::Top, BoundaryCondition::Open);
+        assert!(grid.cell(idx!(7, 3)).mode == CellTypes::Solid);
+        assert!(grid.cell(idx!(7, 3)).velocity.back.x == -1.0);
+        assert!(grid.cell(idx!(7, 7)).mode == CellTypes::Solid, "Corner is not solid.");
+        assert!(grid.cell(idx!(3, 7)).mode == CellTypes::Fluid);
+
+        grid.cell_mut(idx!(3, 7)).velocity.back = vec2!(3.0, 4.0);
+        grid.apply_boundary();
+        assert!(grid.cell(idx!(3, 7)).velocity.back == vec2!(0.0, 4.0));
+
+        // A flow to the right carries the smoke through the periodic sides.
+        let mut config = SceneConfig::channel(dim!(20, 10));
+        config.boundary.left = BoundaryCondition::Periodic;
+        config.boundary.right = BoundaryCondition::Periodic;
+        config.boundary.inflow_velocity = None;
+        config.obstacles.clear();
+        config.emitters.clear();
+        config.initial.push(InitialConfig {
+            region: RegionConfig::Box {
+                min: [0.0, 0.0],
+                max: [2.2, 1.2],
+            },
+            smoke: None,
+            velocity: Some([1.0, 0.0]),
+        });
+        config.initial.push(InitialConfig {
+            region: RegionConfig::Box {
+                min: [1.7, 0.1],
+                max: [1.9, 1.1],
+            },
+            smoke: Some(1.0),
+            velocity: None,
+        });
+
+        let mut sim = Simulation::new(config).unwrap();
+        sim.run_for(Steps(40)).unwrap();
+
+        let grid = sim.grid().unwrap();
+        assert!(grid.cell(idx!(0, 5)).mode == CellTypes::Fluid);
+
+        let v = grid.sample_velocity(vec2!(1.1, 0.6));
+        assert!((v.x - 1.0).abs() < 0.05 && v.y.abs() < 0.05, "{:?}", v);
+
+        let wrapped = grid.sample_velocity(vec2!(2.5, 0.6)) - grid.sample_velocity(vec2!(0.5, 0.6));
+        assert!(wrapped.norm() < 1e-4, "Sampling does not wrap: {:?}", wrapped);
+
+        let smoke = |x: Scalar| grid.cell(grid.cell_index_at(vec2!(x, 0.6)).unwrap()).smoke();
+        assert!(smoke(0.45) > 0.05 && smoke(0.45) > smoke(1.1), "{} {}", smoke(0.45), smoke(1.1));
+    }
+
+    #[test]
+    fn check_velocity_profiles() {
+        use crate::scene::config::Side;
+        use crate::scene::profile::VelocityProfile;
+        use crate::scene::simulation::Simulation;
+
+        let sine = VelocityProfile::sine(vec2!(2.0, 0.0), 0.5);
+        assert!((sine.at(0.5).x - 2.0).abs() < 1e-6, "{:?}", sine.at(0.5));
+
+        let table = VelocityProfile::table(vec![(1.0, vec2!(0.0, 0.0)), (2.0, vec2!(4.0, 2.0))]);
+        assert!(table.at(0.0) == vec2!(0.0, 0.0), "{:?}", table.at(0.0));
+        assert!(table.at(1.5) == vec2!(2.0, 1.0), "{:?}", table.at(1.5));
+        assert!(table.at(3.0) == vec2!(4.0, 2.0), "{:?}", table.at(3.0));
+
+        let content = r#"
+            [grid]
+            dim = [40, 20]
+
+            [[boundary.inflows]]
+            side = "left"
+            velocity = { sine = { amplitude = [2.0, 0.0], frequency = 0.5 } }
+
+            [[obstacles]]
+            center = [0.5, 0.5]
+            radius = 0.1
+            velocity = { table = [[0.0, 0.0, 0.0], [1.0, 0.0, 1.0]] }
+        "#;
+        let config = SceneConfig::parse(content, "toml").unwrap();
+        config.validate().unwrap();
+
+        let bad = content.replace("[[0.0, 0.0, 0.0], [1.0,", "[[1.0, 0.0, 0.0], [0.0,");
+        let bad = SceneConfig::parse(&bad, "toml").unwrap();
+        assert!(bad.validate().is_err(), "Decreasing times are valid.");
+
+        // The velocities are those at the start of the last step.
+        let mut sim = Simulation::new(config).unwrap();
+        sim.run_for(Steps(10)).unwrap();
+        let t = sim.time().value() - sim.dt().value();
+
+        let inflow = |sim: &Simulation| match sim.grid().unwrap().boundary(Side::Left) {
+            BoundaryCondition::Inflow { velocity } => velocity,
+            c => panic!("Left side is not an inflow: {:?}", c),
+        };
+        assert!((inflow(&sim) - sine.at(t)).norm() < 1e-6, "{:?} at {}", inflow(&sim), t);
+
+        let idx = sim.grid().unwrap().cell_index_at(vec2!(0.5, 0.5)).unwrap();
+        let v = sim.grid().unwrap().cell(idx).velocity.back;
+        assert!((v - vec2!(0.0, t)).norm() < 1e-6, "{:?} at {}", v, t);
+
+        // A closure replaces the inflow of the scene.
+        let ramp = VelocityProfile::function(|t| vec2!(t, 0.0));
+        sim.set_inflow(Side::Left, ramp).unwrap();
+        sim.run_for(Steps(1)).unwrap();
+        let t = sim.time().value() - sim.dt().value();
+        assert!((inflow(&sim) - vec2!(t, 0.0)).norm() < 1e-6, "{:?} at {}", inflow(&sim), t);
+
+        let outflow = VelocityProfile::sine(vec2!(1.0, 0.0), 1.0);
+        assert!(sim.set_inflow(Side::Right, outflow).is_err(), "The outflow is an inflow.");
+    }
 }
@@ -1,21 +1,45 @@
-use crate::types::{Scalar, Vector2};
-use slog::{info, Logger};
+use crate::io::checkpoint;
+use crate::scene::events::{Observer, ObserverId, Observers, SolverEvent};
+use crate::scene::force_field::ForceField;
+use crate::scene::grid::Grid;
+use crate::scene::hooks::{HookId, HookInfo, HookPoint, StepHooks};
+use crate::scene::memory::MemoryReport;
+use crate::scene::solver_params::SolverParams;
+use crate::types::{Error, FluidResult, GenericResult, Scalar, Vector2};
+use serde::{Deserialize, Serialize};
+use slog::{info, warn, Logger};
 use std::any::Any;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-pub trait Integrate {
+pub trait Integrate: Send {
     fn reset(&mut self, _log: &Logger) {}
-    fn integrate(&mut self, _log: &Logger, _dt: Scalar, _gravity: Vector2) {}
+    fn integrate(&mut self, _log: &Logger, _dt: Scalar, _params: &SolverParams) {}
+
+    /// Make the velocity divergence free. Fails if the object can not be solved with
+    /// `params`, e.g. a fixed size grid whose dimension changed.
     fn solve_incompressibility(
         &mut self,
         _log: &Logger,
         _dt: Scalar,
-        _iterations: u64,
-        _density: Scalar,
-        _parallel: ExecutionMode,
-    ) {
+        _params: &SolverParams,
+    ) -> FluidResult<()> {
+        return Ok(());
     }
 
-    fn advect(&mut self, _log: &Logger, _dt: Scalar) {}
+    fn advect(&mut self, _log: &Logger, _dt: Scalar, _execution_mode: ExecutionMode) {}
+
+    /// Advect and then integrate the body forces of `params`, in one pass over the
+    /// cells where the object supports it (see `TimeStepper::set_fused_integration`).
+    fn advect_and_integrate(&mut self, log: &Logger, dt: Scalar, params: &SolverParams) {
+        self.advect(log, dt, params.execution_mode);
+        self.integrate(log, dt, params);
+    }
+
+    /// The memory used by the object.
+    fn memory_report(&self) -> MemoryReport {
+        return MemoryReport::default();
+    }
 
     // For downcasting.
     // This can be solved differently and nicer.
@@ -28,7 +52,7 @@ pub trait Integrate {
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-pub trait Manipulator {
+pub trait Manipulator: Send {
     fn manipulate(
         &self,
         log: &Logger,
@@ -36,65 +60,692 @@ pub trait Manipulator {
         dt: Scalar,
         objects: &mut Vec<Box<dyn Integrate>>,
     );
+
+    // For downcasting (e.g. live parameter changes).
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 pub struct TimeStepper<'a> {
-    gravity: Vector2,
-    density: Scalar,
-    incompress_iters: u64,
-
+    params: SolverParams,
     t: Scalar,
-    execution_mode: ExecutionMode,
 
     pub objects: Vec<Box<dyn Integrate>>,
     pub manipulators: Vec<Box<dyn Manipulator>>,
 
+    /// Closures called at the points of each step.
+    hooks: StepHooks,
+
+    /// Listeners of the events of the steps.
+    observers: Observers,
+
+    /// Body forces of the grids in addition to the gravity.
+    force_fields: Vec<Box<dyn ForceField>>,
+
+    /// Pool for all parallel work of a step (rayon's global pool if not set).
+    thread_pool: Option<Arc<rayon::ThreadPool>>,
+
+    /// The pool does not match the threads and the cores of `params` yet.
+    thread_pool_outdated: bool,
+
+    /// Advect before the projection and add the body forces in the same pass.
+    fused_integration: bool,
+
+    /// The GPU solver if the steps are computed on the GPU.
+    #[cfg(feature = "gpu")]
+    gpu: Option<crate::gpu::GpuSolver>,
+    gpu_schedule: GpuSchedule,
+
+    /// The CUDA solver if the pressure solve and the advection run on a CUDA device.
+    #[cfg(feature = "cuda")]
+    cuda: Option<crate::cuda::CudaSolver>,
+
     log: &'a Logger,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum ExecutionMode {
     Single,
     Parallel,
     ParallelUnsafe,
+
+    /// Gauss-Seidel sweeps over bands of rows, with even and odd bands in parallel.
+    ParallelBands,
+
+    /// Serial Gauss-Seidel over cache-sized tiles.
+    Tiled,
+
+    /// Parallel weighted Jacobi iterations, bit-identical for any number of threads.
+    Jacobi,
+}
+
+/// Where the steps are computed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    #[default]
+    Cpu,
+
+    /// wgpu compute shaders in single precision (feature `gpu`).
+    /// Manipulators still run on the CPU and the grid is synchronized each step.
+    Gpu,
+
+    /// CUDA kernels for the pressure solve and the advection (feature `cuda`),
+    /// on the first visible device (see `CUDA_VISIBLE_DEVICES`).
+    Cuda,
+}
+
+/// How a step is split between the CPU and the GPU with backend `Gpu`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GpuSchedule {
+    /// Integration, projection and advection on the GPU.
+    #[default]
+    All,
+
+    /// Integration and projection on the GPU, the advection on the CPU (in the precision
+    /// of the build). Only the velocity is transferred, the smoke stays on the CPU.
+    Projection,
+}
+
+/// Wall-clock time spent in each stage of a step.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    pub manipulate: Duration,
+    pub integrate: Duration,
+    pub solve: Duration,
+    pub advect: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        return self.manipulate + self.integrate + self.solve + self.advect;
+    }
+}
+
+impl std::ops::AddAssign for StageTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.manipulate += other.manipulate;
+        self.integrate += other.integrate;
+        self.solve += other.solve;
+        self.advect += other.advect;
+    }
+}
+
+/// The serializable state of the timestepper (without objects and manipulators).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeStepperState {
+    pub t: Scalar,
+    pub gravity: Vector2,
+    pub density: Scalar,
+    pub incompress_iters: u64,
+    pub execution_mode: ExecutionMode,
 }
 
 impl<'a> TimeStepper<'a> {
+    /// The timestepper of `objects`. The settings of the grids in `params` are not
+    /// copied to the objects (see `set_params`), the thread pool is set up before the
+    /// first step.
     pub fn new(
         log: &'a Logger,
-        density: Scalar,
-        gravity: Vector2,
-        incompress_iters: u64,
-        execution_mode: ExecutionMode,
+        params: SolverParams,
         objects: Vec<Box<dyn Integrate>>,
         manipulators: Vec<Box<dyn Manipulator>>,
     ) -> Self {
+        let mut observers = Observers::default();
+        observers.checks.max_cfl = params.max_cfl;
+        let thread_pool_outdated = params.threads > 0 || !params.cores.is_empty();
+
         return TimeStepper {
             log,
-            gravity,
-            density,
-            incompress_iters,
-            execution_mode: execution_mode,
+            params,
             objects,
             manipulators,
+            hooks: StepHooks::default(),
+            observers,
+            force_fields: vec![],
+            thread_pool: None,
+            thread_pool_outdated,
+            fused_integration: false,
+            #[cfg(feature = "gpu")]
+            gpu: None,
+            gpu_schedule: GpuSchedule::All,
+            #[cfg(feature = "cuda")]
+            cuda: None,
             t: 0.0,
         };
     }
 
-    pub fn compute_step(&mut self, dt: Scalar) {
-        if dt <= 0.0 {
-            panic!("Timestep is invalid.")
+    /// Run the parallel work on `threads` threads (`0`: rayon's global pool).
+    pub fn set_threads(&mut self, threads: usize) -> GenericResult<()> {
+        return self.set_thread_pool(threads, &[]);
+    }
+
+    /// Run the parallel work on an own pool of `threads` threads.
+    /// If `cores` is not empty, worker `i` is pinned to the core `cores[i % cores.len()]`
+    /// and `threads = 0` starts one worker per core. Otherwise `threads = 0` uses rayon's
+    /// global pool. Pinning is not supported on all platforms and then has no effect.
+    /// The threads and the cores are stored in the solver parameters.
+    pub fn set_thread_pool(&mut self, threads: usize, cores: &[usize]) -> GenericResult<()> {
+        if let Some(ids) = core_affinity::get_core_ids() {
+            if let Some(c) = cores.iter().find(|c| !ids.iter().any(|id| id.id == **c)) {
+                return Err(format!("Core '{}' is not available (cores: {}).", c, ids.len()).into());
+            }
+        }
+
+        // A pool which fails to start is set up again before the next step.
+        self.params.threads = threads;
+        self.params.cores = cores.to_vec();
+        self.thread_pool_outdated = true;
+
+        let threads = if threads == 0 { cores.len() } else { threads };
+        if threads == 0 {
+            self.thread_pool = None;
+            self.thread_pool_outdated = false;
+            return Ok(());
+        }
+
+        let mut builder = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("rustofluid-{}", i));
+
+        let cores = cores.to_vec();
+        builder = builder.start_handler(move |i| {
+            if !cores.is_empty() {
+                let id = cores[i % cores.len()];
+                core_affinity::set_for_current(core_affinity::CoreId { id });
+            }
+            crate::math::set_flush_to_zero();
+        });
+
+        self.thread_pool = Some(Arc::new(builder.build()?));
+        self.thread_pool_outdated = false;
+        return Ok(());
+    }
+
+    /// The number of threads used for the parallel work.
+    pub fn threads(&self) -> usize {
+        return self
+            .thread_pool
+            .as_ref()
+            .map_or(rayon::current_num_threads(), |p| p.current_num_threads());
+    }
+
+    /// Compute the steps with `backend` and return the backend in use.
+    /// Falls back to the CPU if the device is not available or the crate is built
+    /// without the backend's feature (`gpu` or `cuda`).
+    pub fn set_backend(&mut self, backend: Backend) -> Backend {
+        #[cfg(feature = "gpu")]
+        {
+            self.gpu = None;
+        }
+        #[cfg(feature = "cuda")]
+        {
+            self.cuda = None;
         }
 
+        match backend {
+            Backend::Cpu => return Backend::Cpu,
+
+            #[cfg(feature = "gpu")]
+            Backend::Gpu => match crate::gpu::GpuSolver::new(self.main_grid()) {
+                Ok(gpu) => {
+                    info!(self.log, "Compute on GPU '{}'.", gpu.adapter_name());
+                    self.gpu = Some(gpu);
+                    return Backend::Gpu;
+                }
+                Err(e) => warn!(self.log, "GPU not available ({}), compute on the CPU.", e),
+            },
+
+            #[cfg(feature = "cuda")]
+            Backend::Cuda => match crate::cuda::CudaSolver::new(self.main_grid(), 0) {
+                Ok(cuda) => {
+                    info!(self.log, "Solve and advect on the CUDA device.");
+                    self.cuda = Some(cuda);
+                    return Backend::Cuda;
+                }
+                Err(e) => warn!(self.log, "CUDA not available ({}), compute on the CPU.", e),
+            },
+
+            #[allow(unreachable_patterns)]
+            _ => warn!(
+                self.log,
+                "Built without the feature of backend '{:?}', compute on the CPU.", backend
+            ),
+        }
+
+        return Backend::Cpu;
+    }
+
+    /// The main grid (the first object), which the device backends copy.
+    #[cfg(any(feature = "gpu", feature = "cuda"))]
+    fn main_grid(&self) -> &crate::scene::grid::Grid {
+        return self.objects[0]
+            .as_any()
+            .downcast_ref::<crate::scene::grid::Grid>()
+            .expect("Not a grid");
+    }
+
+    pub fn backend(&self) -> Backend {
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return Backend::Gpu;
+        }
+
+        #[cfg(feature = "cuda")]
+        if self.cuda.is_some() {
+            return Backend::Cuda;
+        }
+
+        return Backend::Cpu;
+    }
+
+    /// Run `f` in the thread pool of the timestepper, which is set up first if the
+    /// solver parameters changed it. The workers of an own pool flush subnormals to zero
+    /// from their start on (see `set_thread_pool`).
+    fn in_pool<R: Send, F: FnOnce(&mut Self) -> R + Send>(&mut self, f: F) -> FluidResult<R> {
+        if self.thread_pool_outdated {
+            let (threads, cores) = (self.params.threads, self.params.cores.clone());
+            self.set_thread_pool(threads, &cores)?;
+        }
+
+        return Ok(match self.thread_pool.clone() {
+            Some(pool) => pool.install(|| f(self)),
+            None => {
+                crate::math::set_flush_to_zero_global();
+                f(self)
+            }
+        });
+    }
+
+    /// The current simulation time.
+    pub fn time(&self) -> Scalar {
+        return self.t;
+    }
+
+    pub fn state(&self) -> TimeStepperState {
+        return TimeStepperState {
+            t: self.t,
+            gravity: self.params.gravity,
+            density: self.params.density,
+            incompress_iters: self.params.iterations,
+            execution_mode: self.params.execution_mode,
+        };
+    }
+
+    pub fn set_state(&mut self, state: TimeStepperState) {
+        self.t = state.t;
+        self.params.gravity = state.gravity;
+        self.params.density = state.density;
+        self.params.iterations = state.incompress_iters;
+        self.params.execution_mode = state.execution_mode;
+    }
+
+    /// Split the steps between the CPU and the GPU with `schedule` (backend `Gpu`).
+    pub fn set_gpu_schedule(&mut self, schedule: GpuSchedule) {
+        self.gpu_schedule = schedule;
+    }
+
+    pub fn gpu_schedule(&self) -> GpuSchedule {
+        return self.gpu_schedule;
+    }
+
+    /// The memory of all objects (names prefixed with `objects[i].`) and of the
+    /// buffers of the backend (prefixed with `gpu.` or `cuda.`).
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            report.append(&format!("objects[{}].", i), obj.memory_report());
+        }
+
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = self.gpu.as_ref() {
+            report.append("gpu.", gpu.memory_report());
+        }
+
+        #[cfg(feature = "cuda")]
+        if let Some(cuda) = self.cuda.as_ref() {
+            report.append("cuda.", cuda.memory_report());
+        }
+
+        return report;
+    }
+
+    /// The GPU solver of backend `Gpu`, e.g. to composite its `smoke_texture`.
+    #[cfg(feature = "gpu")]
+    pub fn gpu_solver(&self) -> Option<&crate::gpu::GpuSolver> {
+        return self.gpu.as_ref();
+    }
+
+    /// Advect first and add the body forces while the advected smoke is written, which
+    /// saves the separate integration pass over the grid. The projection follows, so
+    /// the velocity at the end of each step stays divergence free. A step is then
+    /// advect, integrate and project instead of integrate, project and advect.
+    /// Grids with vorticity confinement, an activity threshold or a custom smoke scheme
+    /// (and all grids if force fields are added) keep separate passes in this order.
+    /// The hooks `PostProject` and `PostAdvect` are both called after the projection.
+    /// Only used on the CPU backend.
+    pub fn set_fused_integration(&mut self, fused: bool) {
+        self.fused_integration = fused;
+    }
+
+    /// If the steps advect before the projection (see `set_fused_integration`).
+    fn is_fused(&self) -> bool {
+        return self.fused_integration && self.backend() == Backend::Cpu;
+    }
+
+    pub fn params(&self) -> &SolverParams {
+        return &self.params;
+    }
+
+    /// Use the solver parameters `params` from the next step on and copy the settings
+    /// of the grids to all grids (see `SolverParams::apply`). A changed number of
+    /// threads or cores sets up a new thread pool before the next step.
+    pub fn set_params(&mut self, params: SolverParams) {
+        self.thread_pool_outdated |=
+            params.threads != self.params.threads || params.cores != self.params.cores;
+
+        for obj in self.objects.iter_mut() {
+            if let Some(grid) = obj.as_any_mut().downcast_mut::<Grid>() {
+                params.apply(grid);
+            }
+        }
+
+        self.observers.checks.max_cfl = params.max_cfl;
+        self.params = params;
+    }
+
+    pub fn gravity(&self) -> Vector2 {
+        return self.params.gravity;
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector2) {
+        self.params.gravity = gravity;
+    }
+
+    pub fn incompress_iters(&self) -> u64 {
+        return self.params.iterations;
+    }
+
+    pub fn set_incompress_iters(&mut self, iterations: u64) {
+        self.params.iterations = iterations;
+    }
+
+    /// Save the state and all grids to a checkpoint file.
+    pub fn save_checkpoint(
+        &mut self,
+        file: &str,
+        compression: checkpoint::Compression,
+    ) -> GenericResult<()> {
+        checkpoint::save_checkpoint(self, file, compression)?;
+
+        let (t, file) = (self.t, file.to_string());
+        self.observers.emit(SolverEvent::CheckpointWritten { t, file });
+        return Ok(());
+    }
+
+    /// Restore the state and the cells of all grids from a checkpoint file.
+    /// The objects must have been set up with the same scene, whose settings of the grids
+    /// are kept.
+    pub fn load_checkpoint(&mut self, file: &str) -> GenericResult<()> {
+        return checkpoint::load_checkpoint(self, file);
+    }
+
+    /// Call `hook` with the grids at `point` of each step, e.g. to add custom forces or to
+    /// measure. Returns the id to remove the hook.
+    pub fn add_hook<F>(&mut self, point: HookPoint, hook: F) -> HookId
+    where
+        F: FnMut(&HookInfo, &mut Grid) + Send + 'static,
+    {
+        return self.hooks.add(point, Box::new(hook));
+    }
+
+    /// Remove the hook `id`. Returns `false` if it is not registered.
+    pub fn remove_hook(&mut self, id: HookId) -> bool {
+        return self.hooks.remove(id);
+    }
+
+    pub fn hooks_mut(&mut self) -> &mut StepHooks {
+        return &mut self.hooks;
+    }
+
+    /// Notify `observer` of the events of the steps and the checkpoints.
+    /// Returns the id to unsubscribe the observer.
+    pub fn subscribe<O: Observer + 'static>(&mut self, observer: O) -> ObserverId {
+        return self.observers.add(Box::new(observer));
+    }
+
+    /// Unsubscribe the observer `id`. Returns `false` if it is not subscribed.
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        return self.observers.remove(id);
+    }
+
+    /// The observers, e.g. to set the thresholds of the checks.
+    pub fn observers_mut(&mut self) -> &mut Observers {
+        return &mut self.observers;
+    }
+
+    /// Add the body force `field` to the grids in each step (see `ForceField`).
+    /// The GPU backend computes the steps on the CPU while force fields are added.
+    pub fn add_force_field<F: ForceField + 'static>(&mut self, field: F) {
+        self.force_fields.push(Box::new(field));
+    }
+
+    pub fn force_fields(&self) -> &[Box<dyn ForceField>] {
+        return &self.force_fields;
+    }
+
+    /// The force fields, e.g. to remove or reorder them.
+    pub fn force_fields_mut(&mut self) -> &mut Vec<Box<dyn ForceField>> {
+        return &mut self.force_fields;
+    }
+
+    /// Compute one step of size `dt`.
+    /// Fails if `dt` is not positive or an object can not be solved.
+    pub fn compute_step(&mut self, dt: Scalar) -> FluidResult<()> {
+        return self.in_pool(|ts| ts.step(dt))?;
+    }
+
+    /// Same as `compute_step` but measures the time spent in each stage.
+    /// The reset is accounted to the integration.
+    pub fn compute_step_timed(&mut self, dt: Scalar) -> FluidResult<StageTimings> {
+        return self.in_pool(|ts| ts.step_timed(dt))?;
+    }
+
+    fn check_dt(dt: Scalar) -> FluidResult<()> {
+        if !(dt > 0.0 && dt.is_finite()) {
+            return Err(Error::invalid_parameter("dt", format!("{} is not positive", dt)));
+        }
+        return Ok(());
+    }
+
+    fn step(&mut self, dt: Scalar) -> FluidResult<()> {
+        Self::check_dt(dt)?;
+
         info!(self.log, "Time at: '{:0.3}'.", self.t);
+        self.observers.emit(SolverEvent::StepStarted { t: self.t, dt });
+        self.manipulate(self.t, dt);
+
+        self.reset();
+        self.call_hooks(&[HookPoint::PreStep], dt);
+        if self.step_gpu(dt) {
+            self.call_hooks(&[HookPoint::PostIntegrate, HookPoint::PostProject], dt);
+        } else if self.is_fused() {
+            self.advect_and_integrate(dt);
+            self.call_hooks(&[HookPoint::PostIntegrate], dt);
+            self.solve_incompressibility(dt)?;
+            self.call_hooks(&[HookPoint::PostProject], dt);
+        } else {
+            self.integrate(dt);
+            self.call_hooks(&[HookPoint::PostIntegrate], dt);
+
+            if self.solve_and_advect_cuda(dt) {
+                self.call_hooks(&[HookPoint::PostProject], dt);
+            } else {
+                self.solve_incompressibility(dt)?;
+                self.call_hooks(&[HookPoint::PostProject], dt);
+                self.advect(dt);
+            }
+        }
+        self.call_hooks(&[HookPoint::PostAdvect], dt);
+
+        self.finish_step(dt);
+        return Ok(());
+    }
+
+    fn step_timed(&mut self, dt: Scalar) -> FluidResult<StageTimings> {
+        Self::check_dt(dt)?;
+
+        let mut timings = StageTimings::default();
+        self.observers.emit(SolverEvent::StepStarted { t: self.t, dt });
+
+        let start = Instant::now();
         self.manipulate(self.t, dt);
+        timings.manipulate = start.elapsed();
 
+        let start = Instant::now();
         self.reset();
+        self.call_hooks(&[HookPoint::PreStep], dt);
+        if self.step_gpu(dt) {
+            // The stages are not separable on the GPU.
+            self.call_hooks(&HookPoint::ALL[1..], dt);
+            timings.solve = start.elapsed();
+            self.finish_step(dt);
+            return Ok(timings);
+        }
+        if self.is_fused() {
+            // The integration is accounted to the advection.
+            self.advect_and_integrate(dt);
+            self.call_hooks(&[HookPoint::PostIntegrate], dt);
+            timings.advect = start.elapsed();
+
+            let start = Instant::now();
+            self.solve_incompressibility(dt)?;
+            self.call_hooks(&HookPoint::ALL[2..], dt);
+            timings.solve = start.elapsed();
+
+            self.finish_step(dt);
+            return Ok(timings);
+        }
         self.integrate(dt);
-        self.solve_incompressibility(dt);
+        self.call_hooks(&[HookPoint::PostIntegrate], dt);
+        timings.integrate = start.elapsed();
+
+        let start = Instant::now();
+        if self.solve_and_advect_cuda(dt) {
+            // The advection is accounted to the solve.
+            self.call_hooks(&HookPoint::ALL[2..], dt);
+            timings.solve = start.elapsed();
+            self.finish_step(dt);
+            return Ok(timings);
+        }
+        self.solve_incompressibility(dt)?;
+        self.call_hooks(&[HookPoint::PostProject], dt);
+        timings.solve = start.elapsed();
+
+        let start = Instant::now();
         self.advect(dt);
+        self.call_hooks(&[HookPoint::PostAdvect], dt);
+        timings.advect = start.elapsed();
+
+        self.finish_step(dt);
+
+        return Ok(timings);
+    }
+
+    /// Integrate, solve and advect on the GPU (if enabled) with one upload
+    /// and download of the grid. Returns `false` if the CPU has to compute the step.
+    #[cfg(feature = "gpu")]
+    fn step_gpu(&mut self, dt: Scalar) -> bool {
+        if !self.force_fields.is_empty() {
+            return false;
+        }
+
+        let gpu = match self.gpu.as_mut() {
+            Some(gpu) => gpu,
+            None => return false,
+        };
+
+        let grid = self.objects[0]
+            .as_any_mut()
+            .downcast_mut::<crate::scene::grid::Grid>()
+            .expect("Not a grid");
+
+        let p = &self.params;
+        let (gravity, iterations, density) = (p.gravity, p.iterations, p.density);
+        let result = match self.gpu_schedule {
+            GpuSchedule::All => gpu.upload(grid).and_then(|_| {
+                gpu.step(dt, gravity, iterations, density);
+                return gpu.download(grid);
+            }),
+            GpuSchedule::Projection => gpu.upload_velocity(grid).and_then(|_| {
+                gpu.project(dt, gravity, iterations, density);
+                return gpu.download_projection(grid);
+            }),
+        };
+
+        if let Err(e) = result {
+            warn!(self.log, "GPU step failed ({}), compute on the CPU.", e);
+            self.gpu = None;
+            return false;
+        }
+
+        if self.gpu_schedule == GpuSchedule::Projection {
+            grid.advect(self.log, dt, self.params.execution_mode);
+        }
+        grid.compute_stats(self.log);
+        return true;
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    fn step_gpu(&mut self, _dt: Scalar) -> bool {
+        return false;
+    }
+
+    /// Solve and advect on the CUDA device (if enabled).
+    /// Returns `false` if the CPU has to compute them.
+    #[cfg(feature = "cuda")]
+    fn solve_and_advect_cuda(&mut self, dt: Scalar) -> bool {
+        let cuda = match self.cuda.as_mut() {
+            Some(cuda) => cuda,
+            None => return false,
+        };
+
+        let grid = self.objects[0]
+            .as_any_mut()
+            .downcast_mut::<crate::scene::grid::Grid>()
+            .expect("Not a grid");
+
+        let result = cuda
+            .upload(grid)
+            .and_then(|_| cuda.solve_and_advect(dt, self.params.iterations, self.params.density))
+            .and_then(|_| cuda.download(grid));
+
+        if let Err(e) = result {
+            warn!(self.log, "CUDA step failed ({}), compute on the CPU.", e);
+            self.cuda = None;
+            return false;
+        }
+
+        grid.compute_stats(self.log);
+        return true;
+    }
+
+    #[cfg(not(feature = "cuda"))]
+    fn solve_and_advect_cuda(&mut self, _dt: Scalar) -> bool {
+        return false;
+    }
 
+    /// Advance the time and emit the events of the end of the step.
+    fn finish_step(&mut self, dt: Scalar) {
         self.t = self.t + dt;
+
+        self.observers.check_step(self.t, dt, &self.objects);
+        self.observers.emit(SolverEvent::StepFinished { t: self.t, dt });
+    }
+
+    fn call_hooks(&mut self, points: &[HookPoint], dt: Scalar) {
+        for point in points {
+            self.hooks.call(*point, self.t, dt, &mut self.objects);
+        }
     }
 
     fn reset(&mut self) {
@@ -118,29 +769,50 @@ impl<'a> TimeStepper<'a> {
         );
 
         for obj in self.objects.iter_mut() {
-            obj.integrate(self.log, dt, self.gravity);
+            if let Some(grid) = obj.as_any_mut().downcast_mut::<Grid>() {
+                let fields = &self.force_fields;
+                let mode = self.params.execution_mode;
+                grid.apply_force_fields(self.log, fields, self.t, dt, mode);
+            }
+
+            obj.integrate(self.log, dt, &self.params);
+        }
+    }
+
+    /// Advect and integrate in one pass (see `set_fused_integration`).
+    fn advect_and_integrate(&mut self, dt: Scalar) {
+        info!(self.log, "Advect and integrate at t: '{:0.3}'.", self.t);
+
+        let mode = self.params.execution_mode;
+        for obj in self.objects.iter_mut() {
+            if !self.force_fields.is_empty() {
+                if let Some(grid) = obj.as_any_mut().downcast_mut::<Grid>() {
+                    // The force fields need their own pass.
+                    grid.advect(self.log, dt, mode);
+                    grid.apply_force_fields(self.log, &self.force_fields, self.t, dt, mode);
+                    grid.integrate(self.log, dt, &self.params);
+                    continue;
+                }
+            }
+
+            obj.advect_and_integrate(self.log, dt, &self.params);
         }
     }
 
-    fn solve_incompressibility(&mut self, dt: Scalar) {
+    fn solve_incompressibility(&mut self, dt: Scalar) -> FluidResult<()> {
         info!(self.log, "Solve incompressibility at t: '{:0.3}'.", self.t,);
 
         for obj in self.objects.iter_mut() {
-            obj.solve_incompressibility(
-                self.log,
-                dt,
-                self.incompress_iters,
-                self.density,
-                self.execution_mode,
-            );
+            obj.solve_incompressibility(self.log, dt, &self.params)?;
         }
+        return Ok(());
     }
 
     fn advect(&mut self, dt: Scalar) {
         info!(self.log, "Advect at t: '{:0.3}'.", self.t,);
 
         for obj in self.objects.iter_mut() {
-            obj.advect(self.log, dt);
+            obj.advect(self.log, dt, self.params.execution_mode);
         }
     }
 }
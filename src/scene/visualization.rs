@@ -49,7 +49,7 @@ fn make_solid<'a>(
 fn make_masked<'a>(grid: &'a Grid, f: &'a impl ColorFunction) -> impl ColorFunction + 'a {
     return |idx: Index2| {
         let mut c = f(idx);
-        c.a *= grid.cell(idx).smoke.back;
+        c.a *= grid.cell(idx).smoke();
         return c;
     };
 }
@@ -88,9 +88,9 @@ pub fn save_plots(
     let mut file = params.output.replace("{}", &format!("smoke-{:06}", step));
 
     let smoke_color: &dyn plotting::ColorFunction = &|idx: Index2| {
-        let alpha = grid.cell(idx).smoke.back;
-        let mut color = cg.at(0.6 * alpha);
-        color.a = alpha;
+        let alpha = grid.cell(idx).smoke();
+        let mut color = cg.at(0.6 * alpha as f64);
+        color.a = alpha as f64;
         return color;
     };
 
@@ -110,7 +110,7 @@ pub fn save_plots(
 
         let get_color = |idx: Index2| {
             let t = grid.cell(idx).velocity.back.norm() - grid.stats[0].velocity_norm / v_range;
-            return cg.at(t);
+            return cg.at(t as f64);
         };
 
         let get_color_masked = make_masked(&grid, &get_color);
@@ -137,7 +137,7 @@ pub fn save_plots(
 
         let get_color: &dyn ColorFunction = &|idx: Index2| {
             let t = (grid.cell(idx).pressure - grid.stats[0].pressure) / p_range;
-            return cg.at(t);
+            return cg.at(t as f64);
         };
 
         let get_color_masked = make_masked(&grid, &get_color);
@@ -1,6 +1,51 @@
 use nalgebra;
 
+/// The floating point type of all fields: `f64` or `f32` with feature `f32`.
+/// Single precision halves the memory traffic and is accurate enough for visual smoke,
+/// double precision is meant for validation runs.
+#[cfg(not(feature = "f32"))]
 pub type Scalar = f64;
+#[cfg(feature = "f32")]
+pub type Scalar = f32;
+
+/// The storage type of the fields which only feed the rendering (the smoke):
+/// `Scalar` or `half::f16` with feature `f16-smoke`. Half precision cuts the memory
+/// traffic of very large grids further, the values are converted on access.
+#[cfg(not(feature = "f16-smoke"))]
+pub type RenderScalar = Scalar;
+#[cfg(feature = "f16-smoke")]
+pub type RenderScalar = half::f16;
+
+/// Conversion of a `RenderScalar` from and to full precision.
+pub trait RenderStorage: Copy {
+    fn load(self) -> Scalar;
+    fn store(value: Scalar) -> Self;
+}
+
+impl RenderStorage for Scalar {
+    #[inline(always)]
+    fn load(self) -> Scalar {
+        return self;
+    }
+
+    #[inline(always)]
+    fn store(value: Scalar) -> Self {
+        return value;
+    }
+}
+
+#[cfg(feature = "f16-smoke")]
+impl RenderStorage for half::f16 {
+    #[inline(always)]
+    fn load(self) -> Scalar {
+        return self.to_f64() as Scalar;
+    }
+
+    #[inline(always)]
+    fn store(value: Scalar) -> Self {
+        return half::f16::from_f64(value as f64);
+    }
+}
 pub type Vector2 = nalgebra::Vector2<Scalar>;
 pub type Matrix2 = nalgebra::Matrix2<Scalar>;
 pub type Matrix1x2 = nalgebra::Matrix1x2<Scalar>;
@@ -0,0 +1,63 @@
+use crate::types::{Index2, Scalar};
+
+use std::error;
+
+/// The errors of the checked API (`Simulation`, the setters of the grid fields), which
+/// embedding applications can handle instead of a panic.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The scene configuration is invalid.
+    #[error("Invalid scene: {0}")]
+    Scene(String),
+
+    /// A parameter is out of its range, e.g. a non-positive time step.
+    #[error("Invalid parameter '{name}': {message}")]
+    InvalidParameter { name: &'static str, message: String },
+
+    /// A cell index or a number of values does not match the grid.
+    #[error("Index {index} is outside of the grid with dimension {dim}.")]
+    OutOfBounds { index: Index2, dim: Index2 },
+
+    /// A grid or a state does not have the dimension of the grid it is used with.
+    #[error("Grid dimension {dim} does not match the expected dimension {expected}.")]
+    DimensionMismatch { dim: Index2, expected: Index2 },
+
+    /// An object of the timestepper which is used as grid is not a grid (or missing).
+    #[error("Object {index} of the timestepper is not a grid.")]
+    NotAGrid { index: usize },
+
+    /// A cell holds a value which is not finite after a step.
+    #[error("Simulation diverged at t: '{t:0.3}', cell {index} is not finite.")]
+    Diverged { t: Scalar, index: Index2 },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Any other error, e.g. of a file format or a backend.
+    #[error("{0}")]
+    Other(Box<dyn error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn invalid_parameter(name: &'static str, message: String) -> Self {
+        return Error::InvalidParameter { name, message };
+    }
+}
+
+impl From<Box<dyn error::Error>> for Error {
+    fn from(e: Box<dyn error::Error>) -> Self {
+        return match e.downcast::<std::io::Error>() {
+            Ok(e) => Error::Io(*e),
+            Err(e) => Error::Other(e.to_string().into()),
+        };
+    }
+}
+
+impl From<simple_error::SimpleError> for Error {
+    fn from(e: simple_error::SimpleError) -> Self {
+        return Error::Other(Box::new(e));
+    }
+}
+
+/// The result of the checked API.
+pub type FluidResult<T> = std::result::Result<T, Error>;
@@ -1,4 +1,6 @@
-#[derive(Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FrontBackBuffer<T> {
     pub front: T, // Back buffer.
     pub back: T,  // Front buffer.
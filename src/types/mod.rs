@@ -1,7 +1,11 @@
 mod algebra;
 mod frontback;
 mod common;
+mod error;
+mod units;
 
 pub use algebra::*;
 pub use frontback::*;
 pub use common::*;
+pub use error::*;
+pub use units::*;
@@ -0,0 +1,180 @@
+use crate::types::Scalar;
+
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+// Thin wrappers of the physical quantities of the API in SI units, such that e.g. a
+// time in seconds can not be passed as a number of steps or a length in metres as a
+// number of cells. `value()` is the plain number in the unit of the type.
+// In scene files they are plain numbers (`serde(transparent)`).
+macro_rules! unit {
+    ($(#[$doc:meta])* $name:ident, $unit:literal) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub Scalar);
+
+        impl $name {
+            pub const UNIT: &'static str = $unit;
+
+            pub fn value(self) -> Scalar {
+                return self.0;
+            }
+
+            pub fn is_finite(self) -> bool {
+                return self.0.is_finite();
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                return write!(f, "{} {}", self.0, $unit);
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, other: $name) -> $name {
+                return $name(self.0 + other.0);
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name {
+                return $name(self.0 - other.0);
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name {
+                return $name(-self.0);
+            }
+        }
+
+        impl Mul<Scalar> for $name {
+            type Output = $name;
+            fn mul(self, s: Scalar) -> $name {
+                return $name(self.0 * s);
+            }
+        }
+
+        impl Mul<$name> for Scalar {
+            type Output = $name;
+            fn mul(self, q: $name) -> $name {
+                return $name(self * q.0);
+            }
+        }
+
+        impl Div<Scalar> for $name {
+            type Output = $name;
+            fn div(self, s: Scalar) -> $name {
+                return $name(self.0 / s);
+            }
+        }
+
+        /// The ratio of two quantities of the same unit.
+        impl Div for $name {
+            type Output = Scalar;
+            fn div(self, other: $name) -> Scalar {
+                return self.0 / other.0;
+            }
+        }
+    };
+}
+
+unit!(
+    /// A length in metres, e.g. the cell width or a radius in world coordinates.
+    Length,
+    "m"
+);
+unit!(
+    /// A time or a time step in seconds.
+    Time,
+    "s"
+);
+unit!(
+    /// A speed in metres per second.
+    Velocity,
+    "m/s"
+);
+unit!(
+    /// A mass density in kilograms per cubic metre.
+    Density,
+    "kg/m^3"
+);
+
+impl Div<Time> for Length {
+    type Output = Velocity;
+    fn div(self, t: Time) -> Velocity {
+        return Velocity(self.0 / t.0);
+    }
+}
+
+impl Mul<Time> for Velocity {
+    type Output = Length;
+    fn mul(self, t: Time) -> Length {
+        return Length(self.0 * t.0);
+    }
+}
+
+impl Div<Velocity> for Length {
+    type Output = Time;
+    fn div(self, v: Velocity) -> Time {
+        return Time(self.0 / v.0);
+    }
+}
+
+impl Length {
+    /// The length in cells of the width `cell_width`.
+    pub fn in_cells(self, cell_width: Length) -> Cells {
+        return Cells(self.0 / cell_width.0);
+    }
+}
+
+/// A length in cells of a grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cells(pub Scalar);
+
+impl Cells {
+    pub fn value(self) -> Scalar {
+        return self.0;
+    }
+
+    /// The length of the cells of the width `cell_width`.
+    pub fn length(self, cell_width: Length) -> Length {
+        return Length(self.0 * cell_width.0);
+    }
+}
+
+/// A number of time steps.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Steps(pub u64);
+
+impl Steps {
+    pub fn value(self) -> u64 {
+        return self.0;
+    }
+
+    /// The time of the steps of size `dt`.
+    pub fn duration(self, dt: Time) -> Time {
+        return Time(self.0 as Scalar * dt.0);
+    }
+}
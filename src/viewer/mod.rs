@@ -0,0 +1,7 @@
+mod panel;
+mod plots;
+mod window;
+
+pub use panel::*;
+pub use plots::*;
+pub use window::*;
@@ -0,0 +1,215 @@
+use crate::scene::diagnostics::DiagnosticsHistory;
+use crate::scene::probes::CellProbe;
+use crate::scene::replay::Parameters;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+use crate::viewer::plots::diagnostics_ui;
+
+use egui::{ClippedPrimitive, Context, TexturesDelta};
+use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
+use pixels::{wgpu, PixelsContext};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::Window;
+
+/// Live-tunable simulation parameters, applied between steps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Controls {
+    pub gravity: [Scalar; 2],
+    pub over_relaxation: Scalar,
+    pub iterations: u64,
+    pub emitter_smoke: Scalar,
+    pub vorticity_confinement: Scalar,
+    pub paused: bool,
+
+    /// Show the diagnostics plots.
+    pub diagnostics: bool,
+
+    /// The solver state under the mouse cursor.
+    pub inspected: Option<CellProbe>,
+
+    /// A failure shown at the top of the panel, e.g. of writing the recording.
+    pub status: Option<String>,
+}
+
+impl Controls {
+    /// Read the current parameters from the simulation.
+    pub fn from(timestepper: &mut TimeStepper) -> Self {
+        let p = Parameters::from(timestepper);
+
+        return Controls {
+            gravity: p.gravity,
+            over_relaxation: p.over_relaxation,
+            iterations: p.iterations,
+            emitter_smoke: p.emitter_smoke,
+            vorticity_confinement: p.vorticity_confinement,
+            paused: false,
+            diagnostics: false,
+            inspected: None,
+            status: None,
+        };
+    }
+
+    /// The simulation parameters without the viewer state.
+    pub fn parameters(&self) -> Parameters {
+        return Parameters {
+            gravity: self.gravity,
+            over_relaxation: self.over_relaxation,
+            iterations: self.iterations,
+            emitter_smoke: self.emitter_smoke,
+            vorticity_confinement: self.vorticity_confinement,
+        };
+    }
+
+    /// Apply the parameters to the simulation.
+    pub fn apply(&self, timestepper: &mut TimeStepper) {
+        self.parameters().apply(timestepper);
+    }
+
+    /// Draw the side panel with all sliders.
+    pub fn ui(&mut self, ctx: &Context) {
+        egui::SidePanel::left("controls").show(ctx, |ui| {
+            if let Some(ref status) = self.status {
+                ui.colored_label(egui::Color32::RED, status);
+                ui.separator();
+            }
+
+            ui.heading("Parameters");
+
+            ui.add(egui::Slider::new(&mut self.gravity[0], -20.0..=20.0).text("gravity x"));
+            ui.add(egui::Slider::new(&mut self.gravity[1], -20.0..=20.0).text("gravity y"));
+            ui.add(egui::Slider::new(&mut self.over_relaxation, 1.0..=1.99).text("over-relaxation"));
+            ui.add(egui::Slider::new(&mut self.iterations, 1..=500).text("iterations"));
+            ui.add(egui::Slider::new(&mut self.emitter_smoke, 0.0..=1.0).text("emitter smoke"));
+            ui.add(
+                egui::Slider::new(&mut self.vorticity_confinement, 0.0..=50.0)
+                    .text("vorticity confinement"),
+            );
+
+            ui.separator();
+            ui.checkbox(&mut self.paused, "Paused (Space)");
+            ui.checkbox(&mut self.diagnostics, "Diagnostics (D)");
+
+            if let Some(ref p) = self.inspected {
+                ui.separator();
+                ui.heading("Cursor");
+                ui.label(format!("position: ({:.3}, {:.3})", p.position.x, p.position.y));
+                if let (Some(idx), Some(mode)) = (p.index, &p.cell_type) {
+                    ui.label(format!("cell: ({}, {}) {:?}", idx.x, idx.y, mode));
+                }
+                ui.label(format!("velocity: ({:.3}, {:.3})", p.velocity.x, p.velocity.y));
+                ui.label(format!("pressure: {:.3}", p.pressure));
+                ui.label(format!("smoke: {:.3}", p.smoke));
+                ui.label(format!("divergence: {:.2e}", p.divergence));
+            }
+        });
+    }
+}
+
+/// Glue between `egui` and the `pixels` renderer.
+pub struct Panel {
+    ctx: Context,
+    state: egui_winit::State,
+    screen: ScreenDescriptor,
+    renderer: Renderer,
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+}
+
+impl Panel {
+    pub fn new<T>(
+        event_loop: &EventLoopWindowTarget<T>,
+        size: [u32; 2],
+        scale_factor: f32,
+        pixels: &pixels::Pixels,
+    ) -> Self {
+        let mut state = egui_winit::State::new(event_loop);
+        state.set_max_texture_side(pixels.device().limits().max_texture_dimension_2d as usize);
+        state.set_pixels_per_point(scale_factor);
+
+        return Panel {
+            ctx: Context::default(),
+            state,
+            screen: ScreenDescriptor {
+                size_in_pixels: size,
+                pixels_per_point: scale_factor,
+            },
+            renderer: Renderer::new(pixels.device(), pixels.render_texture_format(), None, 1),
+            paint_jobs: Vec::new(),
+            textures: TexturesDelta::default(),
+        };
+    }
+
+    /// Forward a window event. Returns `true` if `egui` consumed it.
+    pub fn handle_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        return self.state.on_event(&self.ctx, event).consumed;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.screen.size_in_pixels = [width, height];
+        }
+    }
+
+    pub fn scale_factor(&mut self, scale_factor: f64) {
+        self.screen.pixels_per_point = scale_factor as f32;
+    }
+
+    /// Run the UI and tessellate it for rendering.
+    pub fn prepare(&mut self, window: &Window, controls: &mut Controls, history: &DiagnosticsHistory) {
+        let input = self.state.take_egui_input(window);
+        let output = self.ctx.run(input, |ctx| {
+            controls.ui(ctx);
+            if controls.diagnostics {
+                diagnostics_ui(ctx, history, &mut controls.diagnostics);
+            }
+        });
+
+        self.textures.append(output.textures_delta);
+        self.state
+            .handle_platform_output(window, &self.ctx, output.platform_output);
+        self.paint_jobs = self.ctx.tessellate(output.shapes);
+    }
+
+    /// Render the UI on top of the pixels frame.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        context: &PixelsContext,
+    ) {
+        for (id, delta) in &self.textures.set {
+            self.renderer
+                .update_texture(&context.device, &context.queue, *id, delta);
+        }
+
+        self.renderer.update_buffers(
+            &context.device,
+            &context.queue,
+            encoder,
+            &self.paint_jobs,
+            &self.screen,
+        );
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.renderer.render(&mut pass, &self.paint_jobs, &self.screen);
+        }
+
+        let textures = std::mem::take(&mut self.textures);
+        for id in &textures.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::scene::diagnostics::{Diagnostics, DiagnosticsHistory};
+use crate::types::*;
+
+use egui::plot::{Line, Plot, PlotPoints};
+use egui::Context;
+
+const PLOTS: [(&str, fn(&Diagnostics) -> Scalar); 4] = [
+    ("max. divergence", |d| d.max_divergence),
+    ("kinetic energy", |d| d.kinetic_energy),
+    ("solver residual", |d| d.residual),
+    ("dt", |d| d.dt),
+];
+
+/// Draw the diagnostics over time in a window which can be closed with `open`.
+pub fn diagnostics_ui(ctx: &Context, history: &DiagnosticsHistory, open: &mut bool) {
+    egui::Window::new("Diagnostics")
+        .open(open)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            for (name, get) in PLOTS {
+                let last = history.last().map_or(0.0, get);
+                ui.label(format!("{}: {:.4e}", name, last));
+
+                Plot::new(name)
+                    .height(80.0)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show(ui, |p| p.line(Line::new(PlotPoints::new(history.points(get)))));
+            }
+        });
+}
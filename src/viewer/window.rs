@@ -0,0 +1,298 @@
+use crate::render::{render_smoke, RenderParams};
+use crate::scene::diagnostics::{Diagnostics, DiagnosticsHistory};
+use crate::scene::grid::Grid;
+use crate::scene::history::StateHistory;
+use crate::scene::probes::CellProbe;
+use crate::scene::replay::{Interaction, Recorder};
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+use crate::viewer::panel::{Controls, Panel};
+
+use pixels::{Pixels, SurfaceTexture};
+use slog::{warn, Logger};
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::WindowBuilder;
+
+#[derive(Builder)]
+#[builder(pattern = "mutable")]
+pub struct ViewerParams {
+    #[builder(default = "0.016")]
+    pub dt: Scalar,
+
+    /// Simulation steps per displayed frame.
+    #[builder(default = "1")]
+    pub steps_per_frame: usize,
+
+    /// Radius of the mouse impulse in cells.
+    #[builder(default = "4.0")]
+    pub impulse_radius: Scalar,
+
+    /// Velocity per pixel of mouse motion.
+    #[builder(default = "0.2")]
+    pub impulse_strength: Scalar,
+
+    /// Start the simulation paused.
+    #[builder(default)]
+    pub paused: bool,
+
+    /// Show the diagnostics plots from the start.
+    #[builder(default)]
+    pub diagnostics: bool,
+
+    /// Number of steps kept for the diagnostics plots.
+    #[builder(default = "2000")]
+    pub diagnostics_history: usize,
+
+    /// Number of steps which can be rewound (`0`: disabled).
+    #[builder(default = "100")]
+    pub rewind_steps: usize,
+}
+
+fn grid_mut<'a>(timestepper: &'a mut TimeStepper) -> &'a mut Grid {
+    return timestepper.objects[0]
+        .as_any_mut()
+        .downcast_mut::<Grid>()
+        .expect("Not a grid");
+}
+
+/// Write the recording before the viewer exits. Returns `false` if this failed, the
+/// failure is then shown in the panel and the next exit discards the recording.
+fn finish_recording(
+    log: &Logger,
+    recorder: &mut Option<(Recorder, String)>,
+    step: u64,
+    timestepper: &mut TimeStepper,
+    controls: &mut Controls,
+) -> bool {
+    let result = match recorder.as_mut() {
+        Some((r, file)) => r
+            .finish(step, grid_mut(timestepper), file)
+            .map_err(|e| format!("Writing the recording '{}' failed: {}", file, e)),
+        None => return true,
+    };
+    *recorder = None;
+
+    if let Err(e) = result {
+        warn!(log, "{}", e);
+        controls.paused = true;
+        controls.status = Some(format!("{} (close again to discard it).", e));
+        return false;
+    }
+
+    return true;
+}
+
+/// Show the smoke field live in a window while the simulation runs.
+///
+/// Keys: `Space` pauses/resumes, `S` or `Right` steps once (when paused),
+/// `Left` pauses and rewinds one step (not while recording),
+/// `D` toggles the diagnostics plots, `Esc` quits.
+/// The panel shows the solver state under the mouse cursor (see `CellProbe`).
+/// Dragging with the left mouse button injects velocity impulses.
+/// The side panel tunes the parameters live between steps.
+/// With a `recorder` all interactions are written to its file on exit. If this fails,
+/// the viewer stays open and shows the failure.
+pub fn run_viewer(
+    log: &'static Logger,
+    mut timestepper: Box<TimeStepper<'static>>,
+    params: ViewerParams,
+    render: RenderParams,
+    mut recorder: Option<(Recorder, String)>,
+) -> GenericResult<()> {
+    let dim = grid_mut(&mut timestepper).dim;
+    let size = dim * render.scale;
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("RustoFluid")
+        .with_inner_size(LogicalSize::new(size.x as f64, size.y as f64))
+        .build(&event_loop)?;
+
+    let window_size = window.inner_size();
+    let surface = SurfaceTexture::new(window_size.width, window_size.height, &window);
+    let mut pixels = Pixels::new(size.x as u32, size.y as u32, surface)?;
+
+    let mut panel = Panel::new(
+        &event_loop,
+        [window_size.width, window_size.height],
+        window.scale_factor() as f32,
+        &pixels,
+    );
+    let mut controls = Controls::from(&mut timestepper);
+    controls.paused = params.paused;
+    controls.diagnostics = params.diagnostics;
+
+    let mut history = DiagnosticsHistory::new(params.diagnostics_history);
+
+    let mut states = StateHistory::new(params.rewind_steps + 1);
+    states.push(0, &timestepper);
+
+    let mut step: u64 = 0;
+    let mut single_step = false;
+    let mut dragging = false;
+    let mut cursor: Option<(usize, usize)> = None;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent { ref event, .. } if panel.handle_event(event) => {}
+
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    if finish_recording(log, &mut recorder, step, &mut timestepper, &mut controls) {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    panel.scale_factor(scale_factor);
+                }
+
+                WindowEvent::Resized(s) => {
+                    panel.resize(s.width, s.height);
+                    if let Err(e) = pixels.resize_surface(s.width, s.height) {
+                        warn!(log, "Resize failed: {}", e);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } => match key {
+                    VirtualKeyCode::Escape => {
+                        if finish_recording(
+                            log,
+                            &mut recorder,
+                            step,
+                            &mut timestepper,
+                            &mut controls,
+                        ) {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    VirtualKeyCode::Space => controls.paused = !controls.paused,
+                    VirtualKeyCode::S | VirtualKeyCode::Right => single_step = true,
+                    VirtualKeyCode::Left if recorder.is_none() => {
+                        controls.paused = true;
+                        if let Some(state) = states.rewind(1) {
+                            match state.restore(&mut timestepper) {
+                                Ok(()) => step = state.steps,
+                                Err(e) => {
+                                    warn!(log, "Rewind failed: {}", e);
+                                    controls.status = Some(format!("Rewind failed: {}", e));
+                                }
+                            }
+                        }
+                    }
+                    VirtualKeyCode::D => controls.diagnostics = !controls.diagnostics,
+                    _ => {}
+                },
+
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Left,
+                    ..
+                } => dragging = state == ElementState::Pressed,
+
+                WindowEvent::CursorMoved { position, .. } => {
+                    let p = pixels
+                        .window_pos_to_pixel((position.x as f32, position.y as f32))
+                        .ok();
+
+                    if let (true, Some(prev), Some(curr)) = (dragging, cursor, p) {
+                        // Pixel `y`-axis points downwards.
+                        let d = vec2!(
+                            curr.0 as Scalar - prev.0 as Scalar,
+                            prev.1 as Scalar - curr.1 as Scalar
+                        );
+                        let index = idx!(curr.0 / render.scale, dim.y - 1 - curr.1 / render.scale);
+                        let vel = d * params.impulse_strength;
+
+                        let impulse = Interaction::Impulse {
+                            index: [index.x, index.y],
+                            velocity: [vel.x, vel.y],
+                            radius: params.impulse_radius,
+                        };
+                        impulse.apply(&mut timestepper);
+
+                        if let Some((ref mut r, _)) = recorder {
+                            r.record(step, impulse);
+                        }
+                    }
+
+                    cursor = p;
+                }
+
+                _ => {}
+            },
+
+            Event::MainEventsCleared => {
+                controls.apply(&mut timestepper);
+
+                if let Some((ref mut r, _)) = recorder {
+                    r.record_parameters(step, &controls.parameters());
+                }
+
+                if !controls.paused || single_step {
+                    for _ in 0..params.steps_per_frame {
+                        if let Err(e) = timestepper.compute_step(params.dt) {
+                            warn!(log, "Step failed: {}", e);
+                            controls.paused = true;
+                            controls.status = Some(format!("Step failed: {}", e));
+                            break;
+                        }
+                        step += 1;
+
+                        let time = timestepper.time();
+                        history.push(Diagnostics::from(grid_mut(&mut timestepper), time, params.dt));
+                        states.push(step, &timestepper);
+                    }
+                    single_step = false;
+                }
+
+                window.request_redraw();
+            }
+
+            Event::RedrawRequested(_) => {
+                let grid = grid_mut(&mut timestepper);
+                let img = render_smoke(grid, &render);
+                pixels.get_frame_mut().copy_from_slice(img.as_raw());
+
+                // Pixel `y`-axis points downwards.
+                controls.inspected = cursor.map(|(x, y)| {
+                    let s = render.scale as Scalar;
+                    let cells = vec2!(x as Scalar + 0.5, (size.y - y) as Scalar - 0.5) / s;
+                    return CellProbe::at(grid, cells * grid.cell_width);
+                });
+
+                panel.prepare(&window, &mut controls, &history);
+                let result = pixels.render_with(|encoder, target, context| {
+                    context.scaling_renderer.render(encoder, target);
+                    panel.render(encoder, target, context);
+                    return Ok(());
+                });
+
+                if let Err(e) = result {
+                    warn!(log, "Rendering failed: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+
+            Event::LoopDestroyed => {
+                // The recording is still open if the viewer exits after a failure.
+                finish_recording(log, &mut recorder, step, &mut timestepper, &mut controls);
+            }
+
+            _ => {}
+        }
+    });
+}
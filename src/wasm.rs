@@ -0,0 +1,102 @@
+use crate::render::{render_smoke, RenderParams, RenderParamsBuilder};
+use crate::scene::config::SceneConfig;
+use crate::scene::grid::Grid;
+use crate::scene::setup::setup_scene;
+use crate::scene::timestepper::{ExecutionMode, TimeStepper};
+use crate::types::*;
+
+use slog::o;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::{CanvasRenderingContext2d, ImageData};
+
+/// A simulation for the browser which draws the smoke into a canvas.
+#[wasm_bindgen]
+pub struct WasmSimulation {
+    timestepper: Box<TimeStepper<'static>>,
+    render: RenderParams,
+    dt: Scalar,
+}
+
+fn to_js(e: Box<dyn std::error::Error>) -> JsValue {
+    return JsValue::from_str(&e.to_string());
+}
+
+#[wasm_bindgen]
+impl WasmSimulation {
+    /// Create the channel scene with `dim_x x dim_y` cells.
+    #[wasm_bindgen(constructor)]
+    pub fn new(dim_x: usize, dim_y: usize) -> Result<WasmSimulation, JsValue> {
+        return WasmSimulation::create(SceneConfig::channel(idx!(dim_x, dim_y)));
+    }
+
+    /// Create a scene from the content of a scene file in
+    /// the format `format` (`toml`, `yaml` or `ron`).
+    pub fn from_scene(content: &str, format: &str) -> Result<WasmSimulation, JsValue> {
+        return WasmSimulation::create(SceneConfig::parse(content, format).map_err(to_js)?);
+    }
+
+    fn create(mut config: SceneConfig) -> Result<WasmSimulation, JsValue> {
+        // No threads in the browser.
+        config.solver.execution_mode = ExecutionMode::Single;
+
+        // The timestepper borrows the logger for its whole lifetime.
+        let log: &'static slog::Logger = Box::leak(Box::new(slog::Logger::root(slog::Discard, o!())));
+        let timestepper = setup_scene(log, &config).map_err(to_js)?;
+
+        return Ok(WasmSimulation {
+            timestepper,
+            render: RenderParamsBuilder::default().build().unwrap(),
+            dt: config.time.dt,
+        });
+    }
+
+    fn grid(&self) -> &Grid {
+        return self.timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+    }
+
+    /// Compute `steps` timesteps.
+    pub fn step(&mut self, steps: u32) -> Result<(), JsValue> {
+        for _ in 0..steps {
+            self.timestepper.compute_step(self.dt).map_err(|e| to_js(e.into()))?;
+        }
+        return Ok(());
+    }
+
+    pub fn time(&self) -> Scalar {
+        return self.timestepper.time();
+    }
+
+    /// Number of pixels per cell.
+    pub fn set_scale(&mut self, scale: usize) {
+        self.render.scale = scale.max(1);
+    }
+
+    /// Width of the rendered image in pixels.
+    pub fn width(&self) -> u32 {
+        return (self.grid().dim.x * self.render.scale) as u32;
+    }
+
+    /// Height of the rendered image in pixels.
+    pub fn height(&self) -> u32 {
+        return (self.grid().dim.y * self.render.scale) as u32;
+    }
+
+    /// The rendered smoke as RGBA pixels (`width() x height()`, top row first),
+    /// e.g. to save a frame at an arbitrary time.
+    pub fn screenshot(&self) -> Vec<u8> {
+        return render_smoke(self.grid(), &self.render).into_raw();
+    }
+
+    /// Draw the smoke field into the canvas at the top left corner.
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        let img = render_smoke(self.grid(), &self.render);
+        let data =
+            ImageData::new_with_u8_clamped_array_and_sh(Clamped(img.as_raw()), img.width(), img.height())?;
+
+        return ctx.put_image_data(&data, 0.0, 0.0);
+    }
+}